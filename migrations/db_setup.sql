@@ -8,10 +8,165 @@ CREATE TABLE IF NOT EXISTS clips
     posted    TIMESTAMP NOT NULL,
     expires   TIMESTAMP,
     password  TEXT,
-    hits      BIGINT NOT NULL
+    hits      BIGINT NOT NULL,
+    deleted   TIMESTAMP
 );
 
+-- Expired and deleted clips are kept as tombstones until they are purged.
+ALTER TABLE clips ADD COLUMN IF NOT EXISTS deleted TIMESTAMP;
+
 CREATE TABLE IF NOT EXISTS api_keys
 (
     api_key BYTEA PRIMARY KEY
-);
\ No newline at end of file
+);
+
+-- Views of each clip, aggregated per hour. IP addresses are never stored.
+CREATE TABLE IF NOT EXISTS clip_views
+(
+    shortcode TEXT NOT NULL REFERENCES clips (shortcode) ON DELETE CASCADE,
+    hour      TIMESTAMP NOT NULL,
+    route     TEXT NOT NULL,
+    agent     TEXT NOT NULL,
+    referrer  TEXT NOT NULL DEFAULT '',
+    views     BIGINT NOT NULL,
+    PRIMARY KEY (shortcode, hour, route, agent, referrer)
+);
+
+-- The API key a clip was created with, if any.
+ALTER TABLE clips ADD COLUMN IF NOT EXISTS owner BYTEA REFERENCES api_keys (api_key) ON DELETE SET NULL;
+DROP INDEX IF EXISTS clips_owner_idx;
+CREATE INDEX IF NOT EXISTS clips_owner_posted_idx ON clips (owner, posted DESC, shortcode DESC);
+
+-- Full-text search over the title and content. Password-protected clips are never indexed.
+ALTER TABLE clips ADD COLUMN IF NOT EXISTS search TSVECTOR GENERATED ALWAYS AS (
+    CASE WHEN password IS NULL
+        THEN to_tsvector('english', coalesce(title, '') || ' ' || content)
+    END
+) STORED;
+CREATE INDEX IF NOT EXISTS clips_search_idx ON clips USING GIN (search);
+
+-- Optional user accounts for the web UI.
+CREATE TABLE IF NOT EXISTS users
+(
+    user_id       TEXT PRIMARY KEY NOT NULL,
+    username      TEXT UNIQUE NOT NULL,
+    password_hash TEXT NOT NULL,
+    created       TIMESTAMP NOT NULL
+);
+
+-- Logged in sessions. Only a hash of the session token is stored.
+CREATE TABLE IF NOT EXISTS sessions
+(
+    token_hash BYTEA PRIMARY KEY,
+    user_id    TEXT NOT NULL REFERENCES users (user_id) ON DELETE CASCADE,
+    expires    TIMESTAMP NOT NULL
+);
+
+-- The user which created a clip, either from the web UI or with one of their API keys.
+ALTER TABLE clips ADD COLUMN IF NOT EXISTS user_id TEXT REFERENCES users (user_id) ON DELETE SET NULL;
+CREATE INDEX IF NOT EXISTS clips_user_posted_idx ON clips (user_id, posted DESC, shortcode DESC);
+
+-- The user which minted an API key, if any.
+ALTER TABLE api_keys ADD COLUMN IF NOT EXISTS user_id TEXT REFERENCES users (user_id) ON DELETE CASCADE;
+ALTER TABLE api_keys ADD COLUMN IF NOT EXISTS created TIMESTAMP;
+
+-- Users who log in through single sign-on have no password.
+ALTER TABLE users ALTER COLUMN password_hash DROP NOT NULL;
+
+-- The accounts of an OpenID Connect provider linked to a user, keyed on the issuer and the
+-- subject of the ID token.
+CREATE TABLE IF NOT EXISTS user_identities
+(
+    issuer  TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    user_id TEXT NOT NULL REFERENCES users (user_id) ON DELETE CASCADE,
+    created TIMESTAMP NOT NULL,
+    PRIMARY KEY (issuer, subject)
+);
+
+-- Groups of users sharing clips which only they can see.
+CREATE TABLE IF NOT EXISTS workspaces
+(
+    workspace_id TEXT PRIMARY KEY NOT NULL,
+    name         TEXT NOT NULL,
+    created      TIMESTAMP NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS workspace_members
+(
+    workspace_id TEXT NOT NULL REFERENCES workspaces (workspace_id) ON DELETE CASCADE,
+    user_id      TEXT NOT NULL REFERENCES users (user_id) ON DELETE CASCADE,
+    role         TEXT NOT NULL CHECK (role IN ('owner', 'member')),
+    PRIMARY KEY (workspace_id, user_id)
+);
+CREATE INDEX IF NOT EXISTS workspace_members_user_idx ON workspace_members (user_id);
+
+-- The workspace a clip is visible to. The clips go along with their workspace, rather than
+-- becoming visible to everyone.
+ALTER TABLE clips ADD COLUMN IF NOT EXISTS workspace_id TEXT REFERENCES workspaces (workspace_id) ON DELETE CASCADE;
+CREATE INDEX IF NOT EXISTS clips_workspace_posted_idx ON clips (workspace_id, posted DESC, shortcode DESC);
+
+-- The workspace an API key is scoped to. Clips created with it are only visible to the workspace.
+ALTER TABLE api_keys ADD COLUMN IF NOT EXISTS workspace_id TEXT REFERENCES workspaces (workspace_id) ON DELETE CASCADE;
+
+-- What an API key may do. Keys minted before scopes existed keep full access.
+ALTER TABLE api_keys ADD COLUMN IF NOT EXISTS scopes TEXT[] NOT NULL
+    DEFAULT ARRAY['clip:read', 'clip:write', 'clip:delete', 'admin'];
+
+-- The revision of a clip, bumped on every edit, and when it was last edited. They are the
+-- validators of conditional requests.
+ALTER TABLE clips ADD COLUMN IF NOT EXISTS revision BIGINT NOT NULL DEFAULT 1;
+ALTER TABLE clips ADD COLUMN IF NOT EXISTS updated TIMESTAMP;
+
+-- The SHA-256 digest of the token letting its holders edit a clip together.
+ALTER TABLE clips ADD COLUMN IF NOT EXISTS edit_token BYTEA;
+
+-- URLs which API keys get the events of the clips created with them sent to. The secret signs
+-- the deliveries, so it is kept as is.
+CREATE TABLE IF NOT EXISTS webhooks
+(
+    webhook_id TEXT PRIMARY KEY NOT NULL,
+    api_key    BYTEA NOT NULL REFERENCES api_keys (api_key) ON DELETE CASCADE,
+    url        TEXT NOT NULL,
+    secret     TEXT NOT NULL,
+    events     TEXT[] NOT NULL,
+    created    TIMESTAMP NOT NULL
+);
+CREATE INDEX IF NOT EXISTS webhooks_api_key_idx ON webhooks (api_key);
+
+-- Every event sent to a webhook, and how its delivery went. Deliveries still to be attempted
+-- have a `next_attempt`.
+CREATE TABLE IF NOT EXISTS webhook_deliveries
+(
+    delivery_id  BIGSERIAL PRIMARY KEY,
+    webhook_id   TEXT NOT NULL REFERENCES webhooks (webhook_id) ON DELETE CASCADE,
+    event        TEXT NOT NULL,
+    shortcode    TEXT NOT NULL,
+    payload      TEXT NOT NULL,
+    created      TIMESTAMP NOT NULL,
+    attempts     INTEGER NOT NULL DEFAULT 0,
+    next_attempt TIMESTAMP,
+    delivered    TIMESTAMP,
+    status       INTEGER,
+    error        TEXT
+);
+CREATE INDEX IF NOT EXISTS webhook_deliveries_due_idx ON webhook_deliveries (next_attempt)
+    WHERE next_attempt IS NOT NULL;
+CREATE INDEX IF NOT EXISTS webhook_deliveries_webhook_idx ON webhook_deliveries (webhook_id, delivery_id DESC);
+CREATE INDEX IF NOT EXISTS webhook_deliveries_views_idx ON webhook_deliveries (webhook_id, shortcode, created)
+    WHERE event = 'clip.viewed';
+
+-- Whether the password of a clip is only known by its Argon2 hash, as for clips restored from
+-- a backup, rather than kept as it was given.
+ALTER TABLE clips ADD COLUMN IF NOT EXISTS password_hashed BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- The version of this schema, checked by `/readyz`. Bump it along with
+-- `SCHEMA_VERSION` in `src/lib/data/mod.rs` whenever the schema changes.
+CREATE TABLE IF NOT EXISTS schema_version
+(
+    id      BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),
+    version INTEGER NOT NULL
+);
+
+INSERT INTO schema_version (version) VALUES (13)
+ON CONFLICT (id) DO UPDATE SET version = GREATEST(schema_version.version, EXCLUDED.version);
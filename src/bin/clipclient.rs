@@ -1,8 +1,12 @@
 use clipstash::domain::clip::field::{Content, Expires, Password, ShortCode, Title};
-use clipstash::service::ask::{GetClip, NewClip, UpdateClip};
-use clipstash::web::api::{ApiKey, API_KEY_HEADER};
+use clipstash::service::ask::{EditClip, GetClip, NewApiKey, NewClip, Requester};
+use clipstash::web::api::{ApiKey, ErrorBody, API_BASE, API_KEY_HEADER, CLIP_PASSWORD_HEADER};
+use clipstash::domain::clip::{ClipList, ClipSummary, SearchResults};
+use clipstash::domain::scope::Scope;
 use clipstash::Clip;
+use serde::de::DeserializeOwned;
 use std::error::Error;
+use std::path::PathBuf;
 use structopt::StructOpt;
 
 /// ClipStash API client, which allows a user to read, write and modify clips from a command
@@ -37,6 +41,36 @@ enum Command {
         expires: Option<Expires>,
         #[structopt(short, long, help = "title")]
         title: Option<Title>
+    },
+    Delete {
+        shortcode: ShortCode
+    },
+    Restore {
+        shortcode: ShortCode
+    },
+    Key {
+        #[structopt(long = "scope", help = "scope of the new key, repeatable (clip:read, clip:write, clip:delete, admin)")]
+        scopes: Vec<Scope>
+    },
+    List {
+        #[structopt(long, help = "cursor returned by the previous page")]
+        cursor: Option<String>,
+        #[structopt(long, help = "maximum number of clips")]
+        limit: Option<u32>
+    },
+    Search {
+        #[structopt(help = "search terms (password-protected clips never match)")]
+        query: String,
+        #[structopt(long, help = "number of results to skip")]
+        offset: Option<u32>,
+        #[structopt(long, help = "maximum number of results")]
+        limit: Option<u32>
+    },
+    Export {
+        #[structopt(long, help = "only the clips created with the API key, rather than every clip (needs admin)")]
+        owner: bool,
+        #[structopt(parse(from_os_str), help = "file to write the backup to, the standard output if left out")]
+        file: Option<PathBuf>
     }
 }
 
@@ -60,7 +94,8 @@ fn run(opt:Opt) -> Result<(), Box<dyn Error>> {
         Command::Get { shortcode, password } => {
             let req = GetClip {
                 password: Password::new(password.unwrap_or_default())?,
-                shortcode
+                shortcode,
+                requester: Requester::default()
             };
             let clip = get_clip(opt.addr.as_str(), req, opt.api_key)?;
             println!("{:#?}", clip);
@@ -71,7 +106,10 @@ fn run(opt:Opt) -> Result<(), Box<dyn Error>> {
                 content: Content::new(clip.as_str())?,
                 title: title.unwrap_or_default(),
                 expires: expires.unwrap_or_default(),
-                password: password.unwrap_or_default()
+                password: password.unwrap_or_default(),
+                owner: None,
+                user: None,
+                workspace: None
             };
 
             let clip = new_clip(opt.addr.as_str(), req, opt.api_key)?;
@@ -82,53 +120,202 @@ fn run(opt:Opt) -> Result<(), Box<dyn Error>> {
             let password = password.unwrap_or_default();
             let svc_get_req = GetClip {
                 password: password.clone(),
-                shortcode: shortcode.clone()
+                shortcode: shortcode.clone(),
+                requester: Requester::default()
             };
             let original_clip = get_clip(opt.addr.as_str(), svc_get_req, opt.api_key.clone())?;
-            let svc_update_req = UpdateClip {
+            let svc_edit_req = EditClip {
                 content: Content::new(clip.as_str())?,
                 expires: expires.unwrap_or(original_clip.expires),
                 title: title.unwrap_or(original_clip.title),
-                password,
-                shortcode
+                password
             };
 
-            let clip = update_clip(opt.addr.as_str(), svc_update_req, opt.api_key);
+            let clip = update_clip(opt.addr.as_str(), shortcode, svc_edit_req, opt.api_key);
+            println!("{:#?}", clip);
+            Ok(())
+        },
+        Command::Delete { shortcode } => {
+            let message = delete_clip(opt.addr.as_str(), shortcode, opt.api_key)?;
+            println!("{}", message);
+            Ok(())
+        },
+        Command::Restore { shortcode } => {
+            let clip = restore_clip(opt.addr.as_str(), shortcode, opt.api_key)?;
             println!("{:#?}", clip);
             Ok(())
+        },
+        Command::Key { scopes } => {
+            let api_key = new_api_key(opt.addr.as_str(), NewApiKey { scopes }, opt.api_key)?;
+            println!("{}", api_key);
+            Ok(())
+        },
+        Command::List { cursor, limit } => {
+            let list = list_clips(opt.addr.as_str(), cursor, limit, opt.api_key)?;
+            print_clips(list.clips.iter().map(|clip| (clip, None)));
+            if let Some(cursor) = list.next_cursor {
+                println!("\nMore clips with --cursor {}", cursor);
+            }
+            Ok(())
+        },
+        Command::Search { query, offset, limit } => {
+            let results = search_clips(opt.addr.as_str(), &query, offset, limit, opt.api_key)?;
+            print_clips(results.clips.iter().map(|found| (&found.clip, Some(found.snippet.as_str()))));
+            if let Some(offset) = results.next_offset {
+                println!("\nMore results with --offset {}", offset);
+            }
+            Ok(())
+        },
+        Command::Export { owner, file } => {
+            match file {
+                Some(file) => export_clips(opt.addr.as_str(), owner, &mut std::fs::File::create(file)?, opt.api_key),
+                None => export_clips(opt.addr.as_str(), owner, &mut std::io::stdout(), opt.api_key)
+            }
         }
     }
 }
 
+/// Reads the body of a response, or the error the API answered with instead.
+fn read<T: DeserializeOwned>(response: reqwest::blocking::Response) -> Result<T, Box<dyn Error>> {
+    if response.status().is_success() {
+        Ok(response.json()?)
+    } else {
+        let body: ErrorBody = response.json()?;
+        Err(format!("{} ({})", body.error, body.error.code).into())
+    }
+}
 
 fn get_clip(addr: &str, ask_svc: GetClip, api_key: ApiKey) -> Result<Clip, Box<dyn Error>> {
     let client = reqwest::blocking::Client::builder().build()?;
-    let addr = format!("{}/api/clip/{}", addr, ask_svc.shortcode.into_inner());
+    let addr = format!("{}{}/clips/{}", addr, API_BASE, ask_svc.shortcode.into_inner());
     let mut request = client.get(addr);
     request = match ask_svc.password.into_inner() {
-        Some(password) => request.header(reqwest::header::COOKIE,
-                                         format!("password={}", password)),
+        Some(password) => request.header(CLIP_PASSWORD_HEADER, password),
         None => request
     };
 
     request = request.header(API_KEY_HEADER, api_key.to_base64());
-    Ok(request.send()?.json()?)
+    read(request.send()?)
 }
 
 fn new_clip(addr: &str, ask_svc: NewClip, api_key: ApiKey) -> Result<Clip, Box<dyn Error>> {
     let client = reqwest::blocking::Client::builder().build()?;
-    let addr = format!("{}/api/clip", addr);
+    let addr = format!("{}{}/clips", addr, API_BASE);
     let mut request = client.post(addr);
     request = request.header(API_KEY_HEADER, api_key.to_base64());
-    Ok(request.json(&ask_svc).send()?.json()?)
+    read(request.json(&ask_svc).send()?)
 }
 
-fn update_clip(addr: &str, ask_svc: UpdateClip, api_key: ApiKey) -> Result<Clip, Box<dyn Error>> {
+fn update_clip(addr: &str, shortcode: ShortCode, ask_svc: EditClip, api_key: ApiKey) -> Result<Clip, Box<dyn Error>> {
     let client = reqwest::blocking::Client::builder().build()?;
-    let addr = format!("{}/api/clip", addr);
+    let addr = format!("{}{}/clips/{}", addr, API_BASE, shortcode.into_inner());
     let mut request = client.put(addr);
     request = request.header(API_KEY_HEADER, api_key.to_base64());
-    Ok(request.json(&ask_svc).send()?.json()?)
+    read(request.json(&ask_svc).send()?)
+}
+
+fn delete_clip(addr: &str, shortcode: ShortCode, api_key: ApiKey) -> Result<String, Box<dyn Error>> {
+    let client = reqwest::blocking::Client::builder().build()?;
+    let addr = format!("{}{}/clips/{}", addr, API_BASE, shortcode.into_inner());
+    let mut request = client.delete(addr);
+    request = request.header(API_KEY_HEADER, api_key.to_base64());
+    read(request.send()?)
+}
+
+fn restore_clip(addr: &str, shortcode: ShortCode, api_key: ApiKey) -> Result<Clip, Box<dyn Error>> {
+    let client = reqwest::blocking::Client::builder().build()?;
+    let addr = format!("{}{}/clips/{}/restore", addr, API_BASE, shortcode.into_inner());
+    let mut request = client.post(addr);
+    request = request.header(API_KEY_HEADER, api_key.to_base64());
+    read(request.send()?)
+}
+
+fn new_api_key(addr: &str, ask_svc: NewApiKey, api_key: ApiKey) -> Result<String, Box<dyn Error>> {
+    let client = reqwest::blocking::Client::builder().build()?;
+    let addr = format!("{}{}/keys", addr, API_BASE);
+    let mut request = client.post(addr);
+    request = request.header(API_KEY_HEADER, api_key.to_base64());
+    read(request.json(&ask_svc).send()?)
+}
+
+fn list_clips(
+    addr: &str,
+    cursor: Option<String>,
+    limit: Option<u32>,
+    api_key: ApiKey
+) -> Result<ClipList, Box<dyn Error>> {
+    let client = reqwest::blocking::Client::builder().build()?;
+    let addr = format!("{}{}/clips", addr, API_BASE);
+    let mut params = vec![];
+    if let Some(cursor) = cursor {
+        params.push(("cursor", cursor));
+    }
+    if let Some(limit) = limit {
+        params.push(("limit", limit.to_string()));
+    }
+    let mut request = client.get(addr).query(&params);
+    request = request.header(API_KEY_HEADER, api_key.to_base64());
+    read(request.send()?)
+}
+
+fn search_clips(
+    addr: &str,
+    query: &str,
+    offset: Option<u32>,
+    limit: Option<u32>,
+    api_key: ApiKey
+) -> Result<SearchResults, Box<dyn Error>> {
+    let client = reqwest::blocking::Client::builder().build()?;
+    let addr = format!("{}{}/clips/search", addr, API_BASE);
+    let mut params = vec![("q", query.to_owned())];
+    if let Some(offset) = offset {
+        params.push(("offset", offset.to_string()));
+    }
+    if let Some(limit) = limit {
+        params.push(("limit", limit.to_string()));
+    }
+    let mut request = client.get(addr).query(&params);
+    request = request.header(API_KEY_HEADER, api_key.to_base64());
+    read(request.send()?)
+}
+
+/// Writes a backup of the clips created with the API key, or of every clip, as it is streamed.
+fn export_clips(addr: &str, owner: bool, out: &mut dyn std::io::Write, api_key: ApiKey) -> Result<(), Box<dyn Error>> {
+    let client = reqwest::blocking::Client::builder().timeout(None).build()?;
+    let path = if owner { "clips/export" } else { "clips/export/all" };
+    let addr = format!("{}{}/{}", addr, API_BASE, path);
+    let mut response = client.get(addr).header(API_KEY_HEADER, api_key.to_base64()).send()?;
+    if !response.status().is_success() {
+        return read(response);
+    }
+    response.copy_to(out)?;
+    Ok(())
+}
+
+/// Prints clips as a table, each followed by an optional excerpt of its content.
+fn print_clips<'a, I>(clips: I)
+where
+    I: IntoIterator<Item = (&'a ClipSummary, Option<&'a str>)>
+{
+    println!("{:<12} {:<32} {:>8}  {:<20}", "SHORTCODE", "TITLE", "HITS", "EXPIRES");
+    for (clip, snippet) in clips {
+        let title = clip.title.clone().into_inner().unwrap_or_default();
+        let expires = clip.expires
+            .clone()
+            .into_inner()
+            .map(|time| time.into_inner().format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_else(|| "never".to_owned());
+        println!(
+            "{:<12} {:<32} {:>8}  {:<20}",
+            clip.shortcode.as_str(),
+            title.chars().take(32).collect::<String>(),
+            clip.hits.clone().into_inner(),
+            expires
+        );
+        if let Some(snippet) = snippet {
+            println!("    {}", snippet);
+        }
+    }
 }
 
 fn main() {
@@ -1,61 +1,334 @@
-use std::env::VarError;
 use clipstash::data::AppDatabase;
 use clipstash::web::{renderer::Renderer};
 use dotenv::dotenv;
 use std::path::PathBuf;
+use std::time::Duration;
+use rocket::futures::StreamExt;
 use rocket::tokio;
 use structopt::StructOpt;
-use clipstash::domain::maintenance::Maintenance;
-use clipstash::web::hitcounter::HitCounter;
-
-/// The binary that gets the webserver running.
+use clipstash::domain::maintenance::{Maintenance, DEFAULT_GRACE_PERIOD};
+use clipstash::domain::webhook::{WebhookConfig, WebhookDispatcher};
+use clipstash::web::hitcounter::{HitCounter, HitCounterConfig};
+use clipstash::web::events::ClipEvents;
+use clipstash::web::collab::Collab;
+use clipstash::service;
+use clipstash::domain::scope::Scope;
+use clipstash::domain::user::oidc::{OidcConfig, OidcProvider};
+use clipstash::web::sso::Sso;
+use clipstash::domain::slash::SlashCommandConfig;
+use clipstash::domain::backup;
+use clipstash::domain::import::{self, ConflictPolicy};
+use clipstash::web::slash::SlashCommands;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::EnvFilter;
 
+/// The binary that gets the webserver running. Every option can also be set through the
+/// environment variable named next to it, or in a `.env` file.
 #[derive(StructOpt, Debug)]
 #[structopt(name = "httpd")]
 struct Opt {
-    #[structopt(default_value = "localhost:5432")]
+    /// URL of the Postgres database.
+    #[structopt(long = "database-url", env = "DATABASE_URL", hide_env_values = true, default_value = "localhost:5432")]
     connection_string: String,
     #[structopt(short, long, parse(from_os_str), default_value = "templates/")]
-    template_directory: PathBuf
+    template_directory: PathBuf,
+    /// Hours an expired or deleted clip can be restored before it is purged.
+    #[structopt(long, env = "GRACE_PERIOD_HOURS")]
+    grace_period_hours: Option<u64>,
+    /// Seconds between writes of the pending clip hits to the database.
+    #[structopt(long, env = "HIT_FLUSH_INTERVAL_SECS")]
+    hit_flush_interval_secs: Option<u64>,
+    /// Number of hit clips which triggers an early write to the database.
+    #[structopt(long, env = "HIT_FLUSH_SIZE")]
+    hit_flush_size: Option<usize>,
+    /// Log format, either `json` or `pretty`. The level is set through `RUST_LOG`.
+    #[structopt(long, env = "LOG_FORMAT", default_value = "json")]
+    log_format: String,
+    /// Generate an API key with every scope, including `admin`, print it and exit.
+    #[structopt(long)]
+    new_api_key: bool,
+    /// Issuer of the OpenID Connect provider to log in with, which enables single sign-on.
+    #[structopt(long, env = "OIDC_ISSUER")]
+    oidc_issuer: Option<String>,
+    #[structopt(long, env = "OIDC_CLIENT_ID")]
+    oidc_client_id: Option<String>,
+    #[structopt(long, env = "OIDC_CLIENT_SECRET", hide_env_values = true)]
+    oidc_client_secret: Option<String>,
+    /// The URL of the `/auth/sso/callback` route, as registered with the provider.
+    #[structopt(long, env = "OIDC_REDIRECT_URL")]
+    oidc_redirect_url: Option<String>,
+    /// Signing secret of the Slack app, or token of the Mattermost command, which enables the
+    /// `/clip` slash command.
+    #[structopt(long, env = "SLASH_COMMAND_SECRET", hide_env_values = true)]
+    slash_command_secret: Option<String>,
+    /// The URL the server is reached at, which the links answered to slash commands start with.
+    #[structopt(long, env = "PUBLIC_URL")]
+    public_url: Option<String>,
+    /// Comma-separated hosts webhooks may deliver to even though they are local or private.
+    #[structopt(long, env = "WEBHOOK_ALLOWED_HOSTS", use_delimiter = true)]
+    webhook_allowed_hosts: Vec<String>,
+    #[structopt(subcommand)]
+    command: Option<Command>
+}
+
+// What to do instead of serving. Not a doc comment, which would replace the help of `httpd`.
+#[derive(StructOpt, Debug)]
+enum Command {
+    /// Bulk-loads clips from the JSON or NDJSON export of another pastebin.
+    Import(ImportOpt),
+    /// Backs up every clip as NDJSON.
+    Export(ExportOpt),
+    /// Restores a backup written by `httpd export`. Clips which already exist are left as they
+    /// are, so a backup can be restored again.
+    Restore(RestoreOpt)
+}
+
+/// The options of `httpd import`.
+#[derive(StructOpt, Debug)]
+struct ImportOpt {
+    /// The export to import, or `-` to read it from the standard input.
+    #[structopt(parse(from_os_str))]
+    file: PathBuf,
+    /// Keep the shortcodes of the export, so that links to the pastes keep working.
+    #[structopt(long)]
+    keep_shortcodes: bool,
+    /// What to do with a clip whose shortcode is already taken, either `skip` or `rename`.
+    #[structopt(long, default_value = "skip")]
+    on_conflict: ConflictPolicy,
+    /// Number of clips imported in every transaction.
+    #[structopt(long)]
+    batch_size: Option<usize>
+}
+
+/// Imports the clips of an export and prints what became of them.
+fn import_clips(opt: ImportOpt, database: &AppDatabase, rt: &tokio::runtime::Runtime) {
+    let export = if opt.file.as_os_str() == "-" {
+        let mut export = String::new();
+        std::io::Read::read_to_string(&mut std::io::stdin(), &mut export).map(|_| export)
+    } else {
+        std::fs::read_to_string(&opt.file)
+    }.unwrap_or_else(|e| exit_with_error("failed to read the export", e));
+
+    let parsed = import::parse_export(&export)
+        .unwrap_or_else(|e| exit_with_error("failed to read the export", e));
+    for invalid in &parsed.invalid {
+        println!("record {}: not imported, {}", invalid.record, invalid.error);
+    }
+
+    let req = service::ask::ImportClips {
+        clips: parsed.clips,
+        keep_shortcodes: opt.keep_shortcodes,
+        on_conflict: opt.on_conflict,
+        batch_size: opt.batch_size.filter(|size| *size > 0).unwrap_or(import::DEFAULT_BATCH_SIZE)
+    };
+    let report = rt.block_on(service::action::import_clips(req, database.get_pool()))
+        .unwrap_or_else(|e| exit_with_error("failed to import the clips", e));
+    for conflict in &report.conflicts {
+        match &conflict.imported_as {
+            Some(shortcode) => println!(
+                "record {}: shortcode {} is taken, imported as {}",
+                conflict.record, conflict.shortcode.as_str(), shortcode.as_str()
+            ),
+            None => println!("record {}: shortcode {} is taken, not imported", conflict.record, conflict.shortcode.as_str())
+        }
+    }
+    println!(
+        "imported {} clips, {} conflicts, {} invalid records",
+        report.imported, report.conflicts.len(), parsed.invalid.len()
+    );
+}
+
+/// The options of `httpd export`.
+#[derive(StructOpt, Debug)]
+struct ExportOpt {
+    /// Where to write the backup, the standard output if left out or `-`.
+    #[structopt(parse(from_os_str))]
+    file: Option<PathBuf>
+}
+
+/// Writes a backup of every clip. A failed export stops before the `end` line, so that the
+/// backup can't be restored.
+fn export_clips(opt: ExportOpt, database: &AppDatabase, rt: &tokio::runtime::Runtime) {
+    use std::io::Write;
+
+    let mut out: Box<dyn Write> = match opt.file.filter(|file| file.as_os_str() != "-") {
+        Some(file) => Box::new(std::io::BufWriter::new(
+            std::fs::File::create(file).unwrap_or_else(|e| exit_with_error("failed to create the backup", e))
+        )),
+        None => Box::new(std::io::BufWriter::new(std::io::stdout()))
+    };
+    let mut lines = backup::export(database.get_pool().clone(), None);
+    while let Some(line) = rt.block_on(lines.next()) {
+        let line = line.unwrap_or_else(|e| exit_with_error("failed to export the clips", e));
+        out.write_all(line.as_bytes()).unwrap_or_else(|e| exit_with_error("failed to write the backup", e));
+    }
+    out.flush().unwrap_or_else(|e| exit_with_error("failed to write the backup", e));
+}
+
+/// The options of `httpd restore`.
+#[derive(StructOpt, Debug)]
+struct RestoreOpt {
+    /// The backup to restore, or `-` to read it from the standard input.
+    #[structopt(parse(from_os_str))]
+    file: PathBuf,
+    /// Number of clips restored in every transaction.
+    #[structopt(long)]
+    batch_size: Option<usize>
+}
+
+/// Restores the clips of a backup and prints what became of them.
+fn restore_clips(opt: RestoreOpt, database: &AppDatabase, rt: &tokio::runtime::Runtime) {
+    let contents = if opt.file.as_os_str() == "-" {
+        let mut contents = String::new();
+        std::io::Read::read_to_string(&mut std::io::stdin(), &mut contents).map(|_| contents)
+    } else {
+        std::fs::read_to_string(&opt.file)
+    }.unwrap_or_else(|e| exit_with_error("failed to read the backup", e));
+
+    let clips = backup::read_backup(&contents)
+        .unwrap_or_else(|e| exit_with_error("failed to read the backup", e));
+    let batch_size = opt.batch_size.filter(|size| *size > 0).unwrap_or(backup::RESTORE_BATCH_SIZE);
+    let report = rt.block_on(service::action::restore_clips(clips, batch_size, database.get_pool()))
+        .unwrap_or_else(|e| exit_with_error("failed to restore the clips", e));
+    for skipped in &report.skipped {
+        println!(
+            "clip {}: not restored, its shortcode {} is taken or its workspace is missing",
+            skipped.clip_id, skipped.shortcode.as_str()
+        );
+    }
+    println!(
+        "restored {} clips, {} already existed, {} skipped",
+        report.restored, report.unchanged, report.skipped.len()
+    );
+}
+
+/// Logs to the standard output, or to the standard error when the output is something else,
+/// such as a backup or a new API key.
+fn init_logging(format: &str, to_stderr: bool) {
+    let filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| EnvFilter::new("info"));
+    let writer = if to_stderr { BoxMakeWriter::new(std::io::stderr) } else { BoxMakeWriter::new(std::io::stdout) };
+    let subscriber = tracing_subscriber::fmt().with_env_filter(filter).with_writer(writer);
+    match format {
+        "pretty" => subscriber.pretty().init(),
+        _ => subscriber.json().init()
+    }
+}
+
+/// Prints why the server could not start and exits with a non-zero code.
+fn exit_with_error<E: std::fmt::Display>(message: &str, error: E) -> ! {
+    tracing::error!(error = %error, "{}", message);
+    eprintln!("httpd: {}: {}", message, error);
+    std::process::exit(1)
+}
+
+/// The OpenID Connect provider to log in with, if an issuer is set. The client and the
+/// redirect URL are then required.
+fn oidc_config(opt: &Opt) -> Option<OidcConfig> {
+    let issuer = opt.oidc_issuer.clone().filter(|issuer| !issuer.is_empty())?;
+    let required = |value: &Option<String>, name: &str| value.clone()
+        .unwrap_or_else(|| exit_with_error("incomplete single sign-on configuration", format!("{} is not set", name)));
+    Some(OidcConfig {
+        issuer,
+        client_id: required(&opt.oidc_client_id, "OIDC_CLIENT_ID"),
+        client_secret: required(&opt.oidc_client_secret, "OIDC_CLIENT_SECRET"),
+        redirect_url: required(&opt.oidc_redirect_url, "OIDC_REDIRECT_URL")
+    })
+}
+
+/// The slash command settings, if a secret is set. The public URL is then required.
+fn slash_command_config(opt: &Opt) -> Option<SlashCommandConfig> {
+    let signing_secret = opt.slash_command_secret.clone().filter(|secret| !secret.is_empty())?;
+    let public_url = opt.public_url.clone()
+        .unwrap_or_else(|| exit_with_error("incomplete slash command configuration", "PUBLIC_URL is not set"));
+    Some(SlashCommandConfig { signing_secret, public_url })
 }
 
 fn main() {
     dotenv().ok();
 
-    let DB_URL = std::env::var("DATABASE_URL")
-        .ok()
-        .unwrap_or("localhost:5432".to_owned());
+    let opt = Opt::from_args();
+    init_logging(&opt.log_format, opt.command.is_some() || opt.new_api_key);
 
-    let opt = Opt {
-        connection_string: DB_URL,
-        template_directory: PathBuf::from("templates/")
+    let grace_period = match opt.grace_period_hours.map(|hours| hours.checked_mul(60 * 60)) {
+        Some(Some(secs)) => Duration::from_secs(secs),
+        Some(None) => exit_with_error("invalid grace period", "GRACE_PERIOD_HOURS is too large"),
+        None => DEFAULT_GRACE_PERIOD
     };
 
+    let mut hit_counter_config = HitCounterConfig::default();
+    if let Some(secs) = opt.hit_flush_interval_secs.filter(|secs| *secs > 0) {
+        hit_counter_config.flush_interval = Duration::from_secs(secs);
+    }
+    if let Some(size) = opt.hit_flush_size {
+        hit_counter_config.flush_size = size;
+    }
+
     let rt = tokio::runtime::Runtime::new()
-        .expect("failed to spawn tokio runtime");
+        .unwrap_or_else(|e| exit_with_error("failed to spawn tokio runtime", e));
 
     let handle = rt.handle().clone();
-    let renderer = Renderer::new(opt.template_directory.clone());
+    let renderer = Renderer::new(opt.template_directory.clone())
+        .unwrap_or_else(|e| exit_with_error("failed to load templates", e));
 
-    let database = rt.block_on(async move {
+    let database = rt.block_on(async {
         AppDatabase::new(&opt.connection_string).await
-    });
+    }).unwrap_or_else(|e| exit_with_error(
+        "failed to connect to the database (if it has not been created, run `sqlx database setup`)",
+        e
+    ));
+
+    match opt.command {
+        Some(Command::Import(import)) => return import_clips(import, &database, &rt),
+        Some(Command::Export(export)) => return export_clips(export, &database, &rt),
+        Some(Command::Restore(restore)) => return restore_clips(restore, &database, &rt),
+        None => ()
+    }
 
-    let hit_counter = HitCounter::new(database.get_pool().clone(), handle.clone());
-    let maintenance = Maintenance::spawn(database.get_pool().clone(), handle.clone());
+    if opt.new_api_key {
+        let api_key = rt.block_on(service::action::generate_api_key(&Scope::ALL, database.get_pool()))
+            .unwrap_or_else(|e| exit_with_error("failed to generate API key", e));
+        println!("{}", api_key.to_base64());
+        return;
+    }
+
+    let sso = match oidc_config(&opt) {
+        Some(config) => {
+            let provider = rt.block_on(OidcProvider::discover(config))
+                .unwrap_or_else(|e| exit_with_error("failed to discover the OpenID Connect provider", e));
+            tracing::info!(issuer = provider.issuer(), "single sign-on enabled");
+            Sso::new(Some(provider))
+        },
+        None => Sso::disabled()
+    };
+
+    let slash = SlashCommands::new(slash_command_config(&opt));
+    if slash.is_enabled() {
+        tracing::info!("slash commands enabled");
+    }
+
+    let events = ClipEvents::default();
+    let hit_counter = HitCounter::new(database.get_pool().clone(), handle.clone(), hit_counter_config, events.clone());
+    let collab = Collab::new(database.get_pool().clone(), handle.clone(), events.clone());
+    let maintenance = Maintenance::spawn(database.get_pool().clone(), handle.clone(), grace_period);
+    let webhook_config = WebhookConfig { allowed_hosts: opt.webhook_allowed_hosts.clone(), ..Default::default() };
+    let webhooks = WebhookDispatcher::spawn(database.get_pool().clone(), handle.clone(), webhook_config);
 
     let config = clipstash::RocketConfig {
         renderer,
         database,
         hit_counter,
-        maintenance
+        events,
+        collab,
+        maintenance,
+        webhooks,
+        sso,
+        slash
     };
 
     rt.block_on(async move {
-        clipstash::rocket(config)
-            .launch()
-            .await
-            .expect("failed to launch rocket server")
-
+        if let Err(e) = clipstash::rocket(config).launch().await {
+            exit_with_error("failed to launch rocket server", e);
+        }
     });
 }
\ No newline at end of file
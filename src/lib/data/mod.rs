@@ -17,25 +17,28 @@ pub type Transaction<'t> = sqlx::Transaction<'t, Postgres>;
 pub type AppDatabaseRow = sqlx::postgres::PgRow;
 pub type AppQueryResult = sqlx::postgres::PgQueryResult;
 
+/// The schema version this build expects, as recorded by `migrations/db_setup.sql`.
+pub const SCHEMA_VERSION: i32 = 13;
+
 pub struct Database<D: sqlx::Database>(sqlx::Pool<D>);
 pub mod model;
 pub mod query;
 
+/// Clones share the connection pool of the original.
+impl<D: sqlx::Database> Clone for Database<D> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
 impl Database<Postgres> {
-    pub async fn new(connection_str: &str) -> Self {
-        /// Establishes a connection with the database.
+    /// Establishes a connection with the database.
+    pub async fn new(connection_str: &str) -> Result<Self, DataError> {
         let pool = sqlx::postgres::PgPoolOptions::new()
             .connect(connection_str)
-            .await;
-
-        match pool {
-            Ok(pool) => Self(pool),
-            Err(e) => {
-                eprintln!("{:?}\n", e);
-                eprintln!("If the database has not been created,\
-                           run \n $ sqlx database setup \n");
-            panic!("database connection error")},
-        }
+            .await?;
+
+        Ok(Self(pool))
     }
 
     pub fn get_pool(&self) -> &DatabasePool {
@@ -81,19 +84,35 @@ pub mod test {
     use crate::data::*;
     use tokio::runtime::Handle;
 
+    /// Connects to the Postgres database named by `TEST_DATABASE_URL`, or else `DATABASE_URL`,
+    /// and sets the schema up in a fresh Postgres schema of its own, so tests don't see each
+    /// other's clips.
     pub fn new_db(handle: &Handle) -> AppDatabase {
-        use sqlx::migrate::Migrator;
-        use std::path::Path;
+        use sqlx::{postgres::PgPoolOptions, Executor};
 
         handle.block_on(async move {
-            let db = Database::new(":memory:").await;
-            let migrator = Migrator::new(Path::new("./migrations")).await.unwrap();
-
-
-            let pool = db.get_pool();
-            migrator.run(pool).await.unwrap();
-
-            db
+            let url = std::env::var("TEST_DATABASE_URL")
+                .or_else(|_| std::env::var("DATABASE_URL"))
+                .expect("TEST_DATABASE_URL or DATABASE_URL must name a Postgres database");
+            let schema = format!("test_{}", Uuid::new_v4().simple());
+            let search_path = format!("CREATE SCHEMA IF NOT EXISTS {0}; SET search_path TO {0}", schema);
+
+            let pool = PgPoolOptions::new()
+                .after_connect(move |conn| {
+                    let search_path = search_path.clone();
+                    Box::pin(async move {
+                        conn.execute(search_path.as_str()).await?;
+                        Ok(())
+                    })
+                })
+                .connect(&url)
+                .await
+                .expect("failed to connect to the database");
+            pool.execute(include_str!("../../../migrations/db_setup.sql"))
+                .await
+                .expect("failed to set up the schema");
+
+            Database(pool)
         })
     }
-}
\ No newline at end of file
+}
@@ -1,6 +1,13 @@
 use crate::data::DbId;
+use crate::domain::backup::BackedUpClip;
+use crate::domain::import::ImportedClip;
+use crate::domain::stats::{AgentClass, View, ViewRoute};
+use crate::domain::user::field::UserId;
+use crate::domain::user::UserError;
+use crate::domain::webhook::WebhookError;
+use crate::domain::workspace::WorkspaceError;
 use crate::{ClipError, ShortCode, Time};
-use chrono::{NaiveDateTime, Utc};
+use chrono::{DateTime, NaiveDateTime, Utc};
 use std::convert::TryFrom;
 
 #[derive(Debug, sqlx::FromRow)]
@@ -14,7 +21,40 @@ pub struct Clip {
     pub(in crate::data) posted: NaiveDateTime,
     pub(in crate::data) expires: Option<NaiveDateTime>,
     pub(in crate::data) password: Option<String>,
-    pub(in crate::data) hits: i64
+    pub(in crate::data) password_hashed: bool,
+    pub(in crate::data) hits: i64,
+    pub(in crate::data) deleted: Option<NaiveDateTime>,
+    pub(in crate::data) user_id: Option<String>,
+    pub(in crate::data) workspace_id: Option<String>,
+    pub(in crate::data) revision: i64,
+    pub(in crate::data) updated: NaiveDateTime
+}
+
+impl Clip {
+    /// Returns whether the clip has been expired or deleted and is waiting to be purged.
+    pub fn is_deleted(&self) -> bool {
+        self.deleted.is_some()
+    }
+
+    /// Returns whether the clip was created by the given user.
+    pub fn is_owned_by(&self, user_id: &UserId) -> bool {
+        self.user_id.as_deref() == Some(String::from(user_id.clone().into_inner()).as_str())
+    }
+
+    /// The workspace the clip is only visible to, if any.
+    pub fn workspace_id(&self) -> Option<&str> {
+        self.workspace_id.as_deref()
+    }
+}
+
+/// The password of a clip as it is stored, which is only its hash for restored clips.
+fn stored_password(password: Option<String>, hashed: bool) -> Result<crate::domain::clip::field::Password, ClipError> {
+    use crate::domain::clip::field::Password;
+    if hashed {
+        Ok(Password::hashed(password))
+    } else {
+        Password::new(password.unwrap_or_default())
+    }
 }
 
 impl TryFrom<Clip> for crate::domain::clip::Clip {
@@ -31,8 +71,53 @@ impl TryFrom<Clip> for crate::domain::clip::Clip {
                 title: field::Title::new(clip.title),
                 posted: field::Posted::new(Time::from_naive_utc(clip.posted)),
                 expires: field::Expires::new(clip.expires.map(Time::from_naive_utc)),
-                password: field::Password::new(clip.password.unwrap_or_default())?,
-                hits: field::Hits::new(u64::try_from(clip.hits)?)
+                password: stored_password(clip.password, clip.password_hashed)?,
+                hits: field::Hits::new(u64::try_from(clip.hits)?),
+                revision: field::Revision::new(u64::try_from(clip.revision)?),
+                updated: field::Updated::new(Time::from_naive_utc(clip.updated))
+            }
+        )
+    }
+}
+
+/// A clip without its content, along with the length and digest of the content.
+pub struct RawClip {
+    pub(in crate::data) shortcode: String,
+    pub(in crate::data) expires: Option<NaiveDateTime>,
+    pub(in crate::data) password: Option<String>,
+    pub(in crate::data) password_hashed: bool,
+    pub(in crate::data) deleted: Option<NaiveDateTime>,
+    pub(in crate::data) workspace_id: Option<String>,
+    pub(in crate::data) revision: i64,
+    pub(in crate::data) updated: NaiveDateTime,
+    pub(in crate::data) length: i32,
+    pub(in crate::data) digest: Vec<u8>
+}
+
+impl RawClip {
+    pub fn is_deleted(&self) -> bool {
+        self.deleted.is_some()
+    }
+
+    pub fn workspace_id(&self) -> Option<&str> {
+        self.workspace_id.as_deref()
+    }
+}
+
+impl TryFrom<RawClip> for crate::domain::clip::RawClip {
+    type Error = ClipError;
+
+    fn try_from(clip: RawClip) -> Result<Self, Self::Error> {
+        use crate::domain::clip::field;
+        Ok(
+            Self {
+                shortcode: field::ShortCode::from(clip.shortcode.as_str()),
+                expires: field::Expires::new(clip.expires.map(Time::from_naive_utc)),
+                password: stored_password(clip.password, clip.password_hashed)?,
+                revision: field::Revision::new(u64::try_from(clip.revision)?),
+                updated: field::Updated::new(Time::from_naive_utc(clip.updated)),
+                length: u64::try_from(clip.length)?,
+                digest: clip.digest
             }
         )
     }
@@ -50,6 +135,32 @@ impl From<crate::service::ask::GetClip> for GetClip {
     }
 }
 
+/// A clip to delete, which only its creator or an admin may do.
+pub struct DeleteClip {
+    pub(in crate::data) shortcode: String,
+    pub(in crate::data) owner: Option<Vec<u8>>,
+    pub(in crate::data) user_id: Option<String>,
+    pub(in crate::data) admin: bool
+}
+
+impl DeleteClip {
+    /// Deletes the clip on behalf of `requester`, or of anyone when `admin` is set.
+    pub fn new(req: crate::service::ask::GetClip, admin: bool) -> Self {
+        use crate::service::ask::Requester;
+        let (owner, user_id) = match req.requester {
+            Requester::ApiKey(api_key) => (Some(api_key.into_inner()), None),
+            Requester::User(user_id) => (None, Some(String::from(user_id.into_inner()))),
+            Requester::Anonymous => (None, None)
+        };
+        Self {
+            shortcode: req.shortcode.into_inner(),
+            owner,
+            user_id,
+            admin
+        }
+    }
+}
+
 impl From<ShortCode> for GetClip {
     fn from(shortcode: ShortCode) -> Self {
         Self {
@@ -75,6 +186,10 @@ pub struct NewClip {
     pub(in crate::data) posted: NaiveDateTime,
     pub(in crate::data) expires: Option<NaiveDateTime>,
     pub(in crate::data) password: Option<String>,
+    pub(in crate::data) password_hashed: bool,
+    pub(in crate::data) owner: Option<Vec<u8>>,
+    pub(in crate::data) user_id: Option<String>,
+    pub(in crate::data) workspace_id: Option<String>,
 }
 
 impl From<crate::service::ask::NewClip> for NewClip {
@@ -84,13 +199,35 @@ impl From<crate::service::ask::NewClip> for NewClip {
             content: req.content.into_inner(),
             title: req.title.into_inner(),
             expires: req.expires.into_inner().map(|time|NaiveDateTime::from_timestamp(time.timestamp(), 0)),
+            password_hashed: req.password.is_hashed(),
             password: req.password.into_inner(),
             shortcode: ShortCode::default().into(),
-            posted: Utc::now().naive_utc()
+            posted: Utc::now().naive_utc(),
+            owner: req.owner.map(|api_key| api_key.into_inner()),
+            user_id: req.user.map(|user_id| user_id.into_inner().into()),
+            workspace_id: req.workspace.map(String::from)
         }
     }
 }
 
+impl NewClip {
+    /// A clip imported from an export under `shortcode`, posted when the export says it was.
+    pub fn imported(clip: ImportedClip, shortcode: ShortCode) -> Self {
+        Self {
+            clip_id: DbId::new().into(),
+            content: clip.content.into_inner(),
+            title: clip.title.into_inner(),
+            expires: clip.expires.into_inner().map(|time| time.into_inner().naive_utc()),
+            password: None,
+            password_hashed: false,
+            shortcode: shortcode.into(),
+            posted: clip.posted.map(|time| time.into_inner().naive_utc()).unwrap_or_else(|| Utc::now().naive_utc()),
+            owner: None,
+            user_id: None,
+            workspace_id: None
+        }
+    }
+}
 
 pub struct UpdateClip {
     // can't update id and posted date
@@ -99,16 +236,529 @@ pub struct UpdateClip {
     pub(in crate::data) title: Option<String>,
     pub(in crate::data) expires: Option<NaiveDateTime>,
     pub(in crate::data) password: Option<String>,
+    pub(in crate::data) password_hashed: bool,
+    pub(in crate::data) revisions: Option<Vec<i64>>,
 }
 
 impl From<crate::service::ask::UpdateClip> for UpdateClip {
     fn from(req: crate::service::ask::UpdateClip) -> Self {
         Self {
+            revisions: req.revisions.map(|revisions| {
+                revisions.into_iter().filter_map(|revision| i64::try_from(revision.into_inner()).ok()).collect()
+            }),
             content: req.content.into_inner(),
             title: req.title.into_inner(),
             expires: req.expires.into_inner().map(|time|NaiveDateTime::from_timestamp(time.timestamp(), 0)),
+            password_hashed: req.password.is_hashed(),
             password: req.password.into_inner(),
-            shortcode: ShortCode::default().into(),
+            shortcode: req.shortcode.into_inner(),
+        }
+    }
+}
+
+/// A clip matching a full-text search, without its content.
+#[derive(Debug, sqlx::FromRow)]
+pub struct ClipMatch {
+    pub(in crate::data) shortcode: String,
+    pub(in crate::data) title: Option<String>,
+    pub(in crate::data) posted: NaiveDateTime,
+    pub(in crate::data) expires: Option<NaiveDateTime>,
+    pub(in crate::data) hits: i64,
+    pub(in crate::data) snippet: String
+}
+
+impl TryFrom<ClipMatch> for crate::domain::clip::ClipMatch {
+    type Error = ClipError;
+
+    fn try_from(clip: ClipMatch) -> Result<Self, Self::Error> {
+        use crate::domain::clip::{field, ClipSummary};
+        Ok(
+            Self {
+                clip: ClipSummary {
+                    shortcode: field::ShortCode::from(clip.shortcode.as_str()),
+                    title: field::Title::new(clip.title),
+                    posted: field::Posted::new(Time::from_naive_utc(clip.posted)),
+                    expires: field::Expires::new(clip.expires.map(Time::from_naive_utc)),
+                    hits: field::Hits::new(u64::try_from(clip.hits)?)
+                },
+                snippet: clip.snippet
+            }
+        )
+    }
+}
+
+pub struct SearchClips {
+    pub(in crate::data) query: String,
+    pub(in crate::data) owner: Vec<u8>,
+    pub(in crate::data) offset: i64,
+    pub(in crate::data) limit: i64
+}
+
+impl From<crate::service::ask::SearchClips> for SearchClips {
+    fn from(req: crate::service::ask::SearchClips) -> Self {
+        Self {
+            query: req.query,
+            owner: req.owner.into_inner(),
+            offset: req.offset.into(),
+            limit: req.limit.into()
+        }
+    }
+}
+
+/// Everything about a clip which goes into a backup.
+#[derive(Debug, sqlx::FromRow)]
+pub struct ClipBackup {
+    pub(in crate::data) clip_id: String,
+    pub(in crate::data) shortcode: String,
+    pub(in crate::data) content: String,
+    pub(in crate::data) title: Option<String>,
+    pub(in crate::data) posted: NaiveDateTime,
+    pub(in crate::data) expires: Option<NaiveDateTime>,
+    pub(in crate::data) password: Option<String>,
+    pub(in crate::data) password_hashed: bool,
+    pub(in crate::data) hits: i64,
+    pub(in crate::data) deleted: Option<NaiveDateTime>,
+    pub(in crate::data) owner: Option<String>,
+    pub(in crate::data) user_id: Option<String>,
+    pub(in crate::data) workspace_id: Option<String>,
+    pub(in crate::data) revision: i64,
+    pub(in crate::data) updated: Option<NaiveDateTime>
+}
+
+/// Passwords which are still in the clear are hashed on the way out.
+impl TryFrom<ClipBackup> for BackedUpClip {
+    type Error = ClipError;
+
+    fn try_from(clip: ClipBackup) -> Result<Self, Self::Error> {
+        Ok(Self {
+            clip_id: clip.clip_id,
+            shortcode: ShortCode::from(clip.shortcode),
+            title: clip.title,
+            content: clip.content,
+            posted: Time::from_naive_utc(clip.posted),
+            updated: clip.updated.map(Time::from_naive_utc),
+            expires: clip.expires.map(Time::from_naive_utc),
+            deleted: clip.deleted.map(Time::from_naive_utc),
+            hits: u64::try_from(clip.hits)?,
+            revision: clip.revision,
+            password_hash: stored_password(clip.password, clip.password_hashed)?.hash()?,
+            owner: clip.owner,
+            user_id: clip.user_id,
+            workspace_id: clip.workspace_id
+        })
+    }
+}
+
+impl From<BackedUpClip> for ClipBackup {
+    fn from(clip: BackedUpClip) -> Self {
+        Self {
+            clip_id: clip.clip_id,
+            shortcode: clip.shortcode.into_inner(),
+            content: clip.content,
+            title: clip.title,
+            posted: clip.posted.into_inner().naive_utc(),
+            expires: clip.expires.map(|time| time.into_inner().naive_utc()),
+            password_hashed: clip.password_hash.is_some(),
+            password: clip.password_hash,
+            hits: i64::try_from(clip.hits).unwrap_or(i64::MAX),
+            deleted: clip.deleted.map(|time| time.into_inner().naive_utc()),
+            owner: clip.owner,
+            user_id: clip.user_id,
+            workspace_id: clip.workspace_id,
+            revision: clip.revision,
+            updated: clip.updated.map(|time| time.into_inner().naive_utc())
+        }
+    }
+}
+
+pub struct ExportClips {
+    pub(in crate::data) owner: Option<Vec<u8>>,
+    pub(in crate::data) after: Option<String>,
+    pub(in crate::data) limit: i64
+}
+
+impl From<crate::service::ask::ExportClips> for ExportClips {
+    fn from(req: crate::service::ask::ExportClips) -> Self {
+        Self {
+            owner: req.owner.map(|api_key| api_key.into_inner()),
+            after: req.after,
+            limit: req.limit
+        }
+    }
+}
+
+/// The listed details of a clip, without its content.
+#[derive(Debug, sqlx::FromRow)]
+pub struct ClipSummary {
+    pub(in crate::data) shortcode: String,
+    pub(in crate::data) title: Option<String>,
+    pub(in crate::data) posted: NaiveDateTime,
+    pub(in crate::data) expires: Option<NaiveDateTime>,
+    pub(in crate::data) hits: i64
+}
+
+impl TryFrom<ClipSummary> for crate::domain::clip::ClipSummary {
+    type Error = ClipError;
+
+    fn try_from(clip: ClipSummary) -> Result<Self, Self::Error> {
+        use crate::domain::clip::field;
+        Ok(
+            Self {
+                shortcode: field::ShortCode::from(clip.shortcode.as_str()),
+                title: field::Title::new(clip.title),
+                posted: field::Posted::new(Time::from_naive_utc(clip.posted)),
+                expires: field::Expires::new(clip.expires.map(Time::from_naive_utc)),
+                hits: field::Hits::new(u64::try_from(clip.hits)?)
+            }
+        )
+    }
+}
+
+pub struct ListClips {
+    pub(in crate::data) owner: Vec<u8>,
+    pub(in crate::data) posted: Option<NaiveDateTime>,
+    pub(in crate::data) shortcode: Option<String>,
+    pub(in crate::data) limit: i64
+}
+
+impl From<crate::service::ask::ListClips> for ListClips {
+    fn from(req: crate::service::ask::ListClips) -> Self {
+        let (posted, shortcode) = match req.cursor {
+            Some(cursor) => (
+                Some(cursor.posted.into_inner().naive_utc()),
+                Some(cursor.shortcode.into_inner())
+            ),
+            None => (None, None)
+        };
+        Self {
+            owner: req.owner.into_inner(),
+            posted,
+            shortcode,
+            limit: req.limit.into()
+        }
+    }
+}
+
+pub struct ListUserClips {
+    pub(in crate::data) user_id: String,
+    pub(in crate::data) posted: Option<NaiveDateTime>,
+    pub(in crate::data) shortcode: Option<String>,
+    pub(in crate::data) limit: i64
+}
+
+impl From<crate::service::ask::ListUserClips> for ListUserClips {
+    fn from(req: crate::service::ask::ListUserClips) -> Self {
+        let (posted, shortcode) = match req.cursor {
+            Some(cursor) => (
+                Some(cursor.posted.into_inner().naive_utc()),
+                Some(cursor.shortcode.into_inner())
+            ),
+            None => (None, None)
+        };
+        Self {
+            user_id: req.user_id.into_inner().into(),
+            posted,
+            shortcode,
+            limit: req.limit.into()
+        }
+    }
+}
+
+pub struct ListWorkspaceClips {
+    pub(in crate::data) workspace_id: String,
+    pub(in crate::data) posted: Option<NaiveDateTime>,
+    pub(in crate::data) shortcode: Option<String>,
+    pub(in crate::data) limit: i64
+}
+
+impl From<crate::service::ask::ListWorkspaceClips> for ListWorkspaceClips {
+    fn from(req: crate::service::ask::ListWorkspaceClips) -> Self {
+        let (posted, shortcode) = match req.cursor {
+            Some(cursor) => (
+                Some(cursor.posted.into_inner().naive_utc()),
+                Some(cursor.shortcode.into_inner())
+            ),
+            None => (None, None)
+        };
+        Self {
+            workspace_id: req.workspace_id.into(),
+            posted,
+            shortcode,
+            limit: req.limit.into()
+        }
+    }
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct User {
+    pub(in crate::data) user_id: String,
+    pub(in crate::data) username: String,
+    pub(in crate::data) password_hash: Option<String>
+}
+
+impl User {
+    /// The Argon2 hash of the password, needed to log in. Users who only log in through
+    /// single sign-on have none.
+    pub fn password_hash(&self) -> Option<&str> {
+        self.password_hash.as_deref()
+    }
+}
+
+impl TryFrom<User> for crate::domain::user::User {
+    type Error = UserError;
+
+    fn try_from(user: User) -> Result<Self, Self::Error> {
+        use crate::domain::user::field;
+        use std::str::FromStr;
+        Ok(
+            Self {
+                user_id: field::UserId::new(DbId::from_str(user.user_id.as_str())?),
+                username: field::Username::new(user.username.as_str())?
+            }
+        )
+    }
+}
+
+pub struct NewUser {
+    pub(in crate::data) user_id: String,
+    pub(in crate::data) username: String,
+    pub(in crate::data) password_hash: Option<String>,
+    pub(in crate::data) created: NaiveDateTime
+}
+
+impl NewUser {
+    pub fn new(username: crate::domain::user::field::Username, password_hash: String) -> Self {
+        Self {
+            user_id: DbId::new().into(),
+            username: username.into_inner(),
+            password_hash: Some(password_hash),
+            created: Utc::now().naive_utc()
+        }
+    }
+
+    /// A user who logs in through single sign-on, so has no password.
+    pub fn without_password(username: crate::domain::user::field::Username) -> Self {
+        Self {
+            user_id: DbId::new().into(),
+            username: username.into_inner(),
+            password_hash: None,
+            created: Utc::now().naive_utc()
+        }
+    }
+}
+
+/// An API key minted by a user. The key itself is only shown once, when it is minted, so
+/// it is identified by a fingerprint.
+#[derive(Debug, sqlx::FromRow)]
+pub struct UserApiKey {
+    pub(in crate::data) fingerprint: String,
+    pub(in crate::data) created: Option<NaiveDateTime>,
+    pub(in crate::data) scopes: Vec<String>
+}
+
+impl From<UserApiKey> for crate::domain::user::ApiKeyInfo {
+    fn from(key: UserApiKey) -> Self {
+        Self {
+            fingerprint: key.fingerprint,
+            created: key.created.map(Time::from_naive_utc),
+            scopes: key.scopes.iter().filter_map(|scope| scope.parse().ok()).collect()
+        }
+    }
+}
+
+pub struct NewWorkspace {
+    pub(in crate::data) workspace_id: String,
+    pub(in crate::data) name: String,
+    pub(in crate::data) created: NaiveDateTime
+}
+
+impl NewWorkspace {
+    pub fn new(name: crate::domain::workspace::field::WorkspaceName) -> Self {
+        Self {
+            workspace_id: DbId::new().into(),
+            name: name.into_inner(),
+            created: Utc::now().naive_utc()
+        }
+    }
+}
+
+/// A workspace along with the role of one of its members.
+#[derive(Debug, sqlx::FromRow)]
+pub struct Membership {
+    pub(in crate::data) workspace_id: String,
+    pub(in crate::data) name: String,
+    pub(in crate::data) role: String
+}
+
+impl TryFrom<Membership> for crate::domain::workspace::Membership {
+    type Error = WorkspaceError;
+
+    fn try_from(membership: Membership) -> Result<Self, Self::Error> {
+        use crate::domain::workspace::{field, Workspace};
+        use std::str::FromStr;
+        Ok(
+            Self {
+                workspace: Workspace {
+                    workspace_id: field::WorkspaceId::from_str(membership.workspace_id.as_str())?,
+                    name: field::WorkspaceName::new(membership.name.as_str())?
+                },
+                role: membership.role.parse()?
+            }
+        )
+    }
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct WorkspaceMember {
+    pub(in crate::data) username: String,
+    pub(in crate::data) role: String
+}
+
+impl TryFrom<WorkspaceMember> for crate::domain::workspace::Member {
+    type Error = crate::ServiceError;
+
+    fn try_from(member: WorkspaceMember) -> Result<Self, Self::Error> {
+        Ok(
+            Self {
+                username: crate::domain::user::field::Username::new(member.username.as_str())?,
+                role: member.role.parse()?
+            }
+        )
+    }
+}
+
+/// The views of a clip in one hour, for a given route, user agent class and referrer.
+#[derive(Debug, sqlx::FromRow)]
+pub struct ClipView {
+    pub(in crate::data) hour: NaiveDateTime,
+    pub(in crate::data) route: String,
+    pub(in crate::data) agent: String,
+    pub(in crate::data) referrer: String,
+    pub(in crate::data) views: i64
+}
+
+impl TryFrom<ClipView> for (DateTime<Utc>, View, u64) {
+    type Error = ClipError;
+
+    fn try_from(view: ClipView) -> Result<Self, Self::Error> {
+        use std::str::FromStr;
+        let referrer = match view.referrer.as_str() {
+            "" => None,
+            _ => Some(view.referrer)
+        };
+        Ok((
+            Time::from_naive_utc(view.hour).into_inner(),
+            View::new(ViewRoute::from_str(&view.route)?, AgentClass::from_str(&view.agent)?, referrer),
+            u64::try_from(view.views)?
+        ))
+    }
+}
+
+pub struct NewWebhook {
+    pub(in crate::data) webhook_id: String,
+    pub(in crate::data) api_key: Vec<u8>,
+    pub(in crate::data) url: String,
+    pub(in crate::data) secret: String,
+    pub(in crate::data) events: Vec<String>,
+    pub(in crate::data) created: NaiveDateTime
+}
+
+impl NewWebhook {
+    pub fn new(
+        api_key: crate::web::api::ApiKey,
+        url: crate::domain::webhook::WebhookUrl,
+        secret: crate::domain::webhook::WebhookSecret,
+        events: &[crate::domain::webhook::WebhookEvent]
+    ) -> Self {
+        Self {
+            webhook_id: DbId::new().into(),
+            api_key: api_key.into_inner(),
+            url: url.into_inner(),
+            secret: secret.into_inner(),
+            events: events.iter().map(|event| event.as_str().to_owned()).collect(),
+            created: Utc::now().naive_utc()
+        }
+    }
+}
+
+/// A webhook, without the secret its deliveries are signed with.
+#[derive(Debug, sqlx::FromRow)]
+pub struct Webhook {
+    pub(in crate::data) webhook_id: String,
+    pub(in crate::data) url: String,
+    pub(in crate::data) events: Vec<String>,
+    pub(in crate::data) created: NaiveDateTime
+}
+
+impl TryFrom<Webhook> for crate::domain::webhook::Webhook {
+    type Error = WebhookError;
+
+    fn try_from(webhook: Webhook) -> Result<Self, Self::Error> {
+        Ok(
+            Self {
+                webhook_id: webhook.webhook_id,
+                url: webhook.url,
+                events: webhook.events.iter().map(|event| event.parse()).collect::<Result<_, _>>()?,
+                created: Time::from_naive_utc(webhook.created)
+            }
+        )
+    }
+}
+
+/// An entry of the delivery log of a webhook.
+#[derive(Debug, sqlx::FromRow)]
+pub struct WebhookDelivery {
+    pub(in crate::data) delivery_id: i64,
+    pub(in crate::data) event: String,
+    pub(in crate::data) shortcode: String,
+    pub(in crate::data) created: NaiveDateTime,
+    pub(in crate::data) attempts: i32,
+    pub(in crate::data) delivered: Option<NaiveDateTime>,
+    pub(in crate::data) next_attempt: Option<NaiveDateTime>,
+    pub(in crate::data) status: Option<i32>,
+    pub(in crate::data) error: Option<String>
+}
+
+impl TryFrom<WebhookDelivery> for crate::domain::webhook::Delivery {
+    type Error = WebhookError;
+
+    fn try_from(delivery: WebhookDelivery) -> Result<Self, Self::Error> {
+        Ok(
+            Self {
+                delivery_id: delivery.delivery_id,
+                event: delivery.event.parse()?,
+                shortcode: ShortCode::from(delivery.shortcode),
+                created: Time::from_naive_utc(delivery.created),
+                attempts: delivery.attempts.max(0) as u32,
+                delivered: delivery.delivered.map(Time::from_naive_utc),
+                next_attempt: delivery.next_attempt.map(Time::from_naive_utc),
+                status: delivery.status.and_then(|status| u16::try_from(status).ok()),
+                error: delivery.error
+            }
+        )
+    }
+}
+
+/// A delivery claimed to be attempted, along with where to send it.
+#[derive(Debug, sqlx::FromRow)]
+pub struct PendingDelivery {
+    pub(in crate::data) delivery_id: i64,
+    pub(in crate::data) url: String,
+    pub(in crate::data) secret: String,
+    pub(in crate::data) event: String,
+    pub(in crate::data) payload: String,
+    pub(in crate::data) attempts: i32
+}
+
+impl From<PendingDelivery> for crate::domain::webhook::PendingDelivery {
+    fn from(delivery: PendingDelivery) -> Self {
+        Self {
+            delivery_id: delivery.delivery_id,
+            url: delivery.url,
+            secret: delivery.secret,
+            event: delivery.event,
+            payload: delivery.payload,
+            attempts: delivery.attempts.max(0) as u32
         }
     }
 }
@@ -1,31 +1,104 @@
 use super::model;
-use crate::data::{DataError, DatabasePool};
+use crate::data::{DataError, DatabasePool, Transaction};
+use crate::domain::stats::View;
 use crate::ShortCode;
-use sqlx::Row;
+use chrono::{NaiveDateTime, Utc};
 use crate::web::api::ApiKey;
 
 type Result<T> = std::result::Result<T, DataError>;
 
-/// Updates the database and increases the hits field. A hit is an access to a clip.
-pub async fn increase_hit_count(
-    shortcode: &ShortCode,
-    hits: u32,
-    pool: &DatabasePool
+/// Postgres accepts at most 65535 bind parameters in a statement, and every hit takes two.
+const HITS_PER_STATEMENT: usize = 30_000;
+
+/// Updates the database and increases the hits field of every clip in `hits`, batching them
+/// into as few statements as possible. A hit is an access to a clip. Returns the new hit
+/// count of every clip which still exists.
+pub async fn increase_hit_counts(
+    hits: &[(ShortCode, u32)],
+    transaction: &mut Transaction<'_>
+) -> Result<Vec<(ShortCode, i64)>> {
+    let mut totals = Vec::with_capacity(hits.len());
+    for batch in hits.chunks(HITS_PER_STATEMENT) {
+        let values = (0..batch.len())
+            .map(|i| format!("(${}::TEXT, ${}::BIGINT)", i * 2 + 1, i * 2 + 2))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let sql = format!(
+            r#"UPDATE clips
+            SET hits = clips.hits + batch.hits
+            FROM (VALUES {}) AS batch(shortcode, hits)
+            WHERE clips.shortcode = batch.shortcode
+            RETURNING clips.shortcode, clips.hits"#,
+            values
+        );
+
+        let mut query = sqlx::query_as::<_, (String, i64)>(&sql);
+        for (shortcode, count) in batch {
+            query = query.bind(shortcode.as_str()).bind(*count as i64);
+        }
+        let rows = query.fetch_all(&mut *transaction).await?;
+        totals.extend(rows.into_iter().map(|(shortcode, hits)| (ShortCode::from(shortcode), hits)));
+    }
+    Ok(totals)
+}
+
+
+/// Adds the given hourly view counts to the access log of each clip, batching them into
+/// as few statements as possible. Views of clips which no longer exist are ignored.
+pub async fn record_views(
+    views: &[((ShortCode, NaiveDateTime, View), u32)],
+    transaction: &mut Transaction<'_>
 ) -> Result<()> {
-    let shortcode = shortcode.as_str();
+    const PARAMS_PER_VIEW: usize = 6;
 
-    Ok(sqlx::query!(
-        r#"UPDATE clips
-        SET hits = hits + $1
-        WHERE shortcode = $2"#,
-        hits as i64,
-        shortcode
-    )
-        .execute(pool)
-        .await
-        .map(|_| ())?)
+    for batch in views.chunks(HITS_PER_STATEMENT * 2 / PARAMS_PER_VIEW) {
+        let values = (0..batch.len())
+            .map(|i| {
+                let p = i * PARAMS_PER_VIEW;
+                format!(
+                    "(${}::TEXT, ${}::TIMESTAMP, ${}::TEXT, ${}::TEXT, ${}::TEXT, ${}::BIGINT)",
+                    p + 1, p + 2, p + 3, p + 4, p + 5, p + 6
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        let sql = format!(
+            r#"INSERT INTO clip_views (shortcode, hour, route, agent, referrer, views)
+            SELECT batch.* FROM (VALUES {}) AS batch(shortcode, hour, route, agent, referrer, views)
+            JOIN clips ON clips.shortcode = batch.shortcode
+            ON CONFLICT (shortcode, hour, route, agent, referrer)
+            DO UPDATE SET views = clip_views.views + EXCLUDED.views"#,
+            values
+        );
+
+        let mut query = sqlx::query(&sql);
+        for ((shortcode, hour, view), count) in batch {
+            query = query
+                .bind(shortcode.as_str())
+                .bind(*hour)
+                .bind(view.route.as_str())
+                .bind(view.agent.as_str())
+                .bind(view.referrer.as_deref().unwrap_or_default())
+                .bind(*count as i64);
+        }
+        query.execute(&mut *transaction).await?;
+    }
+    Ok(())
 }
 
+/// Gets the hourly view counts of a clip, oldest first.
+pub async fn get_clip_views<M: Into<model::GetClip>>(
+    model: M,
+    pool: &DatabasePool
+) -> Result<Vec<model::ClipView>> {
+    let model = model.into();
+    Ok(sqlx::query_as!(
+        model::ClipView,
+        r#"SELECT hour, route, agent, referrer, views FROM clip_views
+        WHERE shortcode = $1 ORDER BY hour"#,
+        model.shortcode
+    ).fetch_all(pool).await?)
+}
 
 pub async fn get_clip<M: Into<model::GetClip>>(
     model: M,
@@ -35,11 +108,49 @@ pub async fn get_clip<M: Into<model::GetClip>>(
     let shortcode = model.shortcode.as_str();
     Ok(sqlx::query_as!(
         model::Clip,
-        r#"SELECT * FROM clips WHERE shortcode = $1"#,
+        r#"SELECT clip_id, shortcode, content, title, posted, expires, password, password_hashed, hits, deleted,
+            user_id, workspace_id, revision, COALESCE(updated, posted) AS "updated!"
+        FROM clips WHERE shortcode = $1"#,
         shortcode
     ).fetch_one(pool).await?)
 }
 
+/// Gets a clip without its content, which is read with [`get_clip_content`] instead.
+pub async fn get_raw_clip<M: Into<model::GetClip>>(
+    model: M,
+    pool: &DatabasePool,
+) -> Result<model::RawClip> {
+    let model = model.into();
+    Ok(sqlx::query_as!(
+        model::RawClip,
+        r#"SELECT shortcode, expires, password, password_hashed, deleted, workspace_id, revision,
+            COALESCE(updated, posted) AS "updated!",
+            octet_length(content) AS "length!",
+            sha256(convert_to(content, 'UTF8')) AS "digest!"
+        FROM clips WHERE shortcode = $1"#,
+        model.shortcode
+    ).fetch_one(pool).await?)
+}
+
+/// Reads up to `length` bytes of the content of a clip starting at byte `offset`, as long as
+/// the clip is still at `revision` and wasn't deleted.
+pub async fn get_clip_content(
+    shortcode: &str,
+    revision: i64,
+    offset: i32,
+    length: i32,
+    pool: &DatabasePool
+) -> Result<Vec<u8>> {
+    Ok(sqlx::query!(
+        r#"SELECT substring(convert_to(content, 'UTF8') FROM $3 + 1 FOR $4) AS "chunk!"
+        FROM clips WHERE shortcode = $1 AND revision = $2 AND deleted IS NULL"#,
+        shortcode,
+        revision,
+        offset,
+        length
+    ).fetch_one(pool).await?.chunk)
+}
+
 pub async fn new_clip<M: Into<model::NewClip>>(
     model: M,
     pool: &DatabasePool
@@ -54,8 +165,14 @@ pub async fn new_clip<M: Into<model::NewClip>>(
             posted,
             expires,
             password,
-            hits)
-           VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#,
+            password_hashed,
+            hits,
+            owner,
+            user_id,
+            workspace_id)
+           VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10,
+            COALESCE($11, (SELECT user_id FROM api_keys WHERE api_key = $10)),
+            COALESCE($12, (SELECT workspace_id FROM api_keys WHERE api_key = $10)))"#,
         model.clip_id,
         model.shortcode,
         model.content,
@@ -63,39 +180,318 @@ pub async fn new_clip<M: Into<model::NewClip>>(
         model.posted,
         model.expires,
         model.password,
-        0
+        model.password_hashed,
+        0,
+        model.owner,
+        model.user_id,
+        model.workspace_id
     ).execute(pool).await?;
 
     get_clip(model.shortcode, pool).await
 
 }
 
+/// Saves imported clips, batching them into as few statements as possible. Clips whose
+/// shortcode is already taken are left out, and the shortcodes of the saved ones returned.
+pub async fn import_clips(
+    clips: &[model::NewClip],
+    transaction: &mut Transaction<'_>
+) -> Result<Vec<String>> {
+    const PARAMS_PER_CLIP: usize = 6;
+
+    let mut saved = Vec::with_capacity(clips.len());
+    for batch in clips.chunks(HITS_PER_STATEMENT * 2 / PARAMS_PER_CLIP) {
+        let values = (0..batch.len())
+            .map(|i| {
+                let p = i * PARAMS_PER_CLIP;
+                format!(
+                    "(${}::TEXT, ${}::TEXT, ${}::TEXT, ${}::TEXT, ${}::TIMESTAMP, ${}::TIMESTAMP)",
+                    p + 1, p + 2, p + 3, p + 4, p + 5, p + 6
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        let sql = format!(
+            r#"INSERT INTO clips (clip_id, shortcode, content, title, posted, expires, hits)
+            SELECT batch.*, 0 FROM (VALUES {}) AS batch(clip_id, shortcode, content, title, posted, expires)
+            ON CONFLICT (shortcode) DO NOTHING
+            RETURNING shortcode"#,
+            values
+        );
+
+        let mut query = sqlx::query_as::<_, (String,)>(&sql);
+        for clip in batch {
+            query = query
+                .bind(&clip.clip_id)
+                .bind(&clip.shortcode)
+                .bind(&clip.content)
+                .bind(&clip.title)
+                .bind(clip.posted)
+                .bind(clip.expires);
+        }
+        let rows = query.fetch_all(&mut *transaction).await?;
+        saved.extend(rows.into_iter().map(|(shortcode,)| shortcode));
+    }
+    Ok(saved)
+}
+
+/// Lists the clips to back up, all of them or only those of an API key, in the order of
+/// their ids and starting after the given id if any.
+pub async fn export_clips<M: Into<model::ExportClips>>(
+    model: M,
+    pool: &DatabasePool
+) -> Result<Vec<model::ClipBackup>> {
+    let model = model.into();
+    Ok(sqlx::query_as!(
+        model::ClipBackup,
+        r#"SELECT clip_id, shortcode, content, title, posted, expires, password, password_hashed, hits, deleted,
+            encode(sha256(owner), 'hex') AS "owner?", user_id, workspace_id, revision, updated
+        FROM clips
+        WHERE ($1::BYTEA IS NULL OR owner = $1)
+            AND ($2::TEXT IS NULL OR clip_id > $2)
+        ORDER BY clip_id
+        LIMIT $3"#,
+        model.owner,
+        model.after,
+        model.limit
+    ).fetch_all(pool).await?)
+}
+
+/// Saves backed up clips, batching them into as few statements as possible. Clips whose id or
+/// shortcode is taken are left out, and so are those whose workspace doesn't exist, rather
+/// than made public. The users and owners of clips are forgotten when they don't exist. Their
+/// passwords are saved as the hashes the backup holds. Returns the ids of the saved clips.
+pub async fn restore_clips(
+    clips: &[model::ClipBackup],
+    transaction: &mut Transaction<'_>
+) -> Result<Vec<String>> {
+    const PARAMS_PER_CLIP: usize = 14;
+
+    let mut saved = Vec::with_capacity(clips.len());
+    for batch in clips.chunks(HITS_PER_STATEMENT * 2 / PARAMS_PER_CLIP) {
+        let values = (0..batch.len())
+            .map(|i| {
+                let p = i * PARAMS_PER_CLIP;
+                format!(
+                    "(${}::TEXT, ${}::TEXT, ${}::TEXT, ${}::TEXT, ${}::TIMESTAMP, ${}::TIMESTAMP, ${}::TEXT, \
+                    ${}::BIGINT, ${}::TIMESTAMP, ${}::TEXT, ${}::TEXT, ${}::TEXT, ${}::BIGINT, ${}::TIMESTAMP)",
+                    p + 1, p + 2, p + 3, p + 4, p + 5, p + 6, p + 7, p + 8, p + 9, p + 10, p + 11, p + 12, p + 13,
+                    p + 14
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        let sql = format!(
+            r#"INSERT INTO clips (clip_id, shortcode, content, title, posted, expires, password, password_hashed,
+                hits, deleted, owner, user_id, workspace_id, revision, updated)
+            SELECT batch.clip_id, batch.shortcode, batch.content, batch.title, batch.posted, batch.expires,
+                batch.password, batch.password IS NOT NULL, batch.hits, batch.deleted, api_keys.api_key, users.user_id,
+                batch.workspace_id, batch.revision, batch.updated
+            FROM (VALUES {}) AS batch(clip_id, shortcode, content, title, posted, expires, password, hits, deleted,
+                owner, user_id, workspace_id, revision, updated)
+            LEFT JOIN api_keys ON encode(sha256(api_keys.api_key), 'hex') = batch.owner
+            LEFT JOIN users ON users.user_id = batch.user_id
+            WHERE batch.workspace_id IS NULL
+                OR EXISTS (SELECT 1 FROM workspaces WHERE workspaces.workspace_id = batch.workspace_id)
+            ON CONFLICT DO NOTHING
+            RETURNING clip_id"#,
+            values
+        );
+
+        let mut query = sqlx::query_as::<_, (String,)>(&sql);
+        for clip in batch {
+            query = query
+                .bind(&clip.clip_id)
+                .bind(&clip.shortcode)
+                .bind(&clip.content)
+                .bind(&clip.title)
+                .bind(clip.posted)
+                .bind(clip.expires)
+                .bind(&clip.password)
+                .bind(clip.hits)
+                .bind(clip.deleted)
+                .bind(&clip.owner)
+                .bind(&clip.user_id)
+                .bind(&clip.workspace_id)
+                .bind(clip.revision)
+                .bind(clip.updated);
+        }
+        let rows = query.fetch_all(&mut *transaction).await?;
+        saved.extend(rows.into_iter().map(|(clip_id,)| clip_id));
+    }
+    Ok(saved)
+}
+
+/// Returns which of the given clip ids exist.
+pub async fn existing_clip_ids(clip_ids: &[String], transaction: &mut Transaction<'_>) -> Result<Vec<String>> {
+    Ok(sqlx::query_scalar!("SELECT clip_id FROM clips WHERE clip_id = ANY($1)", clip_ids)
+        .fetch_all(&mut *transaction)
+        .await?)
+}
+
+/// Whether [`update_clip`] edited the clip.
+pub enum UpdateStatus {
+    Updated(Box<model::Clip>),
+    /// The clip is not at any of the expected revisions, so it was left alone.
+    Stale
+}
+
+/// Edits a [`Clip`](`crate::Clip`) and bumps its revision. When revisions are expected, the clip
+/// is only edited if it is still at one of them, so concurrent edits can't overwrite each other.
+/// A password sent back as it is stored stays what it was, so the hash of a restored password
+/// is never taken for the password itself.
 pub async fn update_clip<M:Into<model::UpdateClip>>(
     model: M,
     pool: &DatabasePool
-) -> Result<model::Clip> {
+) -> Result<UpdateStatus> {
     let model = model.into();
-    let _ = sqlx::query!(
+    let result = sqlx::query!(
         r#"UPDATE clips SET
             content = $1,
             title = $2,
             expires = $3,
-            password = $4
-        WHERE shortcode = $5"#,
+            password = $4,
+            password_hashed = CASE WHEN password IS NOT DISTINCT FROM $4 THEN password_hashed ELSE $5 END,
+            revision = revision + 1,
+            updated = $6
+        WHERE shortcode = $7 AND deleted IS NULL AND ($8::BIGINT[] IS NULL OR revision = ANY($8))"#,
         model.content,
         model.title,
         model.expires,
         model.password,
-        model.shortcode
+        model.password_hashed,
+        Utc::now().naive_utc(),
+        model.shortcode,
+        model.revisions.as_deref()
     ).execute(pool).await?;
 
-    get_clip(model.shortcode, pool).await
+    let clip = get_clip(model.shortcode, pool).await?;
+    if result.rows_affected() == 0 && model.revisions.is_some() && !clip.is_deleted() {
+        Ok(UpdateStatus::Stale)
+    } else {
+        Ok(UpdateStatus::Updated(Box::new(clip)))
+    }
+}
+
+/// Lists the clips created with an API key, newest first, starting after the cursor if any.
+pub async fn list_clips<M: Into<model::ListClips>>(
+    model: M,
+    pool: &DatabasePool
+) -> Result<Vec<model::ClipSummary>> {
+    let model = model.into();
+    Ok(sqlx::query_as!(
+        model::ClipSummary,
+        r#"SELECT shortcode, title, posted, expires, hits FROM clips
+        WHERE owner = $1
+            AND deleted IS NULL
+            AND ($2::TIMESTAMP IS NULL OR (posted, shortcode) < ($2, $3))
+        ORDER BY posted DESC, shortcode DESC
+        LIMIT $4"#,
+        model.owner,
+        model.posted,
+        model.shortcode,
+        model.limit
+    ).fetch_all(pool).await?)
+}
+
+/// Lists the clips created by a user, newest first, starting after the cursor if any.
+pub async fn list_user_clips<M: Into<model::ListUserClips>>(
+    model: M,
+    pool: &DatabasePool
+) -> Result<Vec<model::ClipSummary>> {
+    let model = model.into();
+    Ok(sqlx::query_as!(
+        model::ClipSummary,
+        r#"SELECT shortcode, title, posted, expires, hits FROM clips
+        WHERE user_id = $1
+            AND deleted IS NULL
+            AND ($2::TIMESTAMP IS NULL OR (posted, shortcode) < ($2, $3))
+        ORDER BY posted DESC, shortcode DESC
+        LIMIT $4"#,
+        model.user_id,
+        model.posted,
+        model.shortcode,
+        model.limit
+    ).fetch_all(pool).await?)
+}
+
+/// Lists the clips of a workspace, newest first, starting after the cursor if any.
+pub async fn list_workspace_clips<M: Into<model::ListWorkspaceClips>>(
+    model: M,
+    pool: &DatabasePool
+) -> Result<Vec<model::ClipSummary>> {
+    let model = model.into();
+    Ok(sqlx::query_as!(
+        model::ClipSummary,
+        r#"SELECT shortcode, title, posted, expires, hits FROM clips
+        WHERE workspace_id = $1
+            AND deleted IS NULL
+            AND ($2::TIMESTAMP IS NULL OR (posted, shortcode) < ($2, $3))
+        ORDER BY posted DESC, shortcode DESC
+        LIMIT $4"#,
+        model.workspace_id,
+        model.posted,
+        model.shortcode,
+        model.limit
+    ).fetch_all(pool).await?)
+}
+
+/// Searches the title and content of the clips created with an API key, best matches first.
+/// Queries without any word to look up, such as `the` or `%`, are matched as they are with
+/// `ILIKE` instead, newest first. Password-protected clips are not indexed, and are left out
+/// of those matches too, so they never match, even for the key which created them.
+pub async fn search_clips<M: Into<model::SearchClips>>(
+    model: M,
+    pool: &DatabasePool
+) -> Result<Vec<model::ClipMatch>> {
+    let model = model.into();
+    let pattern = format!("%{}%", model.query.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_"));
+    Ok(sqlx::query_as!(
+        model::ClipMatch,
+        r#"SELECT shortcode, title, posted, expires, hits,
+            CASE WHEN numnode(query) > 0
+                THEN ts_headline('english', content, query, 'StartSel=**, StopSel=**, MaxFragments=1, MaxWords=20, MinWords=5')
+                ELSE left(content, 200)
+            END AS "snippet!"
+        FROM clips, websearch_to_tsquery('english', $1) AS query
+        WHERE owner = $2
+            AND deleted IS NULL
+            AND (expires IS NULL OR expires > $3)
+            AND CASE WHEN numnode(query) > 0
+                THEN search @@ query
+                ELSE password IS NULL AND (title ILIKE $6 OR content ILIKE $6)
+            END
+        ORDER BY CASE WHEN numnode(query) > 0 THEN ts_rank(search, query) END DESC NULLS LAST, posted DESC
+        OFFSET $4 LIMIT $5"#,
+        model.query,
+        model.owner,
+        Utc::now().naive_utc(),
+        model.offset,
+        model.limit,
+        pattern
+    ).fetch_all(pool).await?)
+}
+
+/// Runs a trivial query to check that the database is reachable.
+pub async fn ping(pool: &DatabasePool) -> Result<()> {
+    sqlx::query("SELECT 1").execute(pool).await?;
+    Ok(())
+}
+
+/// Returns the version of the schema applied to the database.
+pub async fn schema_version(pool: &DatabasePool) -> Result<i32> {
+    Ok(
+        sqlx::query!("SELECT version FROM schema_version")
+            .fetch_one(pool)
+            .await?
+            .version
+    )
 }
 
-/// Saves an [`ApiKey`].
-pub async fn save_api_key(api_key: ApiKey, pool: &DatabasePool) -> Result<ApiKey> {
+/// Saves an [`ApiKey`] with the scopes it is granted.
+pub async fn save_api_key(api_key: ApiKey, scopes: &[String], pool: &DatabasePool) -> Result<ApiKey> {
     let bytes = api_key.clone().into_inner();
-    let _ = sqlx::query!("INSERT INTO api_keys (api_key) VALUES ($1)", bytes)
+    let _ = sqlx::query!("INSERT INTO api_keys (api_key, scopes) VALUES ($1, $2)", bytes, scopes)
         .execute(pool)
         .await
         .map(|_| ())?;
@@ -124,25 +520,626 @@ pub async fn revoke_api_key(api_key: ApiKey, pool: &DatabasePool) -> Result<Revo
     )
 }
 
-/// Determines if the [`ApiKey`] is valid.
-pub async fn api_key_is_valid(api_key: ApiKey, pool: &DatabasePool) -> Result<bool> {
+/// Gets the scopes of an [`ApiKey`], or `None` if the key is not valid.
+pub async fn api_key_scopes(api_key: ApiKey, pool: &DatabasePool) -> Result<Option<Vec<String>>> {
+    let bytes = api_key.into_inner();
+    Ok(
+        sqlx::query!("SELECT scopes FROM api_keys WHERE api_key = $1", bytes)
+            .fetch_optional(pool)
+            .await?
+            .map(|row| row.scopes)
+    )
+}
+
+/// Saves a new user. Fails if the username is already taken.
+pub async fn new_user(model: model::NewUser, pool: &DatabasePool) -> Result<model::User> {
+    let _ = sqlx::query!(
+        r#"INSERT INTO users (user_id, username, password_hash, created) VALUES ($1, $2, $3, $4)"#,
+        model.user_id,
+        model.username,
+        model.password_hash,
+        model.created
+    ).execute(pool).await?;
+
+    get_user(&model.username, pool).await
+}
+
+/// Gets a user by username.
+pub async fn get_user(username: &str, pool: &DatabasePool) -> Result<model::User> {
+    Ok(sqlx::query_as!(
+        model::User,
+        r#"SELECT user_id, username, password_hash FROM users WHERE username = $1"#,
+        username
+    ).fetch_one(pool).await?)
+}
+
+/// Gets the user linked to the account of an OpenID Connect provider.
+pub async fn get_identity_user(issuer: &str, subject: &str, pool: &DatabasePool) -> Result<model::User> {
+    Ok(sqlx::query_as!(
+        model::User,
+        r#"SELECT users.user_id, users.username, users.password_hash
+        FROM user_identities JOIN users ON users.user_id = user_identities.user_id
+        WHERE user_identities.issuer = $1 AND user_identities.subject = $2"#,
+        issuer,
+        subject
+    ).fetch_one(pool).await?)
+}
+
+/// Creates a user linked to the account of an OpenID Connect provider, in a single statement
+/// so there can't be a user without its identity.
+pub async fn new_identity_user(
+    model: model::NewUser,
+    issuer: &str,
+    subject: &str,
+    pool: &DatabasePool
+) -> Result<model::User> {
+    let _ = sqlx::query!(
+        r#"WITH new_user AS (
+            INSERT INTO users (user_id, username, password_hash, created) VALUES ($1, $2, $3, $4)
+            RETURNING user_id
+        )
+        INSERT INTO user_identities (issuer, subject, user_id, created)
+        SELECT $5, $6, user_id, $4 FROM new_user"#,
+        model.user_id,
+        model.username,
+        model.password_hash,
+        model.created,
+        issuer,
+        subject
+    ).execute(pool).await?;
+
+    get_identity_user(issuer, subject, pool).await
+}
+
+/// Saves a session, identified by the hash of its token.
+pub async fn new_session(
+    token_hash: Vec<u8>,
+    user_id: String,
+    expires: NaiveDateTime,
+    pool: &DatabasePool
+) -> Result<()> {
+    let _ = sqlx::query!(
+        r#"INSERT INTO sessions (token_hash, user_id, expires) VALUES ($1, $2, $3)"#,
+        token_hash,
+        user_id,
+        expires
+    ).execute(pool).await?;
+    Ok(())
+}
+
+/// Gets the user of a session which has not expired yet.
+pub async fn get_session_user(token_hash: Vec<u8>, pool: &DatabasePool) -> Result<model::User> {
+    Ok(sqlx::query_as!(
+        model::User,
+        r#"SELECT users.user_id, users.username, users.password_hash
+        FROM sessions JOIN users ON users.user_id = sessions.user_id
+        WHERE sessions.token_hash = $1 AND sessions.expires > $2"#,
+        token_hash,
+        Utc::now().naive_utc()
+    ).fetch_one(pool).await?)
+}
+
+/// Deletes a session, logging its user out.
+pub async fn delete_session(token_hash: Vec<u8>, pool: &DatabasePool) -> Result<()> {
+    let _ = sqlx::query!("DELETE FROM sessions WHERE token_hash = $1", token_hash)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Removes the expired sessions.
+pub async fn purge_sessions(pool: &DatabasePool) -> Result<u64> {
+    Ok(
+        sqlx::query!("DELETE FROM sessions WHERE expires < $1", Utc::now().naive_utc())
+            .execute(pool)
+            .await?
+            .rows_affected()
+    )
+}
+
+/// Saves an [`ApiKey`] minted by a user.
+pub async fn save_user_api_key(
+    api_key: ApiKey,
+    user_id: String,
+    scopes: &[String],
+    pool: &DatabasePool
+) -> Result<ApiKey> {
     let bytes = api_key.clone().into_inner();
+    let _ = sqlx::query!(
+        "INSERT INTO api_keys (api_key, user_id, created, scopes) VALUES ($1, $2, $3, $4)",
+        bytes,
+        user_id,
+        Utc::now().naive_utc(),
+        scopes
+    ).execute(pool).await?;
+    Ok(api_key)
+}
+
+/// Lists the API keys minted by a user, newest first.
+pub async fn list_user_api_keys(user_id: String, pool: &DatabasePool) -> Result<Vec<model::UserApiKey>> {
+    Ok(sqlx::query_as!(
+        model::UserApiKey,
+        r#"SELECT encode(sha256(api_key), 'hex') AS "fingerprint!", created, scopes FROM api_keys
+        WHERE user_id = $1 ORDER BY created DESC"#,
+        user_id
+    ).fetch_all(pool).await?)
+}
+
+/// Revokes an API key minted by a user, identified by its fingerprint.
+pub async fn revoke_user_api_key(
+    user_id: String,
+    fingerprint: &str,
+    pool: &DatabasePool
+) -> Result<RevocationStatus> {
     Ok(
-        sqlx::query("SELECT COUNT(api_key) FROM api_keys WHERE api_key = $1")
-            .bind(bytes)
-            .fetch_one(pool)
+        sqlx::query!(
+            "DELETE FROM api_keys WHERE user_id = $1 AND encode(sha256(api_key), 'hex') = $2",
+            user_id,
+            fingerprint
+        )
+            .execute(pool)
             .await
-            .map(|row| {
-                let count: u32 = row.get(0);
-                count > 0
+            .map(|result| match result.rows_affected() {
+                0 => RevocationStatus::NotFound,
+                _ => RevocationStatus::Revoked
             })?,
     )
 }
 
-/// Deletes all expired [`Clips`](`crate::Clip`).
-pub async fn delete_expired(pool: &DatabasePool) -> Result<u64> {
+/// Creates a workspace, along with its first owner.
+pub async fn new_workspace(
+    model: model::NewWorkspace,
+    owner: String,
+    pool: &DatabasePool
+) -> Result<model::Membership> {
+    let _ = sqlx::query!(
+        r#"WITH new_workspace AS (
+            INSERT INTO workspaces (workspace_id, name, created) VALUES ($1, $2, $3)
+            RETURNING workspace_id
+        )
+        INSERT INTO workspace_members (workspace_id, user_id, role)
+        SELECT workspace_id, $4, 'owner' FROM new_workspace"#,
+        model.workspace_id,
+        model.name,
+        model.created,
+        owner
+    ).execute(pool).await?;
+
+    get_membership(model.workspace_id, owner, pool).await
+}
+
+/// Gets a workspace along with the role of one of its members.
+pub async fn get_membership(workspace_id: String, user_id: String, pool: &DatabasePool) -> Result<model::Membership> {
+    Ok(sqlx::query_as!(
+        model::Membership,
+        r#"SELECT workspaces.workspace_id, workspaces.name, workspace_members.role
+        FROM workspace_members JOIN workspaces ON workspaces.workspace_id = workspace_members.workspace_id
+        WHERE workspace_members.workspace_id = $1 AND workspace_members.user_id = $2"#,
+        workspace_id,
+        user_id
+    ).fetch_one(pool).await?)
+}
+
+/// Lists the workspaces of a user, by name.
+pub async fn list_user_workspaces(user_id: String, pool: &DatabasePool) -> Result<Vec<model::Membership>> {
+    Ok(sqlx::query_as!(
+        model::Membership,
+        r#"SELECT workspaces.workspace_id, workspaces.name, workspace_members.role
+        FROM workspace_members JOIN workspaces ON workspaces.workspace_id = workspace_members.workspace_id
+        WHERE workspace_members.user_id = $1
+        ORDER BY workspaces.name, workspaces.workspace_id"#,
+        user_id
+    ).fetch_all(pool).await?)
+}
+
+/// Lists the members of a workspace, owners first.
+pub async fn list_workspace_members(workspace_id: String, pool: &DatabasePool) -> Result<Vec<model::WorkspaceMember>> {
+    Ok(sqlx::query_as!(
+        model::WorkspaceMember,
+        r#"SELECT users.username, workspace_members.role
+        FROM workspace_members JOIN users ON users.user_id = workspace_members.user_id
+        WHERE workspace_members.workspace_id = $1
+        ORDER BY workspace_members.role = 'owner' DESC, users.username"#,
+        workspace_id
+    ).fetch_all(pool).await?)
+}
+
+/// Adds a user to a workspace. Nothing changes if they already are a member.
+pub async fn add_workspace_member(
+    workspace_id: String,
+    user_id: String,
+    role: &str,
+    pool: &DatabasePool
+) -> Result<()> {
+    let _ = sqlx::query!(
+        r#"INSERT INTO workspace_members (workspace_id, user_id, role) VALUES ($1, $2, $3)
+        ON CONFLICT DO NOTHING"#,
+        workspace_id,
+        user_id,
+        role
+    ).execute(pool).await?;
+    Ok(())
+}
+
+/// Removes a member from a workspace. Owners can't be removed, so a workspace always has one.
+pub async fn remove_workspace_member(workspace_id: String, username: &str, pool: &DatabasePool) -> Result<u64> {
     Ok(
-        sqlx::query!(r#"DELETE FROM clips WHERE extract(epoch from now()) > extract(epoch from expires)"#)
+        sqlx::query!(
+            r#"DELETE FROM workspace_members
+            WHERE workspace_id = $1
+                AND role <> 'owner'
+                AND user_id = (SELECT user_id FROM users WHERE username = $2)"#,
+            workspace_id,
+            username
+        )
+            .execute(pool)
+            .await?
+            .rows_affected()
+    )
+}
+
+/// Returns whether a user is a member of a workspace.
+pub async fn is_workspace_member(workspace_id: String, user_id: String, pool: &DatabasePool) -> Result<bool> {
+    Ok(sqlx::query!(
+        r#"SELECT EXISTS(
+            SELECT 1 FROM workspace_members WHERE workspace_id = $1 AND user_id = $2
+        ) AS "member!""#,
+        workspace_id,
+        user_id
+    ).fetch_one(pool).await?.member)
+}
+
+/// Returns whether an API key can read the clips of a workspace, which it can when it is scoped
+/// to the workspace or was minted by one of its members.
+pub async fn api_key_can_access_workspace(
+    workspace_id: String,
+    api_key: Vec<u8>,
+    pool: &DatabasePool
+) -> Result<bool> {
+    Ok(sqlx::query!(
+        r#"SELECT EXISTS(
+            SELECT 1 FROM api_keys WHERE api_key = $2 AND (
+                workspace_id = $1
+                OR user_id IN (SELECT user_id FROM workspace_members WHERE workspace_id = $1)
+            )
+        ) AS "allowed!""#,
+        workspace_id,
+        api_key
+    ).fetch_one(pool).await?.allowed)
+}
+
+/// Saves an [`ApiKey`] scoped to a workspace.
+pub async fn save_workspace_api_key(
+    api_key: ApiKey,
+    workspace_id: String,
+    scopes: &[String],
+    pool: &DatabasePool
+) -> Result<ApiKey> {
+    let bytes = api_key.clone().into_inner();
+    let _ = sqlx::query!(
+        "INSERT INTO api_keys (api_key, workspace_id, created, scopes) VALUES ($1, $2, $3, $4)",
+        bytes,
+        workspace_id,
+        Utc::now().naive_utc(),
+        scopes
+    ).execute(pool).await?;
+    Ok(api_key)
+}
+
+/// Lists the API keys scoped to a workspace, newest first.
+pub async fn list_workspace_api_keys(workspace_id: String, pool: &DatabasePool) -> Result<Vec<model::UserApiKey>> {
+    Ok(sqlx::query_as!(
+        model::UserApiKey,
+        r#"SELECT encode(sha256(api_key), 'hex') AS "fingerprint!", created, scopes FROM api_keys
+        WHERE workspace_id = $1 ORDER BY created DESC"#,
+        workspace_id
+    ).fetch_all(pool).await?)
+}
+
+/// Revokes an API key scoped to a workspace, identified by its fingerprint.
+pub async fn revoke_workspace_api_key(
+    workspace_id: String,
+    fingerprint: &str,
+    pool: &DatabasePool
+) -> Result<RevocationStatus> {
+    Ok(
+        sqlx::query!(
+            "DELETE FROM api_keys WHERE workspace_id = $1 AND encode(sha256(api_key), 'hex') = $2",
+            workspace_id,
+            fingerprint
+        )
+            .execute(pool)
+            .await
+            .map(|result| match result.rows_affected() {
+                0 => RevocationStatus::NotFound,
+                _ => RevocationStatus::Revoked
+            })?,
+    )
+}
+
+/// The return value from the [`delete_clip`] function.
+pub enum DeletionStatus {
+    /// The [`Clip`](`crate::Clip`) was moved into the tombstone state.
+    Deleted,
+    /// The [`Clip`](`crate::Clip`) was not found, was already deleted or isn't the requester's.
+    NotFound
+}
+
+/// Sets the digest of the token letting its holders edit a clip, replacing the previous one.
+pub async fn set_edit_token(shortcode: &str, digest: &[u8], pool: &DatabasePool) -> Result<()> {
+    sqlx::query!(
+        "UPDATE clips SET edit_token = $1 WHERE shortcode = $2 AND deleted IS NULL",
+        digest,
+        shortcode
+    )
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Whether a clip was created with the API key or by the user.
+pub async fn is_clip_creator(
+    shortcode: &str,
+    owner: Option<Vec<u8>>,
+    user_id: Option<String>,
+    pool: &DatabasePool
+) -> Result<bool> {
+    Ok(sqlx::query!(
+        r#"SELECT EXISTS(SELECT 1 FROM clips WHERE shortcode = $1 AND (owner = $2 OR user_id = $3)) AS "exists!""#,
+        shortcode,
+        owner,
+        user_id
+    )
+        .fetch_one(pool)
+        .await?
+        .exists)
+}
+
+/// Whether `digest` is the digest of the edit token of a clip.
+pub async fn is_edit_token(shortcode: &str, digest: &[u8], pool: &DatabasePool) -> Result<bool> {
+    Ok(sqlx::query!(
+        r#"SELECT EXISTS(SELECT 1 FROM clips WHERE shortcode = $1 AND edit_token = $2) AS "exists!""#,
+        shortcode,
+        digest
+    )
+        .fetch_one(pool)
+        .await?
+        .exists)
+}
+
+/// Marks a [`Clip`](`crate::Clip`) as deleted, if it was created with the API key or by the
+/// user of the request, or the request comes from an admin. The clip is kept as a tombstone
+/// until it is purged.
+pub async fn delete_clip(
+    model: model::DeleteClip,
+    pool: &DatabasePool
+) -> Result<DeletionStatus> {
+    Ok(
+        sqlx::query!(
+            r#"UPDATE clips SET deleted = $1
+            WHERE shortcode = $2 AND deleted IS NULL AND ($3 OR owner = $4 OR user_id = $5)"#,
+            Utc::now().naive_utc(),
+            model.shortcode,
+            model.admin,
+            model.owner,
+            model.user_id
+        )
+            .execute(pool)
+            .await
+            .map(|result| match result.rows_affected() {
+                0 => DeletionStatus::NotFound,
+                _ => DeletionStatus::Deleted
+            })?,
+    )
+}
+
+/// Brings back a deleted [`Clip`](`crate::Clip`). An expiration date that has already passed
+/// is cleared, so the clip isn't expired again right away.
+pub async fn restore_clip<M: Into<model::GetClip>>(
+    model: M,
+    pool: &DatabasePool
+) -> Result<model::Clip> {
+    let model = model.into();
+    let _ = sqlx::query!(
+        r#"UPDATE clips SET
+            deleted = NULL,
+            expires = CASE WHEN expires < $1 THEN NULL ELSE expires END
+        WHERE shortcode = $2 AND deleted IS NOT NULL"#,
+        Utc::now().naive_utc(),
+        model.shortcode
+    ).execute(pool).await?;
+
+    get_clip(model.shortcode, pool).await
+}
+
+/// Marks all expired [`Clips`](`crate::Clip`) as deleted.
+pub async fn expire_clips(pool: &DatabasePool) -> Result<Vec<String>> {
+    Ok(
+        sqlx::query!(
+            r#"UPDATE clips SET deleted = $1
+            WHERE deleted IS NULL AND extract(epoch from now()) > extract(epoch from expires)
+            RETURNING shortcode"#,
+            Utc::now().naive_utc()
+        )
+            .fetch_all(pool)
+            .await?
+            .into_iter()
+            .map(|row| row.shortcode)
+            .collect()
+    )
+}
+
+/// Permanently removes the [`Clips`](`crate::Clip`) that were deleted before `deleted_before`.
+pub async fn purge_deleted(deleted_before: NaiveDateTime, pool: &DatabasePool) -> Result<u64> {
+    Ok(
+        sqlx::query!(r#"DELETE FROM clips WHERE deleted < $1"#, deleted_before)
+            .execute(pool)
+            .await?
+            .rows_affected()
+    )
+}
+
+/// Saves a webhook of an API key.
+pub async fn new_webhook(model: model::NewWebhook, pool: &DatabasePool) -> Result<model::Webhook> {
+    Ok(sqlx::query_as!(
+        model::Webhook,
+        r#"INSERT INTO webhooks (webhook_id, api_key, url, secret, events, created)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING webhook_id, url, events, created"#,
+        model.webhook_id,
+        model.api_key,
+        model.url,
+        model.secret,
+        &model.events,
+        model.created
+    ).fetch_one(pool).await?)
+}
+
+/// Lists the webhooks of an API key, oldest first.
+pub async fn list_webhooks(api_key: ApiKey, pool: &DatabasePool) -> Result<Vec<model::Webhook>> {
+    let bytes = api_key.into_inner();
+    Ok(sqlx::query_as!(
+        model::Webhook,
+        r#"SELECT webhook_id, url, events, created FROM webhooks WHERE api_key = $1 ORDER BY created, webhook_id"#,
+        bytes
+    ).fetch_all(pool).await?)
+}
+
+/// Gets a webhook of an API key. The webhooks of other keys are not found.
+pub async fn get_webhook(webhook_id: &str, api_key: ApiKey, pool: &DatabasePool) -> Result<model::Webhook> {
+    let bytes = api_key.into_inner();
+    Ok(sqlx::query_as!(
+        model::Webhook,
+        r#"SELECT webhook_id, url, events, created FROM webhooks WHERE webhook_id = $1 AND api_key = $2"#,
+        webhook_id,
+        bytes
+    ).fetch_one(pool).await?)
+}
+
+/// Deletes a webhook of an API key along with its deliveries, returning how many were deleted.
+pub async fn delete_webhook(webhook_id: &str, api_key: ApiKey, pool: &DatabasePool) -> Result<u64> {
+    let bytes = api_key.into_inner();
+    Ok(
+        sqlx::query!("DELETE FROM webhooks WHERE webhook_id = $1 AND api_key = $2", webhook_id, bytes)
+            .execute(pool)
+            .await?
+            .rows_affected()
+    )
+}
+
+/// Lists the latest deliveries of a webhook, newest first.
+pub async fn list_webhook_deliveries(
+    webhook_id: &str,
+    limit: i64,
+    pool: &DatabasePool
+) -> Result<Vec<model::WebhookDelivery>> {
+    Ok(sqlx::query_as!(
+        model::WebhookDelivery,
+        r#"SELECT delivery_id, event, shortcode, created, attempts, delivered, next_attempt, status, error
+        FROM webhook_deliveries
+        WHERE webhook_id = $1
+        ORDER BY delivery_id DESC
+        LIMIT $2"#,
+        webhook_id,
+        limit
+    ).fetch_all(pool).await?)
+}
+
+/// Queues a delivery of an event of each clip, with its payload, to every webhook of the API key
+/// the clip was created with which subscribed to the event. With `sent_since`, clips which
+/// already had the event sent to a webhook since then are skipped for that webhook.
+pub async fn queue_webhook_deliveries<'e, E: sqlx::PgExecutor<'e>>(
+    event: &str,
+    shortcodes: &[String],
+    payloads: &[String],
+    sent_since: Option<NaiveDateTime>,
+    executor: E
+) -> Result<u64> {
+    let now = Utc::now().naive_utc();
+    Ok(
+        sqlx::query!(
+            r#"INSERT INTO webhook_deliveries (webhook_id, event, shortcode, payload, created, next_attempt)
+            SELECT webhooks.webhook_id, $1, clips.shortcode, events.payload, $4, $4
+            FROM UNNEST($2::TEXT[], $3::TEXT[]) AS events(shortcode, payload)
+            JOIN clips ON clips.shortcode = events.shortcode
+            JOIN webhooks ON webhooks.api_key = clips.owner
+            WHERE $1 = ANY(webhooks.events)
+            AND ($5::TIMESTAMP IS NULL OR NOT EXISTS (
+                SELECT 1 FROM webhook_deliveries sent
+                WHERE sent.webhook_id = webhooks.webhook_id
+                AND sent.shortcode = clips.shortcode
+                AND sent.event = $1
+                AND sent.created > $5
+            ))"#,
+            event,
+            shortcodes,
+            payloads,
+            now,
+            sent_since
+        )
+            .execute(executor)
+            .await?
+            .rows_affected()
+    )
+}
+
+/// Claims up to `limit` deliveries due to be attempted, counting the attempt. Nobody else claims
+/// them again until `lease_until`, when they are due again if the attempt wasn't recorded.
+pub async fn claim_webhook_deliveries(
+    limit: i64,
+    lease_until: NaiveDateTime,
+    pool: &DatabasePool
+) -> Result<Vec<model::PendingDelivery>> {
+    Ok(sqlx::query_as!(
+        model::PendingDelivery,
+        r#"UPDATE webhook_deliveries
+        SET attempts = webhook_deliveries.attempts + 1, next_attempt = $2
+        FROM webhooks
+        WHERE webhooks.webhook_id = webhook_deliveries.webhook_id
+        AND webhook_deliveries.delivery_id IN (
+            SELECT delivery_id FROM webhook_deliveries
+            WHERE next_attempt <= $3
+            ORDER BY next_attempt
+            LIMIT $1
+            FOR UPDATE SKIP LOCKED
+        )
+        RETURNING webhook_deliveries.delivery_id, webhooks.url, webhooks.secret, webhook_deliveries.event,
+            webhook_deliveries.payload, webhook_deliveries.attempts"#,
+        limit,
+        lease_until,
+        Utc::now().naive_utc()
+    ).fetch_all(pool).await?)
+}
+
+/// Records how an attempt to deliver an event went. Deliveries without a `next_attempt` are done with.
+pub async fn record_webhook_attempt(
+    delivery_id: i64,
+    delivered: Option<NaiveDateTime>,
+    status: Option<i32>,
+    error: Option<String>,
+    next_attempt: Option<NaiveDateTime>,
+    pool: &DatabasePool
+) -> Result<()> {
+    let _ = sqlx::query!(
+        r#"UPDATE webhook_deliveries
+        SET delivered = $2, status = $3, error = $4, next_attempt = $5
+        WHERE delivery_id = $1"#,
+        delivery_id,
+        delivered,
+        status,
+        error,
+        next_attempt
+    ).execute(pool).await?;
+    Ok(())
+}
+
+/// Removes the deliveries which are done with and were queued before `created_before`.
+pub async fn purge_webhook_deliveries(created_before: NaiveDateTime, pool: &DatabasePool) -> Result<u64> {
+    Ok(
+        sqlx::query!(
+            "DELETE FROM webhook_deliveries WHERE next_attempt IS NULL AND created < $1",
+            created_before
+        )
             .execute(pool)
             .await?
             .rows_affected()
@@ -167,7 +1164,11 @@ pub mod test {
             shortcode: shortcode.into(),
             posted: NaiveDateTime::from_timestamp(Utc::now().timestamp(), 0),
             expires: None,
-            password: None
+            password: None,
+            password_hashed: false,
+            owner: None,
+            user_id: None,
+            workspace_id: None
         }
     }
 
@@ -190,4 +1191,152 @@ pub mod test {
         assert_eq!(clip.content, format!("content for clip '{}'", test_shortcode));
 
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn clip_delete_and_restore() {
+        let rt = async_runtime();
+        let db = new_db(rt.handle());
+        let pool = db.get_pool();
+
+        let test_shortcode = "4c3b2a1d4c";
+
+        let clip = rt.block_on(async move {
+            super::new_clip(model_new_clip(test_shortcode), pool).await.unwrap();
+            let delete = |owner: Option<Vec<u8>>, admin| model::DeleteClip {
+                shortcode: test_shortcode.to_owned(),
+                owner,
+                user_id: None,
+                admin
+            };
+            assert!(matches!(
+                super::delete_clip(delete(Some(vec![1, 2, 3]), false), pool).await.unwrap(),
+                super::DeletionStatus::NotFound
+            ));
+            assert!(!super::get_clip(test_shortcode.to_owned(), pool).await.unwrap().is_deleted());
+            assert!(matches!(
+                super::delete_clip(delete(None, true), pool).await.unwrap(),
+                super::DeletionStatus::Deleted
+            ));
+            assert!(super::get_clip(test_shortcode.to_owned(), pool).await.unwrap().is_deleted());
+            super::restore_clip(test_shortcode.to_owned(), pool).await
+        });
+
+        assert!(clip.is_ok());
+        assert!(!clip.unwrap().is_deleted());
+    }
+
+    #[test]
+    fn clip_search() {
+        let rt = async_runtime();
+        let db = new_db(rt.handle());
+        let pool = db.get_pool();
+
+        let owner = crate::web::api::ApiKey::default();
+        let results = rt.block_on(async move {
+            let owner = super::save_api_key(owner, &[], pool).await.unwrap().into_inner();
+            let searchable = model::NewClip {
+                content: "the quick brown fox".to_owned(),
+                owner: Some(owner.clone()),
+                ..model_new_clip("5e4a3c1b2d")
+            };
+            let protected = model::NewClip {
+                content: "the quick brown fox".to_owned(),
+                password: Some("123".to_owned()),
+                owner: Some(owner.clone()),
+                ..model_new_clip("5e4a3c1b2e")
+            };
+            let not_owned = model::NewClip {
+                content: "the quick brown fox".to_owned(),
+                ..model_new_clip("5e4a3c1b2f")
+            };
+            for clip in [searchable, protected, not_owned] {
+                super::new_clip(clip, pool).await.unwrap();
+            }
+
+            let mut results = Vec::new();
+            // Only stop words, which full-text search can't look up, and a literal `%`.
+            for query in ["foxes", "the", "%"].iter() {
+                let search = model::SearchClips { query: query.to_string(), owner: owner.clone(), offset: 0, limit: 10 };
+                results.push(super::search_clips(search, pool).await.unwrap());
+            }
+            results
+        });
+
+        for results in &results[..2] {
+            assert_eq!(results.len(), 1);
+            assert_eq!(results[0].shortcode, "5e4a3c1b2d");
+        }
+        assert!(results[2].is_empty());
+    }
+
+    #[test]
+    fn clip_list_pages() {
+        let rt = async_runtime();
+        let db = new_db(rt.handle());
+        let pool = db.get_pool();
+
+        let owner = crate::web::api::ApiKey::default();
+        let pages = rt.block_on(async move {
+            let owner = super::save_api_key(owner, &[], pool).await.unwrap().into_inner();
+            let posted = chrono::DateTime::from_timestamp(1_600_000_000, 0).unwrap().naive_utc();
+            for (i, shortcode) in ["6a1b2c3d4a", "6a1b2c3d4b", "6a1b2c3d4c"].iter().enumerate() {
+                let clip = model::NewClip {
+                    posted: posted + chrono::Duration::seconds(i as i64),
+                    owner: Some(owner.clone()),
+                    ..model_new_clip(shortcode)
+                };
+                super::new_clip(clip, pool).await.unwrap();
+            }
+
+            let first = super::list_clips(model::ListClips {
+                owner: owner.clone(),
+                posted: None,
+                shortcode: None,
+                limit: 2
+            }, pool).await.unwrap();
+            let last = first.last().unwrap();
+            let second = super::list_clips(model::ListClips {
+                owner,
+                posted: Some(last.posted),
+                shortcode: Some(last.shortcode.clone()),
+                limit: 2
+            }, pool).await.unwrap();
+            (first, second)
+        });
+
+        let shortcodes = |page: &[model::ClipSummary]| {
+            page.iter().map(|clip| clip.shortcode.clone()).collect::<Vec<_>>()
+        };
+        assert_eq!(shortcodes(&pages.0), vec!["6a1b2c3d4c", "6a1b2c3d4b"]);
+        assert_eq!(shortcodes(&pages.1), vec!["6a1b2c3d4a"]);
+    }
+
+    #[test]
+    fn clip_import_keeps_posted_and_skips_taken_shortcodes() {
+        let rt = async_runtime();
+        let db = new_db(rt.handle());
+        let pool = db.get_pool();
+
+        let posted = chrono::DateTime::from_timestamp(1_300_000_000, 0).unwrap().naive_utc();
+        let (saved, clip) = rt.block_on(async move {
+            super::new_clip(model_new_clip("7a1b2c3d4a"), pool).await.unwrap();
+            let clips = ["7a1b2c3d4a", "7a1b2c3d4b", "7a1b2c3d4b"]
+                .iter()
+                .map(|shortcode| model::NewClip {
+                    content: "imported".to_owned(),
+                    posted,
+                    ..model_new_clip(shortcode)
+                })
+                .collect::<Vec<_>>();
+            let mut transaction = pool.begin().await.unwrap();
+            let saved = super::import_clips(&clips, &mut transaction).await.unwrap();
+            transaction.commit().await.unwrap();
+            (saved, super::get_clip("7a1b2c3d4b".to_owned(), pool).await.unwrap())
+        });
+
+        assert_eq!(saved, vec!["7a1b2c3d4b"]);
+        assert_eq!(clip.content, "imported");
+        assert_eq!(clip.posted, posted);
+        assert_eq!(clip.hits, 0);
+    }
+}
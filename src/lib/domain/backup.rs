@@ -0,0 +1,202 @@
+//! Backups of the clips, as NDJSON which can be restored into another database. A backup
+//! starts with a `header` line, has a `clip` line for every clip and ends with an `end` line
+//! counting them, so that a backup which was cut short is never restored as if it were whole.
+//! Passwords are only kept as Argon2 hashes, which still unlock the restored clips, and owners
+//! as SHA-256 digests of their API keys, which give the clips back to keys the server knows.
+use crate::data::DatabasePool;
+use crate::service::{self, ask, ServiceError};
+use crate::web::api::ApiKey;
+use crate::{ShortCode, Time};
+use chrono::Utc;
+use rocket::futures::stream::{BoxStream, StreamExt};
+use rocket::response::stream::stream;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// The version of the backup format, bumped when older versions can't read it.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// How many clips are read from the database at a time while exporting.
+pub const EXPORT_PAGE_SIZE: i64 = 500;
+
+/// How many clips are restored in every transaction.
+pub const RESTORE_BATCH_SIZE: usize = 500;
+
+#[derive(Debug, Error)]
+pub enum BackupError {
+    #[error("not a clipstash backup")]
+    NotABackup,
+    #[error("unsupported backup version {0}")]
+    UnsupportedVersion(u32),
+    #[error("line {line}: {error}")]
+    InvalidLine { line: usize, error: String },
+    #[error("the backup is incomplete")]
+    Truncated,
+    #[error("the backup should hold {expected} clips, but holds {found}")]
+    CountMismatch { expected: u64, found: u64 }
+}
+
+/// A clip as saved in a backup. Its edit token is left out, as it is a secret.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct BackedUpClip {
+    pub clip_id: String,
+    pub shortcode: ShortCode,
+    pub title: Option<String>,
+    pub content: String,
+    pub posted: Time,
+    pub updated: Option<Time>,
+    pub expires: Option<Time>,
+    pub deleted: Option<Time>,
+    pub hits: u64,
+    pub revision: i64,
+    pub password_hash: Option<String>,
+    /// The SHA-256 digest of the API key which created the clip, in hex.
+    #[serde(default)]
+    pub owner: Option<String>,
+    pub user_id: Option<String>,
+    pub workspace_id: Option<String>
+}
+
+/// A line of a backup.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum BackupLine {
+    Header { version: u32, created: Time },
+    Clip(Box<BackedUpClip>),
+    End { clips: u64 }
+}
+
+impl BackupLine {
+    /// The line as JSON, ending with a newline.
+    pub fn to_line(&self) -> String {
+        let mut line = serde_json::to_string(self).expect("backup lines always serialize");
+        line.push('\n');
+        line
+    }
+}
+
+/// Reads the clips of a whole backup, which must be complete.
+pub fn read_backup(backup: &str) -> Result<Vec<BackedUpClip>, BackupError> {
+    let mut lines = backup.lines().enumerate().filter(|(_, line)| !line.trim().is_empty());
+    match lines.next().map(|(_, line)| serde_json::from_str::<BackupLine>(line)) {
+        Some(Ok(BackupLine::Header { version, .. })) if version == FORMAT_VERSION => (),
+        Some(Ok(BackupLine::Header { version, .. })) => return Err(BackupError::UnsupportedVersion(version)),
+        _ => return Err(BackupError::NotABackup)
+    }
+
+    let mut clips = Vec::new();
+    for (index, line) in lines {
+        let line = serde_json::from_str::<BackupLine>(line)
+            .map_err(|e| BackupError::InvalidLine { line: index + 1, error: e.to_string() })?;
+        match line {
+            BackupLine::Clip(clip) => clips.push(*clip),
+            BackupLine::End { clips: expected } if expected == clips.len() as u64 => return Ok(clips),
+            BackupLine::End { clips: expected } => {
+                return Err(BackupError::CountMismatch { expected, found: clips.len() as u64 });
+            },
+            BackupLine::Header { .. } => {
+                return Err(BackupError::InvalidLine { line: index + 1, error: "unexpected header".to_owned() });
+            }
+        }
+    }
+    Err(BackupError::Truncated)
+}
+
+/// Streams the lines of a backup of every clip, or only of those created with `owner`. The
+/// `end` line is only sent once every clip was, so a failed export can't pass for a whole one.
+pub fn export(pool: DatabasePool, owner: Option<ApiKey>) -> BoxStream<'static, Result<String, ServiceError>> {
+    stream! {
+        yield Ok(BackupLine::Header { version: FORMAT_VERSION, created: Utc::now().into() }.to_line());
+        let mut count = 0;
+        let mut after = None;
+        loop {
+            let req = ask::ExportClips { owner: owner.clone(), after: after.take(), limit: EXPORT_PAGE_SIZE };
+            let clips = match service::action::export_clips(req, &pool).await {
+                Ok(clips) => clips,
+                Err(e) => {
+                    yield Err(e);
+                    return;
+                }
+            };
+            let last_page = (clips.len() as i64) < EXPORT_PAGE_SIZE;
+            after = clips.last().map(|clip| clip.clip_id.clone());
+            for clip in clips {
+                count += 1;
+                yield Ok(BackupLine::Clip(Box::new(clip)).to_line());
+            }
+            if last_page {
+                break;
+            }
+        }
+        yield Ok(BackupLine::End { clips: count }.to_line());
+    }.boxed()
+}
+
+/// A clip of a backup which wasn't restored, because its shortcode is taken by another clip or
+/// its workspace doesn't exist.
+#[derive(Debug, Serialize)]
+pub struct SkippedClip {
+    pub clip_id: String,
+    pub shortcode: ShortCode
+}
+
+/// What became of the clips of a restored backup. Clips which already exist are left as they
+/// are, so a backup can be restored again and again.
+#[derive(Debug, Default, Serialize)]
+pub struct RestoreReport {
+    pub restored: usize,
+    pub unchanged: usize,
+    pub skipped: Vec<SkippedClip>
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    fn clip(clip_id: &str) -> BackedUpClip {
+        BackedUpClip {
+            clip_id: clip_id.to_owned(),
+            shortcode: ShortCode::from("abc"),
+            title: None,
+            content: "content".to_owned(),
+            posted: Utc::now().into(),
+            updated: None,
+            expires: None,
+            deleted: None,
+            hits: 3,
+            revision: 2,
+            password_hash: None,
+            owner: None,
+            user_id: None,
+            workspace_id: None
+        }
+    }
+
+    #[test]
+    fn reads_only_whole_backups() {
+        let header = BackupLine::Header { version: FORMAT_VERSION, created: Utc::now().into() }.to_line();
+        let clips = vec![clip("1"), clip("2")]
+            .into_iter()
+            .map(|clip| BackupLine::Clip(Box::new(clip)).to_line())
+            .collect::<String>();
+        let end = BackupLine::End { clips: 2 }.to_line();
+
+        let restored = read_backup(&format!("{}{}{}", header, clips, end)).unwrap();
+        let ids: Vec<_> = restored.iter().map(|clip| clip.clip_id.as_str()).collect();
+        assert_eq!(ids, vec!["1", "2"]);
+        assert_eq!(restored[0].hits, 3);
+
+        assert!(matches!(read_backup(&format!("{}{}", header, clips)), Err(BackupError::Truncated)));
+        assert!(matches!(
+            read_backup(&format!("{}{}{}", header, clips, BackupLine::End { clips: 3 }.to_line())),
+            Err(BackupError::CountMismatch { expected: 3, found: 2 })
+        ));
+        assert!(matches!(read_backup(&format!("{}{}", clips, end)), Err(BackupError::NotABackup)));
+        assert!(matches!(
+            read_backup(&format!("{}{{\"type\":\"clip\"}}\n{}", header, end)),
+            Err(BackupError::InvalidLine { line: 2, .. })
+        ));
+        let future = BackupLine::Header { version: FORMAT_VERSION + 1, created: Utc::now().into() }.to_line();
+        assert!(matches!(read_backup(&format!("{}{}", future, end)), Err(BackupError::UnsupportedVersion(_))));
+    }
+}
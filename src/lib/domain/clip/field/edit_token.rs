@@ -0,0 +1,40 @@
+use crate::domain::clip::ClipError;
+use sha2::{Digest, Sha256};
+use std::str::FromStr;
+use crate::redact::redacted_debug;
+
+/// The secret letting its holders edit a clip together, as in `/clip/<shortcode>#edit=<token>`.
+/// Only its digest is stored.
+#[derive(Clone, PartialEq, Eq)]
+pub struct EditToken(Vec<u8>);
+
+redacted_debug!(EditToken);
+
+impl EditToken {
+    /// The token as it appears in URLs.
+    pub fn to_base64(&self) -> String {
+        base64::encode_config(&self.0, base64::URL_SAFE_NO_PAD)
+    }
+
+    /// The SHA-256 digest of the token, which is what the database keeps.
+    pub fn digest(&self) -> Vec<u8> {
+        Sha256::digest(&self.0).to_vec()
+    }
+}
+
+impl Default for EditToken {
+    fn default() -> Self {
+        Self((0..24).map(|_| rand::random::<u8>()).collect())
+    }
+}
+
+impl FromStr for EditToken {
+    type Err = ClipError;
+    fn from_str(token: &str) -> Result<Self, Self::Err> {
+        base64::decode_config(token.trim(), base64::URL_SAFE_NO_PAD)
+            .ok()
+            .filter(|token| !token.is_empty())
+            .map(Self)
+            .ok_or(ClipError::InvalidEditToken)
+    }
+}
@@ -21,4 +21,13 @@ mod password;
 pub use password::Password;
 
 mod hits;
-pub use hits::Hits;
\ No newline at end of file
+pub use hits::Hits;
+
+mod revision;
+pub use revision::Revision;
+
+mod updated;
+pub use updated::Updated;
+
+mod edit_token;
+pub use edit_token::EditToken;
@@ -1,10 +1,25 @@
 use crate::domain::clip::ClipError;
+use crate::redact::Redacted;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
 use serde::{Deserialize, Serialize};
 use std::str::FromStr;
 use rocket::form::{self, FromFormField, ValueField};
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
-pub struct Password(Option<String>);
+/// The password of a clip. Passwords are kept as they were given, except those of clips
+/// restored from a backup, which only hold their Argon2 hash.
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(from = "Option<String>", into = "Option<String>")]
+pub struct Password {
+    password: Option<String>,
+    hashed: bool
+}
+
+impl std::fmt::Debug for Password {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Password").field(&self.password.as_ref().map(|_| Redacted)).finish()
+    }
+}
 
 impl Password {
     pub fn new<T: Into<Option<String>>>(password: T) -> Result<Self, ClipError> {
@@ -12,27 +27,64 @@ impl Password {
         match password {
             Some(password) => {
                 if !password.trim().is_empty() {
-                    Ok(Self(Some(password)))
+                    Ok(Self::from(Some(password)))
                 } else {
-                    Ok(Self(None))
+                    Ok(Self::from(None))
                 }
             },
-            None => Ok(Self(None))
+            None => Ok(Self::from(None))
         }
     }
 
-    /// Return the underlying [`String`].
+    /// A password of which only the hash made by [`hash`](Self::hash) is known.
+    pub fn hashed(hash: Option<String>) -> Self {
+        Self { password: hash, hashed: true }
+    }
+
+    /// Return the underlying [`String`], which is the hash of a [`hashed`](Self::hashed) password.
     pub fn into_inner(self) -> Option<String> {
-        self.0
+        self.password
     }
 
     /// Returns whether a password has been set.
     pub fn has_password(&self) -> bool {
-        self.0.is_some()
+        self.password.is_some()
+    }
+
+    /// Returns whether only the hash of the password is known.
+    pub fn is_hashed(&self) -> bool {
+        self.hashed
+    }
+
+    /// Returns whether `given` is the password, checking it against the hash of a
+    /// [`hashed`](Self::hashed) password.
+    pub fn matches(&self, given: &Password) -> bool {
+        if self.hashed {
+            PasswordHash::new(self.to_str())
+                .map(|hash| Argon2::default().verify_password(given.to_str().as_bytes(), &hash).is_ok())
+                .unwrap_or(false)
+        } else {
+            self.to_str() == given.to_str()
+        }
+    }
+
+    /// Hashes the password with a random salt, in the PHC string format, so backups don't hold
+    /// it in the clear. A [`hashed`](Self::hashed) password is kept as it is.
+    pub fn hash(&self) -> Result<Option<String>, ClipError> {
+        let password = match &self.password {
+            Some(password) if !self.hashed => password,
+            password => return Ok(password.clone())
+        };
+        let salt: Vec<u8> = (0..16).map(|_| rand::random::<u8>()).collect();
+        let salt = SaltString::encode_b64(&salt).map_err(|e| ClipError::PasswordHash(e.to_string()))?;
+        Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .map(|hash| Some(hash.to_string()))
+            .map_err(|e| ClipError::PasswordHash(e.to_string()))
     }
 
     pub fn to_str(&self) -> &str {
-        match self.0 {
+        match self.password {
             Some(ref password) => password,
             None => ""
         }
@@ -42,7 +94,20 @@ impl Password {
 /// The Default implementation is no password.
 impl Default for Password {
     fn default() -> Self {
-        Self(None)
+        Self::from(None)
+    }
+}
+
+/// A password as it was given, which is never taken for a hash.
+impl From<Option<String>> for Password {
+    fn from(password: Option<String>) -> Self {
+        Self { password, hashed: false }
+    }
+}
+
+impl From<Password> for Option<String> {
+    fn from(password: Password) -> Self {
+        password.into_inner()
     }
 }
 
@@ -60,4 +125,28 @@ impl<'r> FromFormField<'r> for Password {
         Ok(Self::new(field.value.to_owned())
             .map_err(|e| form::Error::validation(format!("{:?}", e)))?)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::Password;
+
+    #[test]
+    fn passwords_looking_like_hashes_are_compared_as_given() {
+        let password = Password::new("$argon2x".to_owned()).unwrap();
+        assert!(password.matches(&Password::new("$argon2x".to_owned()).unwrap()));
+        assert!(!password.matches(&Password::new("$argon2y".to_owned()).unwrap()));
+
+        // A PHC string given as the password is the password, not a hash of one.
+        let hash = Password::new("secret".to_owned()).unwrap().hash().unwrap();
+        let phc = Password::new(hash.clone()).unwrap();
+        assert!(!phc.matches(&Password::new("secret".to_owned()).unwrap()));
+        assert!(phc.matches(&Password::new(hash.clone()).unwrap()));
+        assert_ne!(phc.hash().unwrap(), hash);
+
+        let hashed = Password::hashed(hash.clone());
+        assert!(hashed.matches(&Password::new("secret".to_owned()).unwrap()));
+        assert!(!hashed.matches(&Password::new(hash.clone()).unwrap()));
+        assert_eq!(hashed.hash().unwrap(), hash);
+    }
+}
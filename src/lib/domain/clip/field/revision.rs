@@ -0,0 +1,19 @@
+use serde::{Deserialize, Serialize};
+use derive_more::Constructor;
+
+/// The revision of a clip, bumped every time it is edited.
+#[derive(Clone, Copy, Constructor, Debug, Eq, PartialEq, Deserialize, Serialize)]
+pub struct Revision(u64);
+
+impl Revision {
+    pub fn into_inner(self) -> u64 {
+        self.0
+    }
+
+    /// Reads the revision out of an entity tag made by [`Clip::etag`](crate::Clip::etag).
+    pub fn from_etag(etag: &str) -> Option<Self> {
+        let etag = etag.trim().trim_start_matches("W/").trim_matches('"');
+        let (revision, _) = etag.split_once('-')?;
+        revision.parse().ok().map(Self)
+    }
+}
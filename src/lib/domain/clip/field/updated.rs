@@ -0,0 +1,13 @@
+use crate::domain::time::Time;
+use serde::{Deserialize, Serialize};
+use derive_more::Constructor;
+
+/// When a clip was last edited, or posted if it never was.
+#[derive(Clone, Constructor, Debug, Serialize, Deserialize)]
+pub struct Updated(Time);
+
+impl Updated {
+    pub fn into_inner(self) -> Time {
+        self.0
+    }
+}
@@ -1,6 +1,8 @@
 pub mod field;
 
+use crate::Time;
 use serde::{Deserialize, Serialize};
+use std::str::FromStr;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -18,7 +20,19 @@ pub enum ClipError {
     #[error("id parse error: {0}")]
     Id(#[from] uuid::Error),
     #[error("hits parse error: {0}")]
-    Hits(#[from] std::num::TryFromIntError)
+    Hits(#[from] std::num::TryFromIntError),
+    #[error("invalid view: {0}")]
+    InvalidView(String),
+    #[error("invalid search: {0}")]
+    InvalidSearch(String),
+    #[error("invalid cursor")]
+    InvalidCursor,
+    #[error("limit must be between 1 and {0}")]
+    InvalidLimit(u32),
+    #[error("invalid edit token")]
+    InvalidEditToken,
+    #[error("password hash error: {0}")]
+    PasswordHash(String)
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -30,7 +44,135 @@ pub struct Clip {
     pub title: field::Title,
     pub posted: field::Posted,
     pub expires: field::Expires,
+    /// Left out of responses when only its hash is known, as nobody should get the hash back.
+    #[serde(default, skip_serializing_if = "field::Password::is_hashed")]
     pub password: field::Password,
     pub hits: field::Hits,
+    pub revision: field::Revision,
+    pub updated: field::Updated,
 }
 
+/// The entity tag of a clip, made of its revision and the SHA-256 digest of its content.
+fn etag(revision: field::Revision, digest: &[u8]) -> String {
+    let digest: String = digest.iter().take(8).map(|byte| format!("{:02x}", byte)).collect();
+    format!("\"{}-{}\"", revision.into_inner(), digest)
+}
+
+impl Clip {
+    /// The entity tag of the clip, made of its revision and a digest of its content. The hits
+    /// are left out, so views don't invalidate the copies of clients.
+    pub fn etag(&self) -> String {
+        use sha2::{Digest, Sha256};
+        etag(self.revision, &Sha256::digest(self.content.as_str().as_bytes()))
+    }
+}
+
+/// A [`Clip`] without its content, which is streamed from the database rather than held in
+/// memory whole.
+#[derive(Debug, Clone)]
+pub struct RawClip {
+    pub shortcode: field::ShortCode,
+    pub expires: field::Expires,
+    pub password: field::Password,
+    pub revision: field::Revision,
+    pub updated: field::Updated,
+    /// The length of the content, in bytes.
+    pub length: u64,
+    /// The SHA-256 digest of the content.
+    pub digest: Vec<u8>,
+}
+
+impl RawClip {
+    /// The same entity tag as the [`Clip`] it comes from.
+    pub fn etag(&self) -> String {
+        etag(self.revision, &self.digest)
+    }
+}
+
+/// The details of a [`Clip`] shown in listings, without its content.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ClipSummary {
+    pub shortcode: field::ShortCode,
+    pub title: field::Title,
+    pub posted: field::Posted,
+    pub expires: field::Expires,
+    pub hits: field::Hits,
+}
+
+impl From<Clip> for ClipSummary {
+    fn from(clip: Clip) -> Self {
+        Self {
+            shortcode: clip.shortcode,
+            title: clip.title,
+            posted: clip.posted,
+            expires: clip.expires,
+            hits: clip.hits
+        }
+    }
+}
+
+/// A [`Clip`] matching a search, along with an excerpt of its matching content.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ClipMatch {
+    #[serde(flatten)]
+    pub clip: ClipSummary,
+    pub snippet: String,
+}
+
+/// A page of search results. `next_offset` is set when there are more results.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SearchResults {
+    pub clips: Vec<ClipMatch>,
+    pub next_offset: Option<u32>,
+}
+
+/// The position after a [`ClipSummary`] in a listing ordered by posting date, newest first.
+/// Clients get it as an opaque string.
+#[derive(Debug, Clone)]
+pub struct ClipCursor {
+    pub posted: Time,
+    pub shortcode: field::ShortCode,
+}
+
+impl ClipCursor {
+    pub fn after(clip: &ClipSummary) -> Self {
+        Self {
+            posted: clip.posted.clone().into_inner(),
+            shortcode: clip.shortcode.clone(),
+        }
+    }
+}
+
+impl std::fmt::Display for ClipCursor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let cursor = format!("{}:{}", self.posted.clone().into_inner().timestamp_micros(), self.shortcode.as_str());
+        f.write_str(&base64::encode_config(cursor, base64::URL_SAFE_NO_PAD))
+    }
+}
+
+impl FromStr for ClipCursor {
+    type Err = ClipError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let cursor = base64::decode_config(s, base64::URL_SAFE_NO_PAD)
+            .ok()
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+            .ok_or(ClipError::InvalidCursor)?;
+        let (micros, shortcode) = cursor.split_once(':').ok_or(ClipError::InvalidCursor)?;
+        let posted = micros
+            .parse()
+            .ok()
+            .and_then(chrono::DateTime::from_timestamp_micros)
+            .ok_or(ClipError::InvalidCursor)?;
+        Ok(Self {
+            posted: posted.into(),
+            shortcode: field::ShortCode::from(shortcode),
+        })
+    }
+}
+
+/// A page of the clips created with an API key. `next_cursor` is set when there are more clips.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ClipList {
+    pub clips: Vec<ClipSummary>,
+    pub next_cursor: Option<String>,
+}
@@ -0,0 +1,232 @@
+//! Concurrent edits of the text of a clip, merged with operational transformation. The server
+//! holds the [`Document`] every editor's operations are applied to, in the order they arrive.
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use thiserror::Error;
+
+/// How many edits a document remembers, to transform the edits made on older versions.
+pub const HISTORY: usize = 1000;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum CollabError {
+    #[error("the edit doesn't fit the text of the clip")]
+    OutOfBounds,
+    #[error("version {0} is too old to be merged, reload the clip")]
+    Stale(u64),
+    #[error("version {0} is unknown, reload the clip")]
+    Unknown(u64)
+}
+
+/// A change to the text of a clip. Positions and lengths count characters, not bytes.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Op {
+    Insert { pos: usize, text: String },
+    Delete { pos: usize, len: usize }
+}
+
+impl Op {
+    fn apply(&self, text: &mut Vec<char>) -> Result<(), CollabError> {
+        match self {
+            Op::Insert { pos, text: inserted } => {
+                if *pos > text.len() {
+                    return Err(CollabError::OutOfBounds);
+                }
+                text.splice(*pos..*pos, inserted.chars());
+            },
+            Op::Delete { pos, len } => {
+                if pos + len > text.len() {
+                    return Err(CollabError::OutOfBounds);
+                }
+                text.drain(*pos..pos + len);
+            }
+        }
+        Ok(())
+    }
+
+    fn is_noop(&self) -> bool {
+        match self {
+            Op::Insert { text, .. } => text.is_empty(),
+            Op::Delete { len, .. } => *len == 0
+        }
+    }
+
+    /// Transforms this operation, made concurrently with `other`, so it applies after `other`
+    /// with the same intent. When both insert at the same position, the text of this one goes
+    /// first if `wins_ties`. A deletion spanning text inserted by `other` is split in two, so
+    /// that text is kept.
+    fn transform(&self, other: &Op, wins_ties: bool) -> Vec<Op> {
+        let op = match (self, other) {
+            (Op::Insert { pos, text }, Op::Insert { pos: other_pos, text: other_text }) => {
+                if pos < other_pos || (pos == other_pos && wins_ties) {
+                    self.clone()
+                } else {
+                    Op::Insert { pos: pos + other_text.chars().count(), text: text.clone() }
+                }
+            },
+            (Op::Insert { pos, text }, Op::Delete { pos: other_pos, len }) => {
+                let pos = if pos <= other_pos {
+                    *pos
+                } else if *pos >= other_pos + len {
+                    pos - len
+                } else {
+                    *other_pos
+                };
+                Op::Insert { pos, text: text.clone() }
+            },
+            (Op::Delete { pos, len }, Op::Insert { pos: other_pos, text }) => {
+                let inserted = text.chars().count();
+                if other_pos <= pos {
+                    Op::Delete { pos: pos + inserted, len: *len }
+                } else if *other_pos >= pos + len {
+                    self.clone()
+                } else {
+                    return vec![
+                        Op::Delete { pos: *pos, len: other_pos - pos },
+                        Op::Delete { pos: pos + inserted, len: pos + len - other_pos }
+                    ];
+                }
+            },
+            (Op::Delete { pos, len }, Op::Delete { pos: other_pos, len: other_len }) => {
+                if pos + len <= *other_pos {
+                    self.clone()
+                } else if *pos >= other_pos + other_len {
+                    Op::Delete { pos: pos - other_len, len: *len }
+                } else {
+                    let overlap = (pos + len).min(other_pos + other_len) - pos.max(other_pos);
+                    Op::Delete { pos: *pos.min(other_pos), len: len - overlap }
+                }
+            }
+        };
+        if op.is_noop() { vec![] } else { vec![op] }
+    }
+}
+
+/// Transforms the concurrent edits `ops` and `others`, made on the same text, so each applies
+/// after the other. Returns `ops` to apply after `others`, and `others` to apply after `ops`.
+pub fn transform(ops: &[Op], others: &[Op], wins_ties: bool) -> (Vec<Op>, Vec<Op>) {
+    match (ops, others) {
+        ([], _) | (_, []) => (ops.to_vec(), others.to_vec()),
+        ([op], [other]) => (op.transform(other, wins_ties), other.transform(op, !wins_ties)),
+        ([op, rest @ ..], _) if !rest.is_empty() => {
+            let (op, others) = transform(std::slice::from_ref(op), others, wins_ties);
+            let (rest, others) = transform(rest, &others, wins_ties);
+            ([op, rest].concat(), others)
+        },
+        (_, [other, rest @ ..]) => {
+            let (ops, other) = transform(ops, std::slice::from_ref(other), wins_ties);
+            let (ops, rest) = transform(&ops, rest, wins_ties);
+            (ops, [other, rest].concat())
+        }
+    }
+}
+
+/// The text of a clip being edited, and the last edits made to it. Every edit bumps the version.
+#[derive(Debug)]
+pub struct Document {
+    text: Vec<char>,
+    version: u64,
+    history: VecDeque<Vec<Op>>
+}
+
+impl Document {
+    /// A document at `version`. Versions of different documents of a clip must not overlap, so
+    /// the edits made on a discarded document are never merged into a newer one.
+    pub fn new(text: &str, version: u64) -> Self {
+        Self { text: text.chars().collect(), version, history: VecDeque::new() }
+    }
+
+    pub fn text(&self) -> String {
+        self.text.iter().collect()
+    }
+
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Applies an edit made on `version` of the document, after transforming it against the
+    /// edits applied since. Returns the edit as it was applied; the new version is [`version`].
+    ///
+    /// [`version`]: Document::version
+    pub fn apply(&mut self, version: u64, ops: &[Op]) -> Result<Vec<Op>, CollabError> {
+        if version > self.version || version + (self.history.len() as u64) < self.version {
+            return Err(if version < self.version { CollabError::Stale(version) } else { CollabError::Unknown(version) });
+        }
+        let missed = (self.version - version) as usize;
+        let ops = self.history
+            .iter()
+            .skip(self.history.len() - missed)
+            .fold(ops.to_vec(), |ops, applied| transform(&ops, applied, false).0);
+
+        let mut text = self.text.clone();
+        for op in &ops {
+            op.apply(&mut text)?;
+        }
+        self.text = text;
+        self.version += 1;
+        self.history.push_back(ops.clone());
+        if self.history.len() > HISTORY {
+            self.history.pop_front();
+        }
+        Ok(ops)
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::{transform, CollabError, Document, Op};
+
+    fn insert(pos: usize, text: &str) -> Op {
+        Op::Insert { pos, text: text.to_owned() }
+    }
+
+    fn delete(pos: usize, len: usize) -> Op {
+        Op::Delete { pos, len }
+    }
+
+    fn apply(text: &str, ops: &[Op]) -> String {
+        let mut doc = Document::new(text, 0);
+        doc.apply(0, ops).unwrap();
+        doc.text()
+    }
+
+    #[test]
+    fn concurrent_edits_converge() {
+        let text = "the quick brown fox";
+        let edits = vec![
+            vec![insert(4, "very ")],
+            vec![insert(4, "not so ")],
+            vec![delete(4, 6)],
+            vec![delete(8, 8)],
+            vec![delete(0, 19), insert(0, "gone")],
+            vec![delete(10, 5), insert(10, "red")],
+            vec![insert(19, "!"), insert(0, "¡")]
+        ];
+        for a in &edits {
+            for b in &edits {
+                let (a_after_b, b_after_a) = transform(a, b, true);
+                let one = apply(&apply(text, b), &a_after_b);
+                let other = apply(&apply(text, a), &b_after_a);
+                assert_eq!(one, other, "{:?} and {:?} diverge", a, b);
+            }
+        }
+        // Text inserted within a concurrently deleted range survives.
+        let (deletion, _) = transform(&[delete(4, 6)], &[insert(6, "XY")], true);
+        assert_eq!(apply("the quXYick brown fox", &deletion), "the XYbrown fox");
+    }
+
+    #[test]
+    fn late_edits_are_merged_in_order() {
+        let mut doc = Document::new("ab", 40);
+        assert_eq!(doc.apply(40, &[insert(1, "1")]).unwrap(), vec![insert(1, "1")]);
+        assert_eq!(doc.apply(40, &[insert(2, "2")]).unwrap(), vec![insert(3, "2")]);
+        assert_eq!(doc.apply(41, &[delete(0, 1)]).unwrap(), vec![delete(0, 1)]);
+        assert_eq!(doc.text(), "1b2");
+        assert_eq!(doc.version(), 43);
+
+        assert_eq!(doc.apply(44, &[insert(0, "x")]), Err(CollabError::Unknown(44)));
+        assert_eq!(doc.apply(43, &[delete(2, 5)]), Err(CollabError::OutOfBounds));
+        assert_eq!(doc.text(), "1b2");
+        assert_eq!(doc.apply(39, &[insert(0, "x")]), Err(CollabError::Stale(39)));
+    }
+}
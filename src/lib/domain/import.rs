@@ -0,0 +1,266 @@
+//! Clips imported from the exports of other pastebins. An export is either a JSON array of
+//! pastes, a JSON object holding them in a `clips` or `pastes` array, or NDJSON with a paste
+//! on every line. The fields of a paste go by the names other tools commonly give them, and
+//! its timestamps are RFC 3339 strings, `YYYY-MM-DD HH:MM:SS` in UTC, plain dates or Unix
+//! times in seconds.
+use crate::domain::clip::{field, ClipError};
+use crate::{ShortCode, Time};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use std::convert::{TryFrom, TryInto};
+use std::str::FromStr;
+use thiserror::Error;
+
+/// How many clips are imported in every transaction, unless asked otherwise.
+pub const DEFAULT_BATCH_SIZE: usize = 500;
+
+/// The longest shortcode kept from an export.
+const MAX_SHORTCODE_LEN: usize = 64;
+
+#[derive(Debug, Error)]
+pub enum ImportError {
+    #[error("invalid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("invalid timestamp '{0}'")]
+    InvalidTimestamp(String),
+    #[error("invalid shortcode '{0}'")]
+    InvalidShortcode(String),
+    #[error("invalid conflict policy '{0}', expected skip or rename")]
+    InvalidConflictPolicy(String),
+    #[error("clip error: {0}")]
+    Clip(#[from] ClipError)
+}
+
+/// What to do with a clip whose shortcode is already taken, when shortcodes are kept.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Leaves the clip out of the import.
+    Skip,
+    /// Imports the clip under a new shortcode.
+    Rename
+}
+
+impl FromStr for ConflictPolicy {
+    type Err = ImportError;
+    fn from_str(policy: &str) -> Result<Self, Self::Err> {
+        match policy {
+            "skip" => Ok(Self::Skip),
+            "rename" => Ok(Self::Rename),
+            _ => Err(ImportError::InvalidConflictPolicy(policy.to_owned()))
+        }
+    }
+}
+
+/// A paste as found in an export.
+#[derive(Debug, Deserialize)]
+struct RawClip {
+    #[serde(alias = "text", alias = "body")]
+    content: Option<String>,
+    #[serde(alias = "name")]
+    title: Option<String>,
+    #[serde(default, alias = "posted", alias = "date", deserialize_with = "timestamp")]
+    created: Option<Time>,
+    #[serde(default, alias = "expiry", alias = "expire", deserialize_with = "timestamp")]
+    expires: Option<Time>,
+    #[serde(alias = "language", alias = "format")]
+    syntax: Option<String>,
+    #[serde(alias = "key", alias = "slug")]
+    shortcode: Option<String>
+}
+
+/// Reads a timestamp in any of the supported formats. Missing, empty, `never` and zero
+/// timestamps are all read as no timestamp.
+fn timestamp<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Time>, D::Error> {
+    let value = Value::deserialize(deserializer)?;
+    parse_timestamp(&value).map_err(serde::de::Error::custom)
+}
+
+fn parse_timestamp(value: &Value) -> Result<Option<Time>, ImportError> {
+    let invalid = || ImportError::InvalidTimestamp(value.to_string());
+    let seconds = |seconds: i64| match seconds {
+        0 => Ok(None),
+        seconds => DateTime::from_timestamp(seconds, 0).map(|time| Some(time.into())).ok_or_else(invalid)
+    };
+    match value {
+        Value::Null => Ok(None),
+        Value::Number(number) => seconds(number.as_i64().ok_or_else(invalid)?),
+        Value::String(raw) => {
+            let raw = raw.trim();
+            if raw.is_empty() || raw.eq_ignore_ascii_case("never") {
+                return Ok(None);
+            }
+            if let Ok(number) = raw.parse::<i64>() {
+                return seconds(number);
+            }
+            if let Ok(time) = DateTime::parse_from_rfc3339(raw) {
+                return Ok(Some(time.with_timezone(&Utc).into()));
+            }
+            ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S"]
+                .iter()
+                .find_map(|format| NaiveDateTime::parse_from_str(raw, format).ok())
+                .or_else(|| NaiveDate::parse_from_str(raw, "%Y-%m-%d").ok().and_then(|date| date.and_hms_opt(0, 0, 0)))
+                .map(|time| Some(Time::from_naive_utc(time)))
+                .ok_or_else(invalid)
+        },
+        _ => Err(invalid())
+    }
+}
+
+/// A clip to import. Clips without a `created` timestamp are posted at the time of the import.
+#[derive(Clone, Debug)]
+pub struct ImportedClip {
+    pub content: field::Content,
+    pub title: field::Title,
+    pub posted: Option<Time>,
+    pub expires: field::Expires,
+    /// The shortcode of the paste in the export, kept when asked to.
+    pub shortcode: Option<ShortCode>,
+    /// The syntax highlighting of the paste. Clips have none, so it is only read.
+    pub syntax: Option<String>
+}
+
+impl TryFrom<RawClip> for ImportedClip {
+    type Error = ImportError;
+    fn try_from(clip: RawClip) -> Result<Self, Self::Error> {
+        let shortcode = match clip.shortcode {
+            Some(shortcode) if shortcode.is_empty()
+                || shortcode.len() > MAX_SHORTCODE_LEN
+                || !shortcode.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') => {
+                return Err(ImportError::InvalidShortcode(shortcode));
+            },
+            shortcode => shortcode.map(ShortCode::from)
+        };
+        Ok(Self {
+            content: field::Content::new(clip.content.as_deref().unwrap_or_default())?,
+            title: field::Title::new(clip.title),
+            posted: clip.created,
+            expires: field::Expires::new(clip.expires),
+            shortcode,
+            syntax: clip.syntax
+        })
+    }
+}
+
+/// A paste of an export which couldn't be read, by its number: the line of an NDJSON export,
+/// or the position in an array counting from 1.
+#[derive(Debug, Serialize)]
+pub struct InvalidRecord {
+    pub record: usize,
+    pub error: String
+}
+
+/// The clips read from an export, along with their record numbers, and the records which
+/// couldn't be read.
+#[derive(Debug, Default)]
+pub struct ParsedExport {
+    pub clips: Vec<(usize, ImportedClip)>,
+    pub invalid: Vec<InvalidRecord>
+}
+
+impl ParsedExport {
+    fn push(&mut self, record: usize, clip: Result<ImportedClip, ImportError>) {
+        match clip {
+            Ok(clip) => self.clips.push((record, clip)),
+            Err(e) => self.invalid.push(InvalidRecord { record, error: e.to_string() })
+        }
+    }
+}
+
+fn read_clip(value: Value) -> Result<ImportedClip, ImportError> {
+    serde_json::from_value::<RawClip>(value)?.try_into()
+}
+
+/// Reads the pastes of an export. Only a file which is neither JSON nor NDJSON is an error;
+/// pastes which can't be read are listed in [`ParsedExport::invalid`].
+pub fn parse_export(export: &str) -> Result<ParsedExport, ImportError> {
+    let mut parsed = ParsedExport::default();
+    let records = match serde_json::from_str::<Value>(export) {
+        Ok(Value::Array(records)) => records,
+        Ok(Value::Object(mut object)) => match object.remove("clips").or_else(|| object.remove("pastes")) {
+            Some(Value::Array(records)) => records,
+            Some(_) => return Err(ImportError::Json(serde::de::Error::custom("expected an array of pastes"))),
+            None => vec![Value::Object(object)]
+        },
+        Ok(_) => return Err(ImportError::Json(serde::de::Error::custom("expected pastes"))),
+        Err(e) => {
+            // Anything else must be NDJSON, whose first line is a paste on its own.
+            let first = export.lines().find(|line| !line.trim().is_empty()).unwrap_or_default();
+            if serde_json::from_str::<Value>(first).is_err() {
+                return Err(e.into());
+            }
+            for (line, raw) in export.lines().enumerate() {
+                if raw.trim().is_empty() {
+                    continue;
+                }
+                let clip = serde_json::from_str::<Value>(raw).map_err(ImportError::from).and_then(read_clip);
+                parsed.push(line + 1, clip);
+            }
+            return Ok(parsed);
+        }
+    };
+    for (index, record) in records.into_iter().enumerate() {
+        parsed.push(index + 1, read_clip(record));
+    }
+    Ok(parsed)
+}
+
+/// A clip whose shortcode was already taken, and the shortcode it got instead if it was
+/// imported anyway.
+#[derive(Debug, Serialize)]
+pub struct Conflict {
+    pub record: usize,
+    pub shortcode: ShortCode,
+    pub imported_as: Option<ShortCode>
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ImportReport {
+    pub imported: usize,
+    pub conflicts: Vec<Conflict>
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    #[test]
+    fn reads_json_and_ndjson_exports() {
+        let array = r#"[
+            {"title": "Build log", "content": "it failed", "created": "2019-04-01T08:30:00+02:00", "expires": null, "syntax": "text", "shortcode": "abc123"},
+            {"name": "", "text": "fn main() {}", "date": 1554100200, "expire": "2030-01-01", "language": "rust"},
+            {"content": "", "created": "yesterday"}
+        ]"#;
+        let parsed = parse_export(array).unwrap();
+        assert_eq!(parsed.clips.len(), 2);
+        let (record, clip) = &parsed.clips[0];
+        assert_eq!(*record, 1);
+        assert_eq!(clip.title.clone().into_inner().as_deref(), Some("Build log"));
+        assert_eq!(clip.content.clone().into_inner(), "it failed");
+        assert_eq!(clip.posted.clone().unwrap().timestamp(), 1554100200);
+        assert!(clip.expires.clone().into_inner().is_none());
+        assert_eq!(clip.shortcode.as_ref().map(ShortCode::as_str), Some("abc123"));
+        let (_, clip) = &parsed.clips[1];
+        assert!(clip.title.clone().into_inner().is_none());
+        assert_eq!(clip.posted.clone().unwrap().timestamp(), 1554100200);
+        assert_eq!(clip.expires.clone().into_inner().unwrap().timestamp(), 1893456000);
+        assert_eq!(clip.syntax.as_deref(), Some("rust"));
+        assert_eq!(parsed.invalid.len(), 1);
+        assert_eq!(parsed.invalid[0].record, 3);
+
+        let wrapped = r#"{"pastes": [{"content": "wrapped", "posted": "2019-04-01 06:30:00", "expires": "never"}]}"#;
+        let parsed = parse_export(wrapped).unwrap();
+        assert_eq!(parsed.clips[0].1.posted.clone().unwrap().timestamp(), 1554100200);
+        assert!(parsed.clips[0].1.expires.clone().into_inner().is_none());
+
+        let ndjson = "{\"content\": \"first\"}\n\n{\"content\": \"second\", \"key\": \"has spaces\"}\n{\"content\": \"third\"}\nnot json\n";
+        let parsed = parse_export(ndjson).unwrap();
+        let records: Vec<_> = parsed.clips.iter().map(|(record, _)| *record).collect();
+        assert_eq!(records, vec![1, 4]);
+        let invalid: Vec<_> = parsed.invalid.iter().map(|invalid| invalid.record).collect();
+        assert_eq!(invalid, vec![3, 5]);
+
+        assert!(parse_export("title,content\nfoo,bar").is_err());
+        assert!(parse_export("42").is_err());
+    }
+}
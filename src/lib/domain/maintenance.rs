@@ -1,22 +1,69 @@
 use crate::data::DatabasePool;
+use crate::domain::webhook::{WebhookEvent, WebhookPayload, DELIVERY_RETENTION};
+use crate::metrics::METRICS;
 use crate::service;
 use std::time::Duration;
 use tokio::runtime::Handle;
+use tokio::task::JoinHandle;
 
-pub struct Maintenance;
-/// Creates a struct and implements the spawn method to call the [`delete_expired`](`crate::data::query::delete_expired`) query every 10 seconds
+/// How long an expired or deleted clip can be restored before it is purged, when not configured.
+pub const DEFAULT_GRACE_PERIOD: Duration = Duration::from_secs(60 * 60 * 24 * 7);
+
+pub struct Maintenance {
+    task: JoinHandle<()>
+}
+/// Creates a struct and implements the spawn method which, every 10 seconds, moves expired clips
+/// into the tombstone state with [`expire_clips`](`crate::data::query::expire_clips`) and purges
+/// the ones older than the `grace_period` with [`purge_deleted`](`crate::data::query::purge_deleted`).
+/// The webhooks subscribed to `clip.expired` are told about the expired clips. Expired sessions
+/// and old webhook deliveries are removed as well.
 impl Maintenance {
-    pub fn spawn(pool: DatabasePool, handle: Handle) -> Self {
-        handle.spawn(async move {
+    pub fn spawn(pool: DatabasePool, handle: Handle, grace_period: Duration) -> Self {
+        let task = handle.spawn(async move {
             let mut interval = tokio::time::interval(Duration::from_secs(10));
 
             loop {
                 interval.tick().await;
-                if let Err(e) = service::action::delete_expired(&pool).await {
-                    eprintln!("failed to delete expired clips: {:#?}", e);
+                match service::action::expire_clips(&pool).await {
+                    Ok(expired) => {
+                        if !expired.is_empty() {
+                            tracing::info!(count = expired.len(), "expired clips");
+                            let payloads: Vec<_> = expired
+                                .iter()
+                                .map(|shortcode| WebhookPayload::new(WebhookEvent::ClipExpired, shortcode.clone()))
+                                .collect();
+                            service::action::notify_webhooks(&payloads, &pool).await;
+                        }
+                        METRICS.maintenance_deletions
+                            .with_label_values(&["expired"])
+                            .inc_by(expired.len() as u64)
+                    },
+                    Err(e) => tracing::error!(error = %e, "failed to expire clips")
+                }
+                match service::action::purge_deleted(grace_period, &pool).await {
+                    Ok(purged) => {
+                        if purged > 0 {
+                            tracing::info!(count = purged, "purged deleted clips");
+                        }
+                        METRICS.maintenance_deletions
+                            .with_label_values(&["purged"])
+                            .inc_by(purged)
+                    },
+                    Err(e) => tracing::error!(error = %e, "failed to purge deleted clips")
+                }
+                if let Err(e) = service::action::purge_sessions(&pool).await {
+                    tracing::error!(error = %e, "failed to purge expired sessions")
+                }
+                if let Err(e) = service::action::purge_webhook_deliveries(DELIVERY_RETENTION, &pool).await {
+                    tracing::error!(error = %e, "failed to purge old webhook deliveries")
                 }
             }
         });
-        Self
+        Self { task }
+    }
+
+    /// Returns whether the maintenance task is still running.
+    pub fn is_running(&self) -> bool {
+        !self.task.is_finished()
     }
-}
\ No newline at end of file
+}
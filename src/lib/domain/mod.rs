@@ -1,3 +1,12 @@
+pub mod backup;
 pub mod clip;
+pub mod collab;
+pub mod import;
 pub mod time;
 pub mod maintenance;
+pub mod scope;
+pub mod slash;
+pub mod stats;
+pub mod user;
+pub mod webhook;
+pub mod workspace;
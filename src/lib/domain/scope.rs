@@ -0,0 +1,75 @@
+use rocket::form::FromFormField;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+#[error("unknown scope: {0}")]
+pub struct ScopeError(String);
+
+/// What an [`ApiKey`](crate::web::api::ApiKey) may do. Each API route requires one scope.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Deserialize, Serialize, FromFormField)]
+pub enum Scope {
+    #[serde(rename = "clip:read")]
+    #[field(value = "clip:read")]
+    ClipRead,
+    #[serde(rename = "clip:write")]
+    #[field(value = "clip:write")]
+    ClipWrite,
+    #[serde(rename = "clip:delete")]
+    #[field(value = "clip:delete")]
+    ClipDelete,
+    /// Administering the server: minting API keys with any scope, deleting and restoring any
+    /// clip, and backing up every clip.
+    #[serde(rename = "admin")]
+    #[field(value = "admin")]
+    Admin
+}
+
+impl Scope {
+    /// The scopes of keys minted without asking for any: everything but administering the server.
+    pub const CLIPS: [Scope; 3] = [Self::ClipRead, Self::ClipWrite, Self::ClipDelete];
+    pub const ALL: [Scope; 4] = [Self::ClipRead, Self::ClipWrite, Self::ClipDelete, Self::Admin];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::ClipRead => "clip:read",
+            Self::ClipWrite => "clip:write",
+            Self::ClipDelete => "clip:delete",
+            Self::Admin => "admin"
+        }
+    }
+}
+
+impl std::fmt::Display for Scope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Scope {
+    type Err = ScopeError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "clip:read" => Ok(Self::ClipRead),
+            "clip:write" => Ok(Self::ClipWrite),
+            "clip:delete" => Ok(Self::ClipDelete),
+            "admin" => Ok(Self::Admin),
+            other => Err(ScopeError(other.to_owned()))
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::Scope;
+
+    #[test]
+    fn parses_scopes() {
+        for scope in Scope::ALL {
+            assert_eq!(scope.as_str().parse::<Scope>().unwrap(), scope);
+        }
+        assert!("clip:*".parse::<Scope>().is_err());
+        assert_eq!(serde_json::to_string(&Scope::ClipWrite).unwrap(), r#""clip:write""#);
+    }
+}
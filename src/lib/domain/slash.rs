@@ -0,0 +1,240 @@
+//! Slash commands, with which chat users create clips by typing `/clip some text`. The chat
+//! server POSTs the command as a form and is trusted through either a Slack-style signature
+//! of the body made with the signing secret, or the Mattermost-style token of the command.
+use crate::domain::clip::{field, ClipError};
+use crate::Time;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::str::FromStr;
+use std::time::Duration;
+use thiserror::Error;
+
+/// The header carrying the signature of a command, as `v0=<hex digest>`.
+pub const SIGNATURE_HEADER: &str = "x-slack-signature";
+/// The header carrying the Unix time a command was signed at, which is part of what is signed.
+pub const TIMESTAMP_HEADER: &str = "x-slack-request-timestamp";
+
+/// Signed commands older or newer than this are refused, so they can't be replayed.
+pub const MAX_CLOCK_SKEW: Duration = Duration::from_secs(5 * 60);
+
+pub const USAGE: &str = "Usage: /clip [--expires 10m|2h|7d|1w|YYYY-MM-DD] [--password <password>] [--title \"<title>\"] <content>";
+
+/// What the chat server is trusted with, and where the links to the created clips point.
+#[derive(Clone, Debug)]
+pub struct SlashCommandConfig {
+    /// The signing secret of a Slack app, or the token of a Mattermost command.
+    pub signing_secret: String,
+    /// The URL the web UI is served at, such as `https://clips.example.com`.
+    pub public_url: String
+}
+
+#[derive(Debug, Error)]
+pub enum SlashCommandError {
+    #[error("invalid signature")]
+    InvalidSignature,
+    #[error("the request timestamp is too far from the current time")]
+    Stale,
+    #[error("unknown option --{0}")]
+    UnknownOption(String),
+    #[error("missing value of option --{0}")]
+    MissingValue(String),
+    #[error("unterminated quote in option --{0}")]
+    UnterminatedQuote(String),
+    #[error("invalid expiry '{0}'")]
+    InvalidExpires(String),
+    #[error("clip error: {0}")]
+    Clip(#[from] ClipError)
+}
+
+/// Signs the body of a command sent at `timestamp`, by computing the HMAC-SHA256 of
+/// `v0:<timestamp>:<body>` with the signing secret.
+pub fn signature(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any size");
+    mac.update(format!("v0:{}:", timestamp).as_bytes());
+    mac.update(body.as_bytes());
+    let digest: String = mac.finalize().into_bytes().iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("v0={}", digest)
+}
+
+/// Checks the signature of a command, which must have been signed within [`MAX_CLOCK_SKEW`]
+/// of `now`. Timestamps too far off to subtract are stale too.
+pub fn verify_signature(
+    secret: &str,
+    timestamp: &str,
+    body: &str,
+    signature: &str,
+    now: i64
+) -> Result<(), SlashCommandError> {
+    let timestamp: i64 = timestamp.trim().parse().map_err(|_| SlashCommandError::InvalidSignature)?;
+    match now.checked_sub(timestamp) {
+        Some(skew) if skew.unsigned_abs() <= MAX_CLOCK_SKEW.as_secs() => (),
+        _ => return Err(SlashCommandError::Stale)
+    }
+    if constant_time_eq(self::signature(secret, timestamp, body).as_bytes(), signature.trim().as_bytes()) {
+        Ok(())
+    } else {
+        Err(SlashCommandError::InvalidSignature)
+    }
+}
+
+/// Checks the token sent along with a command, as Mattermost does instead of signing it.
+pub fn verify_token(secret: &str, token: &str) -> Result<(), SlashCommandError> {
+    if constant_time_eq(secret.as_bytes(), token.as_bytes()) {
+        Ok(())
+    } else {
+        Err(SlashCommandError::InvalidSignature)
+    }
+}
+
+/// Compares two secrets without returning early, so the time taken tells nothing about them.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// Parses a duration such as `10m`, `2h`, `7d` or `1w`.
+fn parse_duration(raw: &str) -> Option<chrono::Duration> {
+    let unit = raw.chars().last()?;
+    let amount: i64 = raw[..raw.len() - unit.len_utf8()].parse().ok().filter(|amount| *amount > 0)?;
+    match unit {
+        'm' => chrono::Duration::try_minutes(amount),
+        'h' => chrono::Duration::try_hours(amount),
+        'd' => chrono::Duration::try_days(amount),
+        'w' => chrono::Duration::try_weeks(amount),
+        _ => None
+    }
+}
+
+/// The clip asked for by the text of a `/clip` command. Options come before the content, as
+/// `--name value` or `--name=value`, and values with spaces are put in double quotes. A lone
+/// `--` ends the options, for content which starts with `--` itself.
+#[derive(Debug)]
+pub struct ClipCommand {
+    pub content: field::Content,
+    pub title: field::Title,
+    pub expires: field::Expires,
+    pub password: field::Password
+}
+
+impl ClipCommand {
+    /// Parses the text of a command, with relative expiries counted from `now`.
+    pub fn parse(text: &str, now: chrono::DateTime<Utc>) -> Result<Self, SlashCommandError> {
+        let mut title = field::Title::default();
+        let mut expires = field::Expires::default();
+        let mut password = field::Password::default();
+
+        let mut rest = text.trim_start();
+        while let Some(option) = rest.strip_prefix("--") {
+            if option.is_empty() || option.starts_with(char::is_whitespace) {
+                rest = option.trim_start();
+                break;
+            }
+            let name_end = option.find(|c: char| c == '=' || c.is_whitespace()).unwrap_or(option.len());
+            let name = &option[..name_end];
+            let remainder = match option[name_end..].strip_prefix('=') {
+                Some(remainder) => remainder,
+                None => option[name_end..].trim_start()
+            };
+            let (value, remainder) = Self::value(name, remainder)?;
+            match name {
+                "title" => title = field::Title::new(value),
+                "password" => password = field::Password::new(value)?,
+                "expires" => {
+                    expires = match parse_duration(&value) {
+                        Some(duration) => field::Expires::new(Time::from(now + duration)),
+                        None => field::Expires::from_str(&value)
+                            .map_err(|_| SlashCommandError::InvalidExpires(value))?
+                    }
+                },
+                _ => return Err(SlashCommandError::UnknownOption(name.to_owned()))
+            }
+            rest = remainder.trim_start();
+        }
+
+        Ok(Self {
+            content: field::Content::new(rest.trim_end())?,
+            title,
+            expires,
+            password
+        })
+    }
+
+    /// Splits the value of option `name` off the start of `text`.
+    fn value<'a>(name: &str, text: &'a str) -> Result<(String, &'a str), SlashCommandError> {
+        if let Some(quoted) = text.strip_prefix('"') {
+            let end = quoted.find('"').ok_or_else(|| SlashCommandError::UnterminatedQuote(name.to_owned()))?;
+            return Ok((quoted[..end].to_owned(), &quoted[end + 1..]));
+        }
+        let end = text.find(char::is_whitespace).unwrap_or(text.len());
+        if end == 0 {
+            return Err(SlashCommandError::MissingValue(name.to_owned()));
+        }
+        Ok((text[..end].to_owned(), &text[end..]))
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use chrono::TimeZone;
+
+    /// The example request of Slack's documentation on verifying requests.
+    const SECRET: &str = "8f742231b10e8888abcd99yyyzzz85a5";
+    const TIMESTAMP: &str = "1531420618";
+    const BODY: &str = "token=xyzz0WbapA4vBCDEFasx0q6G&team_id=T1DC2JH3J&team_domain=testteamnow&channel_id=G8PSS9T3V&channel_name=foobar&user_id=U2CERLKJA&user_name=roadrunner&command=%2Fwebhook-collect&text=&response_url=https%3A%2F%2Fhooks.slack.com%2Fcommands%2FT1DC2JH3J%2F397700885554%2F96rGlfmibIGlgcZRskXaIFfN&trigger_id=398738663015.47445629121.803a0bc887a14d10d2c447fce8b6703c";
+    const SIGNATURE: &str = "v0=a2114d57b48eac39b9ad189dd8316235a7b4a8d21a10bd27519666489c69b503";
+
+    #[test]
+    fn verifies_slack_signatures() {
+        let now = 1531420618 + 60;
+        assert!(verify_signature(SECRET, TIMESTAMP, BODY, SIGNATURE, now).is_ok());
+        assert!(matches!(
+            verify_signature(SECRET, TIMESTAMP, &BODY.replace("roadrunner", "coyote"), SIGNATURE, now),
+            Err(SlashCommandError::InvalidSignature)
+        ));
+        assert!(matches!(
+            verify_signature("another secret", TIMESTAMP, BODY, SIGNATURE, now),
+            Err(SlashCommandError::InvalidSignature)
+        ));
+        assert!(matches!(
+            verify_signature(SECRET, TIMESTAMP, BODY, SIGNATURE, now + 60 * 60),
+            Err(SlashCommandError::Stale)
+        ));
+        for timestamp in [i64::MIN, i64::MAX].iter() {
+            assert!(matches!(
+                verify_signature(SECRET, &timestamp.to_string(), BODY, SIGNATURE, now),
+                Err(SlashCommandError::Stale)
+            ));
+        }
+        assert!(verify_token(SECRET, SECRET).is_ok());
+        assert!(verify_token(SECRET, "xyzz0WbapA4vBCDEFasx0q6G").is_err());
+    }
+
+    #[test]
+    fn parses_options_before_the_content() {
+        let now = Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap();
+
+        let command = ClipCommand::parse("  fn main() {}\n", now).unwrap();
+        assert_eq!(command.content.into_inner(), "fn main() {}");
+        assert!(command.title.into_inner().is_none());
+        assert!(command.expires.into_inner().is_none());
+        assert!(!command.password.has_password());
+
+        let command = ClipCommand::parse(r#"--expires 2h --title "Build log" --password=hunter2 it --failed"#, now).unwrap();
+        assert_eq!(command.content.into_inner(), "it --failed");
+        assert_eq!(command.title.into_inner().as_deref(), Some("Build log"));
+        assert_eq!(command.expires.into_inner().unwrap().into_inner(), Utc.with_ymd_and_hms(2024, 1, 1, 14, 0, 0).unwrap());
+        assert_eq!(command.password.into_inner().as_deref(), Some("hunter2"));
+
+        let command = ClipCommand::parse("--expires=2030-06-01 -- --verbose", now).unwrap();
+        assert_eq!(command.content.into_inner(), "--verbose");
+        assert_eq!(command.expires.into_inner().unwrap().into_inner(), Utc.with_ymd_and_hms(2030, 6, 1, 0, 0, 0).unwrap());
+
+        assert!(matches!(ClipCommand::parse("", now), Err(SlashCommandError::Clip(ClipError::EmptyContent))));
+        assert!(matches!(ClipCommand::parse("--expires 1h", now), Err(SlashCommandError::Clip(ClipError::EmptyContent))));
+        assert!(matches!(ClipCommand::parse("--expires soon text", now), Err(SlashCommandError::InvalidExpires(_))));
+        assert!(matches!(ClipCommand::parse("--color red text", now), Err(SlashCommandError::UnknownOption(_))));
+        assert!(matches!(ClipCommand::parse(r#"--title "oops text"#, now), Err(SlashCommandError::UnterminatedQuote(_))));
+        assert!(matches!(ClipCommand::parse("--password", now), Err(SlashCommandError::MissingValue(_))));
+    }
+}
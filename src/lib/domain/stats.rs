@@ -0,0 +1,167 @@
+use crate::domain::clip::ClipError;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+/// The route a [`Clip`](crate::Clip) was viewed through.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ViewRoute {
+    Web,
+    Raw,
+    Api
+}
+
+impl ViewRoute {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Web => "web",
+            Self::Raw => "raw",
+            Self::Api => "api"
+        }
+    }
+}
+
+impl FromStr for ViewRoute {
+    type Err = ClipError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "web" => Ok(Self::Web),
+            "raw" => Ok(Self::Raw),
+            "api" => Ok(Self::Api),
+            other => Err(ClipError::InvalidView(format!("unknown route: {}", other)))
+        }
+    }
+}
+
+/// A coarse classification of the user agent which viewed a [`Clip`](crate::Clip).
+/// The full user agent string is never stored.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AgentClass {
+    Browser,
+    Tool,
+    Bot,
+    Other
+}
+
+impl AgentClass {
+    /// Classifies a `User-Agent` header value.
+    pub fn from_user_agent(user_agent: Option<&str>) -> Self {
+        let user_agent = match user_agent {
+            Some(user_agent) => user_agent.to_lowercase(),
+            None => return Self::Other
+        };
+        let is_any = |needles: &[&str]| needles.iter().any(|needle| user_agent.contains(needle));
+
+        if is_any(&["bot", "crawler", "spider", "slurp", "preview"]) {
+            Self::Bot
+        } else if is_any(&["curl", "wget", "httpie", "reqwest", "python", "go-http-client", "postman", "insomnia"]) {
+            Self::Tool
+        } else if user_agent.starts_with("mozilla/") {
+            Self::Browser
+        } else {
+            Self::Other
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Browser => "browser",
+            Self::Tool => "tool",
+            Self::Bot => "bot",
+            Self::Other => "other"
+        }
+    }
+}
+
+impl FromStr for AgentClass {
+    type Err = ClipError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "browser" => Ok(Self::Browser),
+            "tool" => Ok(Self::Tool),
+            "bot" => Ok(Self::Bot),
+            "other" => Ok(Self::Other),
+            other => Err(ClipError::InvalidView(format!("unknown agent class: {}", other)))
+        }
+    }
+}
+
+/// A single view of a [`Clip`](crate::Clip). IP addresses are never recorded, and the
+/// referrer is reduced to its host.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct View {
+    pub route: ViewRoute,
+    pub agent: AgentClass,
+    pub referrer: Option<String>
+}
+
+impl View {
+    pub fn new(route: ViewRoute, agent: AgentClass, referrer: Option<String>) -> Self {
+        Self {
+            route,
+            agent,
+            referrer: referrer.map(|host| host.to_lowercase())
+        }
+    }
+}
+
+/// The number of views of a [`Clip`](crate::Clip) on a given day.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct DailyViews {
+    pub day: NaiveDate,
+    pub views: u64
+}
+
+/// Aggregated views of a [`Clip`](crate::Clip).
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct ClipStats {
+    pub total: u64,
+    pub daily: Vec<DailyViews>,
+    pub routes: BTreeMap<String, u64>,
+    pub agents: BTreeMap<String, u64>,
+    pub referrers: BTreeMap<String, u64>
+}
+
+impl ClipStats {
+    /// Builds the stats out of `(hour, view, count)` tuples.
+    pub fn from_views<I>(views: I) -> Self
+    where
+        I: IntoIterator<Item = (DateTime<Utc>, View, u64)>
+    {
+        let mut stats = Self::default();
+        let mut daily: BTreeMap<NaiveDate, u64> = BTreeMap::new();
+
+        for (hour, view, count) in views {
+            stats.total += count;
+            *daily.entry(hour.date_naive()).or_insert(0) += count;
+            *stats.routes.entry(view.route.as_str().to_owned()).or_insert(0) += count;
+            *stats.agents.entry(view.agent.as_str().to_owned()).or_insert(0) += count;
+            if let Some(referrer) = view.referrer {
+                *stats.referrers.entry(referrer).or_insert(0) += count;
+            }
+        }
+
+        stats.daily = daily
+            .into_iter()
+            .map(|(day, views)| DailyViews { day, views })
+            .collect();
+        stats
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    #[test]
+    fn classifies_user_agents() {
+        let browser = "Mozilla/5.0 (X11; Linux x86_64; rv:105.0) Gecko/20100101 Firefox/105.0";
+        assert_eq!(AgentClass::from_user_agent(Some(browser)), AgentClass::Browser);
+        assert_eq!(AgentClass::from_user_agent(Some("curl/7.85.0")), AgentClass::Tool);
+        assert_eq!(AgentClass::from_user_agent(Some("Mozilla/5.0 (compatible; Googlebot/2.1)")), AgentClass::Bot);
+        assert_eq!(AgentClass::from_user_agent(None), AgentClass::Other);
+    }
+}
@@ -0,0 +1,9 @@
+/// All fields of a user are defined and validated in this module.
+mod user_id;
+pub use user_id::UserId;
+
+mod username;
+pub use username::Username;
+
+mod password;
+pub use password::UserPassword;
@@ -0,0 +1,50 @@
+use crate::domain::user::UserError;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use rocket::form::{self, FromFormField, ValueField};
+use crate::redact::redacted_debug;
+
+/// The password of a [`User`](crate::domain::user::User), which is only ever stored hashed
+/// with Argon2.
+#[derive(Clone)]
+pub struct UserPassword(String);
+
+redacted_debug!(UserPassword);
+
+impl UserPassword {
+    pub fn new(password: &str) -> Result<Self, UserError> {
+        if !(8..=128).contains(&password.chars().count()) {
+            return Err(UserError::InvalidPassword("must be 8 to 128 characters long".to_owned()));
+        }
+        Ok(Self(password.to_owned()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+
+    /// Hashes the password with a random salt, in the PHC string format.
+    pub fn hash(&self) -> Result<String, UserError> {
+        let salt: Vec<u8> = (0..16).map(|_| rand::random::<u8>()).collect();
+        let salt = SaltString::encode_b64(&salt).map_err(|e| UserError::Hash(e.to_string()))?;
+        Argon2::default()
+            .hash_password(self.0.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+            .map_err(|e| UserError::Hash(e.to_string()))
+    }
+
+    /// Returns whether the password matches a hash made by [`hash`](Self::hash).
+    pub fn verify(&self, hash: &str) -> bool {
+        PasswordHash::new(hash)
+            .map(|hash| Argon2::default().verify_password(self.0.as_bytes(), &hash).is_ok())
+            .unwrap_or(false)
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromFormField<'r> for UserPassword {
+    fn from_value(field: ValueField<'r>) -> form::Result<'r, Self> {
+        Ok(Self::new(field.value)
+            .map_err(|e| form::Error::validation(e.to_string()))?)
+    }
+}
@@ -0,0 +1,47 @@
+use crate::domain::user::UserError;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use rocket::form::{self, FromFormField, ValueField};
+
+/// The username of a [`User`](crate::domain::user::User): 3 to 32 letters, digits, `_`, `-`
+/// or `.`. Usernames are case-insensitive, so they are stored in lowercase.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Username(String);
+
+impl Username {
+    pub fn new(username: &str) -> Result<Self, UserError> {
+        let username = username.trim().to_lowercase();
+        if !(3..=32).contains(&username.len()) {
+            return Err(UserError::InvalidUsername("must be 3 to 32 characters long".to_owned()));
+        }
+        if !username.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.') {
+            return Err(UserError::InvalidUsername(
+                "may only contain letters, digits, '_', '-' and '.'".to_owned()
+            ));
+        }
+        Ok(Self(username))
+    }
+
+    pub fn into_inner(self) -> String {
+        self.0
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl FromStr for Username {
+    type Err = UserError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::new(s)
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromFormField<'r> for Username {
+    fn from_value(field: ValueField<'r>) -> form::Result<'r, Self> {
+        Ok(Self::new(field.value)
+            .map_err(|e| form::Error::validation(e.to_string()))?)
+    }
+}
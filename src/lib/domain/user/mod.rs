@@ -0,0 +1,94 @@
+pub mod field;
+pub mod oidc;
+
+use crate::Time;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::str::FromStr;
+use thiserror::Error;
+use crate::redact::redacted_debug;
+
+#[derive(Debug, Error)]
+pub enum UserError {
+    #[error("invalid username: {0}")]
+    InvalidUsername(String),
+    #[error("invalid password: {0}")]
+    InvalidPassword(String),
+    #[error("username is already taken")]
+    UsernameTaken,
+    #[error("invalid session")]
+    InvalidSession,
+    #[error("password hashing error: {0}")]
+    Hash(String),
+    #[error("id parse error: {0}")]
+    Id(#[from] uuid::Error)
+}
+
+/// A user account. The password hash never leaves the data layer.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct User {
+    #[serde(skip)]
+    pub user_id: field::UserId,
+    pub username: field::Username,
+}
+
+/// An API key minted by a [`User`], identified by the fingerprint of the key.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ApiKeyInfo {
+    pub fingerprint: String,
+    pub created: Option<Time>,
+    pub scopes: Vec<crate::domain::scope::Scope>,
+}
+
+/// The random token stored in the session cookie. Only its hash is stored in the database.
+#[derive(Clone)]
+pub struct SessionToken(Vec<u8>);
+
+redacted_debug!(SessionToken);
+
+impl SessionToken {
+    pub fn hash(&self) -> Vec<u8> {
+        Sha256::digest(&self.0).to_vec()
+    }
+
+    pub fn to_base64(&self) -> String {
+        base64::encode_config(&self.0, base64::URL_SAFE_NO_PAD)
+    }
+}
+
+impl Default for SessionToken {
+    fn default() -> Self {
+        Self((0..32).map(|_| rand::random::<u8>()).collect())
+    }
+}
+
+impl FromStr for SessionToken {
+    type Err = UserError;
+    fn from_str(token: &str) -> Result<Self, Self::Err> {
+        base64::decode_config(token, base64::URL_SAFE_NO_PAD)
+            .map(Self)
+            .map_err(|_| UserError::InvalidSession)
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::field::{UserPassword, Username};
+
+    #[test]
+    fn validates_usernames() {
+        assert!(Username::new("alice_01").is_ok());
+        assert_eq!(Username::new("Alice").unwrap().as_str(), "alice");
+        assert!(Username::new("al").is_err());
+        assert!(Username::new("alice smith").is_err());
+    }
+
+    #[test]
+    fn hashes_and_verifies_passwords() {
+        let password = UserPassword::new("correct horse").unwrap();
+        let hash = password.hash().unwrap();
+        assert!(password.verify(&hash));
+        assert!(!UserPassword::new("wrong horse").unwrap().verify(&hash));
+        assert!(UserPassword::new("short").is_err());
+    }
+}
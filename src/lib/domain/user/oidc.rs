@@ -0,0 +1,308 @@
+//! Single sign-on through an OpenID Connect provider, using the authorization code flow
+//! with PKCE.
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{decode, decode_header, DecodingKey, Validation};
+use reqwest::Url;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::str::FromStr;
+use std::sync::RwLock;
+use std::time::Duration;
+use thiserror::Error;
+use crate::redact::redacted_debug;
+
+#[derive(Debug, Error)]
+pub enum OidcError {
+    #[error("discovery failed: {0}")]
+    Discovery(String),
+    #[error("request to the provider failed: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("token request failed: {0}")]
+    Token(String),
+    #[error("invalid ID token: {0}")]
+    IdToken(String),
+    #[error("invalid login flow")]
+    InvalidFlow
+}
+
+impl From<jsonwebtoken::errors::Error> for OidcError {
+    fn from(err: jsonwebtoken::errors::Error) -> Self {
+        Self::IdToken(err.to_string())
+    }
+}
+
+/// How to reach the provider, and how this server is registered with it.
+#[derive(Debug, Clone)]
+pub struct OidcConfig {
+    pub issuer: String,
+    pub client_id: String,
+    pub client_secret: String,
+    /// Where the provider sends users back to, which is the `/auth/sso/callback` route.
+    pub redirect_url: String
+}
+
+#[derive(Debug, Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    id_token: String
+}
+
+#[derive(Debug, Deserialize)]
+struct IdTokenClaims {
+    sub: String,
+    nonce: Option<String>,
+    preferred_username: Option<String>,
+    email: Option<String>
+}
+
+fn random_base64() -> String {
+    let bytes: Vec<u8> = (0..32).map(|_| rand::random::<u8>()).collect();
+    base64::encode_config(bytes, base64::URL_SAFE_NO_PAD)
+}
+
+/// A login in progress: the `state` ties the callback to the browser which started the login,
+/// the `nonce` ties the ID token to it, and the code verifier is the PKCE secret. It is kept
+/// by the browser until it comes back from the provider.
+#[derive(Clone, PartialEq, Eq)]
+pub struct LoginFlow {
+    state: String,
+    nonce: String,
+    code_verifier: String
+}
+
+redacted_debug!(LoginFlow);
+
+impl LoginFlow {
+    pub fn state(&self) -> &str {
+        self.state.as_str()
+    }
+
+    /// The S256 PKCE challenge sent along with the authorization request.
+    pub fn code_challenge(&self) -> String {
+        base64::encode_config(Sha256::digest(self.code_verifier.as_bytes()), base64::URL_SAFE_NO_PAD)
+    }
+}
+
+impl Default for LoginFlow {
+    fn default() -> Self {
+        Self {
+            state: random_base64(),
+            nonce: random_base64(),
+            code_verifier: random_base64()
+        }
+    }
+}
+
+impl std::fmt::Display for LoginFlow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.state, self.nonce, self.code_verifier)
+    }
+}
+
+impl FromStr for LoginFlow {
+    type Err = OidcError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split('.').collect();
+        match parts.as_slice() {
+            [state, nonce, code_verifier] if parts.iter().all(|part| !part.is_empty()) => Ok(Self {
+                state: (*state).to_owned(),
+                nonce: (*nonce).to_owned(),
+                code_verifier: (*code_verifier).to_owned()
+            }),
+            _ => Err(OidcError::InvalidFlow)
+        }
+    }
+}
+
+/// The account of a user at the provider, taken from a verified ID token.
+#[derive(Debug, Clone)]
+pub struct Identity {
+    pub issuer: String,
+    pub subject: String,
+    pub preferred_username: Option<String>,
+    pub email: Option<String>
+}
+
+impl Identity {
+    /// A username for a new user, from the preferred username or the email address. Characters
+    /// which aren't allowed in usernames are replaced, and it is truncated to leave room for a
+    /// suffix in case it is taken.
+    pub fn username_hint(&self) -> String {
+        let hint = self.preferred_username
+            .as_deref()
+            .or_else(|| self.email.as_deref().and_then(|email| email.split('@').next()))
+            .unwrap_or_default()
+            .trim()
+            .to_lowercase()
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.' { c } else { '-' })
+            .take(24)
+            .collect::<String>();
+        if hint.len() < 3 {
+            "user".to_owned()
+        } else {
+            hint
+        }
+    }
+}
+
+/// An OpenID Connect provider, found through its discovery document.
+pub struct OidcProvider {
+    config: OidcConfig,
+    metadata: ProviderMetadata,
+    authorization_endpoint: Url,
+    client: reqwest::Client,
+    jwks: RwLock<JwkSet>
+}
+
+impl OidcProvider {
+    /// Reads the discovery document of the issuer and fetches its signing keys.
+    pub async fn discover(config: OidcConfig) -> Result<Self, OidcError> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()?;
+        let url = format!("{}/.well-known/openid-configuration", config.issuer.trim_end_matches('/'));
+        let metadata: ProviderMetadata = client.get(url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        if metadata.issuer.trim_end_matches('/') != config.issuer.trim_end_matches('/') {
+            return Err(OidcError::Discovery(format!("unexpected issuer {}", metadata.issuer)));
+        }
+        let authorization_endpoint = Url::parse(&metadata.authorization_endpoint)
+            .map_err(|e| OidcError::Discovery(format!("invalid authorization endpoint: {}", e)))?;
+        let jwks = Self::fetch_jwks(&client, &metadata.jwks_uri).await?;
+
+        Ok(Self {
+            config,
+            metadata,
+            authorization_endpoint,
+            client,
+            jwks: RwLock::new(jwks)
+        })
+    }
+
+    async fn fetch_jwks(client: &reqwest::Client, jwks_uri: &str) -> Result<JwkSet, OidcError> {
+        Ok(client.get(jwks_uri).send().await?.error_for_status()?.json().await?)
+    }
+
+    pub fn issuer(&self) -> &str {
+        self.metadata.issuer.as_str()
+    }
+
+    /// The URL of the provider to send the user to in order to log in.
+    pub fn authorization_url(&self, flow: &LoginFlow) -> String {
+        let mut url = self.authorization_endpoint.clone();
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &self.config.client_id)
+            .append_pair("redirect_uri", &self.config.redirect_url)
+            .append_pair("scope", "openid profile email")
+            .append_pair("state", &flow.state)
+            .append_pair("nonce", &flow.nonce)
+            .append_pair("code_challenge", &flow.code_challenge())
+            .append_pair("code_challenge_method", "S256");
+        url.into()
+    }
+
+    /// Exchanges the authorization code the user came back with for an ID token, and returns
+    /// the identity it vouches for once it is verified.
+    pub async fn exchange_code(&self, code: &str, flow: &LoginFlow) -> Result<Identity, OidcError> {
+        let response = self.client.post(&self.metadata.token_endpoint)
+            .basic_auth(&self.config.client_id, Some(&self.config.client_secret))
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("redirect_uri", &self.config.redirect_url),
+                ("code_verifier", &flow.code_verifier)
+            ])
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(OidcError::Token(format!("{}: {}", status, body)));
+        }
+        let token: TokenResponse = response.json().await?;
+        self.verify_id_token(&token.id_token, flow).await
+    }
+
+    /// Checks the signature of the ID token against the keys of the provider, and that it was
+    /// issued by the provider, to this client and for this login.
+    async fn verify_id_token(&self, id_token: &str, flow: &LoginFlow) -> Result<Identity, OidcError> {
+        let header = decode_header(id_token)?;
+        let kid = header.kid.as_deref();
+        let key = match self.decoding_key(kid)? {
+            Some(key) => key,
+            None => {
+                // The provider may have rotated its keys since they were fetched.
+                let jwks = Self::fetch_jwks(&self.client, &self.metadata.jwks_uri).await?;
+                *self.jwks.write().unwrap_or_else(|e| e.into_inner()) = jwks;
+                self.decoding_key(kid)?
+                    .ok_or_else(|| OidcError::IdToken("unknown signing key".to_owned()))?
+            }
+        };
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&self.metadata.issuer]);
+        validation.set_audience(&[&self.config.client_id]);
+        let claims = decode::<IdTokenClaims>(id_token, &key, &validation)?.claims;
+        if claims.nonce.as_deref() != Some(flow.nonce.as_str()) {
+            return Err(OidcError::IdToken("nonce mismatch".to_owned()));
+        }
+
+        Ok(Identity {
+            issuer: self.metadata.issuer.clone(),
+            subject: claims.sub,
+            preferred_username: claims.preferred_username,
+            email: claims.email
+        })
+    }
+
+    fn decoding_key(&self, kid: Option<&str>) -> Result<Option<DecodingKey>, OidcError> {
+        let jwks = self.jwks.read().unwrap_or_else(|e| e.into_inner());
+        let jwk = match kid {
+            Some(kid) => jwks.find(kid),
+            None => jwks.keys.first()
+        };
+        Ok(jwk.map(DecodingKey::from_jwk).transpose()?)
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::{Identity, LoginFlow};
+
+    #[test]
+    fn login_flow_round_trips() {
+        let flow = LoginFlow::default();
+        let parsed: LoginFlow = flow.to_string().parse().unwrap();
+        assert_eq!(parsed, flow);
+        assert_ne!(flow.code_challenge(), flow.code_verifier);
+        assert!("state.nonce".parse::<LoginFlow>().is_err());
+        assert!("state..verifier".parse::<LoginFlow>().is_err());
+    }
+
+    #[test]
+    fn derives_usernames_from_claims() {
+        let identity = |preferred_username: Option<&str>, email: Option<&str>| Identity {
+            issuer: "https://issuer.test".to_owned(),
+            subject: "subject".to_owned(),
+            preferred_username: preferred_username.map(str::to_owned),
+            email: email.map(str::to_owned)
+        };
+        assert_eq!(identity(Some("Jane Doe"), None).username_hint(), "jane-doe");
+        assert_eq!(identity(None, Some("jane@example.com")).username_hint(), "jane");
+        assert_eq!(identity(Some("j"), None).username_hint(), "user");
+        assert_eq!(identity(None, None).username_hint(), "user");
+    }
+}
@@ -0,0 +1,545 @@
+//! Webhooks, which get the events of the clips created with an API key POSTed to a URL. The
+//! actions making the events queue a delivery for every webhook subscribed to them, and the
+//! [`WebhookDispatcher`] task sends them, signed with the secret of their webhook, retrying
+//! the failed ones with an exponential backoff.
+use crate::data::DatabasePool;
+use crate::domain::clip::ClipSummary;
+use crate::service;
+use crate::{ShortCode, Time};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use hyper::client::connect::dns::Name;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::runtime::Handle;
+use tokio::task::JoinHandle;
+use crate::redact::redacted_debug;
+
+/// The header carrying the signature of a delivery, as `sha256=<hex digest>`.
+pub const SIGNATURE_HEADER: &str = "x-clipstash-signature";
+/// The header carrying the Unix time a delivery was signed at, which is part of what is signed.
+pub const TIMESTAMP_HEADER: &str = "x-clipstash-timestamp";
+pub const EVENT_HEADER: &str = "x-clipstash-event";
+/// The header carrying the id of a delivery, which stays the same when it is retried.
+pub const DELIVERY_HEADER: &str = "x-clipstash-delivery";
+
+/// A webhook gets at most one `clip.viewed` event of a clip this often.
+pub const VIEW_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How long the deliveries which are done with are kept in the delivery log.
+pub const DELIVERY_RETENTION: Duration = Duration::from_secs(60 * 60 * 24 * 30);
+
+/// The longest wait between two attempts of a delivery.
+pub const MAX_RETRY_DELAY: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Error)]
+pub enum WebhookError {
+    #[error("invalid webhook URL: {0}")]
+    InvalidUrl(String),
+    #[error("unknown event: {0}")]
+    InvalidEvent(String),
+    #[error("a webhook needs at least one event")]
+    NoEvents,
+    #[error("webhook not found")]
+    NotFound
+}
+
+/// Something which happened to a clip, which webhooks can subscribe to.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
+pub enum WebhookEvent {
+    #[serde(rename = "clip.created")]
+    ClipCreated,
+    #[serde(rename = "clip.updated")]
+    ClipUpdated,
+    /// The clip was viewed, sent at most once every [`VIEW_INTERVAL`].
+    #[serde(rename = "clip.viewed")]
+    ClipViewed,
+    #[serde(rename = "clip.expired")]
+    ClipExpired,
+    #[serde(rename = "clip.deleted")]
+    ClipDeleted
+}
+
+impl WebhookEvent {
+    pub const ALL: [WebhookEvent; 5] = [
+        Self::ClipCreated,
+        Self::ClipUpdated,
+        Self::ClipViewed,
+        Self::ClipExpired,
+        Self::ClipDeleted
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::ClipCreated => "clip.created",
+            Self::ClipUpdated => "clip.updated",
+            Self::ClipViewed => "clip.viewed",
+            Self::ClipExpired => "clip.expired",
+            Self::ClipDeleted => "clip.deleted"
+        }
+    }
+}
+
+impl std::fmt::Display for WebhookEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for WebhookEvent {
+    type Err = WebhookError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .find(|event| event.as_str() == s)
+            .copied()
+            .ok_or_else(|| WebhookError::InvalidEvent(s.to_owned()))
+    }
+}
+
+/// The URL deliveries are POSTed to, which must be an `http` or `https` URL. Its host can't be
+/// on a loopback, link-local or private network unless it is one of the `allowed_hosts`, or the
+/// webhooks would reach the services which only the server can.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct WebhookUrl(String);
+
+impl WebhookUrl {
+    pub fn new(url: &str, allowed_hosts: &[String]) -> Result<Self, WebhookError> {
+        let parsed = Url::parse(url.trim()).map_err(|e| WebhookError::InvalidUrl(e.to_string()))?;
+        match (parsed.scheme(), parsed.host_str()) {
+            ("http" | "https", Some(host)) => {
+                check_host(host, allowed_hosts)?;
+                Ok(Self(parsed.into()))
+            },
+            _ => Err(WebhookError::InvalidUrl("only http and https URLs can be used".to_owned()))
+        }
+    }
+
+    pub fn into_inner(self) -> String {
+        self.0
+    }
+}
+
+/// Refuses hosts which are loopback, link-local or private addresses, or `localhost`, unless
+/// they are allowed. Other names are checked once they are resolved, by [`PublicResolver`].
+fn check_host(host: &str, allowed_hosts: &[String]) -> Result<(), WebhookError> {
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    if is_allowed(host, allowed_hosts) {
+        return Ok(());
+    }
+    let local = match host.parse::<IpAddr>() {
+        Ok(ip) => !is_public(ip),
+        Err(_) => {
+            let host = host.to_ascii_lowercase();
+            host == "localhost" || host.ends_with(".localhost")
+        }
+    };
+    if local {
+        Err(WebhookError::InvalidUrl(format!("{} is a local or private host", host)))
+    } else {
+        Ok(())
+    }
+}
+
+fn is_allowed(host: &str, allowed_hosts: &[String]) -> bool {
+    allowed_hosts
+        .iter()
+        .any(|allowed| allowed.trim_start_matches('[').trim_end_matches(']').eq_ignore_ascii_case(host))
+}
+
+/// Whether an address is reachable from the internet, rather than only from the server or its
+/// network.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, _, _] = ip.octets();
+            // 0.0.0.0/8 is "this network", and 100.64.0.0/10 the shared address space of carriers.
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                || a == 0
+                || (a == 100 && (64..128).contains(&b)))
+        },
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public(IpAddr::V4(ip)),
+            None => {
+                let first = ip.segments()[0];
+                // fc00::/7 holds the unique local addresses, and fe80::/10 the link-local ones.
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    || first & 0xfe00 == 0xfc00
+                    || first & 0xffc0 == 0xfe80)
+            }
+        }
+    }
+}
+
+/// Looks up the hosts deliveries are sent to, refusing the names with a loopback, link-local or
+/// private address unless they are allowed. The addresses which are checked are the ones
+/// connected to, so a name can't be pointed elsewhere once it has been checked.
+struct PublicResolver {
+    allowed_hosts: Vec<String>
+}
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let allowed = is_allowed(name.as_str(), &self.allowed_hosts);
+        let name = name.as_str().to_owned();
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0)).await?.collect();
+            if !allowed && addrs.iter().any(|addr| !is_public(addr.ip())) {
+                return Err(WebhookError::InvalidUrl(format!("{} is a local or private host", name)).into());
+            }
+            let addrs: Addrs = Box::new(addrs.into_iter());
+            Ok(addrs)
+        })
+    }
+}
+
+/// The secret a webhook's deliveries are signed with. It is shown once, when the webhook is
+/// registered.
+#[derive(Clone)]
+pub struct WebhookSecret(String);
+
+redacted_debug!(WebhookSecret);
+
+impl WebhookSecret {
+    pub fn into_inner(self) -> String {
+        self.0
+    }
+}
+
+impl Default for WebhookSecret {
+    fn default() -> Self {
+        let bytes: Vec<u8> = (0..32).map(|_| rand::random::<u8>()).collect();
+        Self(format!("whsec_{}", base64::encode_config(bytes, base64::URL_SAFE_NO_PAD)))
+    }
+}
+
+/// Signs the body of a delivery sent at `timestamp`. Receivers check the signature by computing
+/// the HMAC-SHA256 of `<timestamp>.<body>` with the secret of the webhook.
+pub fn signature(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any size");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    let digest: String = mac.finalize().into_bytes().iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("sha256={}", digest)
+}
+
+/// A webhook of an API key. Its secret is only in the [`RegisteredWebhook`] answer.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Webhook {
+    pub webhook_id: String,
+    pub url: String,
+    pub events: Vec<WebhookEvent>,
+    pub created: Time
+}
+
+/// A webhook just registered, along with the secret its deliveries are signed with.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RegisteredWebhook {
+    #[serde(flatten)]
+    pub webhook: Webhook,
+    pub secret: String
+}
+
+/// The body of a delivery.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct WebhookPayload {
+    pub event: WebhookEvent,
+    /// When the event happened.
+    pub created: Time,
+    pub shortcode: ShortCode,
+    /// The clip after a `clip.created` or `clip.updated` event, without its content.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clip: Option<ClipSummary>,
+    /// The hit count of the clip after a `clip.viewed` event.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hits: Option<u64>
+}
+
+impl WebhookPayload {
+    pub fn new(event: WebhookEvent, shortcode: ShortCode) -> Self {
+        Self { event, created: Utc::now().into(), shortcode, clip: None, hits: None }
+    }
+
+    pub fn with_clip(self, clip: ClipSummary) -> Self {
+        Self { clip: Some(clip), ..self }
+    }
+
+    pub fn with_hits(self, hits: u64) -> Self {
+        Self { hits: Some(hits), ..self }
+    }
+
+    /// The body of the deliveries of the event.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("webhook payloads are always valid JSON")
+    }
+}
+
+/// An entry of the delivery log of a webhook. Deliveries which will be attempted again have a
+/// `next_attempt`, and the ones which were given up on have neither that nor `delivered`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Delivery {
+    pub delivery_id: i64,
+    pub event: WebhookEvent,
+    pub shortcode: ShortCode,
+    pub created: Time,
+    pub attempts: u32,
+    pub delivered: Option<Time>,
+    pub next_attempt: Option<Time>,
+    /// The status the receiver answered the last attempt with.
+    pub status: Option<u16>,
+    /// Why the last attempt failed.
+    pub error: Option<String>
+}
+
+/// A delivery due to be attempted, claimed by the [`WebhookDispatcher`].
+#[derive(Debug)]
+pub struct PendingDelivery {
+    pub delivery_id: i64,
+    pub url: String,
+    pub secret: String,
+    pub event: String,
+    pub payload: String,
+    /// The attempts made so far, including this one.
+    pub attempts: u32
+}
+
+/// How an attempt to deliver an event went.
+#[derive(Debug)]
+pub enum Attempt {
+    Delivered(u16),
+    Failed { status: Option<u16>, error: String }
+}
+
+/// Controls how the [`WebhookDispatcher`] sends the deliveries.
+#[derive(Debug, Clone)]
+pub struct WebhookConfig {
+    /// How often the due deliveries are looked for.
+    pub poll_interval: Duration,
+    /// How long to wait before attempting a failed delivery again. The wait doubles with every
+    /// attempt, up to [`MAX_RETRY_DELAY`].
+    pub retry_delay: Duration,
+    /// How many times a delivery is attempted before it is given up on.
+    pub max_attempts: u32,
+    /// How long receivers have to answer.
+    pub timeout: Duration,
+    /// How many deliveries are sent at once.
+    pub batch_size: u32,
+    /// Hosts deliveries may be sent to even though they are on a loopback, link-local or
+    /// private network, such as a receiver running next to the server.
+    pub allowed_hosts: Vec<String>
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_secs(1),
+            retry_delay: Duration::from_secs(10),
+            max_attempts: 8,
+            timeout: Duration::from_secs(10),
+            batch_size: 50,
+            allowed_hosts: Vec::new()
+        }
+    }
+}
+
+impl WebhookConfig {
+    /// How long to wait after the given number of failed attempts, or `None` to give up.
+    pub fn retry_after(&self, attempts: u32) -> Option<Duration> {
+        if attempts >= self.max_attempts {
+            return None;
+        }
+        let factor = 2u32.saturating_pow(attempts.saturating_sub(1));
+        Some(self.retry_delay.saturating_mul(factor).min(MAX_RETRY_DELAY))
+    }
+}
+
+pub struct WebhookDispatcher {
+    task: JoinHandle<()>,
+    allowed_hosts: Vec<String>
+}
+
+impl WebhookDispatcher {
+    /// Spawns the task sending the due deliveries every [`poll_interval`](WebhookConfig::poll_interval).
+    pub fn spawn(pool: DatabasePool, handle: Handle, config: WebhookConfig) -> Self {
+        let allowed_hosts = config.allowed_hosts.clone();
+        let task = handle.spawn(async move {
+            let client = match reqwest::Client::builder()
+                .timeout(config.timeout)
+                .redirect(reqwest::redirect::Policy::none())
+                .dns_resolver(Arc::new(PublicResolver { allowed_hosts: config.allowed_hosts.clone() }))
+                .build()
+            {
+                Ok(client) => client,
+                Err(e) => {
+                    tracing::error!(error = %e, "failed to build the webhook client");
+                    return;
+                }
+            };
+            let mut interval = tokio::time::interval(config.poll_interval);
+
+            loop {
+                interval.tick().await;
+                // Claimed deliveries are left alone by other servers until they time out.
+                let lease = config.timeout * 2;
+                let deliveries = match service::action::claim_webhook_deliveries(config.batch_size, lease, &pool).await {
+                    Ok(deliveries) => deliveries,
+                    Err(e) => {
+                        tracing::error!(error = %e, "failed to get due webhook deliveries");
+                        continue;
+                    }
+                };
+                let attempts = deliveries.into_iter().map(|delivery| {
+                    let client = &client;
+                    let pool = &pool;
+                    let config = &config;
+                    async move {
+                        let attempt = Self::send(client, &delivery, &config.allowed_hosts).await;
+                        let retry = match attempt {
+                            Attempt::Delivered(_) => None,
+                            Attempt::Failed { .. } => config.retry_after(delivery.attempts)
+                        };
+                        if let Attempt::Failed { status, error } = &attempt {
+                            tracing::warn!(
+                                delivery_id = delivery.delivery_id,
+                                status = ?status,
+                                error = %error,
+                                retry = retry.is_some(),
+                                "webhook delivery failed"
+                            );
+                        }
+                        if let Err(e) = service::action::record_webhook_attempt(delivery.delivery_id, attempt, retry, pool).await {
+                            tracing::error!(error = %e, delivery_id = delivery.delivery_id, "failed to record webhook delivery");
+                        }
+                    }
+                });
+                rocket::futures::future::join_all(attempts).await;
+            }
+        });
+        Self { task, allowed_hosts }
+    }
+
+    /// The hosts webhooks may be registered with even though they are local or private.
+    pub fn allowed_hosts(&self) -> &[String] {
+        &self.allowed_hosts
+    }
+
+    /// POSTs a delivery to its webhook. Any `2xx` answer counts as delivered. Its URL is checked
+    /// again, as addresses aren't resolved when they are given as they are.
+    async fn send(client: &reqwest::Client, delivery: &PendingDelivery, allowed_hosts: &[String]) -> Attempt {
+        if let Err(e) = WebhookUrl::new(&delivery.url, allowed_hosts) {
+            return Attempt::Failed { status: None, error: e.to_string() };
+        }
+        let timestamp = Utc::now().timestamp();
+        let response = client
+            .post(&delivery.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(EVENT_HEADER, &delivery.event)
+            .header(DELIVERY_HEADER, delivery.delivery_id.to_string())
+            .header(TIMESTAMP_HEADER, timestamp.to_string())
+            .header(SIGNATURE_HEADER, signature(&delivery.secret, timestamp, &delivery.payload))
+            .body(delivery.payload.clone())
+            .send()
+            .await;
+        match response {
+            Ok(response) if response.status().is_success() => Attempt::Delivered(response.status().as_u16()),
+            Ok(response) => Attempt::Failed {
+                status: Some(response.status().as_u16()),
+                error: format!("the receiver answered {}", response.status())
+            },
+            Err(e) => Attempt::Failed { status: None, error: e.to_string() }
+        }
+    }
+
+    /// Returns whether the dispatcher task is still running.
+    pub fn is_running(&self) -> bool {
+        !self.task.is_finished()
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::{signature, PublicResolver, WebhookConfig, WebhookEvent, WebhookUrl, MAX_RETRY_DELAY};
+    use reqwest::dns::Resolve;
+    use std::time::Duration;
+
+    #[test]
+    fn signs_the_timestamp_and_body() {
+        assert_eq!(
+            signature("whsec_test", 1700000000, r#"{"event":"clip.created"}"#),
+            "sha256=36e849a8b0644cbfd31f0a596db348c86eee9314206e1f8e3411808b39c0b6b3"
+        );
+        assert_ne!(signature("whsec_test", 1, "body"), signature("whsec_test", 2, "body"));
+        assert_ne!(signature("whsec_test", 1, "body"), signature("whsec_other", 1, "body"));
+    }
+
+    #[test]
+    fn backs_off_exponentially() {
+        let config = WebhookConfig { retry_delay: Duration::from_secs(10), max_attempts: 12, ..Default::default() };
+        assert_eq!(config.retry_after(1), Some(Duration::from_secs(10)));
+        assert_eq!(config.retry_after(2), Some(Duration::from_secs(20)));
+        assert_eq!(config.retry_after(4), Some(Duration::from_secs(80)));
+        assert_eq!(config.retry_after(11), Some(MAX_RETRY_DELAY));
+        assert_eq!(config.retry_after(12), None);
+    }
+
+    #[test]
+    fn checks_webhook_urls_and_events() {
+        assert!(WebhookUrl::new("https://example.com/hooks", &[]).is_ok());
+        assert!(WebhookUrl::new("http://93.184.215.14:8080", &[]).is_ok());
+        assert!(WebhookUrl::new("ftp://example.com", &[]).is_err());
+        assert!(WebhookUrl::new("example.com", &[]).is_err());
+        for event in WebhookEvent::ALL {
+            assert_eq!(event.as_str().parse::<WebhookEvent>().unwrap(), event);
+            assert_eq!(serde_json::to_value(event).unwrap(), event.as_str());
+        }
+    }
+
+    #[test]
+    fn refuses_local_and_private_hosts() {
+        let urls = [
+            "http://127.0.0.1:8080",
+            "http://localhost/hooks",
+            "http://169.254.169.254/latest/meta-data",
+            "http://10.0.0.1",
+            "http://172.16.0.1",
+            "http://192.168.1.1",
+            "http://100.64.0.1",
+            "http://0.0.0.0",
+            "http://[::1]/hooks",
+            "http://[::ffff:127.0.0.1]/hooks",
+            "http://[fd00::1]/hooks",
+            "http://[fe80::1]/hooks"
+        ];
+        for url in urls.iter() {
+            assert!(WebhookUrl::new(url, &[]).is_err(), "{} is accepted", url);
+        }
+        let allowed = vec!["127.0.0.1".to_owned(), "[::1]".to_owned()];
+        assert!(WebhookUrl::new("http://127.0.0.1:8080", &allowed).is_ok());
+        assert!(WebhookUrl::new("http://[::1]/hooks", &allowed).is_ok());
+        assert!(WebhookUrl::new("http://10.0.0.1", &allowed).is_err());
+
+        // Names are checked once they are resolved, as any of them can point to a local address.
+        let rt = crate::test::async_runtime();
+        let resolve = |allowed_hosts: Vec<String>| {
+            rt.block_on(PublicResolver { allowed_hosts }.resolve("localhost".parse().unwrap())).is_ok()
+        };
+        assert!(!resolve(vec![]));
+        assert!(resolve(vec!["localhost".to_owned()]));
+    }
+}
@@ -0,0 +1,6 @@
+/// All fields of a workspace are defined and validated in this module.
+mod workspace_id;
+pub use workspace_id::WorkspaceId;
+
+mod name;
+pub use name::WorkspaceName;
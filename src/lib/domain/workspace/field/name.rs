@@ -0,0 +1,29 @@
+use crate::domain::workspace::WorkspaceError;
+use rocket::form::{self, FromFormField, ValueField};
+use serde::{Deserialize, Serialize};
+
+/// The name of a [`Workspace`](crate::domain::workspace::Workspace), up to 64 characters.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct WorkspaceName(String);
+
+impl WorkspaceName {
+    pub fn new(name: &str) -> Result<Self, WorkspaceError> {
+        let name = name.trim();
+        if name.is_empty() || name.chars().count() > 64 {
+            return Err(WorkspaceError::InvalidName("must be 1 to 64 characters long".to_owned()));
+        }
+        Ok(Self(name.to_owned()))
+    }
+
+    pub fn into_inner(self) -> String {
+        self.0
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromFormField<'r> for WorkspaceName {
+    fn from_value(field: ValueField<'r>) -> form::Result<'r, Self> {
+        Ok(Self::new(field.value)
+            .map_err(|e| form::Error::validation(e.to_string()))?)
+    }
+}
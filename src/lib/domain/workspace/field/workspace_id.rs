@@ -0,0 +1,51 @@
+use crate::data::DbId;
+use crate::domain::workspace::WorkspaceError;
+use derive_more::{Constructor, Display};
+use rocket::http::impl_from_uri_param_identity;
+use rocket::http::uri::fmt::{Formatter, Path, UriDisplay};
+use rocket::request::FromParam;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+#[derive(Clone, Debug, Constructor, Display, Deserialize, Serialize)]
+pub struct WorkspaceId(DbId);
+
+impl WorkspaceId {
+    pub fn into_inner(self) -> DbId {
+        self.0
+    }
+}
+
+impl From<DbId> for WorkspaceId {
+    fn from(id: DbId) -> Self {
+        Self(id)
+    }
+}
+
+impl From<WorkspaceId> for String {
+    fn from(id: WorkspaceId) -> Self {
+        id.0.into()
+    }
+}
+
+impl FromStr for WorkspaceId {
+    type Err = WorkspaceError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(DbId::from_str(s)?))
+    }
+}
+
+impl<'r> FromParam<'r> for WorkspaceId {
+    type Error = WorkspaceError;
+    fn from_param(param: &'r str) -> Result<Self, Self::Error> {
+        Self::from_str(param)
+    }
+}
+
+impl UriDisplay<Path> for WorkspaceId {
+    fn fmt(&self, f: &mut Formatter<'_, Path>) -> std::fmt::Result {
+        f.write_value(self.to_string())
+    }
+}
+
+impl_from_uri_param_identity!([Path] WorkspaceId);
@@ -0,0 +1,91 @@
+pub mod field;
+
+use crate::domain::user::field::Username;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum WorkspaceError {
+    #[error("invalid workspace name: {0}")]
+    InvalidName(String),
+    #[error("invalid role: {0}")]
+    InvalidRole(String),
+    #[error("log in to view the clips of a workspace")]
+    LoginRequired,
+    #[error("only the owners of a workspace can manage it")]
+    NotAnOwner,
+    #[error("id parse error: {0}")]
+    Id(#[from] uuid::Error)
+}
+
+/// What a member can do in a workspace. Owners manage its members and API keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Owner,
+    Member
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Owner => "owner",
+            Self::Member => "member"
+        }
+    }
+}
+
+impl FromStr for Role {
+    type Err = WorkspaceError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "owner" => Ok(Self::Owner),
+            "member" => Ok(Self::Member),
+            other => Err(WorkspaceError::InvalidRole(other.to_owned()))
+        }
+    }
+}
+
+/// A group of users sharing clips which only they can see.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Workspace {
+    pub workspace_id: field::WorkspaceId,
+    pub name: field::WorkspaceName,
+}
+
+/// A [`Workspace`] as seen by one of its members.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Membership {
+    #[serde(flatten)]
+    pub workspace: Workspace,
+    pub role: Role,
+}
+
+impl Membership {
+    pub fn is_owner(&self) -> bool {
+        self.role == Role::Owner
+    }
+}
+
+/// A member of a [`Workspace`].
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Member {
+    pub username: Username,
+    pub role: Role,
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::field::WorkspaceName;
+    use super::Role;
+
+    #[test]
+    fn validates_workspace_names() {
+        assert_eq!(WorkspaceName::new("  Platform team ").unwrap().into_inner(), "Platform team");
+        assert!(WorkspaceName::new("   ").is_err());
+        assert!(WorkspaceName::new(&"x".repeat(65)).is_err());
+        assert_eq!("member".parse::<Role>().unwrap(), Role::Member);
+        assert!("admin".parse::<Role>().is_err());
+    }
+}
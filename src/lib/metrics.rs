@@ -0,0 +1,117 @@
+//! Prometheus metrics collected by the server, exposed at `/metrics`.
+use once_cell::sync::Lazy;
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts, Registry,
+    TextEncoder,
+};
+
+/// All the metrics of the server, registered in their own [`Registry`].
+pub struct Metrics {
+    registry: Registry,
+    /// Handled requests, labeled by route, method and status code.
+    pub requests: IntCounterVec,
+    /// Request latencies in seconds, labeled by route and method.
+    pub request_duration: HistogramVec,
+    /// Clips created through the web UI or the API.
+    pub clips_created: IntCounter,
+    /// Clips read, labeled by how they were viewed.
+    pub clip_reads: IntCounterVec,
+    /// Attempts to view a clip with a wrong password.
+    pub password_failures: IntCounter,
+    /// Hits waiting to be written to the database.
+    pub hit_queue_depth: IntGauge,
+    /// Time taken to write the pending hits to the database.
+    pub hit_flush_duration: HistogramVec,
+    /// Clips handled by maintenance, labeled by whether they were expired or purged.
+    pub maintenance_deletions: IntCounterVec,
+    /// Open database connections.
+    pub db_connections: IntGauge,
+    /// Idle database connections.
+    pub db_idle_connections: IntGauge,
+    /// Templates which failed to render.
+    pub render_errors: IntCounter,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new_custom(Some("clipstash".to_owned()), None)
+            .expect("metrics prefix should be valid");
+
+        let metrics = Self {
+            requests: IntCounterVec::new(
+                Opts::new("http_requests_total", "Handled HTTP requests"),
+                &["route", "method", "status"],
+            )
+            .expect("metric should be valid"),
+            request_duration: HistogramVec::new(
+                HistogramOpts::new("http_request_duration_seconds", "HTTP request latencies"),
+                &["route", "method"],
+            )
+            .expect("metric should be valid"),
+            clips_created: IntCounter::new("clips_created_total", "Created clips")
+                .expect("metric should be valid"),
+            clip_reads: IntCounterVec::new(
+                Opts::new("clip_reads_total", "Read clips"),
+                &["route"],
+            )
+            .expect("metric should be valid"),
+            password_failures: IntCounter::new(
+                "clip_password_failures_total",
+                "Clip views with a wrong password",
+            )
+            .expect("metric should be valid"),
+            hit_queue_depth: IntGauge::new("hit_queue_depth", "Hits waiting to be committed")
+                .expect("metric should be valid"),
+            hit_flush_duration: HistogramVec::new(
+                HistogramOpts::new("hit_flush_duration_seconds", "Time taken to commit hits"),
+                &["result"],
+            )
+            .expect("metric should be valid"),
+            maintenance_deletions: IntCounterVec::new(
+                Opts::new("maintenance_deletions_total", "Clips expired or purged by maintenance"),
+                &["kind"],
+            )
+            .expect("metric should be valid"),
+            db_connections: IntGauge::new("db_connections", "Open database connections")
+                .expect("metric should be valid"),
+            db_idle_connections: IntGauge::new("db_idle_connections", "Idle database connections")
+                .expect("metric should be valid"),
+            render_errors: IntCounter::new("render_errors_total", "Templates which failed to render")
+                .expect("metric should be valid"),
+            registry,
+        };
+
+        let collectors: Vec<Box<dyn prometheus::core::Collector>> = vec![
+            Box::new(metrics.requests.clone()),
+            Box::new(metrics.request_duration.clone()),
+            Box::new(metrics.clips_created.clone()),
+            Box::new(metrics.clip_reads.clone()),
+            Box::new(metrics.password_failures.clone()),
+            Box::new(metrics.hit_queue_depth.clone()),
+            Box::new(metrics.hit_flush_duration.clone()),
+            Box::new(metrics.maintenance_deletions.clone()),
+            Box::new(metrics.db_connections.clone()),
+            Box::new(metrics.db_idle_connections.clone()),
+            Box::new(metrics.render_errors.clone()),
+        ];
+        for collector in collectors {
+            metrics
+                .registry
+                .register(collector)
+                .expect("metric should only be registered once");
+        }
+        metrics
+    }
+
+    /// Encodes every metric in the Prometheus text format.
+    pub fn encode(&self) -> String {
+        let mut buffer = Vec::new();
+        if let Err(e) = TextEncoder::new().encode(&self.registry.gather(), &mut buffer) {
+            tracing::error!(error = %e, "failed to encode metrics");
+        }
+        String::from_utf8(buffer).unwrap_or_default()
+    }
+}
+
+/// The metrics of the server.
+pub static METRICS: Lazy<Metrics> = Lazy::new(Metrics::new);
@@ -1,5 +1,7 @@
 pub mod data;
 pub mod domain;
+pub mod metrics;
+pub mod redact;
 pub mod service;
 pub mod web;
 
@@ -10,10 +12,16 @@ pub use domain::time::Time;
 pub use service::ServiceError;
 use crate::data::AppDatabase;
 use crate::web::renderer::Renderer;
+use rocket::fairing::AdHoc;
 use rocket::fs::FileServer;
 use rocket::{Build, Rocket};
 use domain::maintenance::Maintenance;
+use domain::webhook::WebhookDispatcher;
 use crate::web::hitcounter::HitCounter;
+use crate::web::events::ClipEvents;
+use crate::web::collab::Collab;
+use crate::web::sso::Sso;
+use crate::web::slash::SlashCommands;
 
 /// Build the [`rocket()`] and get the webserver up and running in the async runtime.
 pub fn rocket(config: RocketConfig) -> Rocket<Build> {
@@ -21,12 +29,45 @@ pub fn rocket(config: RocketConfig) -> Rocket<Build> {
         .manage::<AppDatabase>(config.database)
         .manage::<Renderer>(config.renderer)
         .manage::<HitCounter>(config.hit_counter)
+        .manage::<ClipEvents>(config.events)
+        .manage::<Collab>(config.collab)
         .manage::<Maintenance>(config.maintenance)
+        .manage::<WebhookDispatcher>(config.webhooks)
+        .manage::<Sso>(config.sso)
+        .manage::<SlashCommands>(config.slash)
         .mount("/", web::http::routes()) // set up root route
-        .mount("/api/clip", web::api::routes())
+        .mount("/", web::events::routes())
+        .mount("/", web::collab::routes())
+        .mount("/api/v1", web::openapi::routes())
+        .mount("/api/v1/clips", web::api::clip_routes())
+        .mount("/api/v1/keys", web::api::key_routes())
+        .mount("/api/v1/webhooks", web::api::webhook_routes())
+        .mount(web::api::LEGACY_BASE, web::api::legacy_routes())
+        .mount("/", web::metrics::routes())
+        .mount("/", web::health::routes())
+        .mount("/", web::account::routes())
+        .mount("/", web::sso::routes())
+        .mount("/", web::workspace::routes())
+        .mount("/", web::slash::routes())
         .mount("/static", FileServer::from("static"))
         .register("/", web::http::catcher::catchers())
-        .register("/api/clip", web::api::catcher::catchers())
+        .register(web::api::API_BASE, web::api::catcher::catchers())
+        .register(web::api::LEGACY_BASE, web::api::catcher::catchers())
+        .register("/dashboard", web::account::catcher::catchers())
+        .register("/workspace", web::account::catcher::catchers())
+        .attach(web::request_id::RequestLogger)
+        .attach(web::metrics::RequestMetrics)
+        .attach(web::api::Deprecation)
+        .attach(AdHoc::on_shutdown("Flush hit counter", |rocket| Box::pin(async move {
+            if let Some(hit_counter) = rocket.state::<HitCounter>() {
+                hit_counter.flush().await;
+            }
+        })))
+        .attach(AdHoc::on_shutdown("Save clips edited together", |rocket| Box::pin(async move {
+            if let Some(collab) = rocket.state::<Collab>() {
+                collab.flush().await;
+            }
+        })))
 
 }
 
@@ -34,7 +75,12 @@ pub struct RocketConfig {
     pub renderer: Renderer<'static>,
     pub database: AppDatabase,
     pub hit_counter: HitCounter,
-    pub maintenance: Maintenance
+    pub events: ClipEvents,
+    pub collab: Collab,
+    pub maintenance: Maintenance,
+    pub webhooks: WebhookDispatcher,
+    pub sso: Sso,
+    pub slash: SlashCommands
 }
 
 #[cfg(test)]
@@ -0,0 +1,37 @@
+//! Keeping secrets such as passwords, API keys and tokens out of the logs. Types holding one
+//! implement `Debug` with [`redacted_debug!`], or with [`Redacted`] standing in for the secret.
+
+/// Prints as `<redacted>` in place of a secret.
+pub struct Redacted;
+
+impl std::fmt::Debug for Redacted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("<redacted>")
+    }
+}
+
+/// Implements `Debug` for a type holding a secret, which prints as `Type(<redacted>)`.
+macro_rules! redacted_debug {
+    ($type:ident) => {
+        impl std::fmt::Debug for $type {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.debug_tuple(stringify!($type)).field(&$crate::redact::Redacted).finish()
+            }
+        }
+    };
+}
+
+pub(crate) use redacted_debug;
+
+#[cfg(test)]
+pub mod test {
+    struct Secret(String);
+    redacted_debug!(Secret);
+
+    #[test]
+    fn secrets_are_not_printed() {
+        let secret = Secret("hunter2".to_owned());
+        assert_eq!(format!("{:?}", secret), "Secret(<redacted>)");
+        assert!(!format!("{:#?}", secret).contains(&secret.0));
+    }
+}
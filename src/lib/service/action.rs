@@ -1,41 +1,420 @@
 use crate::data::{query, DatabasePool, Transaction};
 use crate::service::ask;
 use crate::{Clip, ShortCode, ServiceError};
-use std::convert::TryInto;
+use std::convert::{TryFrom, TryInto};
 use crate::web::api::ApiKey;
+use crate::domain::clip::field::{EditToken, Password};
+use crate::domain::clip::{ClipCursor, ClipList, ClipMatch, ClipSummary, RawClip, SearchResults};
+use crate::domain::backup::{BackedUpClip, RestoreReport, SkippedClip};
+use crate::domain::import::{Conflict, ConflictPolicy, ImportReport, ImportedClip};
+use crate::domain::stats::{ClipStats, View};
+use crate::domain::user::field::{UserId, Username};
+use crate::domain::scope::Scope;
+use crate::domain::user::oidc::Identity;
+use crate::domain::workspace::field::{WorkspaceId, WorkspaceName};
+use crate::domain::workspace::{Member, Membership, Role, WorkspaceError};
+use crate::domain::user::{ApiKeyInfo, SessionToken, User, UserError};
+use crate::domain::webhook::{
+    Attempt, Delivery, PendingDelivery, RegisteredWebhook, Webhook, WebhookError, WebhookEvent, WebhookPayload,
+    WebhookSecret, WebhookUrl, VIEW_INTERVAL
+};
+use crate::data::model;
+use crate::metrics::METRICS;
+use chrono::{NaiveDateTime, Utc};
+use std::collections::HashSet;
+use std::time::Duration;
+
+/// Checks the requester may access a clip. The clips of a workspace can only be accessed by its
+/// members, or with an API key scoped to it or minted by one of its members. Anyone else is
+/// told the clip doesn't exist, so the shortcodes of private clips don't leak.
+async fn authorize(
+    workspace_id: Option<&str>,
+    requester: &ask::Requester,
+    pool: &DatabasePool
+) -> Result<(), ServiceError> {
+    let workspace_id = match workspace_id {
+        Some(workspace_id) => workspace_id.to_owned(),
+        None => return Ok(())
+    };
+    let allowed = match requester {
+        ask::Requester::Anonymous => return Err(WorkspaceError::LoginRequired.into()),
+        ask::Requester::User(user_id) => {
+            query::is_workspace_member(workspace_id, user_id.clone().into_inner().into(), pool).await?
+        },
+        ask::Requester::ApiKey(api_key) => {
+            query::api_key_can_access_workspace(workspace_id, api_key.clone().into_inner(), pool).await?
+        }
+    };
+    if allowed {
+        Ok(())
+    } else {
+        Err(ServiceError::NotFound)
+    }
+}
 
 /// This module contains the functions to calls queries that make the database transactions.
 pub async fn get_clip(req: ask::GetClip, pool: &DatabasePool) -> Result<Clip, ServiceError> {
     let user_pass = req.password.clone();
-    let clip: Clip = query::get_clip(req, pool).await?.try_into()?;
-    if clip.password.has_password() {
-        if clip.password.to_str() == user_pass.to_str() {
-            Ok(clip)
-        } else {
-            Err(ServiceError::PermissionError("Invalid password".to_owned()))
-        }
+    let requester = req.requester.clone();
+    let clip = query::get_clip(req, pool).await?;
+    authorize(clip.workspace_id(), &requester, pool).await?;
+    if clip.is_deleted() {
+        return Err(ServiceError::Gone);
+    }
+    let clip: Clip = clip.try_into()?;
+    check_password(&clip.password, &user_pass)?;
+    Ok(clip)
+}
+
+/// Checks the password given for a clip, if it has one.
+fn check_password(password: &Password, given: &Password) -> Result<(), ServiceError> {
+    if password.has_password() && !password.matches(given) {
+        METRICS.password_failures.inc();
+        Err(ServiceError::PermissionError("Invalid password".to_owned()))
     } else {
-        Ok(clip)
+        Ok(())
     }
 }
 
+/// Gets a clip without its content, which is then read a chunk at a time with
+/// [`read_clip_content`], so large clips are never held in memory whole.
+pub async fn get_raw_clip(req: ask::GetClip, pool: &DatabasePool) -> Result<RawClip, ServiceError> {
+    let user_pass = req.password.clone();
+    let requester = req.requester.clone();
+    let clip = query::get_raw_clip(req, pool).await?;
+    authorize(clip.workspace_id(), &requester, pool).await?;
+    if clip.is_deleted() {
+        return Err(ServiceError::Gone);
+    }
+    let clip: RawClip = clip.try_into()?;
+    check_password(&clip.password, &user_pass)?;
+    Ok(clip)
+}
+
+/// Reads up to `length` bytes of the content of a clip from byte `offset`. Nothing is read
+/// once the clip was edited or deleted, so the chunks of a stream all come from one revision.
+pub async fn read_clip_content(
+    clip: &RawClip,
+    offset: u64,
+    length: u64,
+    pool: &DatabasePool
+) -> Result<Vec<u8>, ServiceError> {
+    // Postgres counts the bytes of a text in an `i32`, so no content is longer than that.
+    let offset = i32::try_from(offset).unwrap_or(i32::MAX);
+    let length = i32::try_from(length).unwrap_or(i32::MAX);
+    let revision = i64::try_from(clip.revision.into_inner()).unwrap_or(i64::MAX);
+    Ok(query::get_clip_content(clip.shortcode.as_str(), revision, offset, length, pool).await?)
+}
+
+/// Creates a clip. Only the members of a workspace can create clips visible to it.
 pub async fn new_clip(req: ask::NewClip, pool: &DatabasePool) -> Result<Clip, ServiceError> {
-    Ok(query::new_clip(req, pool).await?.try_into()?)
+    if let Some(workspace_id) = &req.workspace {
+        let member = match &req.user {
+            Some(user_id) => query::is_workspace_member(
+                workspace_id.clone().into(),
+                user_id.clone().into_inner().into(),
+                pool
+            ).await?,
+            None => false
+        };
+        if !member {
+            return Err(ServiceError::NotFound);
+        }
+    }
+    let clip: Clip = query::new_clip(req, pool).await?.try_into()?;
+    METRICS.clips_created.inc();
+    notify_webhooks(&[WebhookPayload::new(WebhookEvent::ClipCreated, clip.shortcode.clone()).with_clip(clip.clone().into())], pool).await;
+    Ok(clip)
+}
+
+/// Lists the clips created with the API key of the request. One extra clip is fetched to know
+/// if there is a next page.
+pub async fn list_clips(req: ask::ListClips, pool: &DatabasePool) -> Result<ClipList, ServiceError> {
+    let limit = req.limit;
+    let mut clips = query::list_clips(ask::ListClips { limit: limit + 1, ..req }, pool)
+        .await?
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect::<Result<Vec<ClipSummary>, _>>()?;
+
+    let next_cursor = if clips.len() > limit as usize {
+        clips.truncate(limit as usize);
+        clips.last().map(|clip| ClipCursor::after(clip).to_string())
+    } else {
+        None
+    };
+    Ok(ClipList { clips, next_cursor })
+}
+
+/// Searches the clips created with the API key of the request. One extra clip is fetched to
+/// know if there is a next page.
+pub async fn search_clips(req: ask::SearchClips, pool: &DatabasePool) -> Result<SearchResults, ServiceError> {
+    let (offset, limit) = (req.offset, req.limit);
+    let mut clips = query::search_clips(
+        ask::SearchClips { limit: limit + 1, ..req },
+        pool
+    ).await?
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect::<Result<Vec<ClipMatch>, _>>()?;
+
+    let next_offset = if clips.len() > limit as usize {
+        clips.truncate(limit as usize);
+        Some(offset + limit)
+    } else {
+        None
+    };
+    Ok(SearchResults { clips, next_offset })
 }
 
+/// Edits a clip. Only the API key or the user who created it may, or an admin; other
+/// requesters are told the clip doesn't exist, like for deletions.
 pub async fn update_clip(req: ask::UpdateClip, pool: &DatabasePool) -> Result<Clip, ServiceError> {
-    Ok(query::update_clip(req, pool).await?.try_into()?)
+    let clip = query::get_clip(req.shortcode.clone(), pool).await?;
+    authorize(clip.workspace_id(), &req.requester, pool).await?;
+    if !may_edit(&req.shortcode, &req.requester, pool).await? {
+        return Err(ServiceError::NotFound);
+    }
+    save_clip(req, pool).await
 }
 
-pub async fn increase_hit_count(
-    shortcode: &ShortCode,
-    hits: u32,
-    pool: &DatabasePool
+/// Edits a clip for a holder of its edit token, which proves they may.
+pub async fn update_clip_with_token(req: ask::UpdateClip, pool: &DatabasePool) -> Result<Clip, ServiceError> {
+    let clip = query::get_clip(req.shortcode.clone(), pool).await?;
+    authorize(clip.workspace_id(), &req.requester, pool).await?;
+    save_clip(req, pool).await
+}
+
+async fn save_clip(req: ask::UpdateClip, pool: &DatabasePool) -> Result<Clip, ServiceError> {
+    let clip = match query::update_clip(req, pool).await? {
+        query::UpdateStatus::Updated(clip) => *clip,
+        query::UpdateStatus::Stale => return Err(ServiceError::PreconditionFailed)
+    };
+    if clip.is_deleted() {
+        return Err(ServiceError::Gone);
+    }
+    let clip: Clip = clip.try_into()?;
+    notify_webhooks(&[WebhookPayload::new(WebhookEvent::ClipUpdated, clip.shortcode.clone()).with_clip(clip.clone().into())], pool).await;
+    Ok(clip)
+}
+
+/// Whether the requester created the clip or is an admin, who alone may edit it.
+async fn may_edit(shortcode: &ShortCode, requester: &ask::Requester, pool: &DatabasePool) -> Result<bool, ServiceError> {
+    let (owner, user_id) = match requester {
+        ask::Requester::ApiKey(api_key) => (Some(api_key.clone().into_inner()), None),
+        ask::Requester::User(user_id) => (None, Some(String::from(user_id.clone().into_inner()))),
+        ask::Requester::Anonymous => return Ok(false)
+    };
+    Ok(query::is_clip_creator(shortcode.as_str(), owner, user_id, pool).await? || is_admin(requester, pool).await?)
+}
+
+/// Whether the requester is an API key with the `admin` scope.
+async fn is_admin(requester: &ask::Requester, pool: &DatabasePool) -> Result<bool, ServiceError> {
+    match requester {
+        ask::Requester::ApiKey(api_key) => Ok(api_key_scopes(api_key.clone(), pool)
+            .await?
+            .is_some_and(|scopes| scopes.contains(&Scope::Admin))),
+        _ => Ok(false)
+    }
+}
+
+/// Deletes a clip. Only the API key or the user who created it may, or an admin; other
+/// requesters are told the clip doesn't exist.
+pub async fn delete_clip(req: ask::GetClip, pool: &DatabasePool) -> Result<(), ServiceError> {
+    let clip = query::get_clip(req.shortcode.clone(), pool).await?;
+    authorize(clip.workspace_id(), &req.requester, pool).await?;
+    let admin = is_admin(&req.requester, pool).await?;
+    match query::delete_clip(model::DeleteClip::new(req.clone(), admin), pool).await? {
+        query::DeletionStatus::Deleted => {
+            notify_webhooks(&[WebhookPayload::new(WebhookEvent::ClipDeleted, req.shortcode)], pool).await;
+            Ok(())
+        },
+        query::DeletionStatus::NotFound => Err(ServiceError::NotFound)
+    }
+}
+
+/// Mints a new token to edit a clip together, replacing the previous one. Anyone who may edit
+/// the clip may mint one, and the password of the clip is not needed, just like for edits.
+pub async fn new_edit_token(req: ask::GetClip, pool: &DatabasePool) -> Result<EditToken, ServiceError> {
+    let clip = query::get_clip(req.shortcode.clone(), pool).await?;
+    authorize(clip.workspace_id(), &req.requester, pool).await?;
+    if !may_edit(&req.shortcode, &req.requester, pool).await? {
+        return Err(ServiceError::NotFound);
+    }
+    if clip.is_deleted() {
+        return Err(ServiceError::Gone);
+    }
+    let token = EditToken::default();
+    query::set_edit_token(req.shortcode.as_str(), &token.digest(), pool).await?;
+    Ok(token)
+}
+
+/// Whether `token` is the current edit token of a clip.
+pub async fn is_edit_token(shortcode: &ShortCode, token: &EditToken, pool: &DatabasePool) -> Result<bool, ServiceError> {
+    Ok(query::is_edit_token(shortcode.as_str(), &token.digest(), pool).await?)
+}
+
+/// Restores an expired or deleted clip, which only an admin may do.
+pub async fn restore_clip(req: ask::GetClip, pool: &DatabasePool) -> Result<Clip, ServiceError> {
+    if !is_admin(&req.requester, pool).await? {
+        return Err(ServiceError::PermissionError("Only admins can restore clips".to_owned()));
+    }
+    let clip = query::get_clip(req.shortcode.clone(), pool).await?;
+    authorize(clip.workspace_id(), &req.requester, pool).await?;
+    Ok(query::restore_clip(req, pool).await?.try_into()?)
+}
+
+/// Increases the hit counts of the clips and returns their new totals.
+pub async fn increase_hit_counts(
+    hits: &[(ShortCode, u32)],
+    transaction: &mut Transaction<'_>
+) -> Result<Vec<(ShortCode, u64)>, ServiceError> {
+    let totals: Vec<_> = query::increase_hit_counts(hits, transaction)
+        .await?
+        .into_iter()
+        .map(|(shortcode, total)| (shortcode, total.max(0) as u64))
+        .collect();
+    let payloads: Vec<_> = totals
+        .iter()
+        .map(|(shortcode, total)| WebhookPayload::new(WebhookEvent::ClipViewed, shortcode.clone()).with_hits(*total))
+        .collect();
+    let sent_since = Utc::now().naive_utc() - chrono::Duration::from_std(VIEW_INTERVAL).unwrap_or_else(|_| chrono::Duration::minutes(1));
+    queue_webhook_deliveries(&payloads, Some(sent_since), &mut *transaction).await?;
+    Ok(totals)
+}
+
+pub async fn record_views(
+    views: &[((ShortCode, NaiveDateTime, View), u32)],
+    transaction: &mut Transaction<'_>
 ) -> Result<(), ServiceError> {
-    Ok(query::increase_hit_count(shortcode, hits, pool).await?)
+    Ok(query::record_views(views, transaction).await?)
 }
 
-/// Transactions are started and committed asynchronously in a separate thread.
+/// Gets the view statistics of a clip. The clip password is required, just like when viewing it.
+pub async fn get_clip_stats(req: ask::GetClip, pool: &DatabasePool) -> Result<ClipStats, ServiceError> {
+    let clip = get_clip(req, pool).await?;
+    let views = query::get_clip_views(clip.shortcode, pool)
+        .await?
+        .into_iter()
+        .map(|view| view.try_into())
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(ClipStats::from_views(views))
+}
+
+/// How many shortcodes an imported clip is tried under before it is given up on.
+const IMPORT_ATTEMPTS: usize = 5;
+
+/// Imports clips in batches, each saved in its own transaction so an import which fails
+/// halfway can be run again with the shortcodes kept and conflicts skipped. Clips which
+/// don't keep their shortcode get a new one, and so do the conflicting ones when renaming.
+pub async fn import_clips(req: ask::ImportClips, pool: &DatabasePool) -> Result<ImportReport, ServiceError> {
+    /// A clip to save, with the shortcode of the export it couldn't keep, if any.
+    struct Pending<'a> {
+        record: usize,
+        clip: &'a ImportedClip,
+        shortcode: ShortCode,
+        conflict: Option<ShortCode>
+    }
+
+    let mut report = ImportReport::default();
+    for batch in req.clips.chunks(req.batch_size.max(1)) {
+        let mut pending: Vec<_> = batch
+            .iter()
+            .map(|(record, clip)| Pending {
+                record: *record,
+                clip,
+                shortcode: clip.shortcode.clone().filter(|_| req.keep_shortcodes).unwrap_or_default(),
+                conflict: None
+            })
+            .collect();
+
+        let mut transaction = begin_transaction(pool).await?;
+        for attempt in 0..IMPORT_ATTEMPTS {
+            if pending.is_empty() {
+                break;
+            }
+            let models: Vec<_> = pending
+                .iter()
+                .map(|clip| model::NewClip::imported(clip.clip.clone(), clip.shortcode.clone()))
+                .collect();
+            let saved: HashSet<String> = query::import_clips(&models, &mut transaction)
+                .await?
+                .into_iter()
+                .collect();
+            report.imported += saved.len();
+
+            // A shortcode repeated within the batch is only saved for the first clip with it.
+            let mut claimed = HashSet::new();
+            let mut retry = Vec::new();
+            for clip in pending {
+                let kept = req.keep_shortcodes && clip.conflict.is_none() && clip.clip.shortcode.as_ref() == Some(&clip.shortcode);
+                if saved.contains(clip.shortcode.as_str()) && claimed.insert(clip.shortcode.clone()) {
+                    if let Some(shortcode) = clip.conflict {
+                        report.conflicts.push(Conflict { record: clip.record, shortcode, imported_as: Some(clip.shortcode) });
+                    }
+                } else if (kept && req.on_conflict == ConflictPolicy::Skip) || attempt + 1 == IMPORT_ATTEMPTS {
+                    report.conflicts.push(Conflict {
+                        record: clip.record,
+                        shortcode: clip.conflict.unwrap_or(clip.shortcode),
+                        imported_as: None
+                    });
+                } else {
+                    retry.push(Pending {
+                        conflict: if kept { Some(clip.shortcode) } else { clip.conflict },
+                        shortcode: ShortCode::new(),
+                        ..clip
+                    });
+                }
+            }
+            pending = retry;
+        }
+        end_transaction(transaction).await?;
+    }
+    Ok(report)
+}
+
+pub async fn export_clips(req: ask::ExportClips, pool: &DatabasePool) -> Result<Vec<BackedUpClip>, ServiceError> {
+    query::export_clips(req, pool)
+        .await?
+        .into_iter()
+        .map(|clip| Ok(clip.try_into()?))
+        .collect()
+}
+
+/// Restores the clips of a backup in transactions of `batch_size` clips. Clips whose id
+/// already exists are left as they are, so restoring a backup twice changes nothing.
+pub async fn restore_clips(
+    clips: Vec<BackedUpClip>,
+    batch_size: usize,
+    pool: &DatabasePool
+) -> Result<RestoreReport, ServiceError> {
+    let mut report = RestoreReport::default();
+    for batch in clips.chunks(batch_size.max(1)) {
+        let models: Vec<model::ClipBackup> = batch.iter().cloned().map(Into::into).collect();
+        let ids: Vec<String> = batch.iter().map(|clip| clip.clip_id.clone()).collect();
+
+        let mut transaction = begin_transaction(pool).await?;
+        let restored: HashSet<String> = query::restore_clips(&models, &mut transaction).await?.into_iter().collect();
+        let existing: HashSet<String> = query::existing_clip_ids(&ids, &mut transaction).await?.into_iter().collect();
+        end_transaction(transaction).await?;
+
+        report.restored += restored.len();
+        let mut seen = HashSet::new();
+        for clip in batch {
+            if restored.contains(&clip.clip_id) && seen.insert(clip.clip_id.as_str()) {
+                continue;
+            }
+            if existing.contains(&clip.clip_id) {
+                report.unchanged += 1;
+            } else {
+                report.skipped.push(SkippedClip { clip_id: clip.clip_id.clone(), shortcode: clip.shortcode.clone() });
+            }
+        }
+    }
+    Ok(report)
+}
+
+/// Transactions are started and committed asynchronously by the hit counter task.
 pub async fn begin_transaction(pool: &DatabasePool) -> Result<Transaction<'_>, ServiceError> {
     Ok(pool.begin().await?)
 }
@@ -44,20 +423,492 @@ pub async fn end_transaction(transaction: Transaction<'_>) -> Result<(), Service
     Ok(transaction.commit().await?)
 }
 
-pub async fn generate_api_key(pool: &DatabasePool) -> Result<ApiKey, ServiceError> {
+pub async fn ping(pool: &DatabasePool) -> Result<(), ServiceError> {
+    Ok(query::ping(pool).await?)
+}
+
+pub async fn schema_version(pool: &DatabasePool) -> Result<i32, ServiceError> {
+    Ok(query::schema_version(pool).await?)
+}
+
+fn scope_names(scopes: &[Scope]) -> Vec<String> {
+    scopes.iter().map(|scope| scope.as_str().to_owned()).collect()
+}
+
+/// Checks the scopes asked for a key minted from the web UI. Such keys can't mint keys themselves.
+fn web_key_scopes(scopes: &[Scope]) -> Result<Vec<String>, ServiceError> {
+    if scopes.is_empty() {
+        Err(ServiceError::PermissionError("An API key needs at least one scope".to_owned()))
+    } else if scopes.contains(&Scope::Admin) {
+        Err(ServiceError::PermissionError("Only the server can mint admin API keys".to_owned()))
+    } else {
+        Ok(scope_names(scopes))
+    }
+}
+
+pub async fn generate_api_key(scopes: &[Scope], pool: &DatabasePool) -> Result<ApiKey, ServiceError> {
     let api_key = ApiKey::default();
-    Ok(query::save_api_key(api_key, pool).await?)
+    Ok(query::save_api_key(api_key, &scope_names(scopes), pool).await?)
 }
 
 pub async fn revoke_api_key(api_key: ApiKey, pool: &DatabasePool) -> Result<query::RevocationStatus, ServiceError> {
     Ok(query::revoke_api_key(api_key, pool).await?)
 }
 
-pub async fn api_key_is_valid(api_key: ApiKey, pool: &DatabasePool) -> Result<bool, ServiceError> {
-    Ok(query::api_key_is_valid(api_key, pool).await?)
+/// Gets the scopes of an [`ApiKey`], or `None` if the key is not valid. Unknown scopes are ignored.
+pub async fn api_key_scopes(api_key: ApiKey, pool: &DatabasePool) -> Result<Option<Vec<Scope>>, ServiceError> {
+    Ok(
+        query::api_key_scopes(api_key, pool)
+            .await?
+            .map(|scopes| scopes.iter().filter_map(|scope| scope.parse().ok()).collect())
+    )
+}
+
+
+/// Moves the expired clips into the tombstone state, returning their shortcodes.
+pub async fn expire_clips(pool: &DatabasePool) -> Result<Vec<ShortCode>, ServiceError> {
+    Ok(query::expire_clips(pool).await?.into_iter().map(ShortCode::from).collect())
+}
+
+/// Purges the clips which have been deleted for longer than the `grace_period`.
+pub async fn purge_deleted(grace_period: Duration, pool: &DatabasePool) -> Result<u64, ServiceError> {
+    let deleted_before = chrono::Duration::from_std(grace_period)
+        .ok()
+        .and_then(|grace_period| Utc::now().naive_utc().checked_sub_signed(grace_period))
+        .unwrap_or(NaiveDateTime::MIN);
+    Ok(query::purge_deleted(deleted_before, pool).await?)
+}
+
+/// Creates a user account, hashing its password.
+pub async fn register(req: ask::Register, pool: &DatabasePool) -> Result<User, ServiceError> {
+    let password_hash = req.password.hash()?;
+    match query::new_user(model::NewUser::new(req.username, password_hash), pool).await {
+        Ok(user) => Ok(user.try_into()?),
+        Err(e) if is_unique_violation(&e) => Err(UserError::UsernameTaken.into()),
+        Err(e) => Err(e.into())
+    }
+}
+
+fn is_unique_violation(err: &crate::DataError) -> bool {
+    let crate::DataError::Database(err) = err;
+    err.as_database_error()
+        .and_then(|err| err.code())
+        .map(|code| code == "23505")
+        .unwrap_or(false)
+}
+
+/// Checks the credentials of a user. The same error is returned for an unknown username and
+/// a wrong password.
+pub async fn login(req: ask::Login, pool: &DatabasePool) -> Result<User, ServiceError> {
+    let invalid = || ServiceError::PermissionError("Invalid username or password".to_owned());
+    let username = req.username.trim().to_lowercase();
+    let user = match query::get_user(&username, pool).await.map_err(ServiceError::from) {
+        Ok(user) => user,
+        Err(ServiceError::NotFound) => return Err(invalid()),
+        Err(e) => return Err(e)
+    };
+    let password = crate::domain::user::field::UserPassword::new(&req.password)
+        .map_err(|_| invalid())?;
+    match user.password_hash() {
+        Some(hash) if password.verify(hash) => Ok(user.try_into()?),
+        _ => Err(invalid())
+    }
 }
 
+/// Gets the user linked to an account of an OpenID Connect provider, creating one on their
+/// first login. A random suffix is added to the username if it is taken, so a provider can
+/// never log someone into an existing account.
+pub async fn sso_login(identity: &Identity, pool: &DatabasePool) -> Result<User, ServiceError> {
+    let existing = || query::get_identity_user(&identity.issuer, &identity.subject, pool);
+    match existing().await.map_err(ServiceError::from) {
+        Ok(user) => return Ok(user.try_into()?),
+        Err(ServiceError::NotFound) => (),
+        Err(e) => return Err(e)
+    }
 
-pub async fn delete_expired(pool: &DatabasePool) -> Result<u64, ServiceError> {
-    Ok(query::delete_expired(pool).await?)
-}
\ No newline at end of file
+    let hint = identity.username_hint();
+    for attempt in 0..5 {
+        let username = match attempt {
+            0 => hint.clone(),
+            _ => format!("{}-{:04x}", hint, rand::random::<u16>())
+        };
+        let model = model::NewUser::without_password(Username::new(&username)?);
+        match query::new_identity_user(model, &identity.issuer, &identity.subject, pool).await {
+            Ok(user) => return Ok(user.try_into()?),
+            // Either the username is taken, or the same account logged in concurrently.
+            Err(e) if is_unique_violation(&e) => {
+                if let Ok(user) = existing().await {
+                    return Ok(user.try_into()?);
+                }
+            },
+            Err(e) => return Err(e.into())
+        }
+    }
+    Err(UserError::UsernameTaken.into())
+}
+
+/// Starts a session for a user, valid for `ttl`.
+pub async fn new_session(
+    user: &User,
+    ttl: Duration,
+    pool: &DatabasePool
+) -> Result<SessionToken, ServiceError> {
+    let token = SessionToken::default();
+    let expires = Utc::now().naive_utc()
+        + chrono::Duration::from_std(ttl).unwrap_or_else(|_| chrono::Duration::days(30));
+    query::new_session(token.hash(), user.user_id.clone().into_inner().into(), expires, pool).await?;
+    Ok(token)
+}
+
+/// Gets the user of a session which has not expired.
+pub async fn get_session_user(token: &SessionToken, pool: &DatabasePool) -> Result<User, ServiceError> {
+    Ok(query::get_session_user(token.hash(), pool).await?.try_into()?)
+}
+
+pub async fn end_session(token: &SessionToken, pool: &DatabasePool) -> Result<(), ServiceError> {
+    Ok(query::delete_session(token.hash(), pool).await?)
+}
+
+pub async fn purge_sessions(pool: &DatabasePool) -> Result<u64, ServiceError> {
+    Ok(query::purge_sessions(pool).await?)
+}
+
+/// Lists the clips created by a user. One extra clip is fetched to know if there is a next page.
+pub async fn list_user_clips(req: ask::ListUserClips, pool: &DatabasePool) -> Result<ClipList, ServiceError> {
+    let limit = req.limit;
+    let mut clips = query::list_user_clips(ask::ListUserClips { limit: limit + 1, ..req }, pool)
+        .await?
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect::<Result<Vec<ClipSummary>, _>>()?;
+
+    let next_cursor = if clips.len() > limit as usize {
+        clips.truncate(limit as usize);
+        clips.last().map(|clip| ClipCursor::after(clip).to_string())
+    } else {
+        None
+    };
+    Ok(ClipList { clips, next_cursor })
+}
+
+/// Gets a clip created by the user, regardless of its password.
+pub async fn get_user_clip(shortcode: ShortCode, user_id: &UserId, pool: &DatabasePool) -> Result<Clip, ServiceError> {
+    let clip = query::get_clip(shortcode, pool).await?;
+    if clip.is_deleted() {
+        return Err(ServiceError::Gone);
+    }
+    if !clip.is_owned_by(user_id) {
+        return Err(ServiceError::NotFound);
+    }
+    Ok(clip.try_into()?)
+}
+
+/// Updates a clip created by the user.
+pub async fn update_user_clip(req: ask::UpdateClip, user_id: &UserId, pool: &DatabasePool) -> Result<Clip, ServiceError> {
+    get_user_clip(req.shortcode.clone(), user_id, pool).await?;
+    update_clip(ask::UpdateClip { requester: ask::Requester::User(user_id.clone()), ..req }, pool).await
+}
+
+/// Mints a new token to edit a clip created by the user together.
+pub async fn new_user_edit_token(shortcode: ShortCode, user_id: &UserId, pool: &DatabasePool) -> Result<EditToken, ServiceError> {
+    get_user_clip(shortcode.clone(), user_id, pool).await?;
+    let req = ask::GetClip { requester: ask::Requester::User(user_id.clone()), ..shortcode.into() };
+    new_edit_token(req, pool).await
+}
+
+/// Deletes a clip created by the user.
+pub async fn delete_user_clip(shortcode: ShortCode, user_id: &UserId, pool: &DatabasePool) -> Result<(), ServiceError> {
+    get_user_clip(shortcode.clone(), user_id, pool).await?;
+    let req = ask::GetClip { requester: ask::Requester::User(user_id.clone()), ..shortcode.into() };
+    delete_clip(req, pool).await
+}
+
+pub async fn generate_user_api_key(
+    user_id: &UserId,
+    scopes: &[Scope],
+    pool: &DatabasePool
+) -> Result<ApiKey, ServiceError> {
+    let scopes = web_key_scopes(scopes)?;
+    let api_key = ApiKey::default();
+    Ok(query::save_user_api_key(api_key, user_id.clone().into_inner().into(), &scopes, pool).await?)
+}
+
+pub async fn list_user_api_keys(user_id: &UserId, pool: &DatabasePool) -> Result<Vec<ApiKeyInfo>, ServiceError> {
+    Ok(
+        query::list_user_api_keys(user_id.clone().into_inner().into(), pool)
+            .await?
+            .into_iter()
+            .map(Into::into)
+            .collect()
+    )
+}
+
+pub async fn revoke_user_api_key(
+    user_id: &UserId,
+    fingerprint: &str,
+    pool: &DatabasePool
+) -> Result<query::RevocationStatus, ServiceError> {
+    Ok(query::revoke_user_api_key(user_id.clone().into_inner().into(), fingerprint, pool).await?)
+}
+
+/// Creates a workspace, owned by the user creating it.
+pub async fn new_workspace(name: WorkspaceName, owner: &UserId, pool: &DatabasePool) -> Result<Membership, ServiceError> {
+    let model = model::NewWorkspace::new(name);
+    Ok(query::new_workspace(model, owner.clone().into_inner().into(), pool).await?.try_into()?)
+}
+
+/// Gets a workspace of a user. Workspaces the user is not a member of are not found.
+pub async fn get_workspace(
+    workspace_id: &WorkspaceId,
+    user_id: &UserId,
+    pool: &DatabasePool
+) -> Result<Membership, ServiceError> {
+    let membership = query::get_membership(
+        workspace_id.clone().into(),
+        user_id.clone().into_inner().into(),
+        pool
+    ).await?;
+    Ok(membership.try_into()?)
+}
+
+/// Gets a workspace of a user who must be one of its owners.
+async fn get_owned_workspace(
+    workspace_id: &WorkspaceId,
+    user_id: &UserId,
+    pool: &DatabasePool
+) -> Result<Membership, ServiceError> {
+    let membership = get_workspace(workspace_id, user_id, pool).await?;
+    if membership.is_owner() {
+        Ok(membership)
+    } else {
+        Err(WorkspaceError::NotAnOwner.into())
+    }
+}
+
+pub async fn list_user_workspaces(user_id: &UserId, pool: &DatabasePool) -> Result<Vec<Membership>, ServiceError> {
+    Ok(
+        query::list_user_workspaces(user_id.clone().into_inner().into(), pool)
+            .await?
+            .into_iter()
+            .map(TryInto::try_into)
+            .collect::<Result<_, _>>()?
+    )
+}
+
+pub async fn list_workspace_members(workspace_id: &WorkspaceId, pool: &DatabasePool) -> Result<Vec<Member>, ServiceError> {
+    query::list_workspace_members(workspace_id.clone().into(), pool)
+        .await?
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect()
+}
+
+/// Lists the clips of a workspace. One extra clip is fetched to know if there is a next page.
+pub async fn list_workspace_clips(req: ask::ListWorkspaceClips, pool: &DatabasePool) -> Result<ClipList, ServiceError> {
+    let limit = req.limit;
+    let mut clips = query::list_workspace_clips(ask::ListWorkspaceClips { limit: limit + 1, ..req }, pool)
+        .await?
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect::<Result<Vec<ClipSummary>, _>>()?;
+
+    let next_cursor = if clips.len() > limit as usize {
+        clips.truncate(limit as usize);
+        clips.last().map(|clip| ClipCursor::after(clip).to_string())
+    } else {
+        None
+    };
+    Ok(ClipList { clips, next_cursor })
+}
+
+/// Adds a user to a workspace as a member. Only owners can add members.
+pub async fn add_workspace_member(
+    workspace_id: &WorkspaceId,
+    owner: &UserId,
+    username: &str,
+    pool: &DatabasePool
+) -> Result<(), ServiceError> {
+    get_owned_workspace(workspace_id, owner, pool).await?;
+    let user: User = query::get_user(&username.trim().to_lowercase(), pool).await?.try_into()?;
+    Ok(query::add_workspace_member(
+        workspace_id.clone().into(),
+        user.user_id.into_inner().into(),
+        Role::Member.as_str(),
+        pool
+    ).await?)
+}
+
+/// Removes a member from a workspace. Only owners can remove members, and owners can't be removed.
+pub async fn remove_workspace_member(
+    workspace_id: &WorkspaceId,
+    owner: &UserId,
+    username: &str,
+    pool: &DatabasePool
+) -> Result<(), ServiceError> {
+    get_owned_workspace(workspace_id, owner, pool).await?;
+    match query::remove_workspace_member(workspace_id.clone().into(), username, pool).await? {
+        0 => Err(ServiceError::NotFound),
+        _ => Ok(())
+    }
+}
+
+/// Mints an API key scoped to a workspace. Only owners can mint them.
+pub async fn generate_workspace_api_key(
+    workspace_id: &WorkspaceId,
+    owner: &UserId,
+    scopes: &[Scope],
+    pool: &DatabasePool
+) -> Result<ApiKey, ServiceError> {
+    get_owned_workspace(workspace_id, owner, pool).await?;
+    let scopes = web_key_scopes(scopes)?;
+    Ok(query::save_workspace_api_key(ApiKey::default(), workspace_id.clone().into(), &scopes, pool).await?)
+}
+
+pub async fn list_workspace_api_keys(workspace_id: &WorkspaceId, pool: &DatabasePool) -> Result<Vec<ApiKeyInfo>, ServiceError> {
+    Ok(
+        query::list_workspace_api_keys(workspace_id.clone().into(), pool)
+            .await?
+            .into_iter()
+            .map(Into::into)
+            .collect()
+    )
+}
+
+/// Revokes an API key scoped to a workspace. Only owners can revoke them.
+pub async fn revoke_workspace_api_key(
+    workspace_id: &WorkspaceId,
+    owner: &UserId,
+    fingerprint: &str,
+    pool: &DatabasePool
+) -> Result<query::RevocationStatus, ServiceError> {
+    get_owned_workspace(workspace_id, owner, pool).await?;
+    Ok(query::revoke_workspace_api_key(workspace_id.clone().into(), fingerprint, pool).await?)
+}
+
+/// Queues the deliveries of some events of the same kind to the webhooks subscribed to them.
+async fn queue_webhook_deliveries<'e, E: sqlx::PgExecutor<'e>>(
+    payloads: &[WebhookPayload],
+    sent_since: Option<NaiveDateTime>,
+    executor: E
+) -> Result<(), ServiceError> {
+    let event = match payloads.first() {
+        Some(payload) => payload.event,
+        None => return Ok(())
+    };
+    let shortcodes: Vec<String> = payloads.iter().map(|payload| payload.shortcode.as_str().to_owned()).collect();
+    let bodies: Vec<String> = payloads.iter().map(WebhookPayload::to_json).collect();
+    query::queue_webhook_deliveries(event.as_str(), &shortcodes, &bodies, sent_since, executor).await?;
+    Ok(())
+}
+
+/// Queues the deliveries of some events of the same kind to the webhooks subscribed to them.
+/// The change the events are about is already made, so failing to queue them is only logged.
+pub async fn notify_webhooks(payloads: &[WebhookPayload], pool: &DatabasePool) {
+    if let Err(e) = queue_webhook_deliveries(payloads, None, pool).await {
+        tracing::error!(error = %e, "failed to queue webhook deliveries");
+    }
+}
+
+/// Registers a webhook getting the events of the clips created with an API key. Its host must be
+/// public, or one of the `allowed_hosts`.
+pub async fn new_webhook(
+    req: ask::NewWebhook,
+    api_key: ApiKey,
+    allowed_hosts: &[String],
+    pool: &DatabasePool
+) -> Result<RegisteredWebhook, ServiceError> {
+    let url = WebhookUrl::new(&req.url, allowed_hosts)?;
+    if req.events.is_empty() {
+        return Err(WebhookError::NoEvents.into());
+    }
+    let mut events = req.events;
+    events.sort_by_key(|event| WebhookEvent::ALL.iter().position(|e| e == event));
+    events.dedup();
+    let secret = WebhookSecret::default();
+    let model = model::NewWebhook::new(api_key, url, secret.clone(), &events);
+    Ok(RegisteredWebhook {
+        webhook: query::new_webhook(model, pool).await?.try_into()?,
+        secret: secret.into_inner()
+    })
+}
+
+pub async fn list_webhooks(api_key: ApiKey, pool: &DatabasePool) -> Result<Vec<Webhook>, ServiceError> {
+    query::list_webhooks(api_key, pool)
+        .await?
+        .into_iter()
+        .map(|webhook| Ok(webhook.try_into()?))
+        .collect()
+}
+
+/// Deletes a webhook of an API key, which stops its deliveries.
+pub async fn delete_webhook(webhook_id: &str, api_key: ApiKey, pool: &DatabasePool) -> Result<(), ServiceError> {
+    match query::delete_webhook(webhook_id, api_key, pool).await? {
+        0 => Err(WebhookError::NotFound.into()),
+        _ => Ok(())
+    }
+}
+
+/// Lists the latest deliveries of a webhook of an API key, newest first.
+pub async fn list_webhook_deliveries(
+    webhook_id: &str,
+    limit: u32,
+    api_key: ApiKey,
+    pool: &DatabasePool
+) -> Result<Vec<Delivery>, ServiceError> {
+    match query::get_webhook(webhook_id, api_key, pool).await.map_err(ServiceError::from) {
+        Ok(_) => (),
+        Err(ServiceError::NotFound) => return Err(WebhookError::NotFound.into()),
+        Err(e) => return Err(e)
+    }
+    query::list_webhook_deliveries(webhook_id, limit.into(), pool)
+        .await?
+        .into_iter()
+        .map(|delivery| Ok(delivery.try_into()?))
+        .collect()
+}
+
+/// Claims up to `limit` deliveries due to be attempted, for `lease`.
+pub async fn claim_webhook_deliveries(
+    limit: u32,
+    lease: Duration,
+    pool: &DatabasePool
+) -> Result<Vec<PendingDelivery>, ServiceError> {
+    let lease_until = Utc::now().naive_utc() + chrono::Duration::from_std(lease).unwrap_or_else(|_| chrono::Duration::minutes(1));
+    Ok(
+        query::claim_webhook_deliveries(limit.into(), lease_until, pool)
+            .await?
+            .into_iter()
+            .map(Into::into)
+            .collect()
+    )
+}
+
+/// Records how an attempt to deliver an event went, to be attempted again after `retry` if it
+/// failed. Failed deliveries without a `retry` are given up on.
+pub async fn record_webhook_attempt(
+    delivery_id: i64,
+    attempt: Attempt,
+    retry: Option<Duration>,
+    pool: &DatabasePool
+) -> Result<(), ServiceError> {
+    let now = Utc::now().naive_utc();
+    let (delivered, status, error, next_attempt) = match attempt {
+        Attempt::Delivered(status) => (Some(now), Some(status), None, None),
+        Attempt::Failed { status, error } => {
+            let next_attempt = retry
+                .and_then(|retry| chrono::Duration::from_std(retry).ok())
+                .map(|retry| now + retry);
+            (None, status, Some(error), next_attempt)
+        }
+    };
+    Ok(query::record_webhook_attempt(delivery_id, delivered, status.map(i32::from), error, next_attempt, pool).await?)
+}
+
+/// Removes the deliveries which are done with and older than `retention` from the delivery logs.
+pub async fn purge_webhook_deliveries(retention: Duration, pool: &DatabasePool) -> Result<u64, ServiceError> {
+    let created_before = chrono::Duration::from_std(retention)
+        .ok()
+        .and_then(|retention| Utc::now().naive_utc().checked_sub_signed(retention))
+        .unwrap_or(NaiveDateTime::MIN);
+    Ok(query::purge_webhook_deliveries(created_before, pool).await?)
+}
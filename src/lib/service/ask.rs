@@ -1,13 +1,30 @@
-use crate::domain::clip::field;
-use crate::ShortCode;
+use crate::domain::clip::{field, ClipCursor};
+use crate::domain::import::{ConflictPolicy, ImportedClip};
+use crate::domain::scope::Scope;
+use crate::domain::user::field::{UserId, UserPassword, Username};
+use crate::domain::webhook::WebhookEvent;
+use crate::domain::workspace::field::WorkspaceId;
+use crate::web::api::ApiKey;
+use crate::{ClipError, ShortCode};
 use serde::{Deserialize, Serialize};
 
 /// Define the data structures that interact with the web layer (the client).
 
-#[derive(Debug, Deserialize, Serialize)]
+/// Who is asking for a clip, which decides whether the clips of a workspace can be read.
+#[derive(Debug, Clone, Default)]
+pub enum Requester {
+    #[default]
+    Anonymous,
+    User(UserId),
+    ApiKey(ApiKey)
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct GetClip {
     pub shortcode: ShortCode,
     pub password: field::Password,
+    #[serde(skip)]
+    pub requester: Requester
 }
 
 impl GetClip {
@@ -15,6 +32,7 @@ impl GetClip {
         Self {
             shortcode: ShortCode::from(shortcode),
             password: field::Password::default(),
+            requester: Requester::default()
         }
     }
 }
@@ -24,6 +42,7 @@ impl From<ShortCode> for GetClip {
         Self {
             shortcode,
             password: field::Password::default(),
+            requester: Requester::default()
         }
     }
 }
@@ -39,7 +58,17 @@ pub struct NewClip {
     pub content: field::Content,
     pub title: field::Title,
     pub expires: field::Expires,
-    pub password: field::Password
+    pub password: field::Password,
+    /// The API key the clip is created with, set by the API from the request headers.
+    #[serde(skip)]
+    pub owner: Option<ApiKey>,
+    /// The logged in user creating the clip from the web UI.
+    #[serde(skip)]
+    pub user: Option<UserId>,
+    /// The workspace the clip is only visible to, chosen in the web UI. Clips created with an
+    /// API key scoped to a workspace always go to that workspace.
+    #[serde(skip)]
+    pub workspace: Option<WorkspaceId>
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -48,5 +77,160 @@ pub struct UpdateClip {
     pub title: field::Title,
     pub expires: field::Expires,
     pub password: field::Password,
-    pub shortcode: field::ShortCode
-}
\ No newline at end of file
+    pub shortcode: field::ShortCode,
+    #[serde(skip)]
+    pub requester: Requester,
+    /// The revisions the clip must still be at to be edited, from an `If-Match` header. Any
+    /// revision will do when `None`.
+    #[serde(skip)]
+    pub revisions: Option<Vec<field::Revision>>
+}
+
+/// The password of a protected clip sent to `POST /api/v1/clips/<shortcode>/unlock`.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct UnlockClip {
+    pub password: field::Password
+}
+
+/// The new content of a clip sent to `PUT /api/v1/clips/<shortcode>`, which names the clip in
+/// its path rather than in the body like [`UpdateClip`].
+#[derive(Debug, Deserialize, Serialize)]
+pub struct EditClip {
+    pub content: field::Content,
+    pub title: field::Title,
+    pub expires: field::Expires,
+    pub password: field::Password
+}
+
+impl EditClip {
+    pub fn into_update(self, shortcode: field::ShortCode, requester: Requester) -> UpdateClip {
+        UpdateClip {
+            content: self.content,
+            title: self.title,
+            expires: self.expires,
+            password: self.password,
+            shortcode,
+            requester,
+            revisions: None
+        }
+    }
+}
+
+/// The scopes of an API key to mint. Keys minted without any get all the `clip` scopes.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct NewApiKey {
+    #[serde(default)]
+    pub scopes: Vec<Scope>
+}
+
+/// A webhook to register for the API key of the request, sent to `POST /api/v1/webhooks`.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct NewWebhook {
+    pub url: String,
+    pub events: Vec<WebhookEvent>
+}
+
+/// Clips read from an export, along with their record numbers, to import `batch_size` at a
+/// time. Their shortcodes are only kept when asked to.
+#[derive(Debug)]
+pub struct ImportClips {
+    pub clips: Vec<(usize, ImportedClip)>,
+    pub keep_shortcodes: bool,
+    pub on_conflict: ConflictPolicy,
+    pub batch_size: usize
+}
+
+/// A page of the clips to back up, all of them or only those created with `owner`, in the
+/// order of their ids and starting after the clip id `after`.
+#[derive(Debug)]
+pub struct ExportClips {
+    pub owner: Option<ApiKey>,
+    pub after: Option<String>,
+    pub limit: i64
+}
+
+/// A full-text search over the clips created with the `owner` API key.
+#[derive(Debug)]
+pub struct SearchClips {
+    pub query: String,
+    pub offset: u32,
+    pub limit: u32,
+    pub owner: ApiKey
+}
+
+impl SearchClips {
+    pub const DEFAULT_LIMIT: u32 = 20;
+    pub const MAX_LIMIT: u32 = 100;
+
+    pub fn new(
+        query: &str,
+        offset: Option<u32>,
+        limit: Option<u32>,
+        owner: ApiKey
+    ) -> Result<Self, ClipError> {
+        let query = query.trim();
+        if query.is_empty() {
+            return Err(ClipError::InvalidSearch("empty query".to_owned()));
+        }
+        let limit = limit.unwrap_or(Self::DEFAULT_LIMIT);
+        if limit == 0 || limit > Self::MAX_LIMIT {
+            return Err(ClipError::InvalidLimit(Self::MAX_LIMIT));
+        }
+        Ok(Self {
+            query: query.to_owned(),
+            offset: offset.unwrap_or_default(),
+            limit,
+            owner
+        })
+    }
+}
+
+/// A page of the clips created with the `owner` API key, newest first.
+#[derive(Debug)]
+pub struct ListClips {
+    pub cursor: Option<ClipCursor>,
+    pub limit: u32,
+    pub owner: ApiKey
+}
+
+impl ListClips {
+    pub const DEFAULT_LIMIT: u32 = 20;
+    pub const MAX_LIMIT: u32 = 100;
+
+    pub fn new(cursor: Option<&str>, limit: Option<u32>, owner: ApiKey) -> Result<Self, ClipError> {
+        let cursor = cursor.map(str::parse).transpose()?;
+        let limit = limit.unwrap_or(Self::DEFAULT_LIMIT);
+        if limit == 0 || limit > Self::MAX_LIMIT {
+            return Err(ClipError::InvalidLimit(Self::MAX_LIMIT));
+        }
+        Ok(Self { cursor, limit, owner })
+    }
+}
+
+/// A page of the clips created by a user, newest first.
+#[derive(Debug)]
+pub struct ListUserClips {
+    pub cursor: Option<ClipCursor>,
+    pub limit: u32,
+    pub user_id: UserId
+}
+
+/// A page of the clips of a workspace, newest first.
+#[derive(Debug)]
+pub struct ListWorkspaceClips {
+    pub cursor: Option<ClipCursor>,
+    pub limit: u32,
+    pub workspace_id: WorkspaceId
+}
+
+#[derive(Debug)]
+pub struct Register {
+    pub username: Username,
+    pub password: UserPassword
+}
+
+#[derive(Debug)]
+pub struct Login {
+    pub username: String,
+    pub password: String
+}
@@ -1,6 +1,9 @@
 pub mod ask;
 pub mod action;
 
+use crate::domain::user::UserError;
+use crate::domain::webhook::WebhookError;
+use crate::domain::workspace::WorkspaceError;
 use crate::{ClipError, DataError};
 
 /// Implement general error types and the From<T> trait.
@@ -8,10 +11,20 @@ use crate::{ClipError, DataError};
 pub enum ServiceError {
     #[error("clip error: {0}")]
     Clip(#[from] ClipError),
+    #[error("user error: {0}")]
+    User(#[from] UserError),
+    #[error("workspace error: {0}")]
+    Workspace(#[from] WorkspaceError),
+    #[error("webhook error: {0}")]
+    Webhook(#[from] WebhookError),
     #[error("database error: {0}")]
     Data(DataError),
     #[error("not found")]
     NotFound,
+    #[error("clip was deleted")]
+    Gone,
+    #[error("clip was edited since it was read")]
+    PreconditionFailed,
     #[error("permissions not met")]
     PermissionError(String)
 }
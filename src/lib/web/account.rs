@@ -0,0 +1,406 @@
+use crate::data::AppDatabase;
+use crate::domain::scope::Scope;
+use crate::domain::user::UserError;
+use crate::service::{action, ask};
+use crate::web::events::ClipEvents;
+use crate::web::request_id::RequestId;
+use crate::web::session::{self, CurrentUser};
+use crate::web::sso::Sso;
+use crate::web::{ctx, form, form_errors, renderer::Renderer, PageError};
+use crate::{ServiceError, ShortCode};
+use rocket::form::{Contextual, Form};
+use rocket::http::{CookieJar, Status};
+use rocket::response::content::RawHtml;
+use rocket::response::Redirect;
+use rocket::{uri, State};
+
+/// Renders the dashboard of a user, optionally showing an API key which was just minted.
+pub(crate) async fn render_dashboard(
+    user: CurrentUser,
+    cursor: Option<&str>,
+    new_api_key: Option<String>,
+    database: &AppDatabase,
+    renderer: &Renderer<'_>,
+    errors: &[&str]
+) -> Result<RawHtml<String>, PageError> {
+    let CurrentUser(user) = user;
+    let req = ask::ListUserClips {
+        cursor: cursor.and_then(|cursor| cursor.parse().ok()),
+        limit: ask::ListClips::DEFAULT_LIMIT,
+        user_id: user.user_id.clone()
+    };
+    let clips = action::list_user_clips(req, database.get_pool()).await?;
+    let api_keys = action::list_user_api_keys(&user.user_id, database.get_pool()).await?;
+    let workspaces = action::list_user_workspaces(&user.user_id, database.get_pool()).await?;
+    let context = ctx::Dashboard::new(user.username, clips, api_keys, new_api_key, workspaces);
+    Ok(RawHtml(renderer.render(context, errors)?))
+}
+
+/// Page to create an account.
+#[rocket::get("/register")]
+pub fn register_page(
+    user: Option<CurrentUser>,
+    renderer: &State<Renderer<'_>>
+) -> Result<Result<RawHtml<String>, Redirect>, PageError> {
+    if user.is_some() {
+        return Ok(Err(Redirect::to(uri!(dashboard(_)))));
+    }
+    Ok(Ok(RawHtml(renderer.render(ctx::Register::default(), &[])?)))
+}
+
+/// Creates an account using form data and logs the new user in.
+#[rocket::post("/register", data = "<form>")]
+#[tracing::instrument(skip_all, fields(request_id = %request_id))]
+pub async fn register(
+    request_id: &RequestId,
+    cookies: &CookieJar<'_>,
+    form: Form<Contextual<'_, form::Register>>,
+    database: &State<AppDatabase>,
+    renderer: &State<Renderer<'_>>
+) -> Result<Result<Redirect, (Status, RawHtml<String>)>, PageError> {
+    let form = form.into_inner();
+    let value = match form.value {
+        Some(ref value) if value.password_matches() => value,
+        Some(_) => {
+            let html = renderer.render_with_data(
+                ctx::Register::default(),
+                ("user", &form.context),
+                &["The passwords don't match"]
+            )?;
+            return Ok(Err((Status::BadRequest, RawHtml(html))));
+        },
+        None => {
+            let errors = form_errors(&form);
+            let html = renderer.render_with_data(ctx::Register::default(), ("user", &form.context), &errors)?;
+            return Ok(Err((Status::BadRequest, RawHtml(html))));
+        }
+    };
+
+    let req = ask::Register {
+        username: value.username.clone(),
+        password: value.password.clone()
+    };
+    match action::register(req, database.get_pool()).await {
+        Ok(user) => {
+            tracing::info!("user registered");
+            session::start_session(cookies, &user, database).await?;
+            Ok(Ok(Redirect::to(uri!(dashboard(_)))))
+        },
+        Err(ServiceError::User(UserError::UsernameTaken)) => {
+            let html = renderer.render_with_data(
+                ctx::Register::default(),
+                ("user", &form.context),
+                &["This username is already taken"]
+            )?;
+            Ok(Err((Status::Conflict, RawHtml(html))))
+        },
+        Err(e) => Err(e.into())
+    }
+}
+
+/// Page to log in.
+#[rocket::get("/login")]
+pub fn login_page(
+    user: Option<CurrentUser>,
+    sso: &State<Sso>,
+    renderer: &State<Renderer<'_>>
+) -> Result<Result<RawHtml<String>, Redirect>, PageError> {
+    if user.is_some() {
+        return Ok(Err(Redirect::to(uri!(dashboard(_)))));
+    }
+    Ok(Ok(RawHtml(renderer.render(ctx::Login::new(sso.is_enabled()), &[])?)))
+}
+
+/// Logs a user in using form data.
+#[rocket::post("/login", data = "<form>")]
+#[tracing::instrument(skip_all, fields(request_id = %request_id))]
+pub async fn login(
+    request_id: &RequestId,
+    cookies: &CookieJar<'_>,
+    form: Form<Contextual<'_, form::Login>>,
+    sso: &State<Sso>,
+    database: &State<AppDatabase>,
+    renderer: &State<Renderer<'_>>
+) -> Result<Result<Redirect, (Status, RawHtml<String>)>, PageError> {
+    let form = form.into_inner();
+    let value = match form.value {
+        Some(ref value) => value,
+        None => {
+            let errors = form_errors(&form);
+            let html = renderer.render_with_data(ctx::Login::new(sso.is_enabled()), ("user", &form.context), &errors)?;
+            return Ok(Err((Status::BadRequest, RawHtml(html))));
+        }
+    };
+
+    let req = ask::Login {
+        username: value.username.clone(),
+        password: value.password.clone()
+    };
+    match action::login(req, database.get_pool()).await {
+        Ok(user) => {
+            session::start_session(cookies, &user, database).await?;
+            Ok(Ok(Redirect::to(uri!(dashboard(_)))))
+        },
+        Err(ServiceError::PermissionError(msg)) => {
+            let html = renderer.render_with_data(ctx::Login::new(sso.is_enabled()), ("user", &form.context), &[msg.as_str()])?;
+            Ok(Err((Status::Unauthorized, RawHtml(html))))
+        },
+        Err(e) => Err(e.into())
+    }
+}
+
+/// Logs the user out.
+#[rocket::post("/logout")]
+pub async fn logout(
+    cookies: &CookieJar<'_>,
+    database: &State<AppDatabase>
+) -> Result<Redirect, PageError> {
+    session::end_session(cookies, database).await?;
+    Ok(Redirect::to("/"))
+}
+
+/// Lists the clips and API keys of the logged in user.
+#[rocket::get("/dashboard?<cursor>")]
+pub async fn dashboard(
+    user: CurrentUser,
+    cursor: Option<&str>,
+    database: &State<AppDatabase>,
+    renderer: &State<Renderer<'_>>
+) -> Result<RawHtml<String>, PageError> {
+    render_dashboard(user, cursor, None, database, renderer, &[]).await
+}
+
+/// Mints an API key for the logged in user with the ticked scopes, or all the `clip` scopes when
+/// no form is sent. The key is only shown on the returned page.
+#[rocket::post("/dashboard/key", data = "<form>")]
+#[tracing::instrument(skip_all, fields(request_id = %request_id))]
+pub async fn new_api_key(
+    request_id: &RequestId,
+    user: CurrentUser,
+    form: Option<Form<form::NewApiKey>>,
+    database: &State<AppDatabase>,
+    renderer: &State<Renderer<'_>>
+) -> Result<(Status, RawHtml<String>), PageError> {
+    let scopes = form.map_or_else(|| Scope::CLIPS.to_vec(), |form| form.into_inner().scopes);
+    match action::generate_user_api_key(&user.0.user_id, &scopes, database.get_pool()).await {
+        Ok(api_key) => {
+            tracing::info!("API key generated");
+            let html = render_dashboard(user, None, Some(api_key.to_base64()), database, renderer, &[]).await?;
+            Ok((Status::Ok, html))
+        },
+        Err(ServiceError::PermissionError(msg)) => {
+            let html = render_dashboard(user, None, None, database, renderer, &[msg.as_str()]).await?;
+            Ok((Status::BadRequest, html))
+        },
+        Err(e) => Err(e.into())
+    }
+}
+
+/// Revokes an API key of the logged in user.
+#[rocket::post("/dashboard/key/<fingerprint>/revoke")]
+#[tracing::instrument(skip_all, fields(request_id = %request_id))]
+pub async fn revoke_api_key(
+    request_id: &RequestId,
+    user: CurrentUser,
+    fingerprint: &str,
+    database: &State<AppDatabase>
+) -> Result<Redirect, PageError> {
+    action::revoke_user_api_key(&user.0.user_id, fingerprint, database.get_pool()).await?;
+    Ok(Redirect::to(uri!(dashboard(_))))
+}
+
+/// Page to edit a clip of the logged in user.
+#[rocket::get("/dashboard/clip/<shortcode>")]
+pub async fn edit_clip_page(
+    user: CurrentUser,
+    shortcode: ShortCode,
+    database: &State<AppDatabase>,
+    renderer: &State<Renderer<'_>>
+) -> Result<RawHtml<String>, PageError> {
+    let clip = action::get_user_clip(shortcode, &user.0.user_id, database.get_pool()).await?;
+    Ok(RawHtml(renderer.render(ctx::EditClip::new(clip), &[])?))
+}
+
+/// Updates a clip of the logged in user using form data.
+#[rocket::post("/dashboard/clip/<shortcode>", data = "<form>")]
+#[tracing::instrument(skip_all, fields(request_id = %request_id, shortcode = shortcode.as_str()))]
+pub async fn edit_clip(
+    request_id: &RequestId,
+    user: CurrentUser,
+    shortcode: ShortCode,
+    form: Form<Contextual<'_, form::NewClip>>,
+    database: &State<AppDatabase>,
+    renderer: &State<Renderer<'_>>,
+    events: &State<ClipEvents>
+) -> Result<Result<Redirect, (Status, RawHtml<String>)>, PageError> {
+    let form = form.into_inner();
+    let value = match form.value {
+        Some(value) => value,
+        None => {
+            let clip = action::get_user_clip(shortcode, &user.0.user_id, database.get_pool()).await?;
+            let errors = form_errors(&form);
+            let html = renderer.render(ctx::EditClip::new(clip), &errors)?;
+            return Ok(Err((Status::BadRequest, RawHtml(html))));
+        }
+    };
+
+    let req = ask::UpdateClip {
+        shortcode,
+        content: value.content,
+        title: value.title,
+        expires: value.expires,
+        password: value.password,
+        requester: ask::Requester::default(),
+        revisions: None
+    };
+    let clip = action::update_user_clip(req, &user.0.user_id, database.get_pool()).await?;
+    events.updated(clip.shortcode);
+    Ok(Ok(Redirect::to(uri!(dashboard(_)))))
+}
+
+/// Mints a token to edit a clip of the logged in user together, and opens the clip for editing.
+/// The link of the page lets others edit it too.
+#[rocket::post("/dashboard/clip/<shortcode>/share")]
+#[tracing::instrument(skip_all, fields(request_id = %request_id, shortcode = shortcode.as_str()))]
+pub async fn share_clip(
+    request_id: &RequestId,
+    user: CurrentUser,
+    shortcode: ShortCode,
+    database: &State<AppDatabase>
+) -> Result<Redirect, PageError> {
+    let token = action::new_user_edit_token(shortcode.clone(), &user.0.user_id, database.get_pool()).await?;
+    Ok(Redirect::to(format!("/clip/{}#edit={}", shortcode.as_str(), token.to_base64())))
+}
+
+/// Deletes a clip of the logged in user. The clip can be restored until it is purged.
+#[rocket::post("/dashboard/clip/<shortcode>/delete")]
+#[tracing::instrument(skip_all, fields(request_id = %request_id, shortcode = shortcode.as_str()))]
+pub async fn delete_clip(
+    request_id: &RequestId,
+    user: CurrentUser,
+    shortcode: ShortCode,
+    database: &State<AppDatabase>,
+    events: &State<ClipEvents>
+) -> Result<Redirect, PageError> {
+    action::delete_user_clip(shortcode.clone(), &user.0.user_id, database.get_pool()).await?;
+    events.updated(shortcode);
+    Ok(Redirect::to(uri!(dashboard(_))))
+}
+
+/// The URI [`routes`](rocket::Route) which can be mounted by [`rocket`].
+pub fn routes() -> Vec<rocket::Route> {
+    rocket::routes![
+        register_page,
+        register,
+        login_page,
+        login,
+        logout,
+        dashboard,
+        new_api_key,
+        revoke_api_key,
+        edit_clip_page,
+        edit_clip,
+        share_clip,
+        delete_clip
+    ]
+}
+
+pub mod catcher {
+    //! Sends anonymous users of the dashboard to the login page.
+    use rocket::response::Redirect;
+    use rocket::{catch, catchers, Catcher};
+
+    #[catch(401)]
+    fn login_required() -> Redirect {
+        Redirect::to("/login")
+    }
+
+    /// The [`catchers`](rocket::Catcher) which can be registered by [`rocket`].
+    pub fn catchers() -> Vec<Catcher> {
+        catchers![login_required]
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use crate::web::test::client;
+    use rocket::http::{ContentType, Status};
+
+    fn username() -> String {
+        format!("user-{}", &uuid::Uuid::new_v4().to_string()[..8])
+    }
+
+    #[test]
+    fn register_login_and_logout() {
+        let client = client();
+        let username = username();
+
+        let response = client.get("/dashboard").dispatch();
+        assert_eq!(response.status(), Status::SeeOther);
+        assert_eq!(response.headers().get_one("Location"), Some("/login"));
+
+        let response = client
+            .post("/register")
+            .header(ContentType::Form)
+            .body(format!("username={}&password=password123&confirm_password=password123", username))
+            .dispatch();
+        assert_eq!(response.status(), Status::SeeOther);
+
+        let response = client.get("/dashboard").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert!(response.into_string().unwrap().contains(&username));
+
+        let response = client.post("/logout").dispatch();
+        assert_eq!(response.status(), Status::SeeOther);
+        let response = client.get("/dashboard").dispatch();
+        assert_eq!(response.status(), Status::SeeOther);
+
+        let response = client
+            .post("/login")
+            .header(ContentType::Form)
+            .body(format!("username={}&password=wrong-password", username))
+            .dispatch();
+        assert_eq!(response.status(), Status::Unauthorized);
+
+        let response = client
+            .post("/login")
+            .header(ContentType::Form)
+            .body(format!("username={}&password=password123", username))
+            .dispatch();
+        assert_eq!(response.status(), Status::SeeOther);
+        let response = client.get("/dashboard").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+    }
+
+    #[test]
+    fn lists_own_clips_and_api_keys() {
+        let client = client();
+        let username = username();
+        client
+            .post("/register")
+            .header(ContentType::Form)
+            .body(format!("username={}&password=password123&confirm_password=password123", username))
+            .dispatch();
+
+        let response = client
+            .post("/")
+            .header(ContentType::Form)
+            .body("content=my+own+clip&title=mine&expires=&password=")
+            .dispatch();
+        assert_eq!(response.status(), Status::SeeOther);
+        let location = response.headers().get_one("Location").unwrap().to_owned();
+        let shortcode = location.trim_start_matches("/clip/");
+
+        let response = client.get("/dashboard").dispatch();
+        assert!(response.into_string().unwrap().contains(shortcode));
+
+        let response = client.post("/dashboard/key").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert!(response.into_string().unwrap().contains("new-api-key"));
+
+        let response = client.post(format!("/dashboard/clip/{}/delete", shortcode)).dispatch();
+        assert_eq!(response.status(), Status::SeeOther);
+        let response = client.get("/dashboard").dispatch();
+        assert!(!response.into_string().unwrap().contains(shortcode));
+    }
+}
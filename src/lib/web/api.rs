@@ -1,15 +1,34 @@
 use crate::data::AppDatabase;
 use crate::service;
 use crate::service::action;
-use crate::web::{HitCounter, PASSWORD_COOKIE};
+use crate::service::ask::Requester;
+use crate::domain::backup;
+use crate::domain::clip::field::Password;
+use crate::domain::clip::{ClipError, ClipList, SearchResults};
+use crate::domain::scope::Scope;
+use crate::domain::stats::{ClipStats, ViewRoute};
+use crate::domain::user::UserError;
+use crate::domain::webhook::{Delivery, RegisteredWebhook, Webhook, WebhookDispatcher, WebhookError};
+use crate::domain::workspace::WorkspaceError;
+use crate::web::cache::{Audience, Cached, Preconditions, Validators};
+use crate::web::events::ClipEvents;
+use crate::web::hitcounter::Viewer;
+use crate::web::request_id::RequestId;
+use crate::web::HitCounter;
 use crate::ServiceError;
-use rocket::http::{CookieJar, Status};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::futures::stream::{BoxStream, StreamExt};
+use rocket::http::{ContentType, Status};
+use rocket::outcome::try_outcome;
 use rocket::request::{FromRequest, Outcome};
-use rocket::serde::json::Json;
-use rocket::{Request, Responder};
-use rocket::State;
-use serde::Serialize;
+use rocket::serde::json::{self, Json};
+use rocket::response::stream::TextStream;
+use rocket::response::Responder;
+use rocket::{Request, Response, State};
+use serde::{Deserialize, Serialize};
+use std::marker::PhantomData;
 use std::str::FromStr;
+use crate::redact::redacted_debug;
 
 
 /// Define API endpoints, error types, catchers and rocket async traits. These routes can
@@ -18,38 +37,204 @@ use std::str::FromStr;
 
 pub const API_KEY_HEADER: &str = "x-api-key";
 
-#[derive(Responder, Debug, thiserror::Error, Serialize)]
+/// The header carrying the password of a password-protected clip. Cookies are left to the
+/// browser sessions of the web UI.
+pub const CLIP_PASSWORD_HEADER: &str = "x-clip-password";
+
+/// The base of the current version of the API.
+pub const API_BASE: &str = "/api/v1";
+
+/// The base of the unversioned routes, kept as deprecated aliases of the `/api/v1` ones.
+pub const LEGACY_BASE: &str = "/api/clip";
+
+/// The realm of the `WWW-Authenticate` challenges sent when an API key is missing or refused.
+pub const AUTH_REALM: &str = "clipstash";
+
+/// Query parameters which look like credentials. Query strings end up in logs and browser
+/// history, so requests with any of these are refused even if they also carry a valid key.
+const QUERY_KEY_PARAMS: [&str; 4] = ["access_token", "api_key", "apikey", API_KEY_HEADER];
+
+#[derive(Clone, Debug, thiserror::Error)]
 pub enum ApiKeyError {
+    #[error("API key missing")]
+    Missing(String),
     #[error("API key not found")]
-    #[response(status = 404, content_type = "json")]
     NotFound(String),
     #[error("Invalid API key format")]
-    #[response(status = 400, content_type = "json")]
-    DecodeError(String)
+    DecodeError(String),
+    #[error("API key in the query string")]
+    InQueryString(String)
+}
+
+impl ApiKeyError {
+    pub fn message(&self) -> &str {
+        match self {
+            Self::Missing(msg) | Self::NotFound(msg) | Self::DecodeError(msg) | Self::InQueryString(msg) => msg
+        }
+    }
+
+    /// The `WWW-Authenticate` challenge telling the client how to authenticate, as in RFC 6750.
+    pub fn challenge(&self) -> String {
+        match self {
+            Self::Missing(_) => format!(r#"Bearer realm="{}""#, AUTH_REALM),
+            Self::NotFound(_) | Self::DecodeError(_) => {
+                format!(r#"Bearer realm="{}", error="invalid_token""#, AUTH_REALM)
+            },
+            Self::InQueryString(_) => format!(r#"Bearer realm="{}", error="invalid_request""#, AUTH_REALM)
+        }
+    }
 }
 
-#[derive(Responder, Debug, thiserror::Error)]
-pub enum ApiError {
-    #[error("not found")]
-    #[response(status = 404, content_type = "json")]
-    NotFound(Json<String>),
+/// What went wrong with an API request. Clients can branch on it, and it decides the status of
+/// the response.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// 400: the request is malformed.
+    BadRequest,
+    /// 401: the API key is missing or invalid.
+    Unauthorized,
+    /// 403: the API key may not do this, or the password of the clip is wrong.
+    PermissionDenied,
+    /// 404: the clip or route doesn't exist.
+    NotFound,
+    /// 409: the request conflicts with existing data.
+    Conflict,
+    /// 410: the clip was deleted or has expired.
+    Gone,
+    /// 412: the clip was edited since the revision named by `If-Match`.
+    PreconditionFailed,
+    /// 422: a field of the request is invalid.
+    ValidationFailed,
+    /// 429: too many requests were made.
+    RateLimited,
+    /// 500: something went wrong on the server.
+    ServerError
+}
+
+impl ErrorCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::BadRequest => "bad_request",
+            Self::Unauthorized => "unauthorized",
+            Self::PermissionDenied => "permission_denied",
+            Self::NotFound => "not_found",
+            Self::Conflict => "conflict",
+            Self::Gone => "gone",
+            Self::PreconditionFailed => "precondition_failed",
+            Self::ValidationFailed => "validation_failed",
+            Self::RateLimited => "rate_limited",
+            Self::ServerError => "server_error"
+        }
+    }
 
-    #[error("server error")]
-    #[response(status = 500, content_type = "json")]
-    Server(Json<String>),
+    pub fn status(&self) -> Status {
+        match self {
+            Self::BadRequest => Status::BadRequest,
+            Self::Unauthorized => Status::Unauthorized,
+            Self::PermissionDenied => Status::Forbidden,
+            Self::NotFound => Status::NotFound,
+            Self::Conflict => Status::Conflict,
+            Self::Gone => Status::Gone,
+            Self::PreconditionFailed => Status::PreconditionFailed,
+            Self::ValidationFailed => Status::UnprocessableEntity,
+            Self::RateLimited => Status::TooManyRequests,
+            Self::ServerError => Status::InternalServerError
+        }
+    }
 
-    #[error("client error")]
-    #[response(status = 401, content_type = "json")]
-    User(Json<String>),
+    /// The code for a status which has no code of its own.
+    pub fn from_status(status: Status) -> Self {
+        match status.code {
+            401 => Self::Unauthorized,
+            403 => Self::PermissionDenied,
+            404 => Self::NotFound,
+            409 => Self::Conflict,
+            410 => Self::Gone,
+            412 => Self::PreconditionFailed,
+            422 => Self::ValidationFailed,
+            429 => Self::RateLimited,
+            400..=499 => Self::BadRequest,
+            _ => Self::ServerError
+        }
+    }
+}
+
+impl std::fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// An error of the API. Every error, whether it comes from a route or a catcher, is sent in the
+/// same envelope:
+///
+/// ```json
+/// { "error": { "code": "validation_failed", "message": "empty content", "field": "content" } }
+/// ```
+///
+/// `code` is an [`ErrorCode`], `message` is meant for humans and may change, and `field` names
+/// the invalid field of the request, when there is one.
+#[derive(Clone, Debug, thiserror::Error, Deserialize, Serialize)]
+#[error("{message}")]
+pub struct ApiError {
+    pub code: ErrorCode,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>
+}
+
+/// The envelope every [`ApiError`] is sent in.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ErrorBody {
+    pub error: ApiError
+}
+
+impl ApiError {
+    pub fn new<M: Into<String>>(code: ErrorCode, message: M) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            field: None
+        }
+    }
+
+    /// A `validation_failed` error about a field of the request.
+    pub fn invalid_field<M: Into<String>>(field: &str, message: M) -> Self {
+        Self {
+            field: Some(field.to_owned()),
+            ..Self::new(ErrorCode::ValidationFailed, message)
+        }
+    }
+
+    pub fn server_error() -> Self {
+        Self::new(ErrorCode::ServerError, "a server error occurred")
+    }
+}
+
+impl<'r> Responder<'r, 'static> for ApiError {
+    fn respond_to(self, req: &'r Request<'_>) -> rocket::response::Result<'static> {
+        let status = self.code.status();
+        let mut response = Json(ErrorBody { error: self }).respond_to(req)?;
+        response.set_status(status);
+        Ok(response)
+    }
+}
 
-    #[error("key error")]
-    #[response(status = 400, content_type = "json")]
-    KeyError(Json<ApiKeyError>)
+impl From<ApiKeyError> for ApiError {
+    fn from(err: ApiKeyError) -> Self {
+        match err {
+            ApiKeyError::InQueryString(msg) => Self::new(ErrorCode::BadRequest, msg),
+            other => Self::new(ErrorCode::Unauthorized, other.message())
+        }
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct ApiKey(Vec<u8>);
 
+redacted_debug!(ApiKey);
+
 impl ApiKey {
     pub fn to_base64(&self) -> String {
         base64::encode(self.0.as_slice())
@@ -75,37 +260,111 @@ impl FromStr for ApiKey {
     }
 }
 
+impl From<ClipError> for ApiError {
+    fn from(err: ClipError) -> Self {
+        let field = match &err {
+            ClipError::InvalidPassword(_) => "password",
+            ClipError::InvalidTitle(_) => "title",
+            ClipError::EmptyContent => "content",
+            ClipError::InvalidDate(_) | ClipError::DateParse(_) => "expires",
+            ClipError::InvalidSearch(_) => "q",
+            ClipError::InvalidCursor => "cursor",
+            ClipError::InvalidLimit(_) => "limit",
+            ClipError::InvalidEditToken => "edit_token",
+            ClipError::Id(_) | ClipError::Hits(_) | ClipError::InvalidView(_) | ClipError::PasswordHash(_) => {
+                // These come from the database rather than the request.
+                tracing::error!(error = %err, "invalid clip data");
+                return Self::server_error();
+            }
+        };
+        Self::invalid_field(field, err.to_string())
+    }
+}
+
 impl From<ServiceError> for ApiError {
     fn from(err: ServiceError) -> Self {
         match err {
-            ServiceError::Clip(c) => Self::User(Json(format!("clip parsing error: {:?}", c))),
-            ServiceError::NotFound => Self::User(Json("entity not found".to_owned())),
-            ServiceError::Data(_) => Self::User(Json("a server error occurred".to_owned())),
-            ServiceError::PermissionError(msg) => Self::User(Json(msg))
+            ServiceError::Clip(c) => c.into(),
+            ServiceError::User(u) => match u {
+                UserError::UsernameTaken => Self::new(ErrorCode::Conflict, u.to_string()),
+                UserError::InvalidUsername(_) => Self::invalid_field("username", u.to_string()),
+                UserError::InvalidPassword(_) => Self::invalid_field("password", u.to_string()),
+                UserError::InvalidSession => Self::new(ErrorCode::Unauthorized, u.to_string()),
+                UserError::Hash(_) | UserError::Id(_) => {
+                    tracing::error!(error = %u, "user error");
+                    Self::server_error()
+                }
+            },
+            ServiceError::Workspace(w) => match w {
+                WorkspaceError::InvalidName(_) => Self::invalid_field("name", w.to_string()),
+                WorkspaceError::InvalidRole(_) => Self::invalid_field("role", w.to_string()),
+                WorkspaceError::LoginRequired => Self::new(ErrorCode::Unauthorized, w.to_string()),
+                WorkspaceError::NotAnOwner => Self::new(ErrorCode::PermissionDenied, w.to_string()),
+                WorkspaceError::Id(_) => Self::new(ErrorCode::NotFound, "workspace not found")
+            },
+            ServiceError::Webhook(w) => match w {
+                WebhookError::InvalidUrl(_) => Self::invalid_field("url", w.to_string()),
+                WebhookError::InvalidEvent(_) | WebhookError::NoEvents => Self::invalid_field("events", w.to_string()),
+                WebhookError::NotFound => Self::new(ErrorCode::NotFound, w.to_string())
+            },
+            ServiceError::NotFound => Self::new(ErrorCode::NotFound, "clip not found"),
+            ServiceError::Gone => Self::new(ErrorCode::Gone, "clip was deleted"),
+            ServiceError::PreconditionFailed => Self::new(ErrorCode::PreconditionFailed, err.to_string()),
+            ServiceError::Data(e) => {
+                tracing::error!(error = %e, "database error");
+                Self::server_error()
+            },
+            ServiceError::PermissionError(msg) => Self::new(ErrorCode::PermissionDenied, msg)
         }
     }
 }
 
+/// Reads the API key of a request from the `x-api-key` header, or else from an
+/// `Authorization: Bearer` header.
+fn credentials<'a>(req: &'a Request<'_>) -> Option<&'a str> {
+    req.headers().get_one(API_KEY_HEADER).or_else(|| {
+        let (scheme, token) = req.headers().get_one("Authorization")?.split_once(' ')?;
+        if scheme.eq_ignore_ascii_case("bearer") {
+            Some(token.trim())
+        } else {
+            None
+        }
+    })
+}
+
+/// Whether the query string of a request carries something which looks like an API key.
+fn key_in_query_string(req: &Request<'_>) -> bool {
+    req.uri()
+        .query()
+        .map(|query| query.segments().any(|(name, _)| {
+            QUERY_KEY_PARAMS.iter().any(|param| name.eq_ignore_ascii_case(param))
+        }))
+        .unwrap_or(false)
+}
+
 #[rocket::async_trait]
 impl<'r> FromRequest<'r> for ApiKey {
     type Error = ApiError;
     
     async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
         fn server_error() -> Outcome<ApiKey, ApiError> {
-            Outcome::Failure((
-                Status::InternalServerError,
-                ApiError::Server(Json("server error".to_string()))
-            ))
+            Outcome::Error((Status::InternalServerError, ApiError::server_error()))
         }
-        fn key_error(e: ApiKeyError) -> Outcome<ApiKey, ApiError> {
-            Outcome::Failure((
-                Status::BadRequest,
-                ApiError::KeyError(Json(e))
-            ))
+        fn key_error(req: &Request<'_>, e: ApiKeyError) -> Outcome<ApiKey, ApiError> {
+            // The catchers answer with the challenge of the failure, so keep it for them.
+            req.local_cache(|| KeyFailure(Some(e.clone())));
+            let err = ApiError::from(e);
+            Outcome::Error((err.code.status(), err))
         }
 
-        match req.headers().get_one(API_KEY_HEADER) {
-            None => key_error(ApiKeyError::NotFound("API key not found".to_string())),
+        if key_in_query_string(req) {
+            return key_error(req, ApiKeyError::InQueryString(
+                "API keys must be sent in a header, not in the query string".to_owned()
+            ));
+        }
+
+        match credentials(req) {
+            None => key_error(req, ApiKeyError::Missing("API key missing".to_string())),
             Some(key) => {
                 let db = match req.guard::<&State<AppDatabase>>().await {
                     Outcome::Success(db) => db,
@@ -114,126 +373,1446 @@ impl<'r> FromRequest<'r> for ApiKey {
 
                 let api_key = match ApiKey::from_str(key) {
                     Ok(key) => key,
-                    Err(e) => return key_error(e)
+                    Err(e) => return key_error(req, e)
                 };
 
-                match action::api_key_is_valid(api_key.clone(), db.get_pool()).await {
-                    Ok(valid) if valid => Outcome::Success(api_key),
-                    Ok(valid) if !valid => {
-                        key_error(ApiKeyError::NotFound("API key not found".to_owned()))
+                match action::api_key_scopes(api_key.clone(), db.get_pool()).await {
+                    Ok(Some(scopes)) => {
+                        req.local_cache(|| ApiKeyScopes(scopes));
+                        Outcome::Success(api_key)
                     },
-                    _ => server_error()
+                    Ok(None) => key_error(req, ApiKeyError::NotFound("API key not found".to_owned())),
+                    Err(_) => server_error()
                 }
             }
         }
     }
 }
 
-/// Endpoint to generate an API key.
-#[rocket::get("/key")]
-pub async fn new_api_key(database: &State<AppDatabase>) -> Result<Json<&str>, ApiError> {
-    let api_key = action::generate_api_key(database.get_pool()).await?;
-    println!("API key: {}", api_key.to_base64());
-    Ok(Json("API key generated. See logs for details."))
+/// Why the [`ApiKey`] of a request was refused, read by the catchers.
+struct KeyFailure(Option<ApiKeyError>);
+
+/// The scopes of the [`ApiKey`] of a request, cached by its guard.
+struct ApiKeyScopes(Vec<Scope>);
+
+/// The scope a request was refused for, read by the 403 catcher.
+struct MissingScope(Option<Scope>);
+
+/// A [`Scope`] which a route can require through [`RequireScope`].
+pub trait RequiredScope: Send + Sync + 'static {
+    const SCOPE: Scope;
+}
+
+/// Requires the `clip:read` scope.
+pub struct ClipRead;
+
+impl RequiredScope for ClipRead {
+    const SCOPE: Scope = Scope::ClipRead;
+}
+
+/// Requires the `clip:write` scope.
+pub struct ClipWrite;
+
+impl RequiredScope for ClipWrite {
+    const SCOPE: Scope = Scope::ClipWrite;
+}
+
+/// Requires the `clip:delete` scope.
+pub struct ClipDelete;
+
+impl RequiredScope for ClipDelete {
+    const SCOPE: Scope = Scope::ClipDelete;
+}
+
+/// Requires the `admin` scope.
+pub struct Admin;
+
+impl RequiredScope for Admin {
+    const SCOPE: Scope = Scope::Admin;
+}
+
+/// Request guard for the password sent in the [`CLIP_PASSWORD_HEADER`], if any.
+pub struct ClipPassword(Password);
+
+impl ClipPassword {
+    pub fn into_inner(self) -> Password {
+        self.0
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ClipPassword {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let password = req
+            .headers()
+            .get_one(CLIP_PASSWORD_HEADER)
+            .and_then(|password| Password::new(password.to_owned()).ok())
+            .unwrap_or_default();
+        Outcome::Success(Self(password))
+    }
+}
+
+/// Request guard for a valid [`ApiKey`] which was granted the scope `S`. Other keys are refused
+/// with a 403.
+pub struct RequireScope<S>(ApiKey, PhantomData<S>);
+
+impl<S> RequireScope<S> {
+    pub fn into_key(self) -> ApiKey {
+        self.0
+    }
+}
+
+#[rocket::async_trait]
+impl<'r, S: RequiredScope> FromRequest<'r> for RequireScope<S> {
+    type Error = ApiError;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let api_key = try_outcome!(req.guard::<ApiKey>().await);
+        let ApiKeyScopes(scopes) = req.local_cache(|| ApiKeyScopes(vec![]));
+        if scopes.contains(&S::SCOPE) {
+            Outcome::Success(Self(api_key, PhantomData))
+        } else {
+            req.local_cache(|| MissingScope(Some(S::SCOPE)));
+            Outcome::Error((
+                Status::Forbidden,
+                ApiError::new(ErrorCode::PermissionDenied, format!("API key lacks the {} scope", S::SCOPE))
+            ))
+        }
+    }
+}
+
+/// Endpoint to generate an API key. Anyone may generate a key which can only create clips, like
+/// the keys of CI jobs. Keys with other scopes, given with `scope`, or with all the `clip` scopes
+/// when none are given, need an API key with the `admin` scope. The first admin key can be
+/// generated with `httpd --new-api-key`. The key is sent back rather than written to the logs.
+#[rocket::get("/key?<scope>")]
+#[tracing::instrument(skip_all, fields(request_id = %request_id))]
+pub async fn new_api_key(
+    request_id: &RequestId,
+    scope: Vec<Scope>,
+    database: &State<AppDatabase>,
+    admin: Option<RequireScope<Admin>>
+) -> Result<Json<String>, ApiError> {
+    let scopes = match admin {
+        Some(_) if scope.is_empty() => Scope::CLIPS.to_vec(),
+        Some(_) => scope,
+        None if scope.is_empty() => vec![Scope::ClipWrite],
+        None => return Err(ApiError::new(ErrorCode::PermissionDenied, "API key lacks the admin scope"))
+    };
+    let api_key = action::generate_api_key(&scopes, database.get_pool()).await?;
+    tracing::info!("API key generated");
+    Ok(Json(api_key.to_base64()))
+}
+
+/// Endpoint to mint an API key with the given scopes, or all the `clip` scopes if none are given.
+/// It needs the `admin` scope.
+#[rocket::post("/", data = "<req>")]
+#[tracing::instrument(skip_all, fields(request_id = %request_id))]
+pub async fn create_api_key(
+    request_id: &RequestId,
+    req: Result<Json<service::ask::NewApiKey>, json::Error<'_>>,
+    database: &State<AppDatabase>,
+    _api_key: RequireScope<Admin>
+) -> Result<(Status, Json<String>), ApiError> {
+    let scopes = json_body(req)?.scopes;
+    let scopes = if scopes.is_empty() { Scope::CLIPS.to_vec() } else { scopes };
+    let api_key = action::generate_api_key(&scopes, database.get_pool()).await?;
+    tracing::info!("API key generated");
+    Ok((Status::Created, Json(api_key.to_base64())))
 }
 
-/// Endpoint access a clip, provided you have the shortcode.
+/// Endpoint access a clip, provided you have the shortcode. The password of a protected clip
+/// is sent in the [`CLIP_PASSWORD_HEADER`]. Clients which send the `ETag` or `Last-Modified`
+/// of their copy get a `304 Not Modified` while it is current, which isn't counted as a view.
 #[rocket::get("/<shortcode>")]
+#[tracing::instrument(skip_all, fields(request_id = %request_id, shortcode = shortcode))]
+#[allow(clippy::too_many_arguments)]
 pub async fn get_clip(
+    request_id: &RequestId,
     shortcode: &str,
     database: &State<AppDatabase>,
-    cookie: &CookieJar<'_>,
+    password: ClipPassword,
+    preconditions: Preconditions,
     hit_counter: &State<HitCounter>,
-    _api_key: ApiKey
-) -> Result<Json<crate::Clip>, ApiError> {
-    use crate::domain::clip::field::Password;
+    viewer: Viewer,
+    api_key: RequireScope<ClipRead>
+) -> Result<Cached<Json<crate::Clip>>, ApiError> {
+    let req = service::ask::GetClip {
+        shortcode: shortcode.into(),
+        password: password.into_inner(),
+        requester: Requester::ApiKey(api_key.into_key())
+    };
 
+    let clip = action::get_clip(req, database.get_pool()).await?;
+    let validators = Validators::new(&clip, Audience::Private);
+    if preconditions.is_fresh(&validators) {
+        return Ok(validators.not_modified());
+    }
+    hit_counter.hit(shortcode.into(), viewer.view(ViewRoute::Api));
+    Ok(validators.respond(Json(clip)))
+}
+
+/// Endpoint to access a password-protected clip with the password sent in the body, for
+/// clients which can't set the [`CLIP_PASSWORD_HEADER`].
+#[rocket::post("/<shortcode>/unlock", data = "<req>")]
+#[tracing::instrument(skip_all, fields(request_id = %request_id, shortcode = shortcode))]
+pub async fn unlock_clip(
+    request_id: &RequestId,
+    shortcode: &str,
+    req: Result<Json<service::ask::UnlockClip>, json::Error<'_>>,
+    database: &State<AppDatabase>,
+    hit_counter: &State<HitCounter>,
+    viewer: Viewer,
+    api_key: RequireScope<ClipRead>
+) -> Result<Json<crate::Clip>, ApiError> {
     let req = service::ask::GetClip {
         shortcode: shortcode.into(),
-        password: cookie
-            .get(PASSWORD_COOKIE)
-            .map(|cookie| cookie.value())
-            .map(|raw_password| Password::new(raw_password.to_string()).ok())
-            .flatten()
-            .unwrap_or_else(Password::default)
+        password: json_body(req)?.password,
+        requester: Requester::ApiKey(api_key.into_key())
     };
 
     let clip = action::get_clip(req, database.get_pool()).await?;
-    hit_counter.hit(shortcode.into(), 1);
+    hit_counter.hit(shortcode.into(), viewer.view(ViewRoute::Api));
     Ok(Json(clip))
 }
 
+/// Endpoint to get the view statistics of a clip, provided you have the shortcode and the
+/// password of a protected clip in the [`CLIP_PASSWORD_HEADER`].
+#[rocket::get("/<shortcode>/stats")]
+#[tracing::instrument(skip_all, fields(request_id = %request_id, shortcode = shortcode))]
+pub async fn get_clip_stats(
+    request_id: &RequestId,
+    shortcode: &str,
+    database: &State<AppDatabase>,
+    password: ClipPassword,
+    api_key: RequireScope<ClipRead>
+) -> Result<Json<ClipStats>, ApiError> {
+    let req = service::ask::GetClip {
+        shortcode: shortcode.into(),
+        password: password.into_inner(),
+        requester: Requester::ApiKey(api_key.into_key())
+    };
+
+    let stats = action::get_clip_stats(req, database.get_pool()).await?;
+    Ok(Json(stats))
+}
+
+
+/// A backup streamed as NDJSON, see [`backup`].
+pub type BackupStream = (ContentType, TextStream<BoxStream<'static, String>>);
+
+/// Streams a backup, ending it early when a page of clips can't be read. The `end` line is
+/// then missing, so the backup can't be restored as if it were whole.
+fn backup_stream(database: &AppDatabase, owner: Option<ApiKey>) -> BackupStream {
+    let lines = backup::export(database.get_pool().clone(), owner)
+        .take_while(|line| {
+            if let Err(e) = line {
+                tracing::error!(error = %e, "failed to export clips");
+            }
+            std::future::ready(line.is_ok())
+        })
+        .filter_map(|line| std::future::ready(line.ok()))
+        .boxed();
+    (ContentType::new("application", "x-ndjson"), TextStream::from(lines))
+}
+
+/// Endpoint to back up the clips created with the API key, as NDJSON which `httpd restore`
+/// reads. Passwords are only exported as hashes.
+#[rocket::get("/export")]
+#[tracing::instrument(skip_all, fields(request_id = %request_id))]
+pub async fn export_clips(
+    request_id: &RequestId,
+    database: &State<AppDatabase>,
+    api_key: RequireScope<ClipRead>
+) -> BackupStream {
+    backup_stream(database, Some(api_key.into_key()))
+}
+
+/// Endpoint to back up every clip, like `httpd export`. It needs the `admin` scope.
+#[rocket::get("/export/all")]
+#[tracing::instrument(skip_all, fields(request_id = %request_id))]
+pub async fn export_all_clips(
+    request_id: &RequestId,
+    database: &State<AppDatabase>,
+    _api_key: RequireScope<Admin>
+) -> BackupStream {
+    backup_stream(database, None)
+}
+
+/// Reads a JSON request body, turning a body which doesn't parse into a `validation_failed`
+/// error which tells what is wrong with it.
+fn json_body<T>(body: Result<Json<T>, json::Error<'_>>) -> Result<T, ApiError> {
+    match body {
+        Ok(body) => Ok(body.into_inner()),
+        Err(json::Error::Parse(_, e)) => Err(ApiError::new(ErrorCode::ValidationFailed, e.to_string())),
+        Err(json::Error::Io(e)) => Err(ApiError::new(ErrorCode::BadRequest, e.to_string()))
+    }
+}
 
 /// Endpoint create a new clip.
 #[rocket::post("/", data = "<req>")]
+#[tracing::instrument(skip_all, fields(request_id = %request_id))]
 pub async fn new_clip(
-    req: Json<service::ask::NewClip>,
+    request_id: &RequestId,
+    req: Result<Json<service::ask::NewClip>, json::Error<'_>>,
     database: &State<AppDatabase>,
-    _api_key: ApiKey
-) -> Result<Json<crate::Clip>, ApiError> {
+    api_key: RequireScope<ClipWrite>
+) -> Result<Cached<Json<crate::Clip>>, ApiError> {
+    let req = service::ask::NewClip {
+        owner: Some(api_key.into_key()),
+        ..json_body(req)?
+    };
+    let clip = action::new_clip(req, database.get_pool()).await?;
+    Ok(Validators::new(&clip, Audience::Private).respond(Json(clip)))
+}
 
-    let clip = action::new_clip(req.into_inner(), database.get_pool()).await?;
-    Ok(Json(clip))
+/// Endpoint to list the clips created with the same API key, newest first. The content of
+/// the clips is not included.
+#[rocket::get("/?<cursor>&<limit>")]
+#[tracing::instrument(skip_all, fields(request_id = %request_id))]
+pub async fn list_clips(
+    request_id: &RequestId,
+    cursor: Option<&str>,
+    limit: Option<u32>,
+    database: &State<AppDatabase>,
+    api_key: RequireScope<ClipRead>
+) -> Result<Json<ClipList>, ApiError> {
+    let req = service::ask::ListClips::new(cursor, limit, api_key.into_key())
+        .map_err(ServiceError::from)?;
+    let clips = action::list_clips(req, database.get_pool()).await?;
+    Ok(Json(clips))
+}
+
+/// Endpoint to search the title and content of the clips created with the same API key.
+/// Password-protected clips are never searched.
+#[rocket::get("/search?<q>&<offset>&<limit>")]
+#[tracing::instrument(skip_all, fields(request_id = %request_id))]
+pub async fn search_clips(
+    request_id: &RequestId,
+    q: &str,
+    offset: Option<u32>,
+    limit: Option<u32>,
+    database: &State<AppDatabase>,
+    api_key: RequireScope<ClipRead>
+) -> Result<Json<SearchResults>, ApiError> {
+    let req = service::ask::SearchClips::new(q, offset, limit, api_key.into_key())
+        .map_err(ServiceError::from)?;
+    let results = action::search_clips(req, database.get_pool()).await?;
+    Ok(Json(results))
 }
 
-/// Endpoint modify a clip, provided you have the shortcode.
+/// Endpoint modify a clip created with the API key, or any clip with the `admin` scope. With an
+/// `If-Match` header, the clip is only modified if it wasn't edited since the client read it.
 #[rocket::put("/", data = "<req>")]
+#[tracing::instrument(skip_all, fields(request_id = %request_id))]
 pub async fn update_clip(
-    req: Json<service::ask::UpdateClip>,
+    request_id: &RequestId,
+    req: Result<Json<service::ask::UpdateClip>, json::Error<'_>>,
+    preconditions: Preconditions,
     database: &State<AppDatabase>,
-    _api_key: ApiKey
+    events: &State<ClipEvents>,
+    api_key: RequireScope<ClipWrite>
+) -> Result<Cached<Json<crate::Clip>>, ApiError> {
+    let req = service::ask::UpdateClip {
+        requester: Requester::ApiKey(api_key.into_key()),
+        revisions: preconditions.revisions(),
+        ..json_body(req)?
+    };
+    let clip = action::update_clip(req, database.get_pool()).await?;
+    events.updated(clip.shortcode.clone());
+    Ok(Validators::new(&clip, Audience::Private).respond(Json(clip)))
+}
 
-) -> Result<Json<crate::Clip>, ApiError> {
+/// Endpoint to modify the clip named in the path, if it was created with the API key or the key
+/// has the `admin` scope. With an `If-Match` header, the clip is only modified if it wasn't
+/// edited since the client read it.
+#[rocket::put("/<shortcode>", data = "<req>")]
+#[tracing::instrument(skip_all, fields(request_id = %request_id, shortcode = shortcode))]
+pub async fn edit_clip(
+    request_id: &RequestId,
+    shortcode: &str,
+    req: Result<Json<service::ask::EditClip>, json::Error<'_>>,
+    preconditions: Preconditions,
+    database: &State<AppDatabase>,
+    events: &State<ClipEvents>,
+    api_key: RequireScope<ClipWrite>
+) -> Result<Cached<Json<crate::Clip>>, ApiError> {
+    let req = service::ask::UpdateClip {
+        revisions: preconditions.revisions(),
+        ..json_body(req)?.into_update(shortcode.into(), Requester::ApiKey(api_key.into_key()))
+    };
+    let clip = action::update_clip(req, database.get_pool()).await?;
+    events.updated(clip.shortcode.clone());
+    Ok(Validators::new(&clip, Audience::Private).respond(Json(clip)))
+}
 
-    let clip = action::update_clip(req.into_inner(), database.get_pool()).await?;
+/// Endpoint to delete a clip created with the API key, or any clip with the `admin` scope. The
+/// `clip:delete` scope alone doesn't allow deleting the clips of other keys. The clip can be
+/// restored by an admin until it is purged.
+#[rocket::delete("/<shortcode>")]
+#[tracing::instrument(skip_all, fields(request_id = %request_id, shortcode = shortcode))]
+pub async fn delete_clip(
+    request_id: &RequestId,
+    shortcode: &str,
+    database: &State<AppDatabase>,
+    events: &State<ClipEvents>,
+    api_key: RequireScope<ClipDelete>
+) -> Result<Json<&'static str>, ApiError> {
+    let req = service::ask::GetClip {
+        requester: Requester::ApiKey(api_key.into_key()),
+        ..shortcode.into()
+    };
+    action::delete_clip(req, database.get_pool()).await?;
+    events.updated(shortcode.into());
+    Ok(Json("clip deleted"))
+}
+
+/// Endpoint to restore an expired or deleted clip which has not been purged yet. Only admins
+/// may restore clips, so it needs the `admin` scope.
+#[rocket::post("/<shortcode>/restore")]
+#[tracing::instrument(skip_all, fields(request_id = %request_id, shortcode = shortcode))]
+pub async fn restore_clip(
+    request_id: &RequestId,
+    shortcode: &str,
+    database: &State<AppDatabase>,
+    api_key: RequireScope<Admin>
+) -> Result<Json<crate::Clip>, ApiError> {
+    let req = service::ask::GetClip {
+        requester: Requester::ApiKey(api_key.into_key()),
+        ..shortcode.into()
+    };
+    let clip = action::restore_clip(req, database.get_pool()).await?;
     Ok(Json(clip))
 }
 
-pub fn routes() -> Vec<rocket::Route> {
-    rocket::routes!(get_clip, new_clip, update_clip, new_api_key)
+/// Endpoint to mint the token letting its holders edit a clip together in the browser, at
+/// `/clip/<shortcode>#edit=<token>`. Only those who may edit the clip may mint one. Minting a
+/// token revokes the previous one.
+#[rocket::post("/<shortcode>/edit-token")]
+#[tracing::instrument(skip_all, fields(request_id = %request_id, shortcode = shortcode))]
+pub async fn create_edit_token(
+    request_id: &RequestId,
+    shortcode: &str,
+    database: &State<AppDatabase>,
+    api_key: RequireScope<ClipWrite>
+) -> Result<(Status, Json<String>), ApiError> {
+    let req = service::ask::GetClip {
+        requester: Requester::ApiKey(api_key.into_key()),
+        ..shortcode.into()
+    };
+    let token = action::new_edit_token(req, database.get_pool()).await?;
+    Ok((Status::Created, Json(token.to_base64())))
+}
+
+/// Endpoint to register a webhook getting the events of the clips created with the API key.
+/// The answer holds the secret the deliveries are signed with, which is only shown once. Local
+/// and private hosts are refused, unless the server allows them.
+#[rocket::post("/", data = "<req>")]
+#[tracing::instrument(skip_all, fields(request_id = %request_id))]
+pub async fn create_webhook(
+    request_id: &RequestId,
+    req: Result<Json<service::ask::NewWebhook>, json::Error<'_>>,
+    database: &State<AppDatabase>,
+    webhooks: &State<WebhookDispatcher>,
+    api_key: RequireScope<ClipRead>
+) -> Result<(Status, Json<RegisteredWebhook>), ApiError> {
+    let req = json_body(req)?;
+    let webhook = action::new_webhook(req, api_key.into_key(), webhooks.allowed_hosts(), database.get_pool()).await?;
+    tracing::info!(webhook_id = webhook.webhook.webhook_id.as_str(), "webhook registered");
+    Ok((Status::Created, Json(webhook)))
+}
+
+/// Endpoint to list the webhooks of the API key.
+#[rocket::get("/")]
+#[tracing::instrument(skip_all, fields(request_id = %request_id))]
+pub async fn list_webhooks(
+    request_id: &RequestId,
+    database: &State<AppDatabase>,
+    api_key: RequireScope<ClipRead>
+) -> Result<Json<Vec<Webhook>>, ApiError> {
+    Ok(Json(action::list_webhooks(api_key.into_key(), database.get_pool()).await?))
+}
+
+/// Endpoint to delete a webhook of the API key. Its pending deliveries are dropped.
+#[rocket::delete("/<webhook_id>")]
+#[tracing::instrument(skip_all, fields(request_id = %request_id, webhook_id = webhook_id))]
+pub async fn delete_webhook(
+    request_id: &RequestId,
+    webhook_id: &str,
+    database: &State<AppDatabase>,
+    api_key: RequireScope<ClipRead>
+) -> Result<Json<&'static str>, ApiError> {
+    action::delete_webhook(webhook_id, api_key.into_key(), database.get_pool()).await?;
+    Ok(Json("webhook deleted"))
+}
+
+/// Endpoint to list the latest deliveries of a webhook of the API key, newest first, with how
+/// their last attempt went.
+#[rocket::get("/<webhook_id>/deliveries?<limit>")]
+#[tracing::instrument(skip_all, fields(request_id = %request_id, webhook_id = webhook_id))]
+pub async fn list_webhook_deliveries(
+    request_id: &RequestId,
+    webhook_id: &str,
+    limit: Option<u32>,
+    database: &State<AppDatabase>,
+    api_key: RequireScope<ClipRead>
+) -> Result<Json<Vec<Delivery>>, ApiError> {
+    const MAX_LIMIT: u32 = 100;
+    let limit = limit.unwrap_or(20);
+    if limit == 0 || limit > MAX_LIMIT {
+        return Err(ClipError::InvalidLimit(MAX_LIMIT).into());
+    }
+    let deliveries = action::list_webhook_deliveries(webhook_id, limit, api_key.into_key(), database.get_pool()).await?;
+    Ok(Json(deliveries))
+}
+
+/// The clip routes which can be mounted by [`rocket`] at `/api/v1/clips`.
+pub fn clip_routes() -> Vec<rocket::Route> {
+    rocket::routes!(
+        get_clip,
+        get_clip_stats,
+        unlock_clip,
+        new_clip,
+        list_clips,
+        search_clips,
+        edit_clip,
+        delete_clip,
+        restore_clip,
+        create_edit_token,
+        export_clips,
+        export_all_clips
+    )
+}
+
+/// The API key routes which can be mounted by [`rocket`] at `/api/v1/keys`.
+pub fn key_routes() -> Vec<rocket::Route> {
+    rocket::routes!(create_api_key)
+}
+
+/// The webhook routes which can be mounted by [`rocket`] at `/api/v1/webhooks`.
+pub fn webhook_routes() -> Vec<rocket::Route> {
+    rocket::routes!(create_webhook, list_webhooks, delete_webhook, list_webhook_deliveries)
+}
+
+/// The unversioned routes which can be mounted by [`rocket`] at [`LEGACY_BASE`]. They answer
+/// like the `/api/v1` ones, with headers telling clients to move over.
+pub fn legacy_routes() -> Vec<rocket::Route> {
+    rocket::routes!(
+        get_clip,
+        get_clip_stats,
+        new_clip,
+        list_clips,
+        search_clips,
+        update_clip,
+        delete_clip,
+        restore_clip,
+        new_api_key
+    )
+}
+
+/// Fairing which marks the responses of the [`legacy_routes`] as deprecated (RFC 8594) and
+/// links them to the API version replacing them.
+pub struct Deprecation;
+
+#[rocket::async_trait]
+impl Fairing for Deprecation {
+    fn info(&self) -> Info {
+        Info {
+            name: "Deprecated API routes",
+            kind: Kind::Response
+        }
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        if let Some(successor) = successor(req.uri().path().as_str()) {
+            res.set_raw_header("Deprecation", "true");
+            res.set_raw_header("Link", format!("<{}>; rel=\"successor-version\"", successor));
+        }
+    }
+}
+
+/// The `/api/v1` path replacing a path of the [`legacy_routes`].
+fn successor(path: &str) -> Option<String> {
+    let rest = path.strip_prefix(LEGACY_BASE)?;
+    if rest == "/key" {
+        Some(format!("{}/keys", API_BASE))
+    } else if rest.is_empty() || rest.starts_with('/') {
+        Some(format!("{}/clips{}", API_BASE, rest.trim_end_matches('/')))
+    } else {
+        None
+    }
 }
 
 pub mod catcher {
-    //! Contains all the page catchers.
-    use rocket::serde::json::Json;
-    use rocket::Request;
+    //! Contains all the API catchers. They answer with the same [`ApiError`] envelope as the routes.
+    use super::{ApiError, ErrorCode, KeyFailure, MissingScope, AUTH_REALM};
+    use rocket::http::{Header, Status};
+    use rocket::{Request, Responder};
     use rocket::{catch, catchers, Catcher};
 
-    /// Catch unhandled errors.
+    /// Catch unhandled errors, keeping their status.
     #[catch(default)]
-    fn default(req: &Request) -> Json<&'static str> {
-        eprintln!("General error: {:?}", req);
-        Json("something went wrong...")
+    fn default(status: Status, req: &Request) -> (Status, ApiError) {
+        tracing::warn!(status = status.code, method = %req.method(), path = %req.uri().path(), "general error");
+        let message = status.reason().unwrap_or("something went wrong").to_lowercase();
+        (status, ApiError::new(ErrorCode::from_status(status), message))
     }
 
     /// Catch server errors.
     #[catch(500)]
-    fn internal_error(req: &Request) -> Json<&'static str> {
-        eprintln!("Internal error: {:?}", req);
-        Json("internal server error")
+    fn internal_error(req: &Request) -> ApiError {
+        tracing::error!(method = %req.method(), path = %req.uri().path(), "internal error");
+        ApiError::server_error()
     }
 
     /// Catch missing data errors.
     #[catch(404)]
-    fn not_found() -> Json<&'static str> {
+    fn not_found() -> ApiError {
+        ApiError::new(ErrorCode::NotFound, "not found")
+    }
 
-        Json("404")
+    /// Catch request bodies which don't parse.
+    #[catch(422)]
+    fn unprocessable() -> ApiError {
+        ApiError::new(ErrorCode::ValidationFailed, "the request body is invalid")
     }
 
-    #[catch(401)]
-    fn request_error() -> Json<&'static str> {
+    /// Catch clients making too many requests.
+    #[catch(429)]
+    fn too_many_requests() -> ApiError {
+        ApiError::new(ErrorCode::RateLimited, "too many requests, please slow down")
+    }
+
+    /// An [`ApiError`] along with a `WWW-Authenticate` challenge, telling clients how to authenticate.
+    #[derive(Responder)]
+    struct Challenge {
+        body: ApiError,
+        challenge: Header<'static>
+    }
+
+    impl Challenge {
+        fn new(body: ApiError, challenge: String) -> Self {
+            Self {
+                body,
+                challenge: Header::new("WWW-Authenticate", challenge)
+            }
+        }
+    }
 
-        Json("request error")
+    /// Catch missing or refused API keys.
+    #[catch(401)]
+    fn unauthorized(req: &Request) -> Challenge {
+        match req.local_cache(|| KeyFailure(None)) {
+            KeyFailure(Some(e)) => Challenge::new(e.clone().into(), e.challenge()),
+            KeyFailure(None) => Challenge::new(
+                ApiError::new(ErrorCode::Unauthorized, "API key missing or invalid"),
+                format!(r#"Bearer realm="{}""#, AUTH_REALM)
+            )
+        }
     }
 
+    /// Catch malformed requests, such as API keys sent in the query string.
     #[catch(400)]
-    fn missing_api_key() -> Json<&'static str> {
+    fn bad_request(req: &Request) -> Result<Challenge, ApiError> {
+        match req.local_cache(|| KeyFailure(None)) {
+            KeyFailure(Some(e)) => Ok(Challenge::new(e.clone().into(), e.challenge())),
+            KeyFailure(None) => Err(ApiError::new(ErrorCode::BadRequest, "bad request"))
+        }
+    }
 
-        Json("API key missing or invalid")
+    /// Catch API keys lacking the scope of a route.
+    #[catch(403)]
+    fn insufficient_scope(req: &Request) -> Result<Challenge, ApiError> {
+        match req.local_cache(|| MissingScope(None)) {
+            MissingScope(Some(scope)) => Ok(Challenge::new(
+                ApiError::new(ErrorCode::PermissionDenied, format!("API key lacks the {} scope", scope)),
+                format!(r#"Bearer realm="{}", error="insufficient_scope", scope="{}""#, AUTH_REALM, scope)
+            )),
+            MissingScope(None) => Err(ApiError::new(ErrorCode::PermissionDenied, "forbidden"))
+        }
     }
 
     /// The [`catchers`](rocket::Catcher) which can be registered by [`rocket`].
     pub fn catchers() -> Vec<Catcher> {
-        catchers![not_found, default, internal_error, request_error, missing_api_key]
+        catchers![
+            not_found,
+            default,
+            internal_error,
+            unprocessable,
+            too_many_requests,
+            unauthorized,
+            bad_request,
+            insufficient_scope
+        ]
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::{ErrorBody, ErrorCode};
+    use crate::data::AppDatabase;
+    use crate::domain::scope::Scope;
+    use crate::service::action;
+    use crate::test::async_runtime;
+    use crate::domain::webhook::{signature, DELIVERY_HEADER, EVENT_HEADER, SIGNATURE_HEADER, TIMESTAMP_HEADER};
+    use crate::web::test::client;
+    use crate::web::HitCounter;
+    use rocket::http::{ContentType, Header, Status};
+    use std::collections::HashMap;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    #[test]
+    fn write_only_keys_cannot_read_clips() {
+        let rt = async_runtime();
+        let client = client();
+        let db = client.rocket().state::<AppDatabase>().unwrap();
+        let admin = rt
+            .block_on(async move { action::generate_api_key(&Scope::ALL, db.get_pool()).await })
+            .unwrap()
+            .to_base64();
+
+        let response = client
+            .get("/api/clip/key?scope=clip:write")
+            .header(Header::new(super::API_KEY_HEADER, admin.clone()))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let writer: String = response.into_json().unwrap();
+
+        let response = client
+            .post("/api/clip")
+            .header(ContentType::JSON)
+            .header(Header::new(super::API_KEY_HEADER, writer.clone()))
+            .body(r#"{"content":"build log","title":"","expires":null,"password":null}"#)
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let clip: serde_json::Value = response.into_json().unwrap();
+        let shortcode = clip["shortcode"].as_str().unwrap();
+
+        let response = client
+            .get(format!("/api/clip/{}", shortcode))
+            .header(Header::new(super::API_KEY_HEADER, writer.clone()))
+            .dispatch();
+        assert_eq!(response.status(), Status::Forbidden);
+        assert_eq!(response.content_type(), Some(ContentType::JSON));
+        assert!(response.into_string().unwrap().contains("clip:read"));
+        let response = client
+            .get("/api/clip/key?scope=clip:read")
+            .header(Header::new(super::API_KEY_HEADER, writer))
+            .dispatch();
+        assert_eq!(response.status(), Status::Forbidden);
+        // Keys generated without an admin key can only create clips.
+        let response = client.get("/api/clip/key").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let anyone: String = response.into_json().unwrap();
+        let response = client
+            .get(format!("/api/clip/{}", shortcode))
+            .header(Header::new(super::API_KEY_HEADER, anyone.clone()))
+            .dispatch();
+        assert_eq!(response.status(), Status::Forbidden);
+        let response = client
+            .post("/api/clip")
+            .header(ContentType::JSON)
+            .header(Header::new(super::API_KEY_HEADER, anyone))
+            .body(r#"{"content":"test log","title":"","expires":null,"password":null}"#)
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+
+        let response = client
+            .get(format!("/api/clip/{}", shortcode))
+            .header(Header::new(super::API_KEY_HEADER, admin))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+    }
+
+    #[test]
+    fn accepts_bearer_keys() {
+        let rt = async_runtime();
+        let client = client();
+        let db = client.rocket().state::<AppDatabase>().unwrap();
+        let api_key = rt
+            .block_on(async move { action::generate_api_key(&Scope::CLIPS, db.get_pool()).await })
+            .unwrap()
+            .to_base64();
+
+        let response = client
+            .get("/api/clip")
+            .header(Header::new("Authorization", format!("Bearer {}", api_key)))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let response = client
+            .get("/api/clip")
+            .header(Header::new(super::API_KEY_HEADER, api_key))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+    }
+
+    #[test]
+    fn challenges_missing_and_invalid_keys() {
+        let client = client();
+
+        let response = client.get("/api/clip").dispatch();
+        assert_eq!(response.status(), Status::Unauthorized);
+        assert_eq!(response.headers().get_one("WWW-Authenticate"), Some(r#"Bearer realm="clipstash""#));
+        assert_eq!(response.content_type(), Some(ContentType::JSON));
+
+        let response = client
+            .get("/api/clip")
+            .header(Header::new("Authorization", "Basic dXNlcjpwYXNz"))
+            .dispatch();
+        assert_eq!(response.status(), Status::Unauthorized);
+
+        let unknown = base64::encode([0u8; 16]);
+        for header in [
+            Header::new("Authorization", format!("Bearer {}", unknown)),
+            Header::new(super::API_KEY_HEADER, unknown.clone()),
+            Header::new("Authorization", "Bearer not-base64!")
+        ] {
+            let response = client.get("/api/clip").header(header).dispatch();
+            assert_eq!(response.status(), Status::Unauthorized);
+            assert_eq!(
+                response.headers().get_one("WWW-Authenticate"),
+                Some(r#"Bearer realm="clipstash", error="invalid_token""#)
+            );
+        }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn rejects_keys_in_query_strings() {
+        let rt = async_runtime();
+        let client = client();
+        let db = client.rocket().state::<AppDatabase>().unwrap();
+        let api_key = rt
+            .block_on(async move { action::generate_api_key(&Scope::CLIPS, db.get_pool()).await })
+            .unwrap()
+            .to_base64();
+
+        let encoded = api_key.replace('+', "%2B").replace('/', "%2F").replace('=', "%3D");
+        for param in ["api_key", "access_token"] {
+            let response = client
+                .get(format!("/api/clip?{}={}", param, encoded))
+                .header(Header::new(super::API_KEY_HEADER, api_key.clone()))
+                .dispatch();
+            assert_eq!(response.status(), Status::BadRequest);
+            assert_eq!(
+                response.headers().get_one("WWW-Authenticate"),
+                Some(r#"Bearer realm="clipstash", error="invalid_request""#)
+            );
+        }
+    }
+
+    #[test]
+    fn errors_share_one_envelope() {
+        let rt = async_runtime();
+        let client = client();
+        let db = client.rocket().state::<AppDatabase>().unwrap();
+        let api_key = rt
+            .block_on(async move { action::generate_api_key(&Scope::CLIPS, db.get_pool()).await })
+            .unwrap()
+            .to_base64();
+        let error = |response: rocket::local::blocking::LocalResponse<'_>| {
+            let body: ErrorBody = response.into_json().unwrap();
+            body.error
+        };
+
+        let response = client.get("/api/clip").dispatch();
+        assert_eq!(error(response).code, ErrorCode::Unauthorized);
+
+        let requests = [
+            ("/api/clip/aaaaaaaaaa", Status::NotFound, ErrorCode::NotFound, None),
+            ("/api/clip/search?q=", Status::UnprocessableEntity, ErrorCode::ValidationFailed, Some("q")),
+            ("/api/clip?limit=0", Status::UnprocessableEntity, ErrorCode::ValidationFailed, Some("limit")),
+            ("/api/clip/no/such/route", Status::NotFound, ErrorCode::NotFound, None)
+        ];
+        for (uri, status, code, field) in requests {
+            let response = client
+                .get(uri)
+                .header(Header::new(super::API_KEY_HEADER, api_key.clone()))
+                .dispatch();
+            assert_eq!(response.status(), status, "{}", uri);
+            let error = error(response);
+            assert_eq!(error.code, code, "{}", uri);
+            assert_eq!(error.field.as_deref(), field, "{}", uri);
+        }
+
+        let response = client
+            .post("/api/clip")
+            .header(ContentType::JSON)
+            .header(Header::new(super::API_KEY_HEADER, api_key))
+            .body(r#"{"content":"#)
+            .dispatch();
+        assert_eq!(response.status(), Status::UnprocessableEntity);
+        assert_eq!(error(response).code, ErrorCode::ValidationFailed);
+    }
+
+    #[test]
+    fn legacy_routes_alias_the_v1_ones() {
+        let rt = async_runtime();
+        let client = client();
+        let db = client.rocket().state::<AppDatabase>().unwrap();
+        let admin = rt
+            .block_on(async move { action::generate_api_key(&Scope::ALL, db.get_pool()).await })
+            .unwrap()
+            .to_base64();
+
+        let response = client
+            .post("/api/v1/keys")
+            .header(ContentType::JSON)
+            .header(Header::new(super::API_KEY_HEADER, admin))
+            .body(r#"{"scopes":["clip:read","clip:write"]}"#)
+            .dispatch();
+        assert_eq!(response.status(), Status::Created);
+        assert!(response.headers().get_one("Deprecation").is_none());
+        let api_key: String = response.into_json().unwrap();
+
+        let response = client
+            .post("/api/v1/clips")
+            .header(ContentType::JSON)
+            .header(Header::new(super::API_KEY_HEADER, api_key.clone()))
+            .body(r#"{"content":"first draft","title":"","expires":null,"password":null}"#)
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let clip: serde_json::Value = response.into_json().unwrap();
+        let shortcode = clip["shortcode"].as_str().unwrap();
+
+        let response = client
+            .put(format!("/api/v1/clips/{}", shortcode))
+            .header(ContentType::JSON)
+            .header(Header::new(super::API_KEY_HEADER, api_key.clone()))
+            .body(r#"{"content":"second draft","title":"","expires":null,"password":null}"#)
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+
+        let response = client
+            .get(format!("/api/clip/{}", shortcode))
+            .header(Header::new(super::API_KEY_HEADER, api_key.clone()))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.headers().get_one("Deprecation"), Some("true"));
+        assert_eq!(
+            response.headers().get_one("Link"),
+            Some(format!(r#"</api/v1/clips/{}>; rel="successor-version""#, shortcode).as_str())
+        );
+        let clip: serde_json::Value = response.into_json().unwrap();
+        assert_eq!(clip["content"], "second draft");
+
+        let response = client
+            .delete(format!("/api/v1/clips/{}", shortcode))
+            .header(Header::new(super::API_KEY_HEADER, api_key))
+            .dispatch();
+        assert_eq!(response.status(), Status::Forbidden);
+        let response = client.get("/api/v1/clips").dispatch();
+        assert_eq!(response.status(), Status::Unauthorized);
+        assert_eq!(response.content_type(), Some(ContentType::JSON));
+    }
+
+    #[test]
+    fn only_creators_and_admins_delete_clips() {
+        let rt = async_runtime();
+        let client = client();
+        let db = client.rocket().state::<AppDatabase>().unwrap();
+        let (owner, stranger, admin) = rt
+            .block_on(async move {
+                let owner = action::generate_api_key(&Scope::CLIPS, db.get_pool()).await?;
+                let stranger = action::generate_api_key(&Scope::CLIPS, db.get_pool()).await?;
+                let admin = action::generate_api_key(&Scope::ALL, db.get_pool()).await?;
+                Ok::<_, crate::ServiceError>((owner.to_base64(), stranger.to_base64(), admin.to_base64()))
+            })
+            .unwrap();
+        let key = |key: &str| Header::new(super::API_KEY_HEADER, key.to_owned());
+
+        let response = client
+            .post("/api/v1/clips")
+            .header(ContentType::JSON)
+            .header(key(&owner))
+            .body(r#"{"content":"mine","title":"","expires":null,"password":null}"#)
+            .dispatch();
+        let clip: serde_json::Value = response.into_json().unwrap();
+        let uri = format!("/api/v1/clips/{}", clip["shortcode"].as_str().unwrap());
+        let restore_uri = format!("{}/restore", uri);
+
+        assert_eq!(client.delete(&uri).header(key(&stranger)).dispatch().status(), Status::NotFound);
+        assert_eq!(client.get(&uri).header(key(&owner)).dispatch().status(), Status::Ok);
+        assert_eq!(client.delete(&uri).header(key(&owner)).dispatch().status(), Status::Ok);
+
+        // Only admins bring deleted clips back, even to their creators.
+        assert_eq!(client.post(&restore_uri).header(key(&stranger)).dispatch().status(), Status::Forbidden);
+        let response = client.post(&restore_uri).header(key(&owner)).dispatch();
+        assert_eq!(response.status(), Status::Forbidden);
+        assert!(response.into_string().unwrap().contains("admin"));
+        assert_eq!(client.post(&restore_uri).header(key(&admin)).dispatch().status(), Status::Ok);
+        assert_eq!(client.delete(&uri).header(key(&admin)).dispatch().status(), Status::Ok);
+    }
+
+    #[test]
+    fn only_creators_and_admins_edit_clips() {
+        let rt = async_runtime();
+        let client = client();
+        let db = client.rocket().state::<AppDatabase>().unwrap();
+        let (owner, stranger, admin) = rt
+            .block_on(async move {
+                let owner = action::generate_api_key(&Scope::CLIPS, db.get_pool()).await?;
+                let stranger = action::generate_api_key(&Scope::CLIPS, db.get_pool()).await?;
+                let admin = action::generate_api_key(&Scope::ALL, db.get_pool()).await?;
+                Ok::<_, crate::ServiceError>((owner.to_base64(), stranger.to_base64(), admin.to_base64()))
+            })
+            .unwrap();
+        let key = |key: &str| Header::new(super::API_KEY_HEADER, key.to_owned());
+
+        let response = client
+            .post("/api/v1/clips")
+            .header(ContentType::JSON)
+            .header(key(&owner))
+            .body(r#"{"content":"mine","title":"","expires":null,"password":"secret"}"#)
+            .dispatch();
+        let clip: serde_json::Value = response.into_json().unwrap();
+        let uri = format!("/api/v1/clips/{}", clip["shortcode"].as_str().unwrap());
+        let edit = |key: Header<'static>, content: &str| {
+            client
+                .put(&uri)
+                .header(ContentType::JSON)
+                .header(key)
+                .body(serde_json::json!({ "content": content, "title": "", "expires": null, "password": null }).to_string())
+                .dispatch()
+                .status()
+        };
+
+        // Nor can strangers lift the password, or mint an edit token to get around it.
+        assert_eq!(edit(key(&stranger), "theirs"), Status::NotFound);
+        let token_uri = format!("{}/edit-token", uri);
+        assert_eq!(client.post(&token_uri).header(key(&stranger)).dispatch().status(), Status::NotFound);
+        let response = client
+            .get(&uri)
+            .header(key(&owner))
+            .header(Header::new(super::CLIP_PASSWORD_HEADER, "secret"))
+            .dispatch();
+        assert!(response.into_string().unwrap().contains("mine"));
+
+        assert_eq!(edit(key(&admin), "moderated"), Status::Ok);
+        assert_eq!(edit(key(&owner), "mine again"), Status::Ok);
+    }
+
+    #[test]
+    fn password_comes_from_the_header_or_the_unlock_body() {
+        let rt = async_runtime();
+        let client = client();
+        let db = client.rocket().state::<AppDatabase>().unwrap();
+        let api_key = rt
+            .block_on(async move { action::generate_api_key(&Scope::CLIPS, db.get_pool()).await })
+            .unwrap()
+            .to_base64();
+
+        let response = client
+            .post("/api/v1/clips")
+            .header(ContentType::JSON)
+            .header(Header::new(super::API_KEY_HEADER, api_key.clone()))
+            .body(r#"{"content":"locked","title":"","expires":null,"password":"hunter2"}"#)
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let clip: serde_json::Value = response.into_json().unwrap();
+        let uri = format!("/api/v1/clips/{}", clip["shortcode"].as_str().unwrap());
+
+        let response = client
+            .get(&uri)
+            .header(Header::new(super::API_KEY_HEADER, api_key.clone()))
+            .header(Header::new("Cookie", "password=hunter2"))
+            .dispatch();
+        assert_eq!(response.status(), Status::Forbidden);
+
+        let response = client
+            .get(&uri)
+            .header(Header::new(super::API_KEY_HEADER, api_key.clone()))
+            .header(Header::new(super::CLIP_PASSWORD_HEADER, "hunter2"))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let response = client
+            .get(format!("{}/stats", uri))
+            .header(Header::new(super::API_KEY_HEADER, api_key.clone()))
+            .header(Header::new(super::CLIP_PASSWORD_HEADER, "hunter2"))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+
+        for (password, status) in [("wrong", Status::Forbidden), ("hunter2", Status::Ok)] {
+            let response = client
+                .post(format!("{}/unlock", uri))
+                .header(ContentType::JSON)
+                .header(Header::new(super::API_KEY_HEADER, api_key.clone()))
+                .body(format!(r#"{{"password":"{}"}}"#, password))
+                .dispatch();
+            assert_eq!(response.status(), status);
+        }
+    }
+
+    #[test]
+    fn clip_reads_are_conditional_and_edits_need_a_current_etag() {
+        let rt = async_runtime();
+        let client = client();
+        let db = client.rocket().state::<AppDatabase>().unwrap();
+        let api_key = rt
+            .block_on(async move { action::generate_api_key(&Scope::CLIPS, db.get_pool()).await })
+            .unwrap()
+            .to_base64();
+        let key = || Header::new(super::API_KEY_HEADER, api_key.clone());
+
+        let response = client
+            .post("/api/v1/clips")
+            .header(ContentType::JSON)
+            .header(key())
+            .body(r#"{"content":"v1","title":"","expires":null,"password":null}"#)
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let created = response.headers().get_one("ETag").unwrap().to_owned();
+        let clip: serde_json::Value = response.into_json().unwrap();
+        let uri = format!("/api/v1/clips/{}", clip["shortcode"].as_str().unwrap());
+
+        let response = client.get(&uri).header(key()).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.headers().get_one("ETag"), Some(created.as_str()));
+        assert_eq!(response.headers().get_one("Cache-Control"), Some("private, max-age=3600"));
+        let last_modified = response.headers().get_one("Last-Modified").unwrap().to_owned();
+
+        let response = client.get(&uri).header(key()).header(Header::new("If-None-Match", created.clone())).dispatch();
+        assert_eq!(response.status(), Status::NotModified);
+        assert!(response.into_string().is_none());
+        let response = client.get(&uri).header(key()).header(Header::new("If-Modified-Since", last_modified)).dispatch();
+        assert_eq!(response.status(), Status::NotModified);
+
+        // Only the read which sent the clip is a view.
+        let shortcode = clip["shortcode"].as_str().unwrap().to_owned();
+        let hits = rt.block_on(async {
+            client.rocket().state::<HitCounter>().unwrap().flush().await;
+            action::get_clip(shortcode.as_str().into(), db.get_pool()).await.unwrap().hits.into_inner()
+        });
+        assert_eq!(hits, 1);
+
+        let edit = |content: &str, etag: &str| {
+            client
+                .put(&uri)
+                .header(ContentType::JSON)
+                .header(key())
+                .header(Header::new("If-Match", etag.to_owned()))
+                .body(format!(r#"{{"content":"{}","title":"","expires":null,"password":null}}"#, content))
+                .dispatch()
+        };
+        let response = edit("v2", &created);
+        assert_eq!(response.status(), Status::Ok);
+        let edited = response.headers().get_one("ETag").unwrap().to_owned();
+        assert_ne!(edited, created);
+
+        let response = edit("lost update", &created);
+        assert_eq!(response.status(), Status::PreconditionFailed);
+        let body: ErrorBody = response.into_json().unwrap();
+        assert_eq!(body.error.code, ErrorCode::PreconditionFailed);
+
+        let response = client.get(&uri).header(key()).header(Header::new("If-None-Match", created)).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let clip: serde_json::Value = response.into_json().unwrap();
+        assert_eq!(clip["content"], "v2");
+        assert_eq!(clip["revision"], 2);
+
+        let response = edit("protected", &edited);
+        assert_eq!(response.status(), Status::Ok);
+        let response = client
+            .put(&uri)
+            .header(ContentType::JSON)
+            .header(key())
+            .body(r#"{"content":"protected","title":"","expires":null,"password":"hunter2"}"#)
+            .dispatch();
+        assert_eq!(response.headers().get_one("Cache-Control"), Some("private, no-store"));
+    }
+
+    /// The headers and body of a delivery received by [`stub_receiver`].
+    type ReceivedDelivery = (HashMap<String, String>, String);
+
+    /// A webhook receiver on a local port, answering the deliveries with `statuses` in turn and
+    /// then with 200s. The headers and body of every delivery are sent down the channel.
+    fn stub_receiver(statuses: Vec<u16>) -> (String, mpsc::Receiver<ReceivedDelivery>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hooks", listener.local_addr().unwrap());
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let mut statuses = statuses.into_iter();
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut headers = HashMap::new();
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let line = line.trim_end();
+                    if line.is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':') {
+                        headers.insert(name.trim().to_lowercase(), value.trim().to_owned());
+                    }
+                }
+                let length = headers.get("content-length").map_or(0, |length| length.parse().unwrap());
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                let status = statuses.next().unwrap_or(200);
+                write!(stream, "HTTP/1.1 {} Stub\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status).unwrap();
+                if tx.send((headers, String::from_utf8(body).unwrap())).is_err() {
+                    break;
+                }
+            }
+        });
+        (url, rx)
+    }
+
+    #[test]
+    fn webhooks_get_signed_deliveries_retried_until_they_succeed() {
+        let rt = async_runtime();
+        let client = client();
+        let db = client.rocket().state::<AppDatabase>().unwrap();
+        let (api_key, other_key) = rt.block_on(async move {
+            (
+                action::generate_api_key(&Scope::CLIPS, db.get_pool()).await.unwrap().to_base64(),
+                action::generate_api_key(&Scope::CLIPS, db.get_pool()).await.unwrap().to_base64()
+            )
+        });
+        let key = || Header::new(super::API_KEY_HEADER, api_key.clone());
+        let (url, deliveries) = stub_receiver(vec![500]);
+        let next_delivery = || {
+            let (headers, body) = deliveries.recv_timeout(Duration::from_secs(10)).expect("no delivery came");
+            let payload: serde_json::Value = serde_json::from_str(&body).unwrap();
+            (headers, body, payload)
+        };
+
+        let register = |body: String| client
+            .post("/api/v1/webhooks")
+            .header(ContentType::JSON)
+            .header(key())
+            .body(body)
+            .dispatch();
+        let response = register(r#"{"url":"ftp://example.com","events":["clip.created"]}"#.to_owned());
+        assert_eq!(response.status(), Status::UnprocessableEntity);
+        assert_eq!(response.into_json::<ErrorBody>().unwrap().error.field.as_deref(), Some("url"));
+        let response = register(format!(r#"{{"url":"{}","events":[]}}"#, url));
+        assert_eq!(response.into_json::<ErrorBody>().unwrap().error.field.as_deref(), Some("events"));
+
+        let response = register(format!(
+            r#"{{"url":"{}","events":["clip.viewed","clip.deleted","clip.created","clip.created"]}}"#,
+            url
+        ));
+        assert_eq!(response.status(), Status::Created);
+        let webhook: serde_json::Value = response.into_json().unwrap();
+        let secret = webhook["secret"].as_str().unwrap().to_owned();
+        let webhook_id = webhook["webhook_id"].as_str().unwrap().to_owned();
+        assert_eq!(webhook["events"], serde_json::json!(["clip.created", "clip.viewed", "clip.deleted"]));
+
+        let response = client
+            .post("/api/v1/clips")
+            .header(ContentType::JSON)
+            .header(key())
+            .body(r#"{"content":"hooked","title":"","expires":null,"password":null}"#)
+            .dispatch();
+        let clip: serde_json::Value = response.into_json().unwrap();
+        let shortcode = clip["shortcode"].as_str().unwrap().to_owned();
+
+        // The first attempt is turned down, and the delivery is retried as is.
+        let (first, first_body, _) = next_delivery();
+        let (retry, body, payload) = next_delivery();
+        assert_eq!(first.get(EVENT_HEADER).map(String::as_str), Some("clip.created"));
+        assert_eq!(first.get(DELIVERY_HEADER), retry.get(DELIVERY_HEADER));
+        assert_eq!(first_body, body);
+        assert_eq!(payload["shortcode"], shortcode.as_str());
+        assert_eq!(payload["clip"]["hits"], 0);
+        let timestamp: i64 = retry[TIMESTAMP_HEADER].parse().unwrap();
+        assert_eq!(retry[SIGNATURE_HEADER], signature(&secret, timestamp, &body));
+
+        // Views are sent at most once a minute, and the edit wasn't subscribed to.
+        let hit_counter = client.rocket().state::<HitCounter>().unwrap();
+        for _ in 0..2 {
+            assert_eq!(client.get(format!("/api/v1/clips/{}", shortcode)).header(key()).dispatch().status(), Status::Ok);
+            rt.block_on(hit_counter.flush());
+        }
+        let response = client
+            .put(format!("/api/v1/clips/{}", shortcode))
+            .header(ContentType::JSON)
+            .header(key())
+            .body(r#"{"content":"edited","title":"","expires":null,"password":null}"#)
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(client.delete(format!("/api/v1/clips/{}", shortcode)).header(key()).dispatch().status(), Status::Ok);
+        let (_, _, payload) = next_delivery();
+        assert_eq!((payload["event"].as_str(), payload["hits"].as_u64()), (Some("clip.viewed"), Some(1)));
+        let (_, _, payload) = next_delivery();
+        assert_eq!(payload["event"], "clip.deleted");
+
+        let log_uri = format!("/api/v1/webhooks/{}/deliveries", webhook_id);
+        let mut log = serde_json::Value::Null;
+        for _ in 0..100 {
+            log = client.get(&log_uri).header(key()).dispatch().into_json().unwrap();
+            if log.as_array().unwrap().iter().all(|delivery| !delivery["delivered"].is_null()) {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        let log = log.as_array().unwrap();
+        let events: Vec<_> = log.iter().map(|delivery| delivery["event"].as_str().unwrap()).collect();
+        assert_eq!(events, ["clip.deleted", "clip.viewed", "clip.created"]);
+        assert_eq!(log[2]["attempts"], 2);
+        assert_eq!(log[2]["status"], 200);
+        assert!(log[2]["next_attempt"].is_null());
+
+        let other = Header::new(super::API_KEY_HEADER, other_key);
+        assert_eq!(client.get(&log_uri).header(other.clone()).dispatch().status(), Status::NotFound);
+        let webhook_uri = format!("/api/v1/webhooks/{}", webhook_id);
+        assert_eq!(client.delete(&webhook_uri).header(other).dispatch().status(), Status::NotFound);
+        assert_eq!(client.delete(&webhook_uri).header(key()).dispatch().status(), Status::Ok);
+        let webhooks: serde_json::Value = client.get("/api/v1/webhooks").header(key()).dispatch().into_json().unwrap();
+        assert_eq!(webhooks, serde_json::json!([]));
+    }
+
+    #[test]
+    fn exports_own_clips_and_restores_them_once() {
+        use crate::domain::backup::{read_backup, RESTORE_BATCH_SIZE};
+        use crate::ShortCode;
+
+        let rt = async_runtime();
+        let client = client();
+        let db = client.rocket().state::<AppDatabase>().unwrap();
+        let (owner, other) = rt
+            .block_on(async move {
+                let owner = action::generate_api_key(&Scope::CLIPS, db.get_pool()).await?;
+                let other = action::generate_api_key(&Scope::CLIPS, db.get_pool()).await?;
+                Ok::<_, crate::ServiceError>((owner.to_base64(), other.to_base64()))
+            })
+            .unwrap();
+        let new_clip = |key: &str, body: &str| {
+            let response = client
+                .post("/api/v1/clips")
+                .header(ContentType::JSON)
+                .header(Header::new(super::API_KEY_HEADER, key.to_owned()))
+                .body(body)
+                .dispatch();
+            assert_eq!(response.status(), Status::Ok);
+        };
+        new_clip(&owner, r#"{"content":"backed up","title":"","expires":null,"password":"hunter2"}"#);
+        new_clip(&other, r#"{"content":"someone else's","title":"","expires":null,"password":null}"#);
+
+        let response = client
+            .get("/api/v1/clips/export")
+            .header(Header::new(super::API_KEY_HEADER, owner.clone()))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.content_type(), Some(ContentType::new("application", "x-ndjson")));
+        let exported = response.into_string().unwrap();
+        let lines: Vec<serde_json::Value> = exported.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0]["type"], "header");
+        assert_eq!(lines[1]["content"], "backed up");
+        assert!(lines[1]["password_hash"].as_str().unwrap().starts_with("$argon2"));
+        assert!(!exported.contains("hunter2"));
+        assert_eq!(lines[2], serde_json::json!({ "type": "end", "clips": 1 }));
+
+        let response = client
+            .get("/api/v1/clips/export/all")
+            .header(Header::new(super::API_KEY_HEADER, owner.clone()))
+            .dispatch();
+        assert_eq!(response.status(), Status::Forbidden);
+
+        // Restored as a new clip, whose password still unlocks it, and only once.
+        let mut clips = read_backup(&exported).unwrap();
+        let shortcode = ShortCode::new();
+        clips[0].clip_id = uuid::Uuid::new_v4().to_string();
+        clips[0].shortcode = shortcode.clone();
+        let (first, second) = rt
+            .block_on(async move {
+                let first = action::restore_clips(clips.clone(), RESTORE_BATCH_SIZE, db.get_pool()).await?;
+                let second = action::restore_clips(clips, RESTORE_BATCH_SIZE, db.get_pool()).await?;
+                Ok::<_, crate::ServiceError>((first, second))
+            })
+            .unwrap();
+        assert_eq!((first.restored, first.unchanged, first.skipped.len()), (1, 0, 0));
+        assert_eq!((second.restored, second.unchanged, second.skipped.len()), (0, 1, 0));
+
+        let get_clip = |password: &str| {
+            client
+                .get(format!("/api/v1/clips/{}", shortcode.as_str()))
+                .header(Header::new(super::API_KEY_HEADER, owner.clone()))
+                .header(Header::new(super::CLIP_PASSWORD_HEADER, password.to_owned()))
+                .dispatch()
+                .status()
+        };
+        assert_eq!(get_clip("hunter2"), Status::Ok);
+        assert_eq!(get_clip("hunter3"), Status::Forbidden);
+
+        // The restored clip still belongs to the key which created it, and only to that key.
+        assert_eq!(lines[1]["owner"].as_str().map(str::len), Some(64));
+        assert!(!exported.contains(&owner));
+        let listed = |key: &str| {
+            client
+                .get("/api/v1/clips")
+                .header(Header::new(super::API_KEY_HEADER, key.to_owned()))
+                .dispatch()
+                .into_string()
+                .unwrap()
+                .contains(shortcode.as_str())
+        };
+        assert!(listed(&owner));
+        assert!(!listed(&other));
+
+        // The hash is never sent back, and a client which saves it anyway keeps the password.
+        let uri = format!("/api/v1/clips/{}", shortcode.as_str());
+        let clip: serde_json::Value = client
+            .get(&uri)
+            .header(Header::new(super::API_KEY_HEADER, owner.clone()))
+            .header(Header::new(super::CLIP_PASSWORD_HEADER, "hunter2"))
+            .dispatch()
+            .into_json()
+            .unwrap();
+        assert!(clip.get("password").is_none());
+        let hash = lines[1]["password_hash"].as_str().unwrap();
+        let response = client
+            .put(&uri)
+            .header(ContentType::JSON)
+            .header(Header::new(super::API_KEY_HEADER, owner.clone()))
+            .body(serde_json::json!({ "content": "edited", "title": "", "expires": null, "password": hash }).to_string())
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(get_clip("hunter2"), Status::Ok);
+        assert_eq!(get_clip(hash), Status::Forbidden);
+    }
+
+    #[test]
+    fn passwords_looking_like_hashes_are_passwords() {
+        use crate::domain::clip::field::Password;
+
+        let rt = async_runtime();
+        let client = client();
+        let db = client.rocket().state::<AppDatabase>().unwrap();
+        let api_key = rt
+            .block_on(async move { action::generate_api_key(&Scope::CLIPS, db.get_pool()).await })
+            .unwrap()
+            .to_base64();
+        let key = || Header::new(super::API_KEY_HEADER, api_key.clone());
+        let phc = Password::new("secret".to_owned()).unwrap().hash().unwrap().unwrap();
+
+        for password in ["$argon2x", phc.as_str()].iter() {
+            let response = client
+                .post("/api/v1/clips")
+                .header(ContentType::JSON)
+                .header(key())
+                .body(serde_json::json!({ "content": "guarded", "title": "", "expires": null, "password": password }).to_string())
+                .dispatch();
+            assert_eq!(response.status(), Status::Ok);
+            let clip: serde_json::Value = response.into_json().unwrap();
+            let get_clip = |given: &str| {
+                client
+                    .get(format!("/api/v1/clips/{}", clip["shortcode"].as_str().unwrap()))
+                    .header(key())
+                    .header(Header::new(super::CLIP_PASSWORD_HEADER, given.to_owned()))
+                    .dispatch()
+                    .status()
+            };
+            assert_eq!(get_clip(password), Status::Ok);
+            assert_eq!(get_clip("secret"), Status::Forbidden);
+        }
+    }
+}
@@ -0,0 +1,223 @@
+//! Validators and caching headers of clip responses, and the conditional requests using them.
+use crate::domain::clip::field::Revision;
+use crate::domain::clip::RawClip;
+use crate::Clip;
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome};
+use rocket::response::{self, Responder};
+use rocket::{Request, Response};
+
+/// How long, in seconds, clips which don't expire sooner may be cached.
+pub const MAX_AGE: i64 = 3600;
+
+/// The format of HTTP dates, as in `Sun, 06 Nov 1994 08:49:37 GMT`.
+const HTTP_DATE: &str = "%a, %d %b %Y %H:%M:%S GMT";
+
+pub fn http_date(time: DateTime<Utc>) -> String {
+    time.format(HTTP_DATE).to_string()
+}
+
+fn parse_http_date(date: &str) -> Option<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(date.trim(), HTTP_DATE)
+        .ok()
+        .map(|date| Utc.from_utc_datetime(&date))
+}
+
+/// The entity tags listed in an `If-Match` or `If-None-Match` header. Weak tags are compared
+/// as if they were strong ones.
+fn etags(header: &str) -> Vec<String> {
+    header
+        .split(',')
+        .map(|etag| etag.trim().trim_start_matches("W/").to_owned())
+        .filter(|etag| !etag.is_empty())
+        .collect()
+}
+
+/// Who may keep a copy of a clip response.
+#[derive(Clone, Copy, Debug)]
+pub enum Audience {
+    /// Anyone, including shared caches. Only for clips served to anonymous visitors.
+    Public,
+    /// The client only, for clips served to logged in users or API keys.
+    Private
+}
+
+/// A clip which can be sent along with its [`Validators`].
+pub trait Cacheable {
+    fn etag(&self) -> String;
+    fn updated(&self) -> DateTime<Utc>;
+    fn expires(&self) -> Option<DateTime<Utc>>;
+    fn has_password(&self) -> bool;
+}
+
+impl Cacheable for Clip {
+    fn etag(&self) -> String {
+        Clip::etag(self)
+    }
+    fn updated(&self) -> DateTime<Utc> {
+        self.updated.clone().into_inner().into_inner()
+    }
+    fn expires(&self) -> Option<DateTime<Utc>> {
+        self.expires.clone().into_inner().map(|expires| expires.into_inner())
+    }
+    fn has_password(&self) -> bool {
+        self.password.has_password()
+    }
+}
+
+impl Cacheable for RawClip {
+    fn etag(&self) -> String {
+        RawClip::etag(self)
+    }
+    fn updated(&self) -> DateTime<Utc> {
+        self.updated.clone().into_inner().into_inner()
+    }
+    fn expires(&self) -> Option<DateTime<Utc>> {
+        self.expires.clone().into_inner().map(|expires| expires.into_inner())
+    }
+    fn has_password(&self) -> bool {
+        self.password.has_password()
+    }
+}
+
+/// The `Cache-Control` header of a clip. Password-protected clips are never stored, and the
+/// others may be cached until they expire, for up to [`MAX_AGE`].
+pub fn cache_control<C: Cacheable>(clip: &C, audience: Audience) -> String {
+    if clip.has_password() {
+        return "private, no-store".to_owned();
+    }
+    let audience = match audience {
+        Audience::Public => "public",
+        Audience::Private => "private"
+    };
+    let max_age = match clip.expires() {
+        Some(expires) => (expires - Utc::now()).num_seconds().clamp(0, MAX_AGE),
+        None => MAX_AGE
+    };
+    if max_age == 0 {
+        format!("{}, no-cache", audience)
+    } else {
+        format!("{}, max-age={}", audience, max_age)
+    }
+}
+
+/// Request guard for the `If-Match`, `If-None-Match` and `If-Modified-Since` headers.
+#[derive(Debug, Default)]
+pub struct Preconditions {
+    if_match: Option<Vec<String>>,
+    if_none_match: Option<Vec<String>>,
+    if_modified_since: Option<DateTime<Utc>>
+}
+
+impl Preconditions {
+    /// Whether the copy of a clip the client already has is still current, in which case it
+    /// gets a `304 Not Modified`. `If-None-Match` wins over `If-Modified-Since`, as in RFC 9110.
+    pub fn is_fresh(&self, validators: &Validators) -> bool {
+        match &self.if_none_match {
+            Some(etags) => etags.iter().any(|etag| etag == "*" || *etag == validators.etag),
+            None => self
+                .if_modified_since
+                .is_some_and(|since| validators.updated.timestamp() <= since.timestamp())
+        }
+    }
+
+    /// The revisions named by the `If-Match` header, which a clip must still be at to be
+    /// edited. `None` when there is no such header or it is `*`, so any revision will do.
+    pub fn revisions(&self) -> Option<Vec<Revision>> {
+        let etags = self.if_match.as_ref()?;
+        if etags.iter().any(|etag| etag == "*") {
+            return None;
+        }
+        Some(etags.iter().filter_map(|etag| Revision::from_etag(etag)).collect())
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Preconditions {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let headers = req.headers();
+        Outcome::Success(Self {
+            if_match: headers.get_one("If-Match").map(etags),
+            if_none_match: headers.get_one("If-None-Match").map(etags),
+            if_modified_since: headers.get_one("If-Modified-Since").and_then(parse_http_date)
+        })
+    }
+}
+
+/// The validators and caching policy of a clip, sent along with it.
+#[derive(Debug)]
+pub struct Validators {
+    etag: String,
+    updated: DateTime<Utc>,
+    cache_control: String
+}
+
+impl Validators {
+    pub fn new<C: Cacheable>(clip: &C, audience: Audience) -> Self {
+        Self {
+            etag: clip.etag(),
+            updated: clip.updated(),
+            cache_control: cache_control(clip, audience)
+        }
+    }
+
+    pub fn respond<R>(self, body: R) -> Cached<R> {
+        Cached { body: Some(body), validators: self }
+    }
+
+    /// Answers with a bodiless `304 Not Modified`, for clients whose copy is still current.
+    pub fn not_modified<R>(self) -> Cached<R> {
+        Cached { body: None, validators: self }
+    }
+}
+
+/// A clip response along with its [`Validators`], or a bodiless `304 Not Modified` when the
+/// client's copy is still current.
+pub struct Cached<R> {
+    body: Option<R>,
+    validators: Validators
+}
+
+impl<'r, 'o: 'r, R: Responder<'r, 'o>> Responder<'r, 'o> for Cached<R> {
+    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'o> {
+        let mut response = match self.body {
+            Some(body) => body.respond_to(req)?,
+            None => Response::build().status(Status::NotModified).finalize()
+        };
+        response.set_raw_header("ETag", self.validators.etag);
+        response.set_raw_header("Last-Modified", http_date(self.validators.updated));
+        response.set_raw_header("Cache-Control", self.validators.cache_control);
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::{http_date, parse_http_date, Preconditions};
+    use crate::domain::clip::field::Revision;
+
+    #[test]
+    fn reads_the_revisions_of_if_match() {
+        let preconditions = Preconditions {
+            if_match: Some(super::etags(r#""3-0a1b2c3d4e5f6071", W/"4-0a1b2c3d4e5f6071", "bogus""#)),
+            ..Default::default()
+        };
+        assert_eq!(preconditions.revisions(), Some(vec![Revision::new(3), Revision::new(4)]));
+        let any = Preconditions {
+            if_match: Some(super::etags("*")),
+            ..Default::default()
+        };
+        assert_eq!(any.revisions(), None);
+        assert_eq!(Preconditions::default().revisions(), None);
+    }
+
+    #[test]
+    fn round_trips_http_dates() {
+        let date = "Sun, 06 Nov 1994 08:49:37 GMT";
+        assert_eq!(http_date(parse_http_date(date).unwrap()), date);
+        assert!(parse_http_date("yesterday").is_none());
+    }
+}
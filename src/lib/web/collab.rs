@@ -0,0 +1,664 @@
+//! Editing a clip together over a WebSocket at `/clip/<shortcode>/collab`. Every viewer of a
+//! clip opens one and gets the current text, then every edit made to it. Editors send the edit
+//! token of the clip over the socket before sending their own edits.
+//!
+//! The messages are JSON objects told apart by their `type`, see [`ClientMessage`] and
+//! [`ServerMessage`]. The server holds the text being edited and is the authority on the order
+//! of the edits. It saves the text with the edit of a clip a little while after it changed.
+use crate::data::{AppDatabase, DatabasePool};
+use crate::domain::clip::field::{Content, EditToken};
+use crate::domain::collab::{CollabError, Document, Op};
+use crate::service::{action, ask};
+use crate::web::events::{viewer_request, visible_clip, ClipEvent, ClipEvents};
+use crate::web::request_id::RequestId;
+use crate::web::session::CurrentUser;
+use crate::{Clip, ServiceError, ShortCode};
+use chrono::Utc;
+use rocket::data::{IoHandler, IoStream};
+use rocket::futures::{SinkExt, StreamExt};
+use rocket::http::{CookieJar, Status};
+use rocket::request::{FromRequest, Outcome};
+use rocket::response::{self, Responder};
+use rocket::{Request, Response, Shutdown, State};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::runtime::Handle;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
+use tokio_tungstenite::tungstenite::protocol::{CloseFrame, Role};
+use tokio_tungstenite::tungstenite::{self, Message};
+use tokio_tungstenite::WebSocketStream;
+
+/// How long after being edited the text of a clip is saved.
+pub const SAVE_DELAY: Duration = Duration::from_secs(2);
+
+/// How long the text of a clip nobody edits is kept after it was saved.
+pub const IDLE_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// The text of a clip being edited, and its version.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Snapshot {
+    pub version: u64,
+    pub content: String
+}
+
+/// The operations an editor made on a version of the text. The `client` names the browser
+/// window they were made in, so it can tell its own edits from the others.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct EditRequest {
+    pub client: String,
+    pub version: u64,
+    pub ops: Vec<Op>
+}
+
+/// An edit as it was applied, which brought the text to `version`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Edit {
+    pub client: String,
+    pub version: u64,
+    pub ops: Vec<Op>
+}
+
+/// A message sent by a browser over the WebSocket.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ClientMessage {
+    /// Lets the connection edit the clip with its edit token.
+    Token { token: String },
+    /// Edits the clip, which needs a valid edit token to have been sent first.
+    Edit(EditRequest),
+    /// Asks for the current text again, such as after missing an edit.
+    Sync
+}
+
+/// A message sent to a browser over the WebSocket.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ServerMessage {
+    /// The current text, which the edits that follow apply to. Also sent when an edit of the
+    /// connection was turned down, after which the browser should start over from it.
+    Snapshot(Snapshot),
+    /// An edit made by any editor, including the connection itself.
+    Edit(Edit),
+    /// Something the connection sent was refused.
+    Error { message: String }
+}
+
+/// A clip being edited together.
+struct Session {
+    doc: Document,
+    /// The clip as it was last saved.
+    clip: Clip,
+    /// Who made the last edit. The text is saved on their behalf.
+    requester: ask::Requester,
+    /// The version of the text which was last saved.
+    saved: u64,
+    saving: bool,
+    touched: Instant
+}
+
+impl Session {
+    fn new(clip: Clip) -> Self {
+        // Versions are never reused, so edits made on a discarded session are turned down.
+        let version = Utc::now().timestamp_micros().max(0) as u64;
+        Self {
+            doc: Document::new(clip.content.as_str(), version),
+            clip,
+            requester: ask::Requester::Anonymous,
+            saved: version,
+            saving: false,
+            touched: Instant::now()
+        }
+    }
+
+    fn is_saved(&self) -> bool {
+        self.saved == self.doc.version()
+    }
+}
+
+type Sessions = Arc<Mutex<HashMap<ShortCode, Session>>>;
+
+enum CollabMsg {
+    /// Save the clip soon.
+    Edited(ShortCode),
+    /// Save every clip now and reply once they are saved.
+    Flush(oneshot::Sender<()>)
+}
+
+/// The clips being edited together.
+#[derive(Clone)]
+pub struct Collab {
+    sessions: Sessions,
+    events: ClipEvents,
+    tx: mpsc::Sender<CollabMsg>
+}
+
+impl Collab {
+    /// Spawns the task saving the edited clips on the runtime behind `handle`. Edits are sent
+    /// to the viewers of the clips through `events`.
+    pub fn new(pool: DatabasePool, handle: Handle, events: ClipEvents) -> Self {
+        let sessions = Sessions::default();
+        let (tx, rx) = mpsc::channel(1024);
+        handle.spawn(Self::process_msgs(rx, sessions.clone(), pool, events.clone()));
+        Self { sessions, events, tx }
+    }
+
+    /// The session of a clip just read from the database. The session starts over if the clip
+    /// was edited some other way since it was last saved, as the edit made outside wins.
+    fn session(sessions: &mut HashMap<ShortCode, Session>, clip: Clip) -> &mut Session {
+        let shortcode = clip.shortcode.clone();
+        let outdated = sessions.get(&shortcode).is_some_and(|session| {
+            !session.saving && session.clip.revision.into_inner() < clip.revision.into_inner()
+        });
+        if outdated {
+            sessions.remove(&shortcode);
+        }
+        sessions.entry(shortcode).or_insert_with(|| Session::new(clip))
+    }
+
+    /// The current text of a clip, which may not be saved yet.
+    pub fn snapshot(&self, clip: Clip) -> Snapshot {
+        let mut sessions = self.sessions.lock().expect("collab sessions poisoned");
+        let session = Self::session(&mut sessions, clip);
+        Snapshot { version: session.doc.version(), content: session.doc.text() }
+    }
+
+    /// Applies an edit to a clip and sends it to its viewers.
+    pub async fn edit(&self, clip: Clip, requester: ask::Requester, edit: EditRequest) -> Result<Edit, CollabError> {
+        let shortcode = clip.shortcode.clone();
+        let edit = {
+            let mut sessions = self.sessions.lock().expect("collab sessions poisoned");
+            let session = Self::session(&mut sessions, clip);
+            let ops = session.doc.apply(edit.version, &edit.ops)?;
+            session.requester = requester;
+            session.touched = Instant::now();
+            let edit = Edit { client: edit.client, version: session.doc.version(), ops };
+            // Sent while holding the lock, so the viewers get the edits in order.
+            self.events.edited(shortcode.clone(), edit.clone());
+            edit
+        };
+        if self.tx.send(CollabMsg::Edited(shortcode)).await.is_err() {
+            tracing::error!("collab task is gone, edits won't be saved");
+        }
+        Ok(edit)
+    }
+
+    /// Saves every edited clip and waits until they are saved.
+    pub async fn flush(&self) {
+        let (done_tx, done_rx) = oneshot::channel();
+        if self.tx.send(CollabMsg::Flush(done_tx)).await.is_ok() {
+            let _ = done_rx.await;
+        }
+    }
+
+    /// Saves the text of a clip with an edit of the clip. If the clip was edited some other way
+    /// since, or deleted, the session is dropped and the viewers are told to reload it.
+    async fn save(sessions: &Sessions, shortcode: ShortCode, pool: &DatabasePool, events: &ClipEvents) {
+        let (req, version) = {
+            let mut sessions = sessions.lock().expect("collab sessions poisoned");
+            let session = match sessions.get_mut(&shortcode) {
+                Some(session) if !session.is_saved() => session,
+                _ => return
+            };
+            let content = match Content::new(&session.doc.text()) {
+                Ok(content) => content,
+                // Clips can't be empty, so keep the last text until there is some again.
+                Err(_) => return
+            };
+            session.saving = true;
+            let req = ask::UpdateClip {
+                shortcode: shortcode.clone(),
+                content,
+                title: session.clip.title.clone(),
+                expires: session.clip.expires.clone(),
+                password: session.clip.password.clone(),
+                requester: session.requester.clone(),
+                revisions: Some(vec![session.clip.revision])
+            };
+            (req, session.doc.version())
+        };
+
+        let result = action::update_clip_with_token(req, pool).await;
+        let mut sessions = sessions.lock().expect("collab sessions poisoned");
+        match result {
+            Ok(clip) => {
+                if let Some(session) = sessions.get_mut(&shortcode) {
+                    session.clip = clip;
+                    session.saved = version;
+                    session.saving = false;
+                }
+            },
+            Err(e) => {
+                match e {
+                    ServiceError::PreconditionFailed | ServiceError::Gone | ServiceError::NotFound => (),
+                    e => tracing::error!(error = %e, shortcode = shortcode.as_str(), "failed to save edited clip")
+                }
+                sessions.remove(&shortcode);
+                events.updated(shortcode);
+            }
+        }
+    }
+
+    async fn process_msgs(
+        mut rx: mpsc::Receiver<CollabMsg>,
+        sessions: Sessions,
+        pool: DatabasePool,
+        events: ClipEvents
+    ) {
+        let mut due: HashMap<ShortCode, Instant> = HashMap::new();
+        let mut interval = tokio::time::interval(SAVE_DELAY / 4);
+
+        loop {
+            tokio::select! {
+                msg = rx.recv() => match msg {
+                    Some(CollabMsg::Edited(shortcode)) => {
+                        due.entry(shortcode).or_insert_with(|| Instant::now() + SAVE_DELAY);
+                    },
+                    Some(CollabMsg::Flush(done)) => {
+                        for (shortcode, _) in due.drain() {
+                            Self::save(&sessions, shortcode, &pool, &events).await;
+                        }
+                        let _ = done.send(());
+                    },
+                    // The `Collab` is gone, so save what is left and stop.
+                    None => {
+                        for (shortcode, _) in due.drain() {
+                            Self::save(&sessions, shortcode, &pool, &events).await;
+                        }
+                        break;
+                    }
+                },
+                _ = interval.tick() => {
+                    let now = Instant::now();
+                    let ready: Vec<ShortCode> = due
+                        .iter()
+                        .filter(|(_, deadline)| **deadline <= now)
+                        .map(|(shortcode, _)| shortcode.clone())
+                        .collect();
+                    for shortcode in ready {
+                        due.remove(&shortcode);
+                        Self::save(&sessions, shortcode, &pool, &events).await;
+                    }
+                    sessions
+                        .lock()
+                        .expect("collab sessions poisoned")
+                        .retain(|_, session| !session.is_saved() || session.touched.elapsed() < IDLE_TIMEOUT);
+                }
+            }
+        }
+    }
+}
+
+/// Request guard for the key of a WebSocket handshake, failing with a 426 for requests which
+/// don't ask to upgrade the connection.
+pub struct WebSocketKey(String);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for WebSocketKey {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let upgrade = req.headers().get_one("Upgrade").is_some_and(|upgrade| upgrade.eq_ignore_ascii_case("websocket"));
+        match req.headers().get_one("Sec-WebSocket-Key") {
+            Some(key) if upgrade => Outcome::Success(Self(key.to_owned())),
+            _ => Outcome::Error((Status::UpgradeRequired, ()))
+        }
+    }
+}
+
+/// The WebSocket of a viewer of a clip being edited together.
+pub struct CollabSocket {
+    accept: String,
+    connection: Connection
+}
+
+impl<'r> Responder<'r, 'static> for CollabSocket {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+        Response::build()
+            .raw_header("Sec-WebSocket-Accept", self.accept)
+            .upgrade("websocket", self.connection)
+            .ok()
+    }
+}
+
+/// Why a WebSocket was closed by the server.
+enum Closed {
+    /// The clip was deleted or can't be viewed any more.
+    Gone,
+    /// The viewer went away or the server is shutting down.
+    Done
+}
+
+/// What a viewer's WebSocket needs once the request it was opened with is gone.
+struct Connection {
+    req: ask::GetClip,
+    events: broadcast::Receiver<ClipEvent>,
+    collab: Collab,
+    pool: DatabasePool,
+    shutdown: Shutdown,
+    token: Option<EditToken>
+}
+
+type Socket = WebSocketStream<IoStream>;
+
+impl Connection {
+    async fn send(socket: &mut Socket, msg: &ServerMessage) -> Result<(), tungstenite::Error> {
+        let text = serde_json::to_string(msg).expect("server messages always serialize");
+        socket.send(Message::Text(text)).await
+    }
+
+    fn error(message: &str) -> ServerMessage {
+        ServerMessage::Error { message: message.to_owned() }
+    }
+
+    /// The current text of the clip, or `None` once it can't be viewed.
+    async fn snapshot(&self) -> Option<ServerMessage> {
+        match action::get_clip(self.req.clone(), &self.pool).await {
+            Ok(clip) => Some(ServerMessage::Snapshot(self.collab.snapshot(clip))),
+            Err(ServiceError::NotFound) | Err(ServiceError::Gone) | Err(ServiceError::PermissionError(_))
+            | Err(ServiceError::Workspace(_)) => None,
+            Err(e) => {
+                tracing::error!(error = %e, "failed to get clip");
+                None
+            }
+        }
+    }
+
+    /// Whether the edit token of the connection is still the one of the clip. Minting a new
+    /// token revokes the previous one, so it is checked with every edit.
+    async fn can_edit(&self) -> bool {
+        let token = match &self.token {
+            Some(token) => token,
+            None => return false
+        };
+        match action::is_edit_token(&self.req.shortcode, token, &self.pool).await {
+            Ok(valid) => valid,
+            Err(e) => {
+                tracing::error!(error = %e, "failed to check edit token");
+                false
+            }
+        }
+    }
+
+    /// Answers a message of the browser. Edits which are applied come back to every viewer
+    /// through the clip events, so they aren't answered here.
+    async fn received(&mut self, text: &str) -> Result<Option<ServerMessage>, Closed> {
+        let msg = match serde_json::from_str::<ClientMessage>(text) {
+            Ok(msg) => msg,
+            Err(e) => return Ok(Some(Self::error(&e.to_string())))
+        };
+        match msg {
+            ClientMessage::Token { token } => {
+                self.token = token.parse().ok();
+                if self.can_edit().await {
+                    Ok(None)
+                } else {
+                    self.token = None;
+                    Ok(Some(Self::error("Invalid edit token")))
+                }
+            },
+            ClientMessage::Edit(edit) => {
+                if !self.can_edit().await {
+                    self.token = None;
+                    return Ok(Some(Self::error("Invalid edit token")));
+                }
+                let clip = match action::get_clip(self.req.clone(), &self.pool).await {
+                    Ok(clip) => clip,
+                    Err(_) => return Err(Closed::Gone)
+                };
+                match self.collab.edit(clip.clone(), self.req.requester.clone(), edit).await {
+                    Ok(_) => Ok(None),
+                    Err(_) => Ok(Some(ServerMessage::Snapshot(self.collab.snapshot(clip))))
+                }
+            },
+            ClientMessage::Sync => self.snapshot().await.map(Some).ok_or(Closed::Gone)
+        }
+    }
+
+    async fn run(&mut self, socket: &mut Socket) -> Result<Closed, tungstenite::Error> {
+        let shortcode = self.req.shortcode.clone();
+        match self.snapshot().await {
+            Some(snapshot) => Self::send(socket, &snapshot).await?,
+            None => return Ok(Closed::Gone)
+        }
+        let mut shutdown = self.shutdown.clone();
+        loop {
+            let reply = tokio::select! {
+                msg = socket.next() => match msg {
+                    Some(Ok(Message::Text(text))) => match self.received(&text).await {
+                        Ok(reply) => reply,
+                        Err(closed) => return Ok(closed)
+                    },
+                    Some(Ok(Message::Close(_))) | None => return Ok(Closed::Done),
+                    // Pings are answered by the socket itself.
+                    Some(Ok(_)) => None,
+                    Some(Err(e)) => return Err(e)
+                },
+                event = self.events.recv() => match event {
+                    Ok(ClipEvent::Edited(edited, edit)) if edited == shortcode => Some(ServerMessage::Edit(edit)),
+                    // The clip was edited some other way, which starts the text over.
+                    Ok(ClipEvent::Updated(updated)) if updated == shortcode => match self.snapshot().await {
+                        Some(snapshot) => Some(snapshot),
+                        None => return Ok(Closed::Gone)
+                    },
+                    Ok(_) => None,
+                    // Some edits were missed, so start over from the current text.
+                    Err(RecvError::Lagged(_)) => match self.snapshot().await {
+                        Some(snapshot) => Some(snapshot),
+                        None => return Ok(Closed::Gone)
+                    },
+                    Err(RecvError::Closed) => return Ok(Closed::Done)
+                },
+                _ = &mut shutdown => return Ok(Closed::Done)
+            };
+            if let Some(reply) = reply {
+                Self::send(socket, &reply).await?;
+            }
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl IoHandler for Connection {
+    async fn io(self: Pin<Box<Self>>, io: IoStream) -> std::io::Result<()> {
+        let mut connection = Pin::into_inner(self);
+        let mut socket = WebSocketStream::from_raw_socket(io, Role::Server, None).await;
+        let closed = connection.run(&mut socket).await;
+        let frame = match closed {
+            Ok(Closed::Gone) => Some(CloseFrame { code: 4404u16.into(), reason: "The clip is gone".into() }),
+            Ok(Closed::Done) => None,
+            // The connection is already broken, so there is nobody to tell.
+            Err(_) => return Ok(())
+        };
+        let _ = socket.close(frame).await;
+        Ok(())
+    }
+}
+
+/// Opens the WebSocket to edit a clip together, or to watch it being edited. Plain requests
+/// get a 426, and the clip must be visible with the credentials the socket is opened with.
+#[rocket::get("/clip/<shortcode>/collab", rank = 2)]
+#[tracing::instrument(skip_all, fields(request_id = %request_id, shortcode = shortcode.as_str()))]
+#[allow(clippy::too_many_arguments)]
+pub async fn collab_socket(
+    request_id: &RequestId,
+    cookies: &CookieJar<'_>,
+    shortcode: ShortCode,
+    user: Option<CurrentUser>,
+    key: WebSocketKey,
+    database: &State<AppDatabase>,
+    events: &State<ClipEvents>,
+    collab: &State<Collab>,
+    shutdown: Shutdown
+) -> Result<CollabSocket, Status> {
+    let req = viewer_request(cookies, shortcode, user);
+    // Subscribe before the socket is opened, so no edit made in between is missed.
+    let events = events.subscribe();
+    visible_clip(req.clone(), database).await?;
+    Ok(CollabSocket {
+        accept: derive_accept_key(key.0.as_bytes()),
+        connection: Connection {
+            req,
+            events,
+            collab: collab.inner().clone(),
+            pool: database.get_pool().clone(),
+            shutdown,
+            token: None
+        }
+    })
+}
+
+pub fn routes() -> Vec<rocket::Route> {
+    rocket::routes![collab_socket]
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::{ServerMessage, Snapshot};
+    use crate::data::AppDatabase;
+    use crate::domain::scope::Scope;
+    use crate::service::action;
+    use crate::test::async_runtime;
+    use crate::web::api::API_KEY_HEADER;
+    use crate::web::test::{client, config_with};
+    use rocket::futures::{SinkExt, StreamExt};
+    use rocket::http::{ContentType, Header, Status};
+    use std::time::Duration;
+    use tokio_tungstenite::tungstenite::Message;
+    use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+
+    type Socket = WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>;
+
+    async fn connect(url: &str) -> Socket {
+        // The server may still be starting up.
+        for _ in 0..50 {
+            if let Ok((socket, _)) = connect_async(url).await {
+                return socket;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        panic!("failed to connect to {}", url);
+    }
+
+    async fn send(socket: &mut Socket, msg: String) {
+        socket.send(Message::Text(msg)).await.unwrap();
+    }
+
+    async fn receive(socket: &mut Socket) -> ServerMessage {
+        loop {
+            let msg = tokio::time::timeout(Duration::from_secs(10), socket.next())
+                .await
+                .expect("no message from the server")
+                .unwrap()
+                .unwrap();
+            if let Message::Text(text) = msg {
+                return serde_json::from_str(&text).unwrap();
+            }
+        }
+    }
+
+    async fn snapshot(socket: &mut Socket) -> Snapshot {
+        match receive(socket).await {
+            ServerMessage::Snapshot(snapshot) => snapshot,
+            msg => panic!("expected a snapshot, got {:?}", msg)
+        }
+    }
+
+    async fn edit(socket: &mut Socket) -> serde_json::Value {
+        match receive(socket).await {
+            ServerMessage::Edit(edit) => serde_json::to_value(edit).unwrap(),
+            msg => panic!("expected an edit, got {:?}", msg)
+        }
+    }
+
+    #[test]
+    fn concurrent_edits_are_merged_and_saved() {
+        let rt = async_runtime();
+        let client = client();
+        let database = client.rocket().state::<AppDatabase>().unwrap().clone();
+        let pool = database.get_pool().clone();
+        let api_key = rt
+            .block_on(async move { action::generate_api_key(&Scope::CLIPS, &pool).await })
+            .unwrap()
+            .to_base64();
+        let key = || Header::new(API_KEY_HEADER, api_key.clone());
+
+        let response = client
+            .post("/api/v1/clips")
+            .header(ContentType::JSON)
+            .header(key())
+            .body(r#"{"content":"hello world","title":"","expires":null,"password":null}"#)
+            .dispatch();
+        let clip: serde_json::Value = response.into_json().unwrap();
+        let shortcode = clip["shortcode"].as_str().unwrap().to_owned();
+        let response = client
+            .post(format!("/api/v1/clips/{}/edit-token", shortcode))
+            .header(key())
+            .dispatch();
+        assert_eq!(response.status(), Status::Created);
+        let token: String = response.into_json().unwrap();
+
+        assert_eq!(client.get(format!("/clip/{}/collab", shortcode)).dispatch().status(), Status::UpgradeRequired);
+
+        // The WebSocket needs a real connection, so serve the same database on a free port.
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let config = config_with(database);
+        let collab = config.collab.clone();
+        let figment = rocket::Config::figment().merge(("port", port)).merge(("log_level", "off"));
+        rt.spawn(crate::rocket(config).configure(figment).launch());
+        let url = format!("ws://127.0.0.1:{}/clip/{}/collab", port, shortcode);
+
+        rt.block_on(async move {
+            let mut viewer = connect(&url).await;
+            let mut editor = connect(&url).await;
+            let version = snapshot(&mut viewer).await.version;
+            let first = snapshot(&mut editor).await;
+            assert_eq!(first.content, "hello world");
+            assert_eq!(first.version, version);
+
+            let insert = format!(r#"{{"type":"edit","client":"a","version":{},"ops":[{{"type":"insert","pos":5,"text":","}}]}}"#, version);
+            send(&mut editor, insert.clone()).await;
+            assert!(matches!(receive(&mut editor).await, ServerMessage::Error { .. }));
+            send(&mut editor, r#"{"type":"token","token":"bm90IHRoZSB0b2tlbg"}"#.to_owned()).await;
+            assert!(matches!(receive(&mut editor).await, ServerMessage::Error { .. }));
+
+            send(&mut editor, format!(r#"{{"type":"token","token":"{}"}}"#, token)).await;
+            send(&mut editor, insert).await;
+            assert_eq!(edit(&mut editor).await["version"], version + 1);
+            assert_eq!(edit(&mut viewer).await["version"], version + 1);
+
+            // Made on the same version as the first edit, so it is moved past the comma.
+            send(
+                &mut editor,
+                format!(r#"{{"type":"edit","client":"b","version":{},"ops":[{{"type":"insert","pos":11,"text":"!"}}]}}"#, version)
+            )
+            .await;
+            assert_eq!(edit(&mut editor).await["ops"][0]["pos"], 12);
+            assert_eq!(edit(&mut viewer).await["ops"][0]["pos"], 12);
+
+            // An edit on a version which doesn't exist yet starts the editor over.
+            send(
+                &mut editor,
+                format!(r#"{{"type":"edit","client":"b","version":{},"ops":[{{"type":"delete","pos":0,"len":1}}]}}"#, version + 5)
+            )
+            .await;
+            let current = snapshot(&mut editor).await;
+            assert_eq!(current.content, "hello, world!");
+            assert_eq!(current.version, version + 2);
+
+            collab.flush().await;
+        });
+
+        let clip: serde_json::Value = client
+            .get(format!("/api/v1/clips/{}", shortcode))
+            .header(key())
+            .dispatch()
+            .into_json()
+            .unwrap();
+        assert_eq!(clip["content"], "hello, world!");
+    }
+}
@@ -8,13 +8,10 @@ pub trait PageContext {
     fn template_path(&self) -> &str;
     fn parent(&self) -> &str;
 }
-#[derive(Debug, Serialize)]
-pub struct Home {}
-
-impl Default for Home {
-    fn default() -> Self {
-        Self {}
-    }
+/// The home page, which lets logged in users post clips only visible to one of their workspaces.
+#[derive(Debug, Serialize, Constructor, Default)]
+pub struct Home {
+    workspaces: Vec<crate::domain::workspace::Membership>
 }
 
 impl PageContext for Home {
@@ -61,4 +58,128 @@ impl PageContext for PasswordRequired {
     fn parent(&self) -> &str {
         "base"
     }
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Serialize, Constructor)]
+pub struct ViewClipStats {
+    shortcode: crate::ShortCode,
+    stats: crate::domain::stats::ClipStats
+}
+
+impl PageContext for ViewClipStats {
+    fn title(&self) -> &str {
+        "Clip stats"
+    }
+    fn template_path(&self) -> &str {
+        "clip_stats"
+    }
+    fn parent(&self) -> &str {
+        "base"
+    }
+}
+
+#[derive(Debug, Serialize, Default)]
+pub struct Register {}
+
+impl PageContext for Register {
+    fn title(&self) -> &str {
+        "Register"
+    }
+    fn template_path(&self) -> &str {
+        "register"
+    }
+    fn parent(&self) -> &str {
+        "base"
+    }
+}
+
+/// The login page, which links to single sign-on when it is configured.
+#[derive(Debug, Serialize, Constructor)]
+pub struct Login {
+    sso: bool
+}
+
+impl PageContext for Login {
+    fn title(&self) -> &str {
+        "Log in"
+    }
+    fn template_path(&self) -> &str {
+        "login"
+    }
+    fn parent(&self) -> &str {
+        "base"
+    }
+}
+
+/// The clips and API keys of the logged in user. A newly minted API key is only shown once.
+#[derive(Debug, Serialize, Constructor)]
+pub struct Dashboard {
+    username: crate::domain::user::field::Username,
+    clips: crate::domain::clip::ClipList,
+    api_keys: Vec<crate::domain::user::ApiKeyInfo>,
+    new_api_key: Option<String>,
+    workspaces: Vec<crate::domain::workspace::Membership>
+}
+
+impl PageContext for Dashboard {
+    fn title(&self) -> &str {
+        "My clips"
+    }
+    fn template_path(&self) -> &str {
+        "dashboard"
+    }
+    fn parent(&self) -> &str {
+        "base"
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct EditClip {
+    clip: crate::Clip,
+    expires: Option<String>
+}
+
+impl EditClip {
+    pub fn new(clip: crate::Clip) -> Self {
+        let expires = clip.expires
+            .clone()
+            .into_inner()
+            .map(|time| time.into_inner().format("%Y-%m-%d").to_string());
+        Self { clip, expires }
+    }
+}
+
+impl PageContext for EditClip {
+    fn title(&self) -> &str {
+        "Edit clip"
+    }
+    fn template_path(&self) -> &str {
+        "edit_clip"
+    }
+    fn parent(&self) -> &str {
+        "base"
+    }
+}
+
+/// A workspace as seen by one of its members. Only owners get to see and mint its API keys, and
+/// a newly minted API key is only shown once.
+#[derive(Debug, Serialize, Constructor)]
+pub struct Workspace {
+    workspace: crate::domain::workspace::Membership,
+    clips: crate::domain::clip::ClipList,
+    members: Vec<crate::domain::workspace::Member>,
+    api_keys: Vec<crate::domain::user::ApiKeyInfo>,
+    new_api_key: Option<String>
+}
+
+impl PageContext for Workspace {
+    fn title(&self) -> &str {
+        "Workspace"
+    }
+    fn template_path(&self) -> &str {
+        "workspace"
+    }
+    fn parent(&self) -> &str {
+        "base"
+    }
+}
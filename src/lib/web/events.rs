@@ -0,0 +1,257 @@
+//! Live updates of clips, pushed to the browsers viewing them as Server-Sent Events.
+use crate::data::AppDatabase;
+use crate::domain::clip::field;
+use crate::service::{action, ask};
+use crate::web::collab::Edit;
+use crate::web::cookie_password;
+use crate::web::request_id::RequestId;
+use crate::web::session::CurrentUser;
+use crate::{ServiceError, ShortCode};
+use rocket::http::{CookieJar, Status};
+use rocket::futures::stream::{BoxStream, StreamExt};
+use rocket::response::stream::{stream, Event, EventStream};
+use rocket::{Shutdown, State};
+use serde::Serialize;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+/// How many events can be waiting for the slowest subscriber before it misses some.
+pub const CAPACITY: usize = 1024;
+
+/// Something which changed about a clip.
+#[derive(Clone, Debug)]
+pub enum ClipEvent {
+    /// The clip was edited or deleted.
+    Updated(ShortCode),
+    /// The hit count of the clip is now the given total.
+    Hits(ShortCode, u64),
+    /// The clip is being edited together, and this edit was just made.
+    Edited(ShortCode, Edit)
+}
+
+impl ClipEvent {
+    fn shortcode(&self) -> &ShortCode {
+        match self {
+            ClipEvent::Updated(shortcode) | ClipEvent::Hits(shortcode, _) | ClipEvent::Edited(shortcode, _) => shortcode
+        }
+    }
+}
+
+/// Broadcasts the [`ClipEvent`]s to every open event stream.
+#[derive(Clone, Debug)]
+pub struct ClipEvents {
+    tx: broadcast::Sender<ClipEvent>
+}
+
+impl ClipEvents {
+    pub fn new(capacity: usize) -> Self {
+        let (tx, _) = broadcast::channel(capacity);
+        Self { tx }
+    }
+
+    /// Tells the viewers of a clip it was edited or deleted.
+    pub fn updated(&self, shortcode: ShortCode) {
+        // Sending only fails when nobody is listening.
+        let _ = self.tx.send(ClipEvent::Updated(shortcode));
+    }
+
+    /// Tells the viewers of a clip how many hits it has now.
+    pub fn hits(&self, shortcode: ShortCode, hits: u64) {
+        let _ = self.tx.send(ClipEvent::Hits(shortcode, hits));
+    }
+
+    /// Tells the viewers of a clip edited together about an edit.
+    pub fn edited(&self, shortcode: ShortCode, edit: Edit) {
+        let _ = self.tx.send(ClipEvent::Edited(shortcode, edit));
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<ClipEvent> {
+        self.tx.subscribe()
+    }
+}
+
+impl Default for ClipEvents {
+    fn default() -> Self {
+        Self::new(CAPACITY)
+    }
+}
+
+/// The events sent to the viewers of a clip.
+pub type ClipEventStream = EventStream<BoxStream<'static, Event>>;
+
+/// The part of a clip sent to its viewers when it is edited.
+#[derive(Debug, Serialize)]
+struct ClipUpdate {
+    revision: field::Revision,
+    title: field::Title,
+    content: field::Content,
+    expires: field::Expires,
+    hits: field::Hits
+}
+
+impl From<crate::Clip> for ClipUpdate {
+    fn from(clip: crate::Clip) -> Self {
+        Self {
+            revision: clip.revision,
+            title: clip.title,
+            content: clip.content,
+            expires: clip.expires,
+            hits: clip.hits
+        }
+    }
+}
+
+/// The request to view a clip in the browser, with the password stored in the cookies.
+pub fn viewer_request(cookies: &CookieJar<'_>, shortcode: ShortCode, user: Option<CurrentUser>) -> ask::GetClip {
+    ask::GetClip {
+        shortcode,
+        password: cookie_password(cookies),
+        requester: match user {
+            Some(CurrentUser(user)) => ask::Requester::User(user.user_id),
+            None => ask::Requester::Anonymous
+        }
+    }
+}
+
+/// Gets a clip for the live views of the browser, answering with the status of the error when
+/// it can't be viewed.
+pub async fn visible_clip(req: ask::GetClip, database: &AppDatabase) -> Result<crate::Clip, Status> {
+    match action::get_clip(req, database.get_pool()).await {
+        Ok(clip) => Ok(clip),
+        Err(ServiceError::NotFound) => Err(Status::NotFound),
+        Err(ServiceError::Gone) => Err(Status::Gone),
+        Err(ServiceError::PermissionError(_)) | Err(ServiceError::Workspace(_)) => Err(Status::Unauthorized),
+        Err(e) => {
+            tracing::error!(error = %e, "failed to get clip");
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+/// Streams the changes of a clip: an `update` event with the edited clip, a `hits` event with
+/// the new hit count, or a `closed` event when the clip is deleted or no longer visible with
+/// the credentials the stream was opened with. Opening the stream doesn't count as a view.
+/// Edits made together go over the WebSocket of [`crate::web::collab`] instead.
+#[rocket::get("/clip/<shortcode>/events", rank = 2)]
+#[tracing::instrument(skip_all, fields(request_id = %request_id, shortcode = shortcode.as_str()))]
+pub async fn clip_events(
+    request_id: &RequestId,
+    cookies: &CookieJar<'_>,
+    shortcode: ShortCode,
+    user: Option<CurrentUser>,
+    database: &State<AppDatabase>,
+    events: &State<ClipEvents>,
+    mut shutdown: Shutdown
+) -> Result<ClipEventStream, Status> {
+    let req = viewer_request(cookies, shortcode.clone(), user);
+    let watched = shortcode;
+    // Subscribe before checking the clip, so no edit made in between is missed.
+    let mut rx = events.subscribe();
+    visible_clip(req.clone(), database).await?;
+
+    let pool = database.get_pool().clone();
+    let stream = stream! {
+        loop {
+            let event = tokio::select! {
+                event = rx.recv() => event,
+                _ = &mut shutdown => break
+            };
+            let reload = match event {
+                Ok(ClipEvent::Hits(shortcode, hits)) if shortcode == watched => {
+                    yield Event::data(hits.to_string()).event("hits");
+                    false
+                },
+                Ok(ClipEvent::Edited(..)) => false,
+                Ok(event) => *event.shortcode() == watched,
+                // Some events were missed, which may include an edit of this clip.
+                Err(RecvError::Lagged(_)) => true,
+                Err(RecvError::Closed) => break
+            };
+            if reload {
+                match action::get_clip(req.clone(), &pool).await {
+                    Ok(clip) => yield Event::json(&ClipUpdate::from(clip)).event("update"),
+                    Err(_) => {
+                        yield Event::empty().event("closed");
+                        break;
+                    }
+                }
+            }
+        }
+    };
+    Ok(EventStream::from(stream.boxed()))
+}
+
+pub fn routes() -> Vec<rocket::Route> {
+    rocket::routes![clip_events]
+}
+
+#[cfg(test)]
+pub mod test {
+    use crate::data::AppDatabase;
+    use crate::domain::scope::Scope;
+    use crate::service::action;
+    use crate::test::async_runtime;
+    use crate::web::api::API_KEY_HEADER;
+    use crate::web::test::client;
+    use crate::web::{HitCounter, PASSWORD_COOKIE};
+    use rocket::http::{ContentType, Cookie, Header, Status};
+    use std::io::Read;
+
+    /// Reads the stream until the given event comes through.
+    fn read_until(stream: &mut impl Read, event: &str) -> String {
+        let mut received = String::new();
+        let mut buffer = [0; 4096];
+        while !received.contains(event) {
+            let read = stream.read(&mut buffer).expect("failed to read event stream");
+            assert_ne!(read, 0, "stream ended before {:?}, got {:?}", event, received);
+            received.push_str(&String::from_utf8_lossy(&buffer[..read]));
+        }
+        received
+    }
+
+    #[test]
+    fn streams_edits_and_hits_of_the_clip() {
+        let rt = async_runtime();
+        let client = client();
+        let db = client.rocket().state::<AppDatabase>().unwrap();
+        let api_key = rt
+            .block_on(async move { action::generate_api_key(&Scope::CLIPS, db.get_pool()).await })
+            .unwrap()
+            .to_base64();
+        let key = || Header::new(API_KEY_HEADER, api_key.clone());
+
+        let response = client
+            .post("/api/v1/clips")
+            .header(ContentType::JSON)
+            .header(key())
+            .body(r#"{"content":"before","title":"","expires":null,"password":"secret"}"#)
+            .dispatch();
+        let clip: serde_json::Value = response.into_json().unwrap();
+        let shortcode = clip["shortcode"].as_str().unwrap().to_owned();
+        let uri = format!("/clip/{}/events", shortcode);
+
+        assert_eq!(client.get(&uri).dispatch().status(), Status::Unauthorized);
+        assert_eq!(client.get("/clip/nope/events").dispatch().status(), Status::NotFound);
+
+        let mut stream = client.get(&uri).cookie(Cookie::new(PASSWORD_COOKIE, "secret")).dispatch();
+        assert_eq!(stream.status(), Status::Ok);
+
+        let response = client
+            .put(format!("/api/v1/clips/{}", shortcode))
+            .header(ContentType::JSON)
+            .header(key())
+            .body(r#"{"content":"after","title":"","expires":null,"password":"secret"}"#)
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let received = read_until(&mut stream, r#""content":"after""#);
+        assert!(received.contains("event:update\n"), "got {:?}", received);
+
+        let response = client
+            .get(format!("/clip/raw/{}", shortcode))
+            .cookie(Cookie::new(PASSWORD_COOKIE, "secret"))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let hit_counter = client.rocket().state::<HitCounter>().unwrap();
+        rt.block_on(hit_counter.flush());
+        read_until(&mut stream, "event:hits\ndata:1\n\n");
+    }
+}
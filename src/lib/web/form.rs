@@ -1,4 +1,7 @@
 use crate::domain::clip::field;
+use crate::domain::scope::Scope;
+use crate::domain::user::field::{UserPassword, Username};
+use crate::domain::workspace::field::WorkspaceName;
 use rocket::form::FromForm;
 use serde::Serialize;
 
@@ -7,7 +10,9 @@ pub struct NewClip {
     pub content: field::Content,
     pub title: field::Title,
     pub expires: field::Expires,
-    pub password: field::Password
+    pub password: field::Password,
+    /// The workspace the clip is only visible to, empty for a public clip.
+    pub workspace: Option<String>
 }
 
 #[derive(Debug, Serialize, FromForm)]
@@ -15,3 +20,50 @@ pub struct GetPasswordProtectedClip {
     pub password: field::Password
 }
 
+#[derive(Debug, FromForm)]
+pub struct Register {
+    pub username: Username,
+    pub password: UserPassword,
+    pub confirm_password: String
+}
+
+impl Register {
+    pub fn password_matches(&self) -> bool {
+        self.password.as_str() == self.confirm_password
+    }
+}
+
+#[derive(Debug, FromForm)]
+pub struct Login {
+    pub username: String,
+    pub password: String
+}
+
+#[derive(Debug, FromForm)]
+pub struct NewWorkspace {
+    pub name: WorkspaceName
+}
+
+#[derive(Debug, FromForm)]
+pub struct AddMember {
+    pub username: String
+}
+
+/// The scopes of a new API key, ticked on the dashboard or the page of a workspace.
+#[derive(Debug, FromForm)]
+pub struct NewApiKey {
+    #[field(name = "scope")]
+    pub scopes: Vec<Scope>
+}
+
+
+/// A slash command POSTed by a chat server. Slack and Mattermost send more fields, which are
+/// ignored.
+#[derive(Debug, FromForm)]
+pub struct SlashCommand {
+    /// The token of the command, which Mattermost sends instead of signing it.
+    pub token: Option<String>,
+    pub user_name: String,
+    pub command: String,
+    pub text: String
+}
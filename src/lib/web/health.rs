@@ -0,0 +1,143 @@
+use crate::data::{AppDatabase, SCHEMA_VERSION};
+use crate::domain::maintenance::Maintenance;
+use crate::domain::webhook::WebhookDispatcher;
+use crate::service::action;
+use crate::web::HitCounter;
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use rocket::State;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    Ok,
+    Unavailable
+}
+
+/// The status of a single component of the server.
+#[derive(Debug, Serialize)]
+pub struct ComponentHealth {
+    pub status: HealthStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>
+}
+
+impl ComponentHealth {
+    fn ok() -> Self {
+        Self { status: HealthStatus::Ok, message: None }
+    }
+
+    fn unavailable<M: Into<String>>(message: M) -> Self {
+        Self { status: HealthStatus::Unavailable, message: Some(message.into()) }
+    }
+}
+
+/// The overall status of the server, which is only `ok` when every component is.
+#[derive(Debug, Serialize)]
+pub struct Health {
+    pub status: HealthStatus,
+    pub components: BTreeMap<&'static str, ComponentHealth>
+}
+
+impl Health {
+    fn new(components: BTreeMap<&'static str, ComponentHealth>) -> Self {
+        let status = if components.values().all(|component| component.status == HealthStatus::Ok) {
+            HealthStatus::Ok
+        } else {
+            HealthStatus::Unavailable
+        };
+        Self { status, components }
+    }
+
+    fn respond(self) -> (Status, Json<Self>) {
+        let status = match self.status {
+            HealthStatus::Ok => Status::Ok,
+            HealthStatus::Unavailable => Status::ServiceUnavailable
+        };
+        (status, Json(self))
+    }
+}
+
+/// Liveness endpoint: answers as long as the process is able to serve requests.
+#[rocket::get("/healthz")]
+pub fn healthz() -> (Status, Json<Health>) {
+    let mut components = BTreeMap::new();
+    components.insert("process", ComponentHealth::ok());
+    Health::new(components).respond()
+}
+
+/// Readiness endpoint: checks the database is reachable and its schema is current, and that
+/// the background workers are running.
+#[rocket::get("/readyz")]
+pub async fn readyz(
+    database: &State<AppDatabase>,
+    hit_counter: &State<HitCounter>,
+    maintenance: &State<Maintenance>,
+    webhooks: &State<WebhookDispatcher>
+) -> (Status, Json<Health>) {
+    let pool = database.get_pool();
+    let mut components = BTreeMap::new();
+
+    let database = match action::ping(pool).await {
+        Ok(()) => ComponentHealth::ok(),
+        Err(e) => {
+            tracing::warn!(error = %e, "database is unreachable");
+            ComponentHealth::unavailable("database is unreachable")
+        }
+    };
+    components.insert("database", database);
+
+    let migrations = match action::schema_version(pool).await {
+        Ok(version) if version >= SCHEMA_VERSION => ComponentHealth::ok(),
+        Ok(version) => ComponentHealth::unavailable(format!(
+            "schema version is {}, expected {}", version, SCHEMA_VERSION
+        )),
+        Err(e) => {
+            tracing::warn!(error = %e, "failed to read the schema version");
+            ComponentHealth::unavailable("failed to read the schema version")
+        }
+    };
+    components.insert("migrations", migrations);
+
+    components.insert("hit_counter", match hit_counter.is_running() {
+        true => ComponentHealth::ok(),
+        false => ComponentHealth::unavailable("hit counter task stopped")
+    });
+    components.insert("maintenance", match maintenance.is_running() {
+        true => ComponentHealth::ok(),
+        false => ComponentHealth::unavailable("maintenance task stopped")
+    });
+    components.insert("webhooks", match webhooks.is_running() {
+        true => ComponentHealth::ok(),
+        false => ComponentHealth::unavailable("webhook dispatcher task stopped")
+    });
+
+    Health::new(components).respond()
+}
+
+pub fn routes() -> Vec<rocket::Route> {
+    rocket::routes![healthz, readyz]
+}
+
+#[cfg(test)]
+pub mod test {
+    use crate::web::test::client;
+    use rocket::http::Status;
+
+    #[test]
+    fn reports_health_and_readiness() {
+        let client = client();
+        let response = client.get("/healthz").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+
+        let response = client.get("/readyz").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let body: serde_json::Value = response.into_json().unwrap();
+        assert_eq!(body["status"], "ok");
+        for component in ["database", "migrations", "hit_counter", "maintenance", "webhooks"] {
+            assert_eq!(body["components"][component]["status"], "ok");
+        }
+    }
+}
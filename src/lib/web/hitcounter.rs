@@ -1,116 +1,333 @@
 use crate::data::DatabasePool;
+use crate::metrics::METRICS;
+use crate::domain::stats::{AgentClass, View, ViewRoute};
 use crate::ShortCode;
 use crate::service::{self, ServiceError};
-use crossbeam_channel::TryRecvError;
-use crossbeam_channel::{unbounded, Sender};
-use parking_lot::Mutex;
+use crate::web::events::ClipEvents;
+use chrono::{DurationRound, NaiveDateTime, Utc};
+use rocket::http::uri::Absolute;
+use rocket::request::{FromRequest, Outcome};
+use rocket::Request;
 use std::collections::HashMap;
-use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::runtime::Handle;
+use tokio::sync::{mpsc, oneshot};
 
-/// Defines a type alias for an atomic reference counted pointer, which contains a
-/// mutually exclusive access to a hashmap keyed on the shortcode.
-type HitStore = Arc<Mutex<HashMap<ShortCode, u32>>>;
+/// The pending hits keyed on the shortcode, and the pending views keyed on the shortcode, the
+/// hour they happened in and how the clip was viewed. The store is owned by the hit counter
+/// task, so it needs no locking.
+#[derive(Default)]
+struct HitStore {
+    hits: HashMap<ShortCode, u32>,
+    views: HashMap<(ShortCode, NaiveDateTime, View), u32>
+}
+
+impl HitStore {
+    fn add(&mut self, shortcode: ShortCode, view: View) {
+        let now = Utc::now().naive_utc();
+        let hour = now.duration_trunc(chrono::Duration::hours(1)).unwrap_or(now);
+        *self.views.entry((shortcode.clone(), hour, view)).or_insert(0) += 1;
+        *self.hits.entry(shortcode).or_insert(0) += 1;
+    }
+
+    fn is_empty(&self) -> bool {
+        self.hits.is_empty()
+    }
+
+    fn len(&self) -> usize {
+        self.views.len()
+    }
+}
 
 #[derive(Debug, thiserror::Error)]
 enum HitCountError {
     #[error("Service error: {0}")]
-    Service(#[from] ServiceError),
-    #[error("Communication error: {0}")]
-    Channel(#[from] crossbeam_channel::SendError<HitCountMsg>)
+    Service(#[from] ServiceError)
 }
 
 enum HitCountMsg {
-    Commit,
-    Hit(ShortCode, u32)
+    /// Write the pending hits to the database and reply once they are committed.
+    Flush(oneshot::Sender<()>),
+    Hit(ShortCode, View)
+}
+
+/// Controls how often the [`HitCounter`] writes the pending hits to the database.
+#[derive(Debug, Clone)]
+pub struct HitCounterConfig {
+    /// The pending hits are committed at least this often.
+    pub flush_interval: Duration,
+    /// The pending hits are committed as soon as this many distinct views are pending.
+    pub flush_size: usize,
+    /// How many hits can be queued before new ones are dropped.
+    pub capacity: usize
+}
+
+impl Default for HitCounterConfig {
+    fn default() -> Self {
+        Self {
+            flush_interval: Duration::from_secs(5),
+            flush_size: 500,
+            capacity: 10_000
+        }
+    }
 }
 
 pub struct HitCounter {
-    /// Struct with a crossbeam sender to communicate between threads.
-    tx: Sender<HitCountMsg>
+    /// Struct with a bounded sender to communicate with the hit counter task.
+    tx: mpsc::Sender<HitCountMsg>,
+    capacity: usize
 }
 
 impl HitCounter {
-    fn commit_hits(
-        hits:HitStore,
-        handle:Handle,
-        pool:DatabasePool)
-        -> Result<(), HitCountError> {
-
-        let hits: HitStore = Arc::clone(&hits);
-        let hits: Vec<(ShortCode, u32)> = {
-            let mut hits = hits.lock();
-            let hits_vec = hits.iter().map(|(k ,v)| (k.clone(), *v)).collect();
-            hits.clear();
-            hits_vec
-        };
+    /// Commits all the pending hits and views in a single transaction, returning the new hit
+    /// counts. They are put back into the store if the commit fails, so they are retried on the
+    /// next flush.
+    async fn commit_hits(store: &mut HitStore, pool: &DatabasePool) -> Result<Vec<(ShortCode, u64)>, HitCountError> {
+        if store.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let started = Instant::now();
+        let hits: Vec<(ShortCode, u32)> = store.hits.drain().collect();
+        let views: Vec<_> = store.views.drain().collect();
+        let result = async {
+            let mut transaction = service::action::begin_transaction(pool).await?;
+            let totals = service::action::increase_hit_counts(&hits, &mut transaction).await?;
+            service::action::record_views(&views, &mut transaction).await?;
+            service::action::end_transaction(transaction).await?;
+            Ok::<_, ServiceError>(totals)
+        }.await;
 
-        handle.block_on(async move {
-           let transaction = service::action::begin_transaction(&pool).await?;
-            for (shortcode, hits) in hits {
-                if let Err(e) = service::action::increase_hit_count(&shortcode, hits, &pool).await {
-                    eprintln!("Error increasing hit count: {:?}", e);
+        METRICS.hit_flush_duration
+            .with_label_values(&[if result.is_ok() { "ok" } else { "error" }])
+            .observe(started.elapsed().as_secs_f64());
+
+        match result {
+            Ok(totals) => Ok(totals),
+            Err(e) => {
+                for (shortcode, count) in hits {
+                    *store.hits.entry(shortcode).or_insert(0) += count;
                 }
-            }
-            Ok(service::action::end_transaction(transaction).await?)
-        })
-    }
-
-    fn process_msg(
-        msg:HitCountMsg,
-        hits:HitStore,
-        handle:Handle,
-        pool:DatabasePool)
-        -> Result<(), HitCountError> {
-
-        match msg {
-            HitCountMsg::Commit => Self::commit_hits(hits.clone(), handle.clone(), pool.clone())?,
-            HitCountMsg::Hit(shortcode, count) => {
-                let mut hitcount = hits.lock();
-                let hitcount = hitcount.entry(shortcode).or_insert(0);
-                *hitcount += count;
+                for (key, count) in views {
+                    *store.views.entry(key).or_insert(0) += count;
+                }
+                Err(e.into())
             }
         }
-        Ok(())
     }
 
-    pub fn new(pool: DatabasePool, handle: Handle) -> Self {
-        let (tx, rx) = unbounded();
-        let tx_clone = tx.clone();
-        let rx_clone = rx.clone();
+    /// Commits the pending hits and tells the viewers of the clips their new hit counts.
+    async fn flush_store(store: &mut HitStore, pool: &DatabasePool, events: &ClipEvents) {
+        match Self::commit_hits(store, pool).await {
+            Ok(totals) => {
+                for (shortcode, hits) in totals {
+                    events.hits(shortcode, hits);
+                }
+            },
+            Err(e) => tracing::error!(error = %e, "failed to commit hits")
+        }
+    }
 
-        let _ = std::thread::spawn(move || {
-            /// The hitcounter transactions occur in a separate thread and use
-            /// Arc and Mutex to make sure they're consistent.
-            dbg!("HitCounter thread stored");
-            let store: HitStore = Arc::new(Mutex::new(HashMap::new()));
+    async fn process_msgs(
+        mut rx: mpsc::Receiver<HitCountMsg>,
+        pool: DatabasePool,
+        config: HitCounterConfig,
+        events: ClipEvents
+    ) {
+        let mut store = HitStore::default();
+        let mut interval = tokio::time::interval(config.flush_interval);
 
         loop {
-            match rx_clone.try_recv() {
-                Ok(msg) => if let Err(e) = Self::process_msg(msg, store.clone(), handle.clone(),pool.clone()) {
-                    eprintln!("Message processing error: {:?}", e)
-                }
-                Err(e) => match e {
-                    TryRecvError::Empty => {
-                        std::thread::sleep(Duration::from_secs(5));
-                        if let Err(e) = tx_clone.send(HitCountMsg::Commit) {
-                            eprintln!("Error sending commit message to hits channel: {}", e);
+            tokio::select! {
+                msg = rx.recv() => match msg {
+                    Some(HitCountMsg::Hit(shortcode, view)) => {
+                        store.add(shortcode, view);
+                        if store.len() >= config.flush_size {
+                            Self::flush_store(&mut store, &pool, &events).await;
                         }
+                    },
+                    Some(HitCountMsg::Flush(done)) => {
+                        Self::flush_store(&mut store, &pool, &events).await;
+                        let _ = done.send(());
+                    },
+                    // Every `HitCounter` is gone, so commit what is left and stop.
+                    None => {
+                        Self::flush_store(&mut store, &pool, &events).await;
+                        break;
                     }
-                    _ => break,
-                }
+                },
+                _ = interval.tick() => Self::flush_store(&mut store, &pool, &events).await
             }
         }
-        });
+    }
+
+    /// Spawns the hit counter task on the runtime behind `handle`. The new hit counts are sent
+    /// to `events` after every flush.
+    pub fn new(pool: DatabasePool, handle: Handle, config: HitCounterConfig, events: ClipEvents) -> Self {
+        let capacity = config.capacity;
+        let (tx, rx) = mpsc::channel(capacity);
+        handle.spawn(Self::process_msgs(rx, pool, config, events));
 
-        Self { tx }
+        Self { tx, capacity }
     }
 
-    pub fn hit(&self, shortcode: ShortCode, count: u32) {
-        if let Err(e) = self.tx.
-            send(HitCountMsg::Hit(shortcode, count)) {
-            eprintln!("Hit count error: {}", e)
+    /// Records a hit on a clip, along with how it was viewed. The hit is dropped if the queue
+    /// is full.
+    pub fn hit(&self, shortcode: ShortCode, view: View) {
+        METRICS.clip_reads.with_label_values(&[view.route.as_str()]).inc();
+        if let Err(e) = self.tx.try_send(HitCountMsg::Hit(shortcode, view)) {
+            tracing::warn!(error = %e, "dropped clip hit")
         }
     }
-}
\ No newline at end of file
+
+    /// Returns how many messages are waiting to be processed by the hit counter task.
+    pub fn queue_depth(&self) -> usize {
+        self.capacity - self.tx.capacity()
+    }
+
+    /// Returns whether the hit counter task is still running.
+    pub fn is_running(&self) -> bool {
+        !self.tx.is_closed()
+    }
+
+    /// Writes the pending hits to the database and waits until they are committed.
+    pub async fn flush(&self) {
+        let (done_tx, done_rx) = oneshot::channel();
+        if self.tx.send(HitCountMsg::Flush(done_tx)).await.is_ok() {
+            let _ = done_rx.await;
+        }
+    }
+}
+
+/// The user agent class and referrer host of a request, used to record how a clip was viewed.
+pub struct Viewer {
+    agent: AgentClass,
+    referrer: Option<String>
+}
+
+impl Viewer {
+    pub fn view(self, route: ViewRoute) -> View {
+        View::new(route, self.agent, self.referrer)
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Viewer {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let agent = AgentClass::from_user_agent(req.headers().get_one("User-Agent"));
+        let referrer = req.headers()
+            .get_one("Referer")
+            .and_then(|referrer| Absolute::parse(referrer).ok())
+            .and_then(|uri| uri.authority().map(|authority| authority.host().to_owned()));
+        Outcome::Success(Self { agent, referrer })
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::{HitCounter, HitCounterConfig};
+    use crate::web::events::{ClipEvent, ClipEvents};
+    use crate::data::test::new_db;
+    use crate::data::DatabasePool;
+    use crate::domain::clip::field::{Content, Expires, Password, Title};
+    use crate::domain::stats::{AgentClass, View, ViewRoute};
+    use crate::service;
+    use crate::test::async_runtime;
+    use crate::Clip;
+    use std::time::Duration;
+
+    fn view(route: ViewRoute) -> View {
+        View::new(route, AgentClass::Tool, None)
+    }
+
+    fn config() -> HitCounterConfig {
+        HitCounterConfig {
+            flush_interval: Duration::from_secs(60 * 60),
+            ..HitCounterConfig::default()
+        }
+    }
+
+    async fn new_clip(pool: &DatabasePool) -> Clip {
+        let req = service::ask::NewClip {
+            content: Content::new("content").unwrap(),
+            expires: Expires::default(),
+            password: Password::default(),
+            title: Title::default(),
+            owner: None,
+            user: None,
+            workspace: None,
+        };
+        service::action::new_clip(req, pool).await.unwrap()
+    }
+
+    async fn hits(clip: &Clip, pool: &DatabasePool) -> u64 {
+        service::action::get_clip(clip.shortcode.clone().into(), pool)
+            .await
+            .unwrap()
+            .hits
+            .into_inner()
+    }
+
+    #[test]
+    fn flush_persists_hits() {
+        let rt = async_runtime();
+        let db = new_db(rt.handle());
+        let pool = db.get_pool().clone();
+        let events = ClipEvents::default();
+        let mut rx = events.subscribe();
+        let hit_counter = HitCounter::new(pool.clone(), rt.handle().clone(), config(), events);
+
+        let (first, second) = rt.block_on(async {
+            (new_clip(&pool).await, new_clip(&pool).await)
+        });
+        hit_counter.hit(first.shortcode.clone(), view(ViewRoute::Web));
+        hit_counter.hit(first.shortcode.clone(), view(ViewRoute::Web));
+        hit_counter.hit(first.shortcode.clone(), view(ViewRoute::Api));
+        hit_counter.hit(second.shortcode.clone(), view(ViewRoute::Raw));
+
+        rt.block_on(async {
+            hit_counter.flush().await;
+            assert_eq!(hits(&first, &pool).await, 3);
+            assert_eq!(hits(&second, &pool).await, 1);
+
+            let stats = service::action::get_clip_stats(first.shortcode.clone().into(), &pool)
+                .await
+                .unwrap();
+            assert_eq!(stats.total, 3);
+            assert_eq!(stats.routes.get("web"), Some(&2));
+            assert_eq!(stats.routes.get("api"), Some(&1));
+        });
+
+        let mut totals = vec![];
+        while let Ok(ClipEvent::Hits(shortcode, hits)) = rx.try_recv() {
+            totals.push((shortcode, hits));
+        }
+        totals.sort_by_key(|(_, hits)| *hits);
+        assert_eq!(totals, vec![(second.shortcode.clone(), 1), (first.shortcode.clone(), 3)]);
+    }
+
+    #[test]
+    fn pending_hits_are_committed_on_drop() {
+        let rt = async_runtime();
+        let db = new_db(rt.handle());
+        let pool = db.get_pool().clone();
+        let hit_counter = HitCounter::new(pool.clone(), rt.handle().clone(), config(), ClipEvents::default());
+
+        let clip = rt.block_on(new_clip(&pool));
+        hit_counter.hit(clip.shortcode.clone(), view(ViewRoute::Web));
+        hit_counter.hit(clip.shortcode.clone(), view(ViewRoute::Raw));
+        drop(hit_counter);
+
+        rt.block_on(async {
+            for _ in 0..50 {
+                if hits(&clip, &pool).await == 2 {
+                    return;
+                }
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+            panic!("pending hits were not committed");
+        });
+    }
+}
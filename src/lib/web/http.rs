@@ -1,7 +1,15 @@
 use crate::data::AppDatabase;
 use crate::service;
 use crate::service::{action, ask};
-use crate::web::{ctx, form, renderer::Renderer, PageError, PASSWORD_COOKIE};
+use crate::domain::stats::ViewRoute;
+use crate::domain::workspace::{field::WorkspaceId, WorkspaceError};
+use crate::web::cache::{Audience, Cached, Preconditions, Validators};
+use crate::web::hitcounter::Viewer;
+use crate::web::range::{content_stream, ByteRange, ClipBytes, RangeHeaders, RangeNotSatisfiable, RawResponse, Unsatisfiable};
+use crate::web::request_id::RequestId;
+use crate::web::session::CurrentUser;
+use crate::web::sso::Sso;
+use crate::web::{cookie_password, ctx, form, form_errors, renderer::Renderer, HitCounter, PageError, PASSWORD_COOKIE};
 use crate::{ServiceError, ShortCode};
 use rocket::form::{Contextual, Form};
 use rocket::http::{Cookie, CookieJar, Status};
@@ -9,190 +17,371 @@ use rocket::response::content::RawHtml;
 use rocket::response::{status, Redirect};
 use rocket::{uri, State};
 
+/// The home page context, listing the workspaces a logged in user can post clips to.
+async fn home_context(user: Option<&CurrentUser>, database: &AppDatabase) -> Result<ctx::Home, PageError> {
+    match user {
+        Some(CurrentUser(user)) => {
+            let workspaces = action::list_user_workspaces(&user.user_id, database.get_pool()).await?;
+            Ok(ctx::Home::new(workspaces))
+        },
+        None => Ok(ctx::Home::default())
+    }
+}
+
+/// Who is viewing a clip in the browser.
+fn requester(user: &Option<CurrentUser>) -> ask::Requester {
+    match user {
+        Some(CurrentUser(user)) => ask::Requester::User(user.user_id.clone()),
+        None => ask::Requester::Anonymous
+    }
+}
+
+/// Renders the login page for anonymous users trying to view the clip of a workspace.
+fn login_required(sso: &Sso, renderer: &Renderer<'_>) -> Result<status::Custom<RawHtml<String>>, PageError> {
+    let html = renderer.render(ctx::Login::new(sso.is_enabled()), &["Log in to view this clip"])?;
+    Ok(status::Custom(Status::Unauthorized, RawHtml(html)))
+}
+
 /// Route to the home page.
 #[rocket::get("/")]
-fn home(renderer: &State<Renderer<'_>>) -> RawHtml<String> {
-    let context = ctx::Home::default();
-    RawHtml(renderer.render(context, &[]))
+async fn home(
+    user: Option<CurrentUser>,
+    database: &State<AppDatabase>,
+    renderer: &State<Renderer<'_>>
+) -> Result<RawHtml<String>, PageError> {
+    let context = home_context(user.as_ref(), database).await?;
+    Ok(RawHtml(renderer.render(context, &[])?))
 }
 
-/// Points to an existing clip
+/// The page of a clip, or the page telling what it takes to view the clip.
+type ClipPage = Result<Cached<RawHtml<String>>, status::Custom<RawHtml<String>>>;
+
+/// Points to an existing clip. Browsers and caches which send the `ETag` or `Last-Modified` of
+/// their copy get a `304 Not Modified` while it is current, which isn't counted as a view.
 #[rocket::get("/clip/<shortcode>")]
+#[tracing::instrument(skip_all, fields(request_id = %request_id, shortcode = shortcode.as_str()))]
+#[allow(clippy::too_many_arguments)]
 pub async fn get_clip(
+    request_id: &RequestId,
     shortcode: ShortCode,
+    user: Option<CurrentUser>,
+    preconditions: Preconditions,
     database: &State<AppDatabase>,
-    renderer: &State<Renderer<'_>>
-) -> Result<status::Custom<RawHtml<String>>, PageError> {
+    renderer: &State<Renderer<'_>>,
+    sso: &State<Sso>,
+    hit_counter: &State<HitCounter>,
+    viewer: Viewer
+) -> Result<ClipPage, PageError> {
 
     fn render_with_status<T: ctx::PageContext + serde::Serialize + std::fmt::Debug>(
         status: Status,
         context: T,
         renderer: &Renderer
         ) -> Result<status::Custom<RawHtml<String>>, PageError> {
-        Ok(status::Custom(status, RawHtml(renderer.render(context, &[]))))
+        Ok(status::Custom(status, RawHtml(renderer.render(context, &[])?)))
     }
-    match action::get_clip(shortcode.clone().into(), database.get_pool()).await {
+    // Clips only visible to logged in users must not end up in shared caches.
+    let audience = if user.is_some() { Audience::Private } else { Audience::Public };
+    let req = ask::GetClip {
+        requester: requester(&user),
+        ..shortcode.clone().into()
+    };
+    match action::get_clip(req, database.get_pool()).await {
         Ok(clip) => {
+            let validators = Validators::new(&clip, audience);
+            if preconditions.is_fresh(&validators) {
+                return Ok(Ok(validators.not_modified()));
+            }
+            hit_counter.hit(clip.shortcode.clone(), viewer.view(ViewRoute::Web));
             let context = ctx::ViewClip::new(clip);
-            render_with_status(Status::Ok, context, renderer)
+            Ok(Ok(validators.respond(RawHtml(renderer.render(context, &[])?))))
         },
         Err(e) => match e {
+            ServiceError::Workspace(WorkspaceError::LoginRequired) => login_required(sso, renderer).map(Err),
             ServiceError::PermissionError(_) => {
                 let context = ctx::PasswordRequired::new(shortcode);
-                render_with_status(Status::Unauthorized, context, renderer)
+                render_with_status(Status::Unauthorized, context, renderer).map(Err)
             },
             ServiceError::NotFound => Err(PageError::NotFound("Clip not found".to_owned())),
-            _ => Err(PageError::Internal("Server error".to_owned()))
+            ServiceError::Gone => Err(PageError::Gone("Clip was deleted".to_owned())),
+            e => {
+                tracing::error!(error = %e, "failed to get clip");
+                Err(PageError::Internal("Server error".to_owned()))
+            }
         }
     }
 }
 
-/// Creates a new clip using form data.
+/// Creates a new clip using form data. Invalid forms are shown again along with their errors.
 #[rocket::post("/", data = "<form>")]
+#[tracing::instrument(skip_all, fields(request_id = %request_id))]
 pub async fn new_clip(
+    request_id: &RequestId,
+    user: Option<CurrentUser>,
     form: Form<Contextual<'_, form::NewClip>>,
     database: &State<AppDatabase>,
     renderer: &State<Renderer<'_>>
-) -> Result<Redirect, (Status, RawHtml<String>)> {
+) -> Result<Result<Redirect, (Status, RawHtml<String>)>, PageError> {
 
     let form = form.into_inner();
+    let context = home_context(user.as_ref(), database).await?;
     if let Some(value) = form.value {
+        let workspace = match value.workspace.as_deref().map(str::trim) {
+            None | Some("") => None,
+            Some(workspace) => match workspace.parse::<WorkspaceId>() {
+                Ok(workspace_id) => Some(workspace_id),
+                Err(_) => return Ok(Err((
+                    Status::BadRequest,
+                    RawHtml(renderer.render_with_data(context, ("clip", &form.context), &["Unknown workspace"])?)
+                )))
+            }
+        };
         let req = service::ask::NewClip {
             content: value.content,
             title: value.title,
             expires: value.expires,
-            password: value.password
+            password: value.password,
+            owner: None,
+            user: user.map(|CurrentUser(user)| user.user_id),
+            workspace
         };
         match action::new_clip(req, database.get_pool()).await {
-            Ok(clip) => Ok(Redirect::to(uri!(get_clip(shortcode = clip.shortcode)))),
+            Ok(clip) => Ok(Ok(Redirect::to(uri!(get_clip(shortcode = clip.shortcode))))),
+            Err(ServiceError::NotFound) => Ok(Err((
+                Status::BadRequest,
+                RawHtml(renderer.render_with_data(context, ("clip", &form.context), &["Unknown workspace"])?)
+            ))),
             Err(e) => {
-                eprintln!("Internal error: {:?}", e);
-                Err((Status::InternalServerError,
+                tracing::error!(error = %e, "failed to create clip");
+                Ok(Err((Status::InternalServerError,
                     RawHtml(renderer.render(
-                        ctx::Home::default(),
+                        context,
                         &["A server error occurred. Please try again."]
-                    )),
-                ))
+                    )?),
+                )))
             }
         }
     } else {
-        let errors = form
-            .context
-            .errors()
-            .map(|err| {
-                use rocket::form::error::ErrorKind;
-                if let ErrorKind::Validation(msg) = &err.kind {
-                    msg.as_ref()
-                } else {
-                    eprintln!("unhandled error: {}", err);
-                    "An error occurred, please try again."
-                }
-            })
-            .collect::<Vec<_>>();
-        Err((
+        let errors = form_errors(&form);
+        Ok(Err((
             Status::BadRequest,
             RawHtml(
                 renderer.render_with_data(
-                    ctx::Home::default(),
-                    ("clip", &form.context), &errors)),
-            ))
+                    context,
+                    ("clip", &form.context), &errors)?),
+            )))
     }
 }
 
 /// Creates a new password-protected clip using form data and checks if the user has
 /// the password already stored in the browser cookies.
 #[rocket::post("/clip/<shortcode>", data = "<form>")]
+#[tracing::instrument(skip_all, fields(request_id = %request_id, shortcode = shortcode.as_str()))]
+#[allow(clippy::too_many_arguments)]
 pub async fn submit_clip_password(
+    request_id: &RequestId,
     cookies: &CookieJar<'_>,
     form: Form<Contextual<'_, form::GetPasswordProtectedClip>>,
     shortcode: ShortCode,
+    user: Option<CurrentUser>,
     database: &State<AppDatabase>,
-    renderer: &State<Renderer<'_>>
-) -> Result<RawHtml<String>, PageError> {
+    renderer: &State<Renderer<'_>>,
+    sso: &State<Sso>,
+    hit_counter: &State<HitCounter>,
+    viewer: Viewer
+) -> Result<status::Custom<RawHtml<String>>, PageError> {
     if let Some(form) = &form.value {
         let req = service::ask::GetClip {
             shortcode: shortcode.clone(),
-            password: form.password.clone()
+            password: form.password.clone(),
+            requester: requester(&user)
         };
 
         match action::get_clip(req, database.get_pool()).await {
             Ok(clip) => {
+                hit_counter.hit(clip.shortcode.clone(), viewer.view(ViewRoute::Web));
                 let context = ctx::ViewClip::new(clip);
                 cookies.add(Cookie::new(
                     PASSWORD_COOKIE,
                     form.password.clone().into_inner().unwrap_or_default()
                 ));
-                Ok(RawHtml(renderer.render(context, &[])))
+                Ok(status::Custom(Status::Ok, RawHtml(renderer.render(context, &[])?)))
             },
             Err(e) => match e {
+                ServiceError::Workspace(WorkspaceError::LoginRequired) => login_required(sso, renderer),
                 ServiceError::PermissionError(e) => {
                     let context = ctx::PasswordRequired::new(shortcode);
-                    Ok(RawHtml(renderer.render(context, &[e.as_str()])))
+                    Ok(status::Custom(Status::Ok, RawHtml(renderer.render(context, &[e.as_str()])?)))
                 },
                 ServiceError::NotFound => Err(PageError::NotFound("Clip not found".to_owned())),
-                _ => Err(PageError::Internal("Server error".to_owned()))
+                ServiceError::Gone => Err(PageError::Gone("Clip was deleted".to_owned())),
+                e => {
+                tracing::error!(error = %e, "failed to get clip");
+                Err(PageError::Internal("Server error".to_owned()))
+            }
             }
         }
     } else {
         let context = ctx::PasswordRequired::new(shortcode);
-        Ok(RawHtml(renderer.render(
+        Ok(status::Custom(Status::Ok, RawHtml(renderer.render(
             context,
             &["A password is required to view this clip"],
-        )))
+        )?)))
     }
 }
-/// Shows raw text of a clip.
+/// Sends the raw text of a clip, or the byte range asked for, streamed from the database.
+/// Browsers and caches which send the `ETag` or `Last-Modified` of their copy get a
+/// `304 Not Modified` while it is current. Only reads from the start of a clip which are sent
+/// count as views.
+#[allow(clippy::too_many_arguments)]
+async fn raw_clip(
+    cookies: &CookieJar<'_>,
+    shortcode: &str,
+    user: Option<CurrentUser>,
+    preconditions: Preconditions,
+    range: RangeHeaders,
+    database: &AppDatabase,
+    hit_counter: Option<(&HitCounter, Viewer)>
+) -> Result<Result<Cached<RawResponse>, status::Custom<String>>, Status> {
+    // Clips only visible to logged in users must not end up in shared caches.
+    let audience = if user.is_some() { Audience::Private } else { Audience::Public };
+    let req = ask::GetClip {
+        shortcode: shortcode.into(),
+        password: cookie_password(cookies),
+        requester: requester(&user)
+    };
+
+    match action::get_raw_clip(req, database.get_pool()).await {
+        Ok(clip) => {
+            let validators = Validators::new(&clip, audience);
+            if preconditions.is_fresh(&validators) {
+                return Ok(Ok(validators.not_modified()));
+            }
+            let response = match range.resolve(clip.length, &clip.etag()) {
+                Ok(range) => {
+                    if let Some((hit_counter, viewer)) = hit_counter {
+                        if range.is_none_or(|range| range.start == 0) {
+                            hit_counter.hit(clip.shortcode.clone(), viewer.view(ViewRoute::Raw));
+                        }
+                    }
+                    let length = clip.length;
+                    let bytes = range.unwrap_or_else(|| ByteRange::whole(length));
+                    let stream = content_stream(clip, bytes, database.get_pool().clone());
+                    RawResponse::Bytes(ClipBytes { stream, length, range })
+                },
+                Err(Unsatisfiable) => RawResponse::NotSatisfiable(RangeNotSatisfiable(clip.length))
+            };
+            Ok(Ok(validators.respond(response)))
+        },
+        Err(e) => match e {
+            ServiceError::Workspace(WorkspaceError::LoginRequired) => {
+                Ok(Err(status::Custom(Status::Unauthorized, "Log in to view this clip".to_owned())))
+            },
+            ServiceError::PermissionError(msg) => Ok(Err(status::Custom(Status::Unauthorized, msg))),
+            ServiceError::NotFound => Err(Status::NotFound),
+            ServiceError::Gone => Err(Status::Gone),
+            e => {
+                tracing::error!(error = %e, "failed to get clip");
+                Err(Status::InternalServerError)
+            }
+        }
+    }
+}
+
+/// Shows raw text of a clip, or the byte range asked for with a `Range` header.
 #[rocket::get("/clip/raw/<shortcode>")]
+#[tracing::instrument(skip_all, fields(request_id = %request_id, shortcode = shortcode))]
+#[allow(clippy::too_many_arguments)]
 pub async fn get_raw_clip(
+    request_id: &RequestId,
     cookies: &CookieJar<'_>,
     shortcode: &str,
+    user: Option<CurrentUser>,
+    preconditions: Preconditions,
+    range: RangeHeaders,
+    database: &State<AppDatabase>,
+    hit_counter: &State<HitCounter>,
+    viewer: Viewer
+) -> Result<Result<Cached<RawResponse>, status::Custom<String>>, Status> {
+    raw_clip(cookies, shortcode, user, preconditions, range, database, Some((hit_counter, viewer))).await
+}
+
+/// Sends the headers of a raw clip, such as its length, without reading it or counting a view.
+#[rocket::head("/clip/raw/<shortcode>")]
+#[tracing::instrument(skip_all, fields(request_id = %request_id, shortcode = shortcode))]
+pub async fn head_raw_clip(
+    request_id: &RequestId,
+    cookies: &CookieJar<'_>,
+    shortcode: &str,
+    user: Option<CurrentUser>,
+    preconditions: Preconditions,
+    range: RangeHeaders,
     database: &State<AppDatabase>
-) -> Result<status::Custom<String>, Status> {
-    use crate::domain::clip::field::Password;
+) -> Result<Result<Cached<RawResponse>, status::Custom<String>>, Status> {
+    raw_clip(cookies, shortcode, user, preconditions, range, database, None).await
+}
 
+/// Shows how often a clip has been viewed.
+#[rocket::get("/clip/stats/<shortcode>")]
+#[tracing::instrument(skip_all, fields(request_id = %request_id, shortcode = shortcode.as_str()))]
+pub async fn get_clip_stats(
+    request_id: &RequestId,
+    cookies: &CookieJar<'_>,
+    shortcode: ShortCode,
+    user: Option<CurrentUser>,
+    database: &State<AppDatabase>,
+    renderer: &State<Renderer<'_>>,
+    sso: &State<Sso>
+) -> Result<status::Custom<RawHtml<String>>, PageError> {
     let req = ask::GetClip {
-        shortcode: shortcode.into(),
-        password: cookies
-            .get(PASSWORD_COOKIE)
-            .map(|cookie| cookie.value())
-            .map(|raw_password| Password::new(raw_password.to_string()).ok())
-            .flatten()
-            .unwrap_or_else(Password::default)
+        shortcode: shortcode.clone(),
+        password: cookie_password(cookies),
+        requester: requester(&user)
     };
 
-    match action::get_clip(req, database.get_pool()).await {
-        Ok(clip) => {
-            Ok(status::Custom(Status::Ok, clip.content.into_inner()))
+    match action::get_clip_stats(req, database.get_pool()).await {
+        Ok(stats) => {
+            let context = ctx::ViewClipStats::new(shortcode, stats);
+            Ok(status::Custom(Status::Ok, RawHtml(renderer.render(context, &[])?)))
         },
         Err(e) => match e {
-            ServiceError::PermissionError(msg) => Ok(status::Custom(Status::Unauthorized, msg)),
-            ServiceError::NotFound => Err(Status::NotFound),
-            _ => Err(Status::InternalServerError)
+            ServiceError::Workspace(WorkspaceError::LoginRequired) => login_required(sso, renderer),
+            ServiceError::PermissionError(_) => {
+                let context = ctx::PasswordRequired::new(shortcode);
+                Ok(status::Custom(Status::Unauthorized, RawHtml(renderer.render(context, &[])?)))
+            },
+            ServiceError::NotFound => Err(PageError::NotFound("Clip not found".to_owned())),
+            ServiceError::Gone => Err(PageError::Gone("Clip was deleted".to_owned())),
+            e => {
+                tracing::error!(error = %e, "failed to get clip stats");
+                Err(PageError::Internal("Server error".to_owned()))
+            }
         }
     }
 }
 
 /// The URI [`routes`](rocket::Route) which can be mounted by [`rocket`].
 pub fn routes() -> Vec<rocket::Route> {
-    rocket::routes![home, get_clip, new_clip, submit_clip_password, get_raw_clip]
+    rocket::routes![home, get_clip, new_clip, submit_clip_password, get_raw_clip, head_raw_clip, get_clip_stats]
 }
 
 pub mod catcher {
     //! Contains all the page catchers.
+    use rocket::http::Status;
     use rocket::Request;
     use rocket::{catch, catchers, Catcher};
 
     /// Catch unhandled errors.
     #[catch(default)]
-    fn default(req: &Request) -> &'static str {
-        eprintln!("General error: {:?}", req);
+    fn default(status: Status, req: &Request) -> &'static str {
+        tracing::warn!(status = status.code, method = %req.method(), path = %req.uri().path(), "general error");
         "something went wrong..."
     }
 
     /// Catch server errors.
     #[catch(500)]
     fn internal_error(req: &Request) -> &'static str {
-        eprintln!("Internal error: {:?}", req);
+        tracing::error!(method = %req.method(), path = %req.uri().path(), "internal error");
         "internal server error"
     }
 
@@ -246,6 +435,9 @@ pub mod test {
             expires: Expires::default(),
             password: Password::new("123".to_owned()).unwrap(),
             title: Title::default(),
+            owner: None,
+            user: None,
+            workspace: None,
         };
         let clip = rt
             .block_on(async move { service::action::new_clip(req, db.get_pool()).await })
@@ -274,6 +466,7 @@ pub mod test {
             .cookie(Cookie::new("password", "123"))
             .dispatch();
         assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.headers().get_one("Cache-Control"), Some("private, no-store"));
 
         // Get clip when the password is provided, but incorrect
         let response = client
@@ -282,4 +475,220 @@ pub mod test {
             .dispatch();
         assert_eq!(response.status(), Status::Unauthorized);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn raw_clips_are_cacheable() {
+        use crate::domain::clip::field::{Content, Expires, Password, Title};
+        use crate::service;
+
+        let rt = async_runtime();
+        let client = client();
+        let db = client.rocket().state::<AppDatabase>().unwrap();
+
+        let req = service::ask::NewClip {
+            content: Content::new("content").unwrap(),
+            expires: Expires::default(),
+            password: Password::default(),
+            title: Title::default(),
+            owner: None,
+            user: None,
+            workspace: None,
+        };
+        let clip = rt
+            .block_on(async move { service::action::new_clip(req, db.get_pool()).await })
+            .unwrap();
+
+        let uri = format!("/clip/raw/{}", clip.shortcode.as_str());
+        let response = client.get(&uri).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.headers().get_one("Cache-Control"), Some("public, max-age=3600"));
+        assert_eq!(response.headers().get_one("ETag"), Some(clip.etag().as_str()));
+
+        let response = client
+            .get(&uri)
+            .header(rocket::http::Header::new("If-None-Match", clip.etag()))
+            .dispatch();
+        assert_eq!(response.status(), Status::NotModified);
+        let response = client
+            .get(&uri)
+            .header(rocket::http::Header::new("If-Modified-Since", "Sun, 06 Nov 1994 08:49:37 GMT"))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+    }
+
+    #[test]
+    fn clip_pages_are_cacheable() {
+        use crate::domain::clip::field::{Content, Expires, Password, Title};
+        use crate::service;
+        use crate::web::HitCounter;
+        use rocket::http::Header;
+
+        let rt = async_runtime();
+        let client = client();
+        let db = client.rocket().state::<AppDatabase>().unwrap();
+
+        let req = service::ask::NewClip {
+            content: Content::new("content").unwrap(),
+            expires: Expires::default(),
+            password: Password::default(),
+            title: Title::default(),
+            owner: None,
+            user: None,
+            workspace: None,
+        };
+        let clip = rt
+            .block_on(async move { service::action::new_clip(req, db.get_pool()).await })
+            .unwrap();
+
+        let uri = format!("/clip/{}", clip.shortcode.as_str());
+        let response = client.get(&uri).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.headers().get_one("Cache-Control"), Some("public, max-age=3600"));
+        assert_eq!(response.headers().get_one("ETag"), Some(clip.etag().as_str()));
+        let last_modified = response.headers().get_one("Last-Modified").unwrap().to_owned();
+
+        let response = client.get(&uri).header(Header::new("If-None-Match", clip.etag())).dispatch();
+        assert_eq!(response.status(), Status::NotModified);
+        assert!(response.into_string().is_none());
+        let response = client.get(&uri).header(Header::new("If-Modified-Since", last_modified)).dispatch();
+        assert_eq!(response.status(), Status::NotModified);
+
+        // The copies which were still current weren't views.
+        let shortcode = clip.shortcode.clone();
+        let hits = rt.block_on(async {
+            client.rocket().state::<HitCounter>().unwrap().flush().await;
+            service::action::get_clip(shortcode.into(), db.get_pool()).await.unwrap().hits.into_inner()
+        });
+        assert_eq!(hits, 1);
+    }
+
+    #[test]
+    fn raw_clips_are_served_in_byte_ranges() {
+        use crate::domain::clip::field::{Content, Expires, Password, Title};
+        use crate::service;
+        use rocket::http::Header;
+
+        let rt = async_runtime();
+        let client = client();
+        let db = client.rocket().state::<AppDatabase>().unwrap();
+
+        // Longer than a chunk, so the stream reads it in several queries.
+        let content: String = (0..100_000).map(|i| char::from(b'a' + (i % 26) as u8)).collect();
+        let req = service::ask::NewClip {
+            content: Content::new(&content).unwrap(),
+            expires: Expires::default(),
+            password: Password::default(),
+            title: Title::default(),
+            owner: None,
+            user: None,
+            workspace: None,
+        };
+        let clip = rt
+            .block_on(async move { service::action::new_clip(req, db.get_pool()).await })
+            .unwrap();
+        let uri = format!("/clip/raw/{}", clip.shortcode.as_str());
+
+        let response = client.get(&uri).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.headers().get_one("Accept-Ranges"), Some("bytes"));
+        assert_eq!(response.headers().get_one("Content-Length"), Some("100000"));
+        assert_eq!(response.into_string().unwrap(), content);
+
+        let response = client.get(&uri).header(Header::new("Range", "bytes=65530-65545")).dispatch();
+        assert_eq!(response.status(), Status::PartialContent);
+        assert_eq!(response.headers().get_one("Content-Range"), Some("bytes 65530-65545/100000"));
+        assert_eq!(response.into_string().unwrap(), &content[65530..=65545]);
+
+        let response = client.get(&uri).header(Header::new("Range", "bytes=-5")).dispatch();
+        assert_eq!(response.status(), Status::PartialContent);
+        assert_eq!(response.into_string().unwrap(), &content[99_995..]);
+
+        let response = client
+            .get(&uri)
+            .header(Header::new("Range", "bytes=0-4"))
+            .header(Header::new("If-Range", "\"0-0000000000000000\""))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+
+        let response = client.get(&uri).header(Header::new("Range", "bytes=100000-")).dispatch();
+        assert_eq!(response.status(), Status::RangeNotSatisfiable);
+        assert_eq!(response.headers().get_one("Content-Range"), Some("bytes */100000"));
+
+        let response = client.head(&uri).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.headers().get_one("Content-Length"), Some("100000"));
+        assert_eq!(response.headers().get_one("ETag"), Some(clip.etag().as_str()));
+        assert!(response.into_string().unwrap_or_default().is_empty());
+    }
+
+    #[test]
+    fn gone_on_deleted_clip() {
+        use crate::domain::clip::field::{Content, Expires, Password, Title};
+        use crate::service;
+
+        let rt = async_runtime();
+
+        let client = client();
+        let db = client.rocket().state::<AppDatabase>().unwrap();
+
+        let clip = rt
+            .block_on(async move {
+                let owner = service::action::generate_api_key(&crate::domain::scope::Scope::CLIPS, db.get_pool()).await?;
+                let req = service::ask::NewClip {
+                    content: Content::new("content").unwrap(),
+                    expires: Expires::default(),
+                    password: Password::default(),
+                    title: Title::default(),
+                    owner: Some(owner.clone()),
+                    user: None,
+                    workspace: None,
+                };
+                let clip = service::action::new_clip(req, db.get_pool()).await?;
+                let req = service::ask::GetClip {
+                    requester: service::ask::Requester::ApiKey(owner),
+                    ..clip.shortcode.clone().into()
+                };
+                service::action::delete_clip(req, db.get_pool()).await?;
+                Ok::<_, service::ServiceError>(clip)
+            })
+            .unwrap();
+
+        let response = client
+            .get(format!("/clip/{}", clip.shortcode.as_str()))
+            .dispatch();
+        assert_eq!(response.status(), Status::Gone);
+        let response = client
+            .get(format!("/clip/raw/{}", clip.shortcode.as_str()))
+            .dispatch();
+        assert_eq!(response.status(), Status::Gone);
+    }
+
+    #[test]
+    fn gets_clip_stats() {
+        use crate::domain::clip::field::{Content, Expires, Password, Title};
+        use crate::service;
+
+        let rt = async_runtime();
+
+        let client = client();
+        let db = client.rocket().state::<AppDatabase>().unwrap();
+
+        let req = service::ask::NewClip {
+            content: Content::new("content").unwrap(),
+            expires: Expires::default(),
+            password: Password::default(),
+            title: Title::default(),
+            owner: None,
+            user: None,
+            workspace: None,
+        };
+        let clip = rt
+            .block_on(async move { service::action::new_clip(req, db.get_pool()).await })
+            .unwrap();
+
+        let response = client
+            .get(format!("/clip/stats/{}", clip.shortcode.as_str()))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+    }
+}
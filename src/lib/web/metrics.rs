@@ -0,0 +1,83 @@
+use crate::data::AppDatabase;
+use crate::metrics::METRICS;
+use crate::web::HitCounter;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::ContentType;
+use rocket::{Data, Request, Response, State};
+use std::time::Instant;
+
+/// Fairing which counts every request and measures how long it took, labeled by the
+/// matched route.
+pub struct RequestMetrics;
+
+/// The moment a request was received, cached in the request.
+struct RequestStart(Instant);
+
+#[rocket::async_trait]
+impl Fairing for RequestMetrics {
+    fn info(&self) -> Info {
+        Info {
+            name: "Request metrics",
+            kind: Kind::Request | Kind::Response
+        }
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, _: &mut Data<'_>) {
+        req.local_cache(|| RequestStart(Instant::now()));
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        let started = req.local_cache(|| RequestStart(Instant::now()));
+        let route = req.route()
+            .map(|route| route.uri.as_str().to_owned())
+            .unwrap_or_else(|| "unmatched".to_owned());
+        let method = req.method().as_str();
+        let status = res.status().code.to_string();
+
+        METRICS.requests
+            .with_label_values(&[&route, method, &status])
+            .inc();
+        METRICS.request_duration
+            .with_label_values(&[&route, method])
+            .observe(started.0.elapsed().as_secs_f64());
+    }
+}
+
+/// Endpoint to scrape the metrics in the Prometheus text format.
+#[rocket::get("/metrics")]
+pub fn metrics(
+    database: &State<AppDatabase>,
+    hit_counter: &State<HitCounter>
+) -> (ContentType, String) {
+    let pool = database.get_pool();
+    METRICS.db_connections.set(pool.size() as i64);
+    METRICS.db_idle_connections.set(pool.num_idle() as i64);
+    METRICS.hit_queue_depth.set(hit_counter.queue_depth() as i64);
+
+    let content_type = ContentType::new("text", "plain")
+        .with_params(("version", "0.0.4"));
+    (content_type, METRICS.encode())
+}
+
+pub fn routes() -> Vec<rocket::Route> {
+    rocket::routes![metrics]
+}
+
+#[cfg(test)]
+pub mod test {
+    use crate::web::test::client;
+    use rocket::http::Status;
+
+    #[test]
+    fn exposes_metrics() {
+        let client = client();
+        let response = client.get("/").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+
+        let response = client.get("/metrics").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let body = response.into_string().unwrap();
+        assert!(body.contains("clipstash_http_requests_total"));
+        assert!(body.contains("clipstash_db_connections"));
+    }
+}
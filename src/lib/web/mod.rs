@@ -4,22 +4,88 @@ pub mod form;
 pub mod http;
 pub mod hitcounter;
 pub mod api;
+pub mod cache;
+pub mod range;
+pub mod events;
+pub mod collab;
+pub mod openapi;
+pub mod metrics;
+pub mod health;
+pub mod session;
+pub mod account;
+pub mod sso;
+pub mod slash;
+pub mod workspace;
+pub mod request_id;
 
 pub use hitcounter::HitCounter;
 pub const PASSWORD_COOKIE: &str = "password";
 
+/// Reads the clip password stored in the browser cookies, if any.
+pub fn cookie_password(cookies: &rocket::http::CookieJar<'_>) -> crate::domain::clip::field::Password {
+    use crate::domain::clip::field::Password;
+    cookies
+        .get(PASSWORD_COOKIE)
+        .and_then(|cookie| Password::new(cookie.value().to_string()).ok())
+        .unwrap_or_default()
+}
+
+/// Collects the validation messages of a form which failed to parse.
+pub fn form_errors<'a, T>(form: &'a rocket::form::Contextual<'_, T>) -> Vec<&'a str> {
+    form.context
+        .errors()
+        .map(|err| {
+            use rocket::form::error::ErrorKind;
+            if let ErrorKind::Validation(msg) = &err.kind {
+                msg.as_ref()
+            } else {
+                tracing::warn!(error = %err.kind, "unhandled form error");
+                "An error occurred, please try again."
+            }
+        })
+        .collect()
+}
+
 #[derive(rocket::Responder)]
 pub enum PageError {
     #[response(status = 500)]
     Serialization(String),
     #[response(status = 500)]
     Render(String),
+    #[response(status = 403)]
+    Forbidden(String),
     #[response(status = 404)]
     NotFound(String),
+    #[response(status = 410)]
+    Gone(String),
     #[response(status = 500)]
     Internal(String)
 }
 
+impl From<crate::ServiceError> for PageError {
+    fn from(err: crate::ServiceError) -> Self {
+        use crate::domain::workspace::WorkspaceError;
+        use crate::ServiceError;
+        match err {
+            ServiceError::NotFound => PageError::NotFound("Clip not found".to_owned()),
+            ServiceError::Gone => PageError::Gone("Clip was deleted".to_owned()),
+            ServiceError::Workspace(WorkspaceError::NotAnOwner) => {
+                PageError::Forbidden("Only the owners of the workspace can do this".to_owned())
+            },
+            e => {
+                tracing::error!(error = %e, "service error");
+                PageError::Internal("Server error".to_owned())
+            }
+        }
+    }
+}
+
+impl From<renderer::RenderError> for PageError {
+    fn from(err: renderer::RenderError) -> Self {
+        PageError::Render(format!("{}", err))
+    }
+}
+
 impl From<handlebars::RenderError> for PageError {
     fn from(err: handlebars::RenderError) -> Self {
         PageError::Render(format!("{}", err))
@@ -34,23 +100,61 @@ impl From<serde_json::Error> for PageError {
 
 #[cfg(test)]
 pub mod test {
+    use crate::data::AppDatabase;
     use crate::test::async_runtime;
     use crate::RocketConfig;
     use rocket::local::blocking::Client;
 
     pub fn config() -> RocketConfig {
-        use crate::web::{hitcounter::HitCounter, renderer::Renderer};
         let rt = async_runtime();
-        let renderer = Renderer::new("./templates/".into());
         let database = crate::data::test::new_db(rt.handle());
-        let maintenance = crate::domain::maintenance::Maintenance::spawn(database.get_pool().clone(), rt.handle().clone());
-        let hit_counter = HitCounter::new(database.get_pool().clone(), rt.handle().clone());
+        config_with(database)
+    }
+
+    /// A configuration using `database`, for clients which must see the same data.
+    pub fn config_with(database: AppDatabase) -> RocketConfig {
+        use crate::web::{hitcounter::{HitCounter, HitCounterConfig}, renderer::Renderer};
+        let rt = async_runtime();
+        let renderer = Renderer::new("./templates/".into()).expect("failed to load templates");
+        let maintenance = crate::domain::maintenance::Maintenance::spawn(
+            database.get_pool().clone(),
+            rt.handle().clone(),
+            crate::domain::maintenance::DEFAULT_GRACE_PERIOD
+        );
+        let events = crate::web::events::ClipEvents::default();
+        let hit_counter = HitCounter::new(
+            database.get_pool().clone(),
+            rt.handle().clone(),
+            HitCounterConfig::default(),
+            events.clone()
+        );
+        let collab = crate::web::collab::Collab::new(database.get_pool().clone(), rt.handle().clone(), events.clone());
+        // Retry failed deliveries quickly, so the tests see them, and let them reach the local
+        // receivers of the tests.
+        let webhooks = crate::domain::webhook::WebhookDispatcher::spawn(
+            database.get_pool().clone(),
+            rt.handle().clone(),
+            crate::domain::webhook::WebhookConfig {
+                poll_interval: std::time::Duration::from_millis(100),
+                retry_delay: std::time::Duration::from_millis(100),
+                allowed_hosts: vec!["127.0.0.1".to_owned()],
+                ..Default::default()
+            }
+        );
+
+        // The background tasks run on this runtime, so keep it alive for the whole test.
+        std::mem::forget(rt);
 
         RocketConfig {
             renderer,
             database,
             hit_counter,
-            maintenance
+            events,
+            collab,
+            maintenance,
+            webhooks,
+            sso: crate::web::sso::Sso::disabled(),
+            slash: crate::web::slash::SlashCommands::disabled()
         }
     }
 
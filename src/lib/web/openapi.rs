@@ -0,0 +1,793 @@
+//! The OpenAPI 3 document describing the `/api/v1` routes, served at `/api/v1/openapi.json`.
+use crate::domain::clip::{ClipList, ClipMatch, ClipSummary, SearchResults};
+use crate::domain::scope::Scope;
+use crate::domain::stats::{ClipStats, DailyViews};
+use crate::domain::webhook::{Delivery, RegisteredWebhook, Webhook, WebhookEvent, DELIVERY_HEADER, SIGNATURE_HEADER, TIMESTAMP_HEADER};
+use crate::service::ask;
+use crate::web::api::{ErrorBody, ErrorCode, API_BASE, API_KEY_HEADER, CLIP_PASSWORD_HEADER};
+use crate::Clip;
+use rocket::serde::json::Json;
+use serde_json::{json, Map, Value};
+
+/// A type sent or received by the API, described as an OpenAPI schema. The `example` is a
+/// value of the type as it goes over the wire.
+pub trait ApiSchema {
+    const NAME: &'static str;
+    fn schema() -> Value;
+    fn example() -> Value;
+}
+
+fn reference(name: &str) -> Value {
+    json!({ "$ref": format!("#/components/schemas/{}", name) })
+}
+
+fn nullable(schema_type: &str, format: Option<&str>) -> Value {
+    let mut schema = json!({ "type": schema_type, "nullable": true });
+    if let Some(format) = format {
+        schema["format"] = json!(format);
+    }
+    schema
+}
+
+/// The fields of a clip the API takes when creating or editing one.
+fn clip_fields() -> Value {
+    json!({
+        "content": { "type": "string" },
+        "title": nullable("string", None),
+        "expires": nullable("string", Some("date-time")),
+        "password": nullable("string", None)
+    })
+}
+
+fn clip_fields_example() -> Value {
+    json!({
+        "content": "fn main() {}",
+        "title": "Hello",
+        "expires": "2030-01-01T00:00:00Z",
+        "password": null
+    })
+}
+
+/// The fields of a clip the API answers with in listings.
+fn summary_fields() -> Map<String, Value> {
+    let fields = json!({
+        "shortcode": { "type": "string" },
+        "title": nullable("string", None),
+        "posted": { "type": "string", "format": "date-time" },
+        "expires": nullable("string", Some("date-time")),
+        "hits": { "type": "integer", "format": "int64", "minimum": 0 }
+    });
+    match fields {
+        Value::Object(fields) => fields,
+        _ => unreachable!()
+    }
+}
+
+fn object(properties: Value, required: &[&str]) -> Value {
+    json!({ "type": "object", "properties": properties, "required": required })
+}
+
+impl ApiSchema for ask::NewClip {
+    const NAME: &'static str = "NewClip";
+    fn schema() -> Value {
+        object(clip_fields(), &["content", "title", "expires", "password"])
+    }
+    fn example() -> Value {
+        clip_fields_example()
+    }
+}
+
+impl ApiSchema for ask::EditClip {
+    const NAME: &'static str = "EditClip";
+    fn schema() -> Value {
+        object(clip_fields(), &["content", "title", "expires", "password"])
+    }
+    fn example() -> Value {
+        clip_fields_example()
+    }
+}
+
+impl ApiSchema for ask::UnlockClip {
+    const NAME: &'static str = "UnlockClip";
+    fn schema() -> Value {
+        object(json!({ "password": nullable("string", None) }), &["password"])
+    }
+    fn example() -> Value {
+        json!({ "password": "hunter2" })
+    }
+}
+
+impl ApiSchema for ask::NewApiKey {
+    const NAME: &'static str = "NewApiKey";
+    fn schema() -> Value {
+        let scopes: Vec<_> = Scope::ALL.iter().map(Scope::as_str).collect();
+        object(
+            json!({
+                "scopes": {
+                    "type": "array",
+                    "items": { "type": "string", "enum": scopes },
+                    "description": "All the clip scopes when empty or missing."
+                }
+            }),
+            &[]
+        )
+    }
+    fn example() -> Value {
+        json!({ "scopes": ["clip:read"] })
+    }
+}
+
+impl ApiSchema for Clip {
+    const NAME: &'static str = "Clip";
+    fn schema() -> Value {
+        let mut properties = summary_fields();
+        properties.insert("content".to_owned(), json!({ "type": "string" }));
+        properties.insert("password".to_owned(), nullable("string", None));
+        properties.insert(
+            "revision".to_owned(),
+            json!({ "type": "integer", "format": "int64", "minimum": 1, "description": "Bumped on every edit." })
+        );
+        properties.insert("updated".to_owned(), json!({ "type": "string", "format": "date-time" }));
+        let required: Vec<_> = properties.keys().map(String::as_str).collect();
+        object(Value::Object(properties.clone()), &required)
+    }
+    fn example() -> Value {
+        let mut example = ClipSummary::example();
+        example["content"] = json!("fn main() {}");
+        example["password"] = Value::Null;
+        example["revision"] = json!(2);
+        example["updated"] = json!("2026-01-02T08:30:00Z");
+        example
+    }
+}
+
+impl ApiSchema for ClipSummary {
+    const NAME: &'static str = "ClipSummary";
+    fn schema() -> Value {
+        let properties = summary_fields();
+        let required: Vec<_> = properties.keys().map(String::as_str).collect();
+        object(Value::Object(properties.clone()), &required)
+    }
+    fn example() -> Value {
+        json!({
+            "shortcode": "a1b2c3d4e5",
+            "title": "Hello",
+            "posted": "2026-01-01T12:00:00Z",
+            "expires": null,
+            "hits": 3
+        })
+    }
+}
+
+impl ApiSchema for ClipList {
+    const NAME: &'static str = "ClipList";
+    fn schema() -> Value {
+        object(
+            json!({
+                "clips": { "type": "array", "items": reference(ClipSummary::NAME) },
+                "next_cursor": nullable("string", None)
+            }),
+            &["clips", "next_cursor"]
+        )
+    }
+    fn example() -> Value {
+        json!({ "clips": [ClipSummary::example()], "next_cursor": null })
+    }
+}
+
+impl ApiSchema for ClipMatch {
+    const NAME: &'static str = "ClipMatch";
+    fn schema() -> Value {
+        json!({
+            "allOf": [
+                reference(ClipSummary::NAME),
+                object(json!({ "snippet": { "type": "string" } }), &["snippet"])
+            ]
+        })
+    }
+    fn example() -> Value {
+        let mut example = ClipSummary::example();
+        example["snippet"] = json!("fn <b>main</b>() {}");
+        example
+    }
+}
+
+impl ApiSchema for SearchResults {
+    const NAME: &'static str = "SearchResults";
+    fn schema() -> Value {
+        object(
+            json!({
+                "clips": { "type": "array", "items": reference(ClipMatch::NAME) },
+                "next_offset": nullable("integer", Some("int32"))
+            }),
+            &["clips", "next_offset"]
+        )
+    }
+    fn example() -> Value {
+        json!({ "clips": [ClipMatch::example()], "next_offset": 20 })
+    }
+}
+
+impl ApiSchema for DailyViews {
+    const NAME: &'static str = "DailyViews";
+    fn schema() -> Value {
+        object(
+            json!({
+                "day": { "type": "string", "format": "date" },
+                "views": { "type": "integer", "format": "int64", "minimum": 0 }
+            }),
+            &["day", "views"]
+        )
+    }
+    fn example() -> Value {
+        json!({ "day": "2026-01-01", "views": 3 })
+    }
+}
+
+impl ApiSchema for ClipStats {
+    const NAME: &'static str = "ClipStats";
+    fn schema() -> Value {
+        let counts = json!({ "type": "object", "additionalProperties": { "type": "integer", "format": "int64" } });
+        object(
+            json!({
+                "total": { "type": "integer", "format": "int64", "minimum": 0 },
+                "daily": { "type": "array", "items": reference(DailyViews::NAME) },
+                "routes": counts,
+                "agents": counts,
+                "referrers": counts
+            }),
+            &["total", "daily", "routes", "agents", "referrers"]
+        )
+    }
+    fn example() -> Value {
+        json!({
+            "total": 3,
+            "daily": [DailyViews::example()],
+            "routes": { "api": 1, "web": 2 },
+            "agents": { "browser": 2, "cli": 1 },
+            "referrers": { "example.com": 1 }
+        })
+    }
+}
+
+fn webhook_events() -> Value {
+    let events: Vec<_> = WebhookEvent::ALL.iter().map(WebhookEvent::as_str).collect();
+    json!({ "type": "array", "items": { "type": "string", "enum": events } })
+}
+
+/// The fields of a webhook the API answers with.
+fn webhook_fields() -> Map<String, Value> {
+    let fields = json!({
+        "webhook_id": { "type": "string" },
+        "url": { "type": "string", "format": "uri" },
+        "events": webhook_events(),
+        "created": { "type": "string", "format": "date-time" }
+    });
+    match fields {
+        Value::Object(fields) => fields,
+        _ => unreachable!()
+    }
+}
+
+impl ApiSchema for ask::NewWebhook {
+    const NAME: &'static str = "NewWebhook";
+    fn schema() -> Value {
+        object(
+            json!({ "url": { "type": "string", "format": "uri" }, "events": webhook_events() }),
+            &["url", "events"]
+        )
+    }
+    fn example() -> Value {
+        json!({ "url": "https://example.com/hooks/clipstash", "events": ["clip.created", "clip.deleted"] })
+    }
+}
+
+impl ApiSchema for Webhook {
+    const NAME: &'static str = "Webhook";
+    fn schema() -> Value {
+        let properties = webhook_fields();
+        let required: Vec<_> = properties.keys().map(String::as_str).collect();
+        object(Value::Object(properties.clone()), &required)
+    }
+    fn example() -> Value {
+        let mut example = ask::NewWebhook::example();
+        example["webhook_id"] = json!("1b4e28ba-2fa1-11d2-883f-0016d3cca427");
+        example["created"] = json!("2026-01-01T12:00:00Z");
+        example
+    }
+}
+
+impl ApiSchema for RegisteredWebhook {
+    const NAME: &'static str = "RegisteredWebhook";
+    fn schema() -> Value {
+        let mut properties = webhook_fields();
+        properties.insert(
+            "secret".to_owned(),
+            json!({
+                "type": "string",
+                "description": format!(
+                    "Signs the deliveries, which carry the HMAC-SHA256 of `<{}>.<body>` in the `{}` header. Only shown once.",
+                    TIMESTAMP_HEADER, SIGNATURE_HEADER
+                )
+            })
+        );
+        let required: Vec<_> = properties.keys().map(String::as_str).collect();
+        object(Value::Object(properties.clone()), &required)
+    }
+    fn example() -> Value {
+        let mut example = Webhook::example();
+        example["secret"] = json!("whsec_3q2-7wAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA");
+        example
+    }
+}
+
+impl ApiSchema for Delivery {
+    const NAME: &'static str = "Delivery";
+    fn schema() -> Value {
+        let events: Vec<_> = WebhookEvent::ALL.iter().map(WebhookEvent::as_str).collect();
+        object(
+            json!({
+                "delivery_id": {
+                    "type": "integer",
+                    "format": "int64",
+                    "description": format!("Sent in the `{}` header, and the same for every attempt.", DELIVERY_HEADER)
+                },
+                "event": { "type": "string", "enum": events },
+                "shortcode": { "type": "string" },
+                "created": { "type": "string", "format": "date-time" },
+                "attempts": { "type": "integer", "format": "int32", "minimum": 0 },
+                "delivered": nullable("string", Some("date-time")),
+                "next_attempt": {
+                    "type": "string",
+                    "format": "date-time",
+                    "nullable": true,
+                    "description": "When the delivery is attempted again. Failed deliveries without one were given up on."
+                },
+                "status": nullable("integer", Some("int32")),
+                "error": nullable("string", None)
+            }),
+            &["delivery_id", "event", "shortcode", "created", "attempts", "delivered", "next_attempt", "status", "error"]
+        )
+    }
+    fn example() -> Value {
+        json!({
+            "delivery_id": 42,
+            "event": "clip.created",
+            "shortcode": "a1b2c3d4e5",
+            "created": "2026-01-01T12:00:00Z",
+            "attempts": 2,
+            "delivered": "2026-01-01T12:00:10Z",
+            "next_attempt": null,
+            "status": 200,
+            "error": null
+        })
+    }
+}
+
+impl ApiSchema for ErrorBody {
+    const NAME: &'static str = "Error";
+    fn schema() -> Value {
+        let codes = [
+            ErrorCode::BadRequest,
+            ErrorCode::Unauthorized,
+            ErrorCode::PermissionDenied,
+            ErrorCode::NotFound,
+            ErrorCode::Conflict,
+            ErrorCode::Gone,
+            ErrorCode::PreconditionFailed,
+            ErrorCode::ValidationFailed,
+            ErrorCode::RateLimited,
+            ErrorCode::ServerError
+        ];
+        let codes: Vec<_> = codes.iter().map(ErrorCode::as_str).collect();
+        object(
+            json!({
+                "error": object(
+                    json!({
+                        "code": { "type": "string", "enum": codes },
+                        "message": { "type": "string" },
+                        "field": { "type": "string", "description": "The invalid field of the request, if any." }
+                    }),
+                    &["code", "message"]
+                )
+            }),
+            &["error"]
+        )
+    }
+    fn example() -> Value {
+        json!({ "error": { "code": "validation_failed", "message": "the limit must be between 1 and 100", "field": "limit" } })
+    }
+}
+
+fn component<T: ApiSchema>() -> (String, Value) {
+    let mut schema = T::schema();
+    schema["example"] = T::example();
+    (T::NAME.to_owned(), schema)
+}
+
+fn path_param(name: &str, description: &str) -> Value {
+    json!({ "name": name, "in": "path", "required": true, "description": description, "schema": { "type": "string" } })
+}
+
+fn header_param(name: &str, description: &str) -> Value {
+    json!({ "name": name, "in": "header", "required": false, "description": description, "schema": { "type": "string" } })
+}
+
+fn query_param(name: &str, schema_type: &str, required: bool, description: &str) -> Value {
+    json!({ "name": name, "in": "query", "required": required, "description": description, "schema": { "type": schema_type } })
+}
+
+/// An API operation needing `scope`, answering `status` with a body of the `response` schema.
+fn operation(
+    summary: &str,
+    scope: Scope,
+    parameters: Vec<Value>,
+    body: Option<&str>,
+    status: u16,
+    response: Value
+) -> Value {
+    let mut operation = json!({
+        "summary": summary,
+        "description": format!("Needs an API key with the `{}` scope.", scope),
+        "x-required-scope": scope.as_str(),
+        "parameters": parameters,
+        "security": [{ "apiKey": [] }, { "bearer": [] }],
+        "responses": {
+            status.to_string(): {
+                "description": "Success",
+                "content": { "application/json": { "schema": response } }
+            },
+            "default": {
+                "description": "Error",
+                "content": { "application/json": { "schema": reference(ErrorBody::NAME) } }
+            }
+        }
+    });
+    if let Some(body) = body {
+        operation["requestBody"] = json!({
+            "required": true,
+            "content": { "application/json": { "schema": reference(body) } }
+        });
+    }
+    operation
+}
+
+/// An operation answering with a backup, as NDJSON lines of `header`, `clip` and `end` type.
+fn backup_operation(summary: &str, scope: Scope) -> Value {
+    let mut operation = operation(summary, scope, vec![], None, 200, json!({ "type": "string" }));
+    operation["responses"]["200"]["content"] = json!({
+        "application/x-ndjson": {
+            "schema": {
+                "type": "string",
+                "description": "A `header` line, a `clip` line for every clip and an `end` line counting them. \
+                    A backup without its `end` line was cut short."
+            }
+        }
+    });
+    operation
+}
+
+/// Builds the OpenAPI document of the `/api/v1` routes.
+pub fn spec() -> Value {
+    let shortcode = || path_param("shortcode", "The shortcode of the clip.");
+    let password = || header_param(CLIP_PASSWORD_HEADER, "The password of a password-protected clip.");
+    let if_match = || header_param("If-Match", "The `ETag` of the revision the edit is based on. Edits of a newer revision fail with a 412.");
+    let limit = || query_param("limit", "integer", false, "The maximum number of clips, from 1 to 100.");
+    let webhook_id = || path_param("webhook_id", "The id of the webhook.");
+    let schemas: Map<String, Value> = vec![
+        component::<ask::NewClip>(),
+        component::<ask::EditClip>(),
+        component::<ask::UnlockClip>(),
+        component::<ask::NewApiKey>(),
+        component::<Clip>(),
+        component::<ClipSummary>(),
+        component::<ClipList>(),
+        component::<ClipMatch>(),
+        component::<SearchResults>(),
+        component::<DailyViews>(),
+        component::<ClipStats>(),
+        component::<ask::NewWebhook>(),
+        component::<Webhook>(),
+        component::<RegisteredWebhook>(),
+        component::<Delivery>(),
+        component::<ErrorBody>()
+    ]
+    .into_iter()
+    .collect();
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "ClipStash API",
+            "version": "1",
+            "description": format!(
+                "The unversioned routes under `/api/clip` are deprecated aliases of these. \
+                 Authenticate with the `{}` header or an `Authorization: Bearer` header.",
+                API_KEY_HEADER
+            )
+        },
+        "servers": [{ "url": "/" }],
+        "paths": {
+            format!("{}/openapi.json", API_BASE): {
+                "get": {
+                    "summary": "This document",
+                    "responses": { "200": { "description": "The OpenAPI document", "content": { "application/json": {} } } }
+                }
+            },
+            format!("{}/clips", API_BASE): {
+                "get": operation(
+                    "List the clips created with the API key, newest first",
+                    Scope::ClipRead,
+                    vec![query_param("cursor", "string", false, "The `next_cursor` of the previous page."), limit()],
+                    None,
+                    200,
+                    reference(ClipList::NAME)
+                ),
+                "post": operation(
+                    "Create a clip",
+                    Scope::ClipWrite,
+                    vec![],
+                    Some(ask::NewClip::NAME),
+                    200,
+                    reference(Clip::NAME)
+                )
+            },
+            format!("{}/clips/search", API_BASE): {
+                "get": operation(
+                    "Search the title and content of the clips created with the API key, except password-protected ones",
+                    Scope::ClipRead,
+                    vec![
+                        query_param("q", "string", true, "The search terms."),
+                        query_param("offset", "integer", false, "The number of results to skip."),
+                        limit()
+                    ],
+                    None,
+                    200,
+                    reference(SearchResults::NAME)
+                )
+            },
+            format!("{}/clips/export", API_BASE): {
+                "get": backup_operation(
+                    "Back up the clips created with the API key, with their passwords as hashes",
+                    Scope::ClipRead
+                )
+            },
+            format!("{}/clips/export/all", API_BASE): {
+                "get": backup_operation("Back up every clip, with their passwords as hashes", Scope::Admin)
+            },
+            format!("{}/clips/{{shortcode}}", API_BASE): {
+                "get": operation(
+                    "Get a clip",
+                    Scope::ClipRead,
+                    vec![
+                        shortcode(),
+                        password(),
+                        header_param("If-None-Match", "The `ETag` of the copy the client has, answered with a 304 while current.")
+                    ],
+                    None,
+                    200,
+                    reference(Clip::NAME)
+                ),
+                "put": operation(
+                    "Edit a clip created with the API key, or any clip as an admin",
+                    Scope::ClipWrite,
+                    vec![shortcode(), if_match()],
+                    Some(ask::EditClip::NAME),
+                    200,
+                    reference(Clip::NAME)
+                ),
+                "delete": operation(
+                    "Delete a clip created with the API key, or any clip as an admin",
+                    Scope::ClipDelete,
+                    vec![shortcode()],
+                    None,
+                    200,
+                    json!({ "type": "string" })
+                )
+            },
+            format!("{}/clips/{{shortcode}}/stats", API_BASE): {
+                "get": operation(
+                    "Get the view statistics of a clip",
+                    Scope::ClipRead,
+                    vec![shortcode(), password()],
+                    None,
+                    200,
+                    reference(ClipStats::NAME)
+                )
+            },
+            format!("{}/clips/{{shortcode}}/unlock", API_BASE): {
+                "post": operation(
+                    "Get a password-protected clip with the password in the body",
+                    Scope::ClipRead,
+                    vec![shortcode()],
+                    Some(ask::UnlockClip::NAME),
+                    200,
+                    reference(Clip::NAME)
+                )
+            },
+            format!("{}/clips/{{shortcode}}/restore", API_BASE): {
+                "post": operation(
+                    "Restore an expired or deleted clip",
+                    Scope::Admin,
+                    vec![shortcode()],
+                    None,
+                    200,
+                    reference(Clip::NAME)
+                )
+            },
+            format!("{}/clips/{{shortcode}}/edit-token", API_BASE): {
+                "post": operation(
+                    "Mint the token to edit a clip created with the API key together in the browser, revoking the previous one",
+                    Scope::ClipWrite,
+                    vec![shortcode()],
+                    None,
+                    201,
+                    json!({ "type": "string", "description": "The edit token, used as `/clip/{shortcode}#edit={token}`." })
+                )
+            },
+            format!("{}/webhooks", API_BASE): {
+                "get": operation(
+                    "List the webhooks of the API key",
+                    Scope::ClipRead,
+                    vec![],
+                    None,
+                    200,
+                    json!({ "type": "array", "items": reference(Webhook::NAME) })
+                ),
+                "post": operation(
+                    "Register a webhook getting the events of the clips created with the API key",
+                    Scope::ClipRead,
+                    vec![],
+                    Some(ask::NewWebhook::NAME),
+                    201,
+                    reference(RegisteredWebhook::NAME)
+                )
+            },
+            format!("{}/webhooks/{{webhook_id}}", API_BASE): {
+                "delete": operation(
+                    "Delete a webhook",
+                    Scope::ClipRead,
+                    vec![webhook_id()],
+                    None,
+                    200,
+                    json!({ "type": "string" })
+                )
+            },
+            format!("{}/webhooks/{{webhook_id}}/deliveries", API_BASE): {
+                "get": operation(
+                    "List the latest deliveries of a webhook, newest first",
+                    Scope::ClipRead,
+                    vec![webhook_id(), query_param("limit", "integer", false, "The maximum number of deliveries, from 1 to 100.")],
+                    None,
+                    200,
+                    json!({ "type": "array", "items": reference(Delivery::NAME) })
+                )
+            },
+            format!("{}/keys", API_BASE): {
+                "post": operation(
+                    "Mint an API key",
+                    Scope::Admin,
+                    vec![],
+                    Some(ask::NewApiKey::NAME),
+                    201,
+                    json!({ "type": "string", "description": "The new API key, which is only shown once." })
+                )
+            }
+        },
+        "components": {
+            "schemas": schemas,
+            "securitySchemes": {
+                "apiKey": { "type": "apiKey", "in": "header", "name": API_KEY_HEADER },
+                "bearer": { "type": "http", "scheme": "bearer" }
+            }
+        }
+    })
+}
+
+/// Endpoint serving the OpenAPI document. It needs no API key.
+#[rocket::get("/openapi.json")]
+pub fn openapi() -> Json<Value> {
+    Json(spec())
+}
+
+/// The URI [`routes`](rocket::Route) which can be mounted by [`rocket`] at `/api/v1`.
+pub fn routes() -> Vec<rocket::Route> {
+    rocket::routes![openapi]
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::ApiSchema;
+    use crate::domain::clip::{ClipList, ClipMatch, ClipSummary, SearchResults};
+    use crate::domain::stats::{ClipStats, DailyViews};
+    use crate::domain::webhook::{Delivery, RegisteredWebhook, Webhook};
+    use crate::service::ask;
+    use crate::web::api::{ErrorBody, API_BASE};
+    use crate::web::test::client;
+    use crate::Clip;
+    use rocket::http::Status;
+    use serde::{de::DeserializeOwned, Serialize};
+    use serde_json::Value;
+    use std::collections::BTreeSet;
+
+    /// The method, path and parameters of an operation, as `("get", "/api/v1/clips/{shortcode}", ["shortcode"])`.
+    type Operation = (String, String, BTreeSet<String>);
+
+    fn assert_round_trips<T: ApiSchema + DeserializeOwned + Serialize>() {
+        let example = T::example();
+        let value: T = serde_json::from_value(example.clone()).unwrap();
+        assert_eq!(serde_json::to_value(value).unwrap(), example, "{}", T::NAME);
+    }
+
+    #[test]
+    fn examples_match_the_types() {
+        assert_round_trips::<ask::NewClip>();
+        assert_round_trips::<ask::EditClip>();
+        assert_round_trips::<ask::UnlockClip>();
+        assert_round_trips::<ask::NewApiKey>();
+        assert_round_trips::<Clip>();
+        assert_round_trips::<ClipSummary>();
+        assert_round_trips::<ClipList>();
+        assert_round_trips::<ClipMatch>();
+        assert_round_trips::<SearchResults>();
+        assert_round_trips::<DailyViews>();
+        assert_round_trips::<ClipStats>();
+        assert_round_trips::<ask::NewWebhook>();
+        assert_round_trips::<Webhook>();
+        assert_round_trips::<RegisteredWebhook>();
+        assert_round_trips::<Delivery>();
+        assert_round_trips::<ErrorBody>();
+    }
+
+    #[test]
+    fn spec_matches_the_mounted_routes() {
+        let client = client();
+        let response = client.get(format!("{}/openapi.json", API_BASE)).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let spec: Value = response.into_json().unwrap();
+
+        let mut documented: BTreeSet<Operation> = BTreeSet::new();
+        for (path, operations) in spec["paths"].as_object().unwrap() {
+            for (method, operation) in operations.as_object().unwrap() {
+                // Rocket routes only name their path and query parameters.
+                let params = operation["parameters"]
+                    .as_array()
+                    .map(|params| {
+                        params
+                            .iter()
+                            .filter(|param| param["in"] != "header")
+                            .map(|param| param["name"].as_str().unwrap().to_owned())
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                documented.insert((method.clone(), path.clone(), params));
+            }
+        }
+
+        let mounted: BTreeSet<Operation> = client
+            .rocket()
+            .routes()
+            .filter(|route| route.uri.path().starts_with(API_BASE))
+            .map(|route| {
+                let mut params = BTreeSet::new();
+                let segments: Vec<_> = route.uri.path().trim_end_matches('/').split('/').map(|segment| {
+                    match segment.strip_prefix('<').and_then(|segment| segment.strip_suffix('>')) {
+                        Some(name) => {
+                            params.insert(name.to_owned());
+                            format!("{{{}}}", name)
+                        },
+                        None => segment.to_owned()
+                    }
+                }).collect();
+                for param in route.uri.query().unwrap_or_default().split('&') {
+                    if let Some(name) = param.strip_prefix('<').and_then(|param| param.strip_suffix('>')) {
+                        params.insert(name.to_owned());
+                    }
+                }
+                (route.method.as_str().to_lowercase(), segments.join("/"), params)
+            })
+            .collect();
+
+        assert_eq!(documented, mounted);
+    }
+}
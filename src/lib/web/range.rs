@@ -0,0 +1,197 @@
+//! Byte ranges of raw clips, whose content is streamed from the database a chunk at a time.
+use crate::data::DatabasePool;
+use crate::domain::clip::RawClip;
+use crate::service::action;
+use rocket::futures::stream::{Stream, StreamExt};
+use rocket::http::{ContentType, Status};
+use rocket::request::{FromRequest, Outcome};
+use rocket::response::stream::{stream, ReaderStream};
+use rocket::response::{self, Responder};
+use rocket::{Request, Response};
+use std::io::Cursor;
+use std::pin::Pin;
+
+/// How many bytes of a clip are read from the database at a time.
+pub const CHUNK_SIZE: u64 = 64 * 1024;
+
+/// The chunks of the content of a clip.
+pub type ContentStream = Pin<Box<dyn Stream<Item = Vec<u8>> + Send>>;
+
+/// The bytes from `start` to `end` of a clip, both included.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ByteRange {
+    pub start: u64,
+    pub end: u64
+}
+
+impl ByteRange {
+    /// The whole content of a clip which is `length` bytes long.
+    pub fn whole(length: u64) -> Self {
+        Self { start: 0, end: length.saturating_sub(1) }
+    }
+
+    pub fn len(&self) -> u64 {
+        self.end - self.start + 1
+    }
+
+    /// A range always holds at least one byte.
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+}
+
+/// Request guard for the `Range` and `If-Range` headers.
+#[derive(Debug, Default)]
+pub struct RangeHeaders {
+    range: Option<String>,
+    if_range: Option<String>
+}
+
+/// The range asked for starts past the end of the clip.
+#[derive(Debug)]
+pub struct Unsatisfiable;
+
+impl RangeHeaders {
+    /// The byte range asked for within a clip of `length` bytes whose entity tag is `etag`.
+    /// `None` means the whole clip is sent: when there is no `Range` header, when it asks for
+    /// several ranges or can't be read, or when the `If-Range` tag is not the current one.
+    pub fn resolve(&self, length: u64, etag: &str) -> Result<Option<ByteRange>, Unsatisfiable> {
+        let range = match &self.range {
+            Some(range) => range,
+            None => return Ok(None)
+        };
+        if self.if_range.as_deref().is_some_and(|if_range| if_range.trim() != etag) {
+            return Ok(None);
+        }
+        let (start, end) = match range.trim().strip_prefix("bytes=").and_then(|spec| spec.split_once('-')) {
+            Some(bounds) if !range.contains(',') => bounds,
+            _ => return Ok(None)
+        };
+        let (start, end) = match (start.trim(), end.trim()) {
+            ("", suffix) => match suffix.parse::<u64>() {
+                Ok(0) => return Err(Unsatisfiable),
+                Ok(suffix) => (length.saturating_sub(suffix), length.saturating_sub(1)),
+                Err(_) => return Ok(None)
+            },
+            (start, "") => match start.parse() {
+                Ok(start) => (start, length.saturating_sub(1)),
+                Err(_) => return Ok(None)
+            },
+            (start, end) => match (start.parse::<u64>(), end.parse::<u64>()) {
+                (Ok(start), Ok(end)) if start <= end => (start, end.min(length.saturating_sub(1))),
+                _ => return Ok(None)
+            }
+        };
+        if start >= length {
+            return Err(Unsatisfiable);
+        }
+        Ok(Some(ByteRange { start, end }))
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for RangeHeaders {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(Self {
+            range: req.headers().get_one("Range").map(str::to_owned),
+            if_range: req.headers().get_one("If-Range").map(str::to_owned)
+        })
+    }
+}
+
+/// Streams the bytes of `range` of a clip. The stream stops short if the clip is edited or
+/// deleted meanwhile, so clients see a truncated body rather than a mix of two revisions.
+pub fn content_stream(clip: RawClip, range: ByteRange, pool: DatabasePool) -> ContentStream {
+    Box::pin(stream! {
+        let mut offset = range.start;
+        while offset <= range.end {
+            let length = CHUNK_SIZE.min(range.end - offset + 1);
+            match action::read_clip_content(&clip, offset, length, &pool).await {
+                Ok(chunk) if !chunk.is_empty() => {
+                    offset += chunk.len() as u64;
+                    yield chunk;
+                },
+                Ok(_) => break,
+                Err(e) => {
+                    tracing::warn!(error = %e, shortcode = clip.shortcode.as_str(), "raw clip stream cut short");
+                    break;
+                }
+            }
+        }
+    })
+}
+
+/// The content of a raw clip, or the part of it asked for with a `Range` header.
+pub struct ClipBytes {
+    pub stream: ContentStream,
+    /// The length of the whole content, in bytes.
+    pub length: u64,
+    /// The part which is sent, if not all of it.
+    pub range: Option<ByteRange>
+}
+
+impl<'r> Responder<'r, 'static> for ClipBytes {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+        let mut response = Response::build();
+        response
+            .header(ContentType::Plain)
+            .raw_header("Accept-Ranges", "bytes")
+            .streamed_body(ReaderStream::from(self.stream.map(Cursor::new)));
+        match self.range {
+            Some(range) => response
+                .status(Status::PartialContent)
+                .raw_header("Content-Range", format!("bytes {}-{}/{}", range.start, range.end, self.length))
+                .raw_header("Content-Length", range.len().to_string()),
+            None => response.raw_header("Content-Length", self.length.to_string())
+        };
+        response.ok()
+    }
+}
+
+/// A `416 Range Not Satisfiable` for a clip of the given length.
+pub struct RangeNotSatisfiable(pub u64);
+
+impl<'r> Responder<'r, 'static> for RangeNotSatisfiable {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+        Response::build()
+            .status(Status::RangeNotSatisfiable)
+            .raw_header("Accept-Ranges", "bytes")
+            .raw_header("Content-Range", format!("bytes */{}", self.0))
+            .ok()
+    }
+}
+
+/// The answer to a request for a raw clip.
+#[derive(rocket::Responder)]
+pub enum RawResponse {
+    Bytes(ClipBytes),
+    NotSatisfiable(RangeNotSatisfiable)
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::{ByteRange, RangeHeaders};
+
+    fn resolve(range: &str) -> Option<Option<ByteRange>> {
+        let headers = RangeHeaders { range: Some(range.to_owned()), if_range: None };
+        headers.resolve(100, "\"1-ab\"").ok()
+    }
+
+    #[test]
+    fn resolves_byte_ranges() {
+        assert_eq!(resolve("bytes=0-9"), Some(Some(ByteRange { start: 0, end: 9 })));
+        assert_eq!(resolve("bytes=90-"), Some(Some(ByteRange { start: 90, end: 99 })));
+        assert_eq!(resolve("bytes=-10"), Some(Some(ByteRange { start: 90, end: 99 })));
+        assert_eq!(resolve("bytes=50-500"), Some(Some(ByteRange { start: 50, end: 99 })));
+        assert_eq!(resolve("bytes=-500"), Some(Some(ByteRange { start: 0, end: 99 })));
+        assert_eq!(resolve("bytes=0-1,5-9"), Some(None));
+        assert_eq!(resolve("lines=0-9"), Some(None));
+        assert_eq!(resolve("bytes=9-0"), Some(None));
+        assert_eq!(resolve("bytes=100-"), None);
+
+        let stale = RangeHeaders { range: Some("bytes=0-9".to_owned()), if_range: Some("\"0-ab\"".to_owned()) };
+        assert_eq!(stale.resolve(100, "\"1-ab\"").unwrap(), None);
+    }
+}
@@ -1,36 +1,51 @@
+use crate::metrics::METRICS;
 use crate::web::ctx;
 
+/// The handlebars errors are boxed, as they are much larger than the rendered pages.
 #[derive(Debug, thiserror::Error)]
 pub enum RenderError {
     #[error("Rendering error: {0}")]
-    RenderError(#[from] handlebars::RenderError)
+    RenderError(Box<handlebars::RenderError>),
+    #[error("Template error: {0}")]
+    Template(Box<handlebars::TemplateError>),
+    #[error("Serialization error: {0}")]
+    Serialization(#[from] serde_json::Error)
+}
+
+impl From<handlebars::RenderError> for RenderError {
+    fn from(err: handlebars::RenderError) -> Self {
+        Self::RenderError(Box::new(err))
+    }
+}
+
+impl From<handlebars::TemplateError> for RenderError {
+    fn from(err: handlebars::TemplateError) -> Self {
+        Self::Template(Box::new(err))
+    }
 }
 /// Define the page renderer, which evaluates the current context and displays the
 /// appropriate html.
 pub struct Renderer<'a>(handlebars::Handlebars<'a>);
 
 impl<'a> Renderer<'a> {
-    pub fn new(template_dir: std::path::PathBuf) -> Self {
+    pub fn new(template_dir: std::path::PathBuf) -> Result<Self, RenderError> {
         let mut renderer = handlebars::Handlebars::new();
-        renderer
-            .register_templates_directory(".hbs", &template_dir)
-            .expect("Failed to register handlebars templates");
+        renderer.register_templates_directory(".hbs", &template_dir)?;
 
-        Self(renderer)
+        Ok(Self(renderer))
     }
 
-    fn convert_to_value<S>(serializable: &S) -> serde_json::Value
+    fn convert_to_value<S>(serializable: &S) -> Result<serde_json::Value, RenderError>
     where
         S: serde::Serialize + std::fmt::Debug,
     {
-        serde_json::to_value(&serializable)
-            .expect("Failed to convert structure to value")
+        Ok(serde_json::to_value(serializable)?)
     }
 
-    pub fn render<P>(&self, context: P, errors: &[&str]) -> String
+    pub fn render<P>(&self, context: P, errors: &[&str]) -> Result<String, RenderError>
     where
         P: ctx::PageContext + serde::Serialize + std::fmt::Debug {
-        let mut value = Self::convert_to_value(&context);
+        let mut value = Self::convert_to_value(&context)?;
         if let Some(value) = value.as_object_mut() {
             value.insert("_errors".into(), errors.into());
             value.insert("_title".into(), context.title().into());
@@ -39,8 +54,12 @@ impl<'a> Renderer<'a> {
         self.do_render(context.template_path(), value)
     }
 
-    fn do_render(&self, path: &str, ctx: serde_json::Value) -> String {
-        self.0.render(path, &ctx).expect("error rendering template")
+    fn do_render(&self, path: &str, ctx: serde_json::Value) -> Result<String, RenderError> {
+        self.0.render(path, &ctx).map_err(|e| {
+            METRICS.render_errors.inc();
+            tracing::error!(error = %e, template = path, "failed to render template");
+            e.into()
+        })
     }
 
     pub fn render_with_data<P, D>(
@@ -48,14 +67,14 @@ impl<'a> Renderer<'a> {
         context: P,
         data: (&str, D),
         errors: &[&str])
-        -> String
+        -> Result<String, RenderError>
         where
             P: ctx::PageContext + serde::Serialize + std::fmt::Debug,
             D: serde::Serialize + std::fmt::Debug {
 
         use handlebars::to_json;
 
-        let mut value = Self::convert_to_value(&context);
+        let mut value = Self::convert_to_value(&context)?;
         if let Some(value) = value.as_object_mut() {
             value.insert("_errors".into(), errors.into());
             value.insert("_title".into(), context.title().into());
@@ -64,4 +83,30 @@ impl<'a> Renderer<'a> {
         }
         self.do_render(context.template_path(), value)
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+pub mod test {
+    use super::{RenderError, Renderer};
+    use crate::web::ctx::PageContext;
+
+    #[derive(Debug, serde::Serialize)]
+    struct Missing;
+
+    impl PageContext for Missing {
+        fn title(&self) -> &str {
+            "Missing"
+        }
+        fn template_path(&self) -> &str {
+            "missing"
+        }
+        fn parent(&self) -> &str {
+            "base"
+        }
+    }
+
+    #[test]
+    fn missing_template_is_an_error() {
+        let renderer = Renderer::new("./templates/".into()).unwrap();
+        assert!(matches!(renderer.render(Missing, &[]), Err(RenderError::RenderError(_))));
+    }
+}
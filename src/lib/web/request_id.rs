@@ -0,0 +1,117 @@
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::Header;
+use rocket::request::{FromRequest, Outcome};
+use rocket::{Data, Request, Response};
+use std::time::Instant;
+use uuid::Uuid;
+
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+/// The identifier of a request, taken from the `X-Request-Id` header set by a proxy when it
+/// looks sane, or generated otherwise. It is sent back in the response headers.
+#[derive(Clone, Debug)]
+pub struct RequestId(String);
+
+impl RequestId {
+    fn from_header(value: &str) -> Option<Self> {
+        let is_sane = !value.is_empty()
+            && value.len() <= 64
+            && value.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if is_sane {
+            Some(Self(value.to_owned()))
+        } else {
+            None
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl Default for RequestId {
+    fn default() -> Self {
+        Self(Uuid::new_v4().to_string())
+    }
+}
+
+impl std::fmt::Display for RequestId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Cached in every request by the [`RequestLogger`].
+struct RequestStart(RequestId, Instant);
+
+fn request_start<'r>(req: &'r Request<'_>) -> &'r RequestStart {
+    req.local_cache(|| {
+        let request_id = req.headers()
+            .get_one(REQUEST_ID_HEADER)
+            .and_then(RequestId::from_header)
+            .unwrap_or_default();
+        RequestStart(request_id, Instant::now())
+    })
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for &'r RequestId {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(&request_start(req).0)
+    }
+}
+
+/// Fairing which assigns an id to every request, adds it to the response headers and logs
+/// the outcome. Only the method, path and status are logged: never headers, query strings or
+/// bodies, as they may hold passwords, API keys or clip content.
+pub struct RequestLogger;
+
+#[rocket::async_trait]
+impl Fairing for RequestLogger {
+    fn info(&self) -> Info {
+        Info {
+            name: "Request id and logging",
+            kind: Kind::Request | Kind::Response
+        }
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, _: &mut Data<'_>) {
+        request_start(req);
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        let RequestStart(request_id, started) = request_start(req);
+        res.set_header(Header::new(REQUEST_ID_HEADER, request_id.to_string()));
+
+        tracing::info!(
+            request_id = %request_id,
+            method = %req.method(),
+            path = %req.uri().path(),
+            route = req.route().map(|route| route.uri.as_str()).unwrap_or("unmatched"),
+            status = res.status().code,
+            elapsed_ms = started.elapsed().as_millis() as u64,
+            "request handled"
+        );
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::REQUEST_ID_HEADER;
+    use crate::web::test::client;
+
+    #[test]
+    fn responses_carry_a_request_id() {
+        let client = client();
+        let response = client.get("/").dispatch();
+        assert!(response.headers().get_one(REQUEST_ID_HEADER).is_some());
+
+        let response = client
+            .get("/")
+            .header(rocket::http::Header::new(REQUEST_ID_HEADER, "proxy-id-1"))
+            .dispatch();
+        assert_eq!(response.headers().get_one(REQUEST_ID_HEADER), Some("proxy-id-1"));
+    }
+}
@@ -0,0 +1,74 @@
+use crate::data::AppDatabase;
+use crate::domain::user::{SessionToken, User};
+use crate::service::action;
+use crate::ServiceError;
+use rocket::http::{Cookie, CookieJar, SameSite, Status};
+use rocket::request::{FromRequest, Outcome};
+use rocket::{Request, State};
+use std::time::Duration;
+
+pub const SESSION_COOKIE: &str = "session";
+
+/// How long a user stays logged in.
+pub const SESSION_TTL: Duration = Duration::from_secs(60 * 60 * 24 * 30);
+
+/// The user logged in through the session cookie. The guard fails with `401 Unauthorized`
+/// when there is no valid session, so use `Option<CurrentUser>` for pages which don't
+/// require a login.
+#[derive(Debug, Clone)]
+pub struct CurrentUser(pub User);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for CurrentUser {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        // Cached, as several guards of the same request may ask for the user.
+        let user: &Option<User> = req.local_cache_async(async {
+            let token: SessionToken = req.cookies().get(SESSION_COOKIE)?.value().parse().ok()?;
+            let database = req.guard::<&State<AppDatabase>>().await.succeeded()?;
+            match action::get_session_user(&token, database.get_pool()).await {
+                Ok(user) => Some(user),
+                Err(ServiceError::NotFound) => None,
+                Err(e) => {
+                    tracing::error!(error = %e, "failed to get session");
+                    None
+                }
+            }
+        }).await;
+
+        match user {
+            Some(user) => Outcome::Success(CurrentUser(user.clone())),
+            None => Outcome::Error((Status::Unauthorized, ()))
+        }
+    }
+}
+
+/// Logs a user in by starting a session and setting the session cookie.
+pub async fn start_session(
+    cookies: &CookieJar<'_>,
+    user: &User,
+    database: &AppDatabase
+) -> Result<(), ServiceError> {
+    let token = action::new_session(user, SESSION_TTL, database.get_pool()).await?;
+    let cookie = Cookie::build((SESSION_COOKIE, token.to_base64()))
+        .path("/")
+        .http_only(true)
+        .same_site(SameSite::Lax)
+        .max_age(rocket::time::Duration::seconds(SESSION_TTL.as_secs() as i64))
+        .build();
+    cookies.add(cookie);
+    Ok(())
+}
+
+/// Logs the user out by ending the session and removing the session cookie.
+pub async fn end_session(cookies: &CookieJar<'_>, database: &AppDatabase) -> Result<(), ServiceError> {
+    if let Some(token) = cookies
+        .get(SESSION_COOKIE)
+        .and_then(|cookie| cookie.value().parse::<SessionToken>().ok())
+    {
+        action::end_session(&token, database.get_pool()).await?;
+    }
+    cookies.remove(Cookie::from(SESSION_COOKIE));
+    Ok(())
+}
@@ -0,0 +1,257 @@
+use crate::data::AppDatabase;
+use crate::domain::slash::{self, ClipCommand, SlashCommandConfig, SlashCommandError};
+use crate::service::{action, ask};
+use crate::web::form;
+use crate::web::request_id::RequestId;
+use crate::ShortCode;
+use chrono::Utc;
+use rocket::data::{Data, Limits};
+use rocket::form::Form;
+use rocket::http::{RawStr, Status};
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::serde::json::Json;
+use rocket::{uri, State};
+use serde::Serialize;
+
+/// The chat server allowed to create clips with slash commands, if one is configured.
+pub struct SlashCommands(Option<SlashCommandConfig>);
+
+impl SlashCommands {
+    pub fn new(config: Option<SlashCommandConfig>) -> Self {
+        Self(config)
+    }
+
+    pub fn disabled() -> Self {
+        Self(None)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.0.is_some()
+    }
+}
+
+/// The signature headers of a command, which are missing when it comes from Mattermost.
+pub struct CommandSignature<'r> {
+    timestamp: Option<&'r str>,
+    signature: Option<&'r str>
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for CommandSignature<'r> {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(Self {
+            timestamp: req.headers().get_one(slash::TIMESTAMP_HEADER),
+            signature: req.headers().get_one(slash::SIGNATURE_HEADER)
+        })
+    }
+}
+
+/// The answer to a command, shown in the chat. Ephemeral answers are only shown to the user
+/// who typed the command.
+#[derive(Debug, Serialize)]
+pub struct CommandResponse {
+    response_type: &'static str,
+    text: String
+}
+
+impl CommandResponse {
+    fn ephemeral<T: Into<String>>(text: T) -> Json<Self> {
+        Json(Self { response_type: "ephemeral", text: text.into() })
+    }
+}
+
+/// Creates a clip with the `/clip` slash command of Slack or Mattermost, and answers with its
+/// link. Mistakes in the command are answered with the usage and a 200, as chat servers only
+/// show the text of successful answers.
+#[rocket::post("/slash/clip", data = "<body>")]
+#[tracing::instrument(skip_all, fields(request_id = %request_id))]
+pub async fn clip_command(
+    request_id: &RequestId,
+    signature: CommandSignature<'_>,
+    body: Data<'_>,
+    limits: &Limits,
+    slash: &State<SlashCommands>,
+    database: &State<AppDatabase>
+) -> Result<Json<CommandResponse>, Status> {
+    let config = slash.0.as_ref().ok_or(Status::NotFound)?;
+    let body = body
+        .open(limits.get("form").unwrap_or(Limits::FORM))
+        .into_string()
+        .await
+        .map_err(|_| Status::BadRequest)?;
+    if !body.is_complete() {
+        return Err(Status::PayloadTooLarge);
+    }
+    let body = body.into_inner();
+    let command = Form::<form::SlashCommand>::parse_encoded(RawStr::new(&body)).map_err(|_| Status::BadRequest)?;
+
+    let verified = match (signature.timestamp, signature.signature) {
+        (Some(timestamp), Some(signature)) => slash::verify_signature(
+            &config.signing_secret,
+            timestamp,
+            &body,
+            signature,
+            Utc::now().timestamp()
+        ),
+        _ => slash::verify_token(&config.signing_secret, command.token.as_deref().unwrap_or_default())
+    };
+    if let Err(e) = verified {
+        tracing::warn!(error = %e, "refused slash command");
+        return Err(Status::Unauthorized);
+    }
+
+    let clip = match ClipCommand::parse(&command.text, Utc::now()) {
+        Ok(clip) => clip,
+        Err(SlashCommandError::Clip(crate::ClipError::EmptyContent)) => {
+            return Ok(CommandResponse::ephemeral(slash::USAGE));
+        },
+        Err(e) => return Ok(CommandResponse::ephemeral(format!("{}\n{}", e, slash::USAGE)))
+    };
+    let req = ask::NewClip {
+        content: clip.content,
+        title: clip.title,
+        expires: clip.expires,
+        password: clip.password,
+        owner: None,
+        user: None,
+        workspace: None
+    };
+    let clip = match action::new_clip(req, database.get_pool()).await {
+        Ok(clip) => clip,
+        Err(e) => {
+            tracing::error!(error = %e, "failed to create a clip from a slash command");
+            return Ok(CommandResponse::ephemeral("The clip could not be created, please try again."));
+        }
+    };
+    tracing::info!(user_name = command.user_name.as_str(), command = command.command.as_str(), "clip created with a slash command");
+
+    let url = format!(
+        "{}{}",
+        config.public_url.trim_end_matches('/'),
+        uri!(crate::web::http::get_clip(shortcode = clip.shortcode.clone()))
+    );
+    let mut text = format!("Clip created: {}", url);
+    if clip.password.has_password() {
+        text.push_str("\nIt is protected by the password you gave.");
+    }
+    if let Some(expires) = clip.expires.into_inner() {
+        text.push_str(&format!("\nIt expires {}.", expires.into_inner().format("%Y-%m-%d %H:%M UTC")));
+    }
+    Ok(CommandResponse::ephemeral(text))
+}
+
+/// The URI [`routes`](rocket::Route) which can be mounted by [`rocket`].
+pub fn routes() -> Vec<rocket::Route> {
+    rocket::routes![clip_command]
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::SlashCommands;
+    use crate::domain::slash::{signature, SlashCommandConfig, SIGNATURE_HEADER, TIMESTAMP_HEADER};
+    use crate::web::test::config;
+    use chrono::Utc;
+    use rocket::http::{ContentType, Header, Status};
+    use rocket::local::blocking::Client;
+    use rocket::serde::json::Value;
+
+    const SECRET: &str = "8f742231b10e8888abcd99yyyzzz85a5";
+
+    fn client() -> Client {
+        let mut config = config();
+        config.slash = SlashCommands::new(Some(SlashCommandConfig {
+            signing_secret: SECRET.to_owned(),
+            public_url: "https://clips.example.com/".to_owned()
+        }));
+        Client::tracked(crate::rocket(config)).expect("failed to build rocket instance")
+    }
+
+    /// A command as Slack sends it, with `text` already encoded.
+    fn slack_body(text: &str) -> String {
+        format!(
+            "token=xyzz0WbapA4vBCDEFasx0q6G&team_id=T1DC2JH3J&team_domain=testteamnow&channel_id=G8PSS9T3V&channel_name=foobar&user_id=U2CERLKJA&user_name=roadrunner&command=%2Fclip&text={}&response_url=https%3A%2F%2Fhooks.slack.com%2Fcommands%2FT1DC2JH3J%2F397700885554%2F96rGlfmibIGlgcZRskXaIFfN&trigger_id=398738663015.47445629121.803a0bc887a14d10d2c447fce8b6703c",
+            text
+        )
+    }
+
+    fn send_signed(client: &Client, body: String, timestamp: i64, secret: &str) -> (Status, Option<Value>) {
+        let response = client
+            .post("/slash/clip")
+            .header(ContentType::Form)
+            .header(Header::new(TIMESTAMP_HEADER, timestamp.to_string()))
+            .header(Header::new(SIGNATURE_HEADER, signature(secret, timestamp, &body)))
+            .body(body)
+            .dispatch();
+        let status = response.status();
+        (status, response.into_json())
+    }
+
+    /// The raw path of the clip linked to by a successful command.
+    fn raw_path(answer: &Value) -> String {
+        assert_eq!(answer["response_type"], "ephemeral");
+        let text = answer["text"].as_str().unwrap();
+        let shortcode = text.lines().next().unwrap().strip_prefix("Clip created: https://clips.example.com/clip/").unwrap();
+        format!("/clip/raw/{}", shortcode)
+    }
+
+    #[test]
+    fn creates_clips_from_signed_slack_commands() {
+        let client = client();
+        let now = Utc::now().timestamp();
+
+        let (status, answer) = send_signed(&client, slack_body("fn+main%28%29+%7B%7D"), now, SECRET);
+        assert_eq!(status, Status::Ok);
+        let response = client.get(raw_path(&answer.unwrap())).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.into_string().unwrap(), "fn main() {}");
+
+        let (status, answer) = send_signed(
+            &client,
+            slack_body("--password+hunter2+--expires+1d+--title+%22Build+log%22+it+failed"),
+            now,
+            SECRET
+        );
+        assert_eq!(status, Status::Ok);
+        let answer = answer.unwrap();
+        assert!(answer["text"].as_str().unwrap().contains("password"));
+        assert!(answer["text"].as_str().unwrap().contains("expires"));
+        let response = client.get(raw_path(&answer)).dispatch();
+        assert_eq!(response.status(), Status::Unauthorized);
+
+        // Mistakes are answered with the usage, for the user to see.
+        let (status, answer) = send_signed(&client, slack_body("--expires+soon+text"), now, SECRET);
+        assert_eq!(status, Status::Ok);
+        let text = answer.unwrap()["text"].as_str().unwrap().to_owned();
+        assert!(text.starts_with("invalid expiry 'soon'"));
+        assert!(text.contains("Usage: /clip"));
+
+        let (status, _) = send_signed(&client, slack_body("text"), now, "another secret");
+        assert_eq!(status, Status::Unauthorized);
+        let (status, _) = send_signed(&client, slack_body("text"), now - 60 * 60, SECRET);
+        assert_eq!(status, Status::Unauthorized);
+    }
+
+    #[test]
+    fn creates_clips_from_mattermost_commands_with_their_token() {
+        let client = client();
+        let body = |token: &str| format!(
+            "channel_id=cniah6qa73bjjjan6mzn11f4ie&channel_name=town-square&command=%2Fclip&response_url=http%3A%2F%2Flocalhost%3A8065%2Fhooks%2Fcommands%2Fr5dotbqn3tbyz&team_domain=someteam&team_id=rdc9bgriktyx9p4kowh3dmgqyc&text=hello+mattermost&token={}&trigger_id=dGVzdA&user_id=pan9zk5ixj8cm8o1ixh9ac1tfe&user_name=somename",
+            token
+        );
+
+        let response = client.post("/slash/clip").header(ContentType::Form).body(body(SECRET)).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let response = client.get(raw_path(&response.into_json().unwrap())).dispatch();
+        assert_eq!(response.into_string().unwrap(), "hello mattermost");
+
+        let response = client.post("/slash/clip").header(ContentType::Form).body(body("wrong")).dispatch();
+        assert_eq!(response.status(), Status::Unauthorized);
+
+        // Without a configured secret, there is no endpoint.
+        let client = Client::tracked(crate::rocket(config())).expect("failed to build rocket instance");
+        let response = client.post("/slash/clip").header(ContentType::Form).body(body(SECRET)).dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+    }
+}
@@ -0,0 +1,278 @@
+use crate::data::AppDatabase;
+use crate::domain::scope::Scope;
+use crate::domain::workspace::field::WorkspaceId;
+use crate::service::{action, ask};
+use crate::web::request_id::RequestId;
+use crate::web::session::CurrentUser;
+use crate::web::{account, ctx, form, form_errors, renderer::Renderer, PageError};
+use crate::ServiceError;
+use rocket::form::{Contextual, Form};
+use rocket::http::Status;
+use rocket::response::content::RawHtml;
+use rocket::response::Redirect;
+use rocket::{uri, State};
+
+/// Renders a workspace of the logged in user, optionally showing an API key which was just minted.
+async fn render_workspace(
+    user: &CurrentUser,
+    workspace_id: WorkspaceId,
+    cursor: Option<&str>,
+    new_api_key: Option<String>,
+    database: &AppDatabase,
+    renderer: &Renderer<'_>,
+    errors: &[&str]
+) -> Result<RawHtml<String>, PageError> {
+    let pool = database.get_pool();
+    let workspace = match action::get_workspace(&workspace_id, &user.0.user_id, pool).await {
+        Ok(workspace) => workspace,
+        Err(ServiceError::NotFound) => return Err(PageError::NotFound("Workspace not found".to_owned())),
+        Err(e) => return Err(e.into())
+    };
+    let req = ask::ListWorkspaceClips {
+        cursor: cursor.and_then(|cursor| cursor.parse().ok()),
+        limit: ask::ListClips::DEFAULT_LIMIT,
+        workspace_id: workspace_id.clone()
+    };
+    let clips = action::list_workspace_clips(req, pool).await?;
+    let members = action::list_workspace_members(&workspace_id, pool).await?;
+    let api_keys = if workspace.is_owner() {
+        action::list_workspace_api_keys(&workspace_id, pool).await?
+    } else {
+        vec![]
+    };
+    let context = ctx::Workspace::new(workspace, clips, members, api_keys, new_api_key);
+    Ok(RawHtml(renderer.render(context, errors)?))
+}
+
+/// Creates a workspace owned by the logged in user using form data.
+#[rocket::post("/workspace", data = "<form>")]
+#[tracing::instrument(skip_all, fields(request_id = %request_id))]
+pub async fn new_workspace(
+    request_id: &RequestId,
+    user: CurrentUser,
+    form: Form<Contextual<'_, form::NewWorkspace>>,
+    database: &State<AppDatabase>,
+    renderer: &State<Renderer<'_>>
+) -> Result<Result<Redirect, (Status, RawHtml<String>)>, PageError> {
+    let form = form.into_inner();
+    let value = match form.value {
+        Some(value) => value,
+        None => {
+            let errors = form_errors(&form);
+            let html = account::render_dashboard(user, None, None, database, renderer, &errors).await?;
+            return Ok(Err((Status::BadRequest, html)));
+        }
+    };
+
+    let membership = action::new_workspace(value.name, &user.0.user_id, database.get_pool()).await?;
+    tracing::info!(workspace_id = %membership.workspace.workspace_id, "workspace created");
+    Ok(Ok(Redirect::to(uri!(workspace(membership.workspace.workspace_id, _)))))
+}
+
+/// Shows the clips and members of a workspace of the logged in user.
+#[rocket::get("/workspace/<workspace_id>?<cursor>")]
+pub async fn workspace(
+    user: CurrentUser,
+    workspace_id: WorkspaceId,
+    cursor: Option<&str>,
+    database: &State<AppDatabase>,
+    renderer: &State<Renderer<'_>>
+) -> Result<RawHtml<String>, PageError> {
+    render_workspace(&user, workspace_id, cursor, None, database, renderer, &[]).await
+}
+
+/// Adds a user to a workspace of the logged in user, who must be one of its owners.
+#[rocket::post("/workspace/<workspace_id>/member", data = "<form>")]
+#[tracing::instrument(skip_all, fields(request_id = %request_id, workspace_id = %workspace_id))]
+pub async fn add_member(
+    request_id: &RequestId,
+    user: CurrentUser,
+    workspace_id: WorkspaceId,
+    form: Form<form::AddMember>,
+    database: &State<AppDatabase>,
+    renderer: &State<Renderer<'_>>
+) -> Result<Result<Redirect, (Status, RawHtml<String>)>, PageError> {
+    match action::add_workspace_member(&workspace_id, &user.0.user_id, &form.username, database.get_pool()).await {
+        Ok(()) => Ok(Ok(Redirect::to(uri!(workspace(workspace_id, _))))),
+        Err(ServiceError::NotFound) => {
+            let html = render_workspace(
+                &user,
+                workspace_id,
+                None,
+                None,
+                database,
+                renderer,
+                &["There is no user with this username"]
+            ).await?;
+            Ok(Err((Status::BadRequest, html)))
+        },
+        Err(e) => Err(e.into())
+    }
+}
+
+/// Removes a member from a workspace of the logged in user, who must be one of its owners.
+#[rocket::post("/workspace/<workspace_id>/member/<username>/remove")]
+#[tracing::instrument(skip_all, fields(request_id = %request_id, workspace_id = %workspace_id))]
+pub async fn remove_member(
+    request_id: &RequestId,
+    user: CurrentUser,
+    workspace_id: WorkspaceId,
+    username: &str,
+    database: &State<AppDatabase>
+) -> Result<Redirect, PageError> {
+    action::remove_workspace_member(&workspace_id, &user.0.user_id, username, database.get_pool()).await?;
+    Ok(Redirect::to(uri!(workspace(workspace_id, _))))
+}
+
+/// Mints an API key scoped to a workspace with the ticked scopes, or all the `clip` scopes when
+/// no form is sent. The key is only shown on the returned page.
+#[rocket::post("/workspace/<workspace_id>/key", data = "<form>")]
+#[tracing::instrument(skip_all, fields(request_id = %request_id, workspace_id = %workspace_id))]
+pub async fn new_api_key(
+    request_id: &RequestId,
+    user: CurrentUser,
+    workspace_id: WorkspaceId,
+    form: Option<Form<form::NewApiKey>>,
+    database: &State<AppDatabase>,
+    renderer: &State<Renderer<'_>>
+) -> Result<(Status, RawHtml<String>), PageError> {
+    let scopes = form.map_or_else(|| Scope::CLIPS.to_vec(), |form| form.into_inner().scopes);
+    let pool = database.get_pool();
+    match action::generate_workspace_api_key(&workspace_id, &user.0.user_id, &scopes, pool).await {
+        Ok(api_key) => {
+            tracing::info!("API key generated");
+            let new_api_key = Some(api_key.to_base64());
+            let html = render_workspace(&user, workspace_id, None, new_api_key, database, renderer, &[]).await?;
+            Ok((Status::Ok, html))
+        },
+        Err(ServiceError::PermissionError(msg)) => {
+            let html = render_workspace(&user, workspace_id, None, None, database, renderer, &[msg.as_str()]).await?;
+            Ok((Status::BadRequest, html))
+        },
+        Err(e) => Err(e.into())
+    }
+}
+
+/// Revokes an API key scoped to a workspace.
+#[rocket::post("/workspace/<workspace_id>/key/<fingerprint>/revoke")]
+#[tracing::instrument(skip_all, fields(request_id = %request_id, workspace_id = %workspace_id))]
+pub async fn revoke_api_key(
+    request_id: &RequestId,
+    user: CurrentUser,
+    workspace_id: WorkspaceId,
+    fingerprint: &str,
+    database: &State<AppDatabase>
+) -> Result<Redirect, PageError> {
+    action::revoke_workspace_api_key(&workspace_id, &user.0.user_id, fingerprint, database.get_pool()).await?;
+    Ok(Redirect::to(uri!(workspace(workspace_id, _))))
+}
+
+/// The URI [`routes`](rocket::Route) which can be mounted by [`rocket`].
+pub fn routes() -> Vec<rocket::Route> {
+    rocket::routes![new_workspace, workspace, add_member, remove_member, new_api_key, revoke_api_key]
+}
+
+#[cfg(test)]
+pub mod test {
+    use crate::data::AppDatabase;
+    use crate::web::test::{client, config_with};
+    use rocket::http::{ContentType, Status};
+    use rocket::local::blocking::Client;
+
+    fn register(client: &Client) -> String {
+        let username = format!("user-{}", &uuid::Uuid::new_v4().to_string()[..8]);
+        let response = client
+            .post("/register")
+            .header(ContentType::Form)
+            .body(format!("username={}&password=password123&confirm_password=password123", username))
+            .dispatch();
+        assert_eq!(response.status(), Status::SeeOther);
+        username
+    }
+
+    /// Reads the API key shown once on the page, undoing the escaping of the base64 padding.
+    fn new_api_key(html: &str) -> String {
+        let start = html.find("id=\"new-api-key\">").unwrap() + "id=\"new-api-key\">".len();
+        let end = start + html[start..].find('<').unwrap();
+        html[start..end].replace("&#x3D;", "=")
+    }
+
+    #[test]
+    fn workspace_clips_are_only_visible_to_members() {
+        // Every user browses the same server, each with their own cookies.
+        let owner = client();
+        let database = owner.rocket().state::<AppDatabase>().unwrap().clone();
+        let same_server = || Client::tracked(crate::rocket(config_with(database.clone()))).unwrap();
+        let member = same_server();
+        let outsider = same_server();
+        register(&owner);
+        let member_name = register(&member);
+        register(&outsider);
+
+        let response = owner
+            .post("/workspace")
+            .header(ContentType::Form)
+            .body("name=Platform+team")
+            .dispatch();
+        assert_eq!(response.status(), Status::SeeOther);
+        let workspace = response.headers().get_one("Location").unwrap().to_owned();
+        let workspace_id = workspace.trim_start_matches("/workspace/").to_owned();
+
+        let response = owner
+            .post(format!("{}/member", workspace))
+            .header(ContentType::Form)
+            .body(format!("username={}", member_name))
+            .dispatch();
+        assert_eq!(response.status(), Status::SeeOther);
+        let response = owner
+            .post(format!("{}/member", workspace))
+            .header(ContentType::Form)
+            .body("username=nobody-by-this-name")
+            .dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+
+        let response = owner
+            .post("/")
+            .header(ContentType::Form)
+            .body(format!("content=team+secret&title=&expires=&password=&workspace={}", workspace_id))
+            .dispatch();
+        assert_eq!(response.status(), Status::SeeOther);
+        let clip = response.headers().get_one("Location").unwrap().to_owned();
+
+        assert_eq!(owner.get(&clip).dispatch().status(), Status::Ok);
+        let response = member.get(&clip).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert!(response.into_string().unwrap().contains("team secret"));
+        assert_eq!(member.get(&workspace).dispatch().status(), Status::Ok);
+        assert_eq!(member.post(format!("{}/key", workspace)).dispatch().status(), Status::Forbidden);
+        assert_eq!(outsider.get(&clip).dispatch().status(), Status::NotFound);
+        assert_eq!(outsider.get(&workspace).dispatch().status(), Status::NotFound);
+
+        let anonymous = same_server();
+        let response = anonymous.get(&clip).dispatch();
+        assert_eq!(response.status(), Status::Unauthorized);
+        let html = response.into_string().unwrap();
+        assert!(html.contains("action=\"/login\""));
+        assert!(!html.contains("team secret"));
+
+        let response = owner.post(format!("{}/key", workspace)).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let api_key = new_api_key(&response.into_string().unwrap());
+        let response = anonymous
+            .post("/api/clip")
+            .header(ContentType::JSON)
+            .header(rocket::http::Header::new(crate::web::api::API_KEY_HEADER, api_key.clone()))
+            .body(r#"{"content":"from the API","title":"","expires":null,"password":null}"#)
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let api_clip: serde_json::Value = response.into_json().unwrap();
+        let shortcode = api_clip["shortcode"].as_str().unwrap();
+        assert_eq!(anonymous.get(format!("/clip/{}", shortcode)).dispatch().status(), Status::Unauthorized);
+        assert_eq!(member.get(format!("/clip/{}", shortcode)).dispatch().status(), Status::Ok);
+        let response = anonymous
+            .get(format!("/api/clip/{}", shortcode))
+            .header(rocket::http::Header::new(crate::web::api::API_KEY_HEADER, api_key))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+    }
+}
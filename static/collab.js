@@ -0,0 +1,222 @@
+// Edits a clip together with the other viewers of its page over the WebSocket at
+// `/clip/<shortcode>/collab`, which sends the clip's text and then every edit made to it,
+// including our own. The transformation of concurrent edits mirrors
+// `src/lib/domain/collab.rs`. Positions count characters, not UTF-16 code units.
+function ClipCollab(shortcode, textarea, token) {
+  var client = Math.random().toString(36).slice(2);
+  var version = null;
+  var text = Array.from(textarea.value);
+  var inflight = null;
+  var buffer = null;
+  var socket = new WebSocket((location.protocol === 'https:' ? 'wss://' : 'ws://')
+    + location.host + '/clip/' + shortcode + '/collab');
+
+  function transformOp(op, other, winsTies) {
+    var result;
+    if (op.type === 'insert' && other.type === 'insert') {
+      if (op.pos < other.pos || (op.pos === other.pos && winsTies)) {
+        result = op;
+      } else {
+        result = { type: 'insert', pos: op.pos + Array.from(other.text).length, text: op.text };
+      }
+    } else if (op.type === 'insert') {
+      var pos = op.pos <= other.pos ? op.pos
+        : op.pos >= other.pos + other.len ? op.pos - other.len
+        : other.pos;
+      result = { type: 'insert', pos: pos, text: op.text };
+    } else if (other.type === 'insert') {
+      var inserted = Array.from(other.text).length;
+      if (other.pos <= op.pos) {
+        result = { type: 'delete', pos: op.pos + inserted, len: op.len };
+      } else if (other.pos >= op.pos + op.len) {
+        result = op;
+      } else {
+        return [
+          { type: 'delete', pos: op.pos, len: other.pos - op.pos },
+          { type: 'delete', pos: op.pos + inserted, len: op.pos + op.len - other.pos }
+        ];
+      }
+    } else if (op.pos + op.len <= other.pos) {
+      result = op;
+    } else if (op.pos >= other.pos + other.len) {
+      result = { type: 'delete', pos: op.pos - other.len, len: op.len };
+    } else {
+      var overlap = Math.min(op.pos + op.len, other.pos + other.len) - Math.max(op.pos, other.pos);
+      result = { type: 'delete', pos: Math.min(op.pos, other.pos), len: op.len - overlap };
+    }
+    var noop = result.type === 'insert' ? result.text.length === 0 : result.len === 0;
+    return noop ? [] : [result];
+  }
+
+  // Returns `ops` to apply after `others`, and `others` to apply after `ops`.
+  function transform(ops, others, winsTies) {
+    if (ops.length === 0 || others.length === 0) {
+      return [ops, others];
+    }
+    if (ops.length === 1 && others.length === 1) {
+      return [transformOp(ops[0], others[0], winsTies), transformOp(others[0], ops[0], !winsTies)];
+    }
+    if (ops.length > 1) {
+      var first = transform(ops.slice(0, 1), others, winsTies);
+      var rest = transform(ops.slice(1), first[1], winsTies);
+      return [first[0].concat(rest[0]), rest[1]];
+    }
+    var head = transform(ops, others.slice(0, 1), winsTies);
+    var tail = transform(head[0], others.slice(1), winsTies);
+    return [tail[0], head[1].concat(tail[1])];
+  }
+
+  function apply(chars, ops) {
+    ops.forEach(function (op) {
+      if (op.type === 'insert') {
+        chars.splice.apply(chars, [op.pos, 0].concat(Array.from(op.text)));
+      } else {
+        chars.splice(op.pos, op.len);
+      }
+    });
+    return chars;
+  }
+
+  // The operations turning `before` into `after`, replacing what lies between their
+  // common beginning and end.
+  function diff(before, after) {
+    var start = 0;
+    while (start < before.length && start < after.length && before[start] === after[start]) {
+      start++;
+    }
+    var end = 0;
+    while (end < before.length - start && end < after.length - start
+      && before[before.length - 1 - end] === after[after.length - 1 - end]) {
+      end++;
+    }
+    var ops = [];
+    if (before.length - start - end > 0) {
+      ops.push({ type: 'delete', pos: start, len: before.length - start - end });
+    }
+    if (after.length - start - end > 0) {
+      ops.push({ type: 'insert', pos: start, text: after.slice(start, after.length - end).join('') });
+    }
+    return ops;
+  }
+
+  // Shows `text`, keeping the selection where it was relative to the text around it.
+  function show(ops) {
+    var value = textarea.value;
+    function position(offset) {
+      var pos = Array.from(value.slice(0, offset)).length;
+      ops.forEach(function (op) {
+        if (op.type === 'insert' && op.pos <= pos) {
+          pos += Array.from(op.text).length;
+        } else if (op.type === 'delete' && op.pos < pos) {
+          pos -= Math.min(op.len, pos - op.pos);
+        }
+      });
+      return text.slice(0, pos).join('').length;
+    }
+    var start = position(textarea.selectionStart);
+    var end = position(textarea.selectionEnd);
+    textarea.value = text.join('');
+    if (document.activeElement === textarea) {
+      textarea.setSelectionRange(start, end);
+    }
+  }
+
+  function send(ops) {
+    inflight = ops;
+    socket.send(JSON.stringify({ type: 'edit', client: client, version: version, ops: ops }));
+  }
+
+  function receive(edit) {
+    if (version === null || edit.version <= version) {
+      return;
+    }
+    if (edit.version !== version + 1) {
+      sync();
+      return;
+    }
+    version = edit.version;
+    if (edit.client === client && inflight) {
+      inflight = null;
+      if (buffer) {
+        var ops = buffer;
+        buffer = null;
+        send(ops);
+      }
+      return;
+    }
+    var remote = edit.ops;
+    if (inflight) {
+      var transformed = transform(inflight, remote, false);
+      inflight = transformed[0];
+      remote = transformed[1];
+    }
+    if (buffer) {
+      var rebased = transform(buffer, remote, false);
+      buffer = rebased[0];
+      remote = rebased[1];
+    }
+    apply(text, remote);
+    show(remote);
+  }
+
+  // Starts over from the text held by the server. Unsent changes are lost.
+  function restart(snapshot) {
+    version = snapshot.version;
+    inflight = null;
+    buffer = null;
+    var removed = Array.from(textarea.value).length;
+    text = Array.from(snapshot.content);
+    show([{ type: 'delete', pos: 0, len: removed }, { type: 'insert', pos: 0, text: snapshot.content }]);
+  }
+
+  // Asks the server for its text again, such as after missing an edit.
+  function sync() {
+    if (socket.readyState === WebSocket.OPEN) {
+      socket.send(JSON.stringify({ type: 'sync' }));
+    }
+  }
+
+  socket.addEventListener('open', function () {
+    if (token) {
+      socket.send(JSON.stringify({ type: 'token', token: token }));
+    }
+  });
+  socket.addEventListener('message', function (event) {
+    var msg = JSON.parse(event.data);
+    if (msg.type === 'snapshot') {
+      restart(msg);
+    } else if (msg.type === 'edit') {
+      receive(msg);
+    } else if (msg.type === 'error' && token) {
+      token = null;
+      textarea.readOnly = true;
+      alert('This edit link is no longer valid.');
+    }
+  });
+  socket.addEventListener('close', function () {
+    version = null;
+    textarea.readOnly = true;
+  });
+
+  if (token) {
+    textarea.readOnly = false;
+    textarea.addEventListener('input', function () {
+      if (version === null || !token) {
+        return;
+      }
+      var after = Array.from(textarea.value);
+      var ops = diff(text, after);
+      text = after;
+      if (ops.length === 0) {
+        return;
+      }
+      if (inflight) {
+        buffer = (buffer || []).concat(ops);
+      } else {
+        send(ops);
+      }
+    });
+  }
+
+  return { isEditing: function () { return version !== null; } };
+}